@@ -0,0 +1,287 @@
+//! # Auto-Accept Rules
+//!
+//! Rules deciding what to do with an incoming file offer without asking the frontend.
+//!
+//! - Rules are evaluated in order, the first rule matching the offer applies.
+//!   If no rule matches, the frontend is asked as usual.
+//! - A rule matches on the peer (by identity, see [super::ecdsa_identity]), the extension of every file, the total size,
+//!   and a glob pattern every file path must match. Criteria left empty match anything.
+//!   The identity is not proven for each message: see the limitation in [super::ecdsa_identity].
+//! - A matching rule accepts the offer into a folder (or the download directory),
+//!   rejects it, or explicitly prompts the frontend.
+//!
+//! Rules are only applied to offers that passed the offer policy ([super::offer_policy]),
+//! which checks the free space of the folder the matching rule saves to.
+//! They are persisted to [AUTO_ACCEPT_RULES_FILE], and replaced with `FrontendEvent::SetAutoAcceptRules`.
+
+use std::path::{Path, PathBuf};
+
+use glob::Pattern;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::js_api::frontend_event::{AutoAcceptAction, AutoAcceptRule};
+
+use super::{
+    manifest::{FileSet, Manifest},
+    peer_manager::PeerManager,
+};
+
+/// File the rules are saved to, next to the log file.
+pub const AUTO_ACCEPT_RULES_FILE: &str = "kuaip2p-auto-accept.json";
+
+/// The auto-accept rules, in the order they are evaluated.
+#[derive(Debug, Default)]
+pub struct AutoAcceptRules {
+    rules: Vec<(AutoAcceptRule, Option<Pattern>)>,
+}
+
+impl AutoAcceptRules {
+    /// Check the rules (glob patterns and folders), and compile their patterns.
+    pub fn new(rules: Vec<AutoAcceptRule>) -> Result<Self, String> {
+        let rules = rules
+            .into_iter()
+            .map(|rule| {
+                if let AutoAcceptAction::Accept {
+                    folder: Some(folder),
+                } = &rule.action
+                {
+                    // A relative folder would depend on the working directory of the backend
+                    if !Path::new(folder).is_absolute() {
+                        return Err(format!(
+                            "Rule {}: folder {} is not an absolute path",
+                            rule.name, folder
+                        ));
+                    }
+                    if !Path::new(folder).is_dir() {
+                        return Err(format!(
+                            "Rule {}: folder {} does not exist",
+                            rule.name, folder
+                        ));
+                    }
+                }
+                let pattern = rule
+                    .pattern
+                    .as_deref()
+                    .map(Pattern::new)
+                    .transpose()
+                    .map_err(|e| format!("Rule {}: invalid pattern: {}", rule.name, e))?;
+                Ok((rule, pattern))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Load the rules saved to [AUTO_ACCEPT_RULES_FILE]. No rules if there are none saved, or they are invalid.
+    pub fn load() -> Self {
+        let Ok(json) = std::fs::read_to_string(AUTO_ACCEPT_RULES_FILE) else {
+            return Self::default();
+        };
+        match serde_json::from_str(&json)
+            .map_err(|e| e.to_string())
+            .and_then(Self::new)
+        {
+            Ok(rules) => {
+                info!("Loaded {} auto-accept rules", rules.rules.len());
+                rules
+            }
+            Err(e) => {
+                warn!("Ignoring invalid {}: {}", AUTO_ACCEPT_RULES_FILE, e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Save the rules to [AUTO_ACCEPT_RULES_FILE].
+    pub async fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&self.rules())
+            .map_err(|e| format!("Failed to serialize auto-accept rules: {}", e))?;
+        tokio::fs::write(AUTO_ACCEPT_RULES_FILE, json)
+            .await
+            .map_err(|e| format!("Failed to save auto-accept rules: {}", e))
+    }
+
+    /// The rules, in the order they are evaluated.
+    pub fn rules(&self) -> Vec<AutoAcceptRule> {
+        self.rules.iter().map(|(rule, _)| rule.clone()).collect()
+    }
+
+    /// The first rule matching an offer from the peer (by identity).
+    pub fn find(&self, peer_identity: &str, manifest: &Manifest) -> Option<&AutoAcceptRule> {
+        self.rules
+            .iter()
+            .find(|(rule, pattern)| {
+                rule.peer_identity
+                    .as_ref()
+                    .is_none_or(|identity| identity == peer_identity)
+                    && rule
+                        .max_size
+                        .is_none_or(|max_size| manifest.total_size() <= max_size)
+                    && manifest.files.iter().all(|file| {
+                        matches_extension(&rule.extensions, &file.path)
+                            && pattern
+                                .as_ref()
+                                .is_none_or(|pattern| pattern.matches(&file.path))
+                    })
+            })
+            .map(|(rule, _)| rule)
+    }
+}
+
+/// Does the file have one of the extensions (case-insensitive, without the dot)? No extensions match any file.
+fn matches_extension(extensions: &[String], path: &str) -> bool {
+    if extensions.is_empty() {
+        return true;
+    }
+    Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extensions.iter().any(|allowed| {
+                allowed
+                    .trim_start_matches('.')
+                    .eq_ignore_ascii_case(extension)
+            })
+        })
+}
+
+impl PeerManager {
+    /// The folder an offer handled by `rule` is saved to: the rule's folder, or the download directory.
+    pub(crate) async fn offer_folder(&self, rule: Option<&AutoAcceptRule>) -> PathBuf {
+        match rule.map(|rule| &rule.action) {
+            Some(AutoAcceptAction::Accept {
+                folder: Some(folder),
+            }) => PathBuf::from(folder),
+            _ => self.download_dir.lock().await.clone(),
+        }
+    }
+
+    /// Create the files of an automatically accepted offer under `folder` (see [Self::offer_folder]).
+    pub(crate) async fn create_auto_accepted_files(
+        &self,
+        unique_id: Uuid,
+        manifest: &mut Manifest,
+        folder: &Path,
+    ) -> Result<FileSet, String> {
+        manifest.set_download_dir(folder);
+        FileSet::create(manifest, unique_id)
+            .await
+            .map_err(|e| format!("Failed to create files: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::protocol::{FileMetadata, ManifestEntry};
+
+    fn offer(files: &[(&str, u64)]) -> Manifest {
+        let entries: Vec<ManifestEntry> = files
+            .iter()
+            .map(|(path, size)| ManifestEntry {
+                path: path.to_string(),
+                size: *size,
+                checksum: vec![0; blake3::OUT_LEN],
+                metadata: FileMetadata::default(),
+            })
+            .collect();
+        let total_size = files.iter().map(|(_, size)| size).sum();
+        Manifest::from_offer(entries, total_size).unwrap()
+    }
+
+    fn rule(name: &str) -> AutoAcceptRule {
+        AutoAcceptRule {
+            name: name.to_string(),
+            peer_identity: None,
+            extensions: Vec::new(),
+            max_size: None,
+            pattern: None,
+            action: AutoAcceptAction::Accept { folder: None },
+        }
+    }
+
+    fn found(rules: &AutoAcceptRules, peer_identity: &str, manifest: &Manifest) -> Option<String> {
+        rules
+            .find(peer_identity, manifest)
+            .map(|rule| rule.name.clone())
+    }
+
+    #[test]
+    fn first_matching_rule_applies() {
+        let rules = AutoAcceptRules::new(vec![
+            AutoAcceptRule {
+                extensions: vec![".JPG".to_string(), "png".to_string()],
+                ..rule("images")
+            },
+            AutoAcceptRule {
+                action: AutoAcceptAction::Reject,
+                ..rule("everything else")
+            },
+            rule("never reached"),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            found(&rules, "alice", &offer(&[("a.jpg", 1), ("b/c.PNG", 2)])),
+            Some("images".to_string())
+        );
+        // Every file must match, not just one
+        assert_eq!(
+            found(&rules, "alice", &offer(&[("a.jpg", 1), ("b.txt", 2)])),
+            Some("everything else".to_string())
+        );
+        assert_eq!(
+            found(&AutoAcceptRules::default(), "alice", &offer(&[("a", 1)])),
+            None
+        );
+    }
+
+    #[test]
+    fn rules_match_on_peer_size_and_pattern() {
+        let rules = AutoAcceptRules::new(vec![
+            AutoAcceptRule {
+                peer_identity: Some("alice".to_string()),
+                max_size: Some(10),
+                ..rule("small from alice")
+            },
+            AutoAcceptRule {
+                pattern: Some("docs/*.md".to_string()),
+                ..rule("docs")
+            },
+        ])
+        .unwrap();
+
+        assert_eq!(
+            found(&rules, "alice", &offer(&[("a", 4), ("b", 6)])),
+            Some("small from alice".to_string())
+        );
+        // The size limit is on the whole offer
+        assert_eq!(found(&rules, "alice", &offer(&[("a", 4), ("b", 7)])), None);
+        assert_eq!(found(&rules, "bob", &offer(&[("a", 1)])), None);
+        assert_eq!(
+            found(&rules, "bob", &offer(&[("docs/a.md", 100)])),
+            Some("docs".to_string())
+        );
+        assert_eq!(
+            found(&rules, "bob", &offer(&[("docs/a.md", 1), ("a.md", 1)])),
+            None
+        );
+    }
+
+    #[test]
+    fn invalid_rules_are_refused() {
+        let relative_folder = AutoAcceptRule {
+            action: AutoAcceptAction::Accept {
+                folder: Some("downloads".to_string()),
+            },
+            ..rule("relative")
+        };
+        assert!(AutoAcceptRules::new(vec![relative_folder]).is_err());
+
+        let invalid_pattern = AutoAcceptRule {
+            pattern: Some("[".to_string()),
+            ..rule("pattern")
+        };
+        assert!(AutoAcceptRules::new(vec![invalid_pattern]).is_err());
+    }
+}
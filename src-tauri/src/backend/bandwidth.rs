@@ -0,0 +1,350 @@
+//! # Bandwidth
+//!
+//! Token-bucket bandwidth limits for file transfers.
+//!
+//! Upload and download are limited separately, both globally (all peers combined) and per peer.
+//! A chunk must get through both the global and the peer's bucket before it is sent (upload),
+//! or before it is acknowledged (download). Delaying the acks also slows down the
+//! peer's sender, so the download limit is honoured end to end. The ack is held back in a task
+//! of its own, so the other messages from the peer (chat, acks, control) are not held back with it.
+//!
+//! Limits can be changed at runtime with `FrontendEvent::SetBandwidthLimits`.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Mutex;
+
+/// How many seconds worth of tokens a bucket can hold, i.e. how bursty a limited link may be.
+const BURST_SECS: f64 = 0.25;
+
+/// Bandwidth limits in bytes per second. `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BandwidthLimits {
+    /// Upload limit in bytes per second
+    pub upload: Option<u64>,
+    /// Download limit in bytes per second
+    pub download: Option<u64>,
+}
+
+/// Transfer direction to limit.
+#[derive(Debug, Clone, Copy)]
+pub enum BandwidthDirection {
+    Upload,
+    Download,
+}
+
+/// A token bucket.
+///
+/// Tokens are bytes. Reserving more tokens than available puts the bucket into debt, and the
+/// caller waits for the debt to be paid off. This keeps the bucket lock-free while waiting.
+#[derive(Debug)]
+struct TokenBucket {
+    /// Refill rate in bytes per second (`None` is unlimited)
+    rate: Option<u64>,
+    /// Available tokens (negative when in debt)
+    tokens: f64,
+    /// When the bucket was last refilled
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: Option<u64>) -> Self {
+        Self {
+            rate,
+            tokens: capacity(rate),
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Change the rate, keeping the current tokens (bounded by the new capacity)
+    fn set_rate(&mut self, rate: Option<u64>) {
+        self.refill();
+        self.rate = rate;
+        self.tokens = self.tokens.min(capacity(rate));
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        if let Some(rate) = self.rate {
+            let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * rate as f64).min(capacity(self.rate));
+        }
+        self.last_refill = now;
+    }
+
+    /// Take `bytes` tokens, returning how long to wait until they are paid for.
+    fn reserve(&mut self, bytes: u64) -> Duration {
+        let Some(rate) = self.rate.filter(|rate| *rate > 0) else {
+            return Duration::ZERO;
+        };
+
+        self.refill();
+        self.tokens -= bytes as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate as f64)
+        }
+    }
+}
+
+/// Bucket capacity for a rate
+fn capacity(rate: Option<u64>) -> f64 {
+    rate.map_or(0.0, |rate| rate as f64 * BURST_SECS)
+}
+
+/// Upload and download buckets.
+#[derive(Debug)]
+struct Buckets {
+    upload: TokenBucket,
+    download: TokenBucket,
+}
+
+impl Buckets {
+    fn new(limits: BandwidthLimits) -> Self {
+        Self {
+            upload: TokenBucket::new(limits.upload),
+            download: TokenBucket::new(limits.download),
+        }
+    }
+
+    fn set_limits(&mut self, limits: BandwidthLimits) {
+        self.upload.set_rate(limits.upload);
+        self.download.set_rate(limits.download);
+    }
+
+    fn reserve(&mut self, direction: BandwidthDirection, bytes: u64) -> Duration {
+        match direction {
+            BandwidthDirection::Upload => self.upload.reserve(bytes),
+            BandwidthDirection::Download => self.download.reserve(bytes),
+        }
+    }
+}
+
+/// Global and per-peer bandwidth limiter.
+#[derive(Debug)]
+pub struct BandwidthLimiter {
+    /// Limits for all peers combined
+    global: Mutex<Buckets>,
+    /// Limits per peer
+    peers: Mutex<HashMap<SocketAddr, Buckets>>,
+}
+
+impl Default for BandwidthLimiter {
+    fn default() -> Self {
+        Self {
+            global: Mutex::new(Buckets::new(BandwidthLimits::default())),
+            peers: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl BandwidthLimiter {
+    /// Set the global limits
+    pub async fn set_global_limits(&self, limits: BandwidthLimits) {
+        self.global.lock().await.set_limits(limits);
+    }
+
+    /// Set the limits of a single peer
+    pub async fn set_peer_limits(&self, peer_addr: SocketAddr, limits: BandwidthLimits) {
+        let mut peers = self.peers.lock().await;
+        if limits.upload.is_none() && limits.download.is_none() {
+            peers.remove(&peer_addr);
+        } else if let Some(buckets) = peers.get_mut(&peer_addr) {
+            buckets.set_limits(limits);
+        } else {
+            peers.insert(peer_addr, Buckets::new(limits));
+        }
+    }
+
+    /// Wait until `bytes` may be transferred with the peer in the given direction.
+    pub async fn acquire(&self, peer_addr: SocketAddr, direction: BandwidthDirection, bytes: u64) {
+        let wait = self.reserve(peer_addr, direction, bytes).await;
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Take `bytes` from the buckets without waiting, returning how long to wait
+    /// until they may be transferred with the peer in the given direction.
+    pub async fn reserve(
+        &self,
+        peer_addr: SocketAddr,
+        direction: BandwidthDirection,
+        bytes: u64,
+    ) -> Duration {
+        let global_wait = self.global.lock().await.reserve(direction, bytes);
+        let peer_wait = self
+            .peers
+            .lock()
+            .await
+            .get_mut(&peer_addr)
+            .map_or(Duration::ZERO, |buckets| buckets.reserve(direction, bytes));
+        global_wait.max(peer_wait)
+    }
+}
+
+/// Measures the effective transfer rate of a single file transfer.
+#[derive(Debug, Clone)]
+pub struct RateMeter {
+    /// Smoothed rate in bytes per second
+    rate: f64,
+    /// Bytes recorded since the last sample
+    pending_bytes: u64,
+    /// When the last sample was taken
+    last_sample: Instant,
+}
+
+impl Default for RateMeter {
+    fn default() -> Self {
+        Self {
+            rate: 0.0,
+            pending_bytes: 0,
+            last_sample: Instant::now(),
+        }
+    }
+}
+
+impl RateMeter {
+    /// Minimum time between samples, so bursts of chunks do not make the rate jumpy
+    const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+    /// Record transferred bytes
+    pub fn record(&mut self, bytes: u64) {
+        self.pending_bytes += bytes;
+
+        let elapsed = self.last_sample.elapsed();
+        if elapsed >= Self::SAMPLE_INTERVAL {
+            let sample = self.pending_bytes as f64 / elapsed.as_secs_f64();
+            self.rate = if self.rate == 0.0 {
+                sample
+            } else {
+                0.7 * self.rate + 0.3 * sample
+            };
+            self.pending_bytes = 0;
+            self.last_sample = Instant::now();
+        }
+    }
+
+    /// Start measuring afresh, e.g. after the transfer was paused
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// The smoothed rate in bytes per second
+    pub fn rate(&self) -> u64 {
+        self.rate as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pretend the bucket was last refilled `ago`.
+    fn refilled_ago(bucket: &mut TokenBucket, ago: Duration) {
+        bucket.last_refill = Instant::now() - ago;
+    }
+
+    fn assert_about(wait: Duration, expected: Duration) {
+        assert!(
+            wait <= expected && expected - wait < Duration::from_millis(50),
+            "{:?} is not about {:?}",
+            wait,
+            expected
+        );
+    }
+
+    #[test]
+    fn bucket_refills_at_its_rate_up_to_its_capacity() {
+        let mut bucket = TokenBucket::new(Some(1000));
+        // A full bucket lets a burst through at once
+        assert_eq!(bucket.reserve(250), Duration::ZERO);
+        // Then goes into debt, paid off at 1000 bytes per second
+        assert_about(bucket.reserve(500), Duration::from_millis(500));
+
+        refilled_ago(&mut bucket, Duration::from_millis(200));
+        assert_about(bucket.reserve(0), Duration::from_millis(300));
+
+        // Idle time only refills up to the capacity
+        refilled_ago(&mut bucket, Duration::from_secs(10));
+        bucket.refill();
+        assert_eq!(bucket.tokens, 250.0);
+    }
+
+    #[test]
+    fn unlimited_buckets_never_wait() {
+        let mut bucket = TokenBucket::new(None);
+        assert_eq!(bucket.reserve(u64::MAX), Duration::ZERO);
+        let mut bucket = TokenBucket::new(Some(0));
+        assert_eq!(bucket.reserve(u64::MAX), Duration::ZERO);
+    }
+
+    #[test]
+    fn lower_rate_caps_the_tokens() {
+        let mut bucket = TokenBucket::new(Some(1000));
+        bucket.set_rate(Some(100));
+        assert_eq!(bucket.tokens, 25.0);
+        assert_about(bucket.reserve(125), Duration::from_secs(1));
+
+        // Debt is kept across rate changes
+        bucket.set_rate(Some(200));
+        assert_about(bucket.reserve(0), Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn slowest_of_global_and_peer_limits_applies() {
+        let limiter = BandwidthLimiter::default();
+        let peer: SocketAddr = "192.168.1.2:1234".parse().unwrap();
+        let other: SocketAddr = "192.168.1.3:1234".parse().unwrap();
+        limiter
+            .set_global_limits(BandwidthLimits {
+                upload: Some(1000),
+                download: None,
+            })
+            .await;
+        limiter
+            .set_peer_limits(
+                peer,
+                BandwidthLimits {
+                    upload: Some(100),
+                    download: Some(100),
+                },
+            )
+            .await;
+
+        assert_about(
+            limiter.reserve(peer, BandwidthDirection::Upload, 125).await,
+            Duration::from_secs(1),
+        );
+        // A limit set on an unlimited bucket starts empty, and the peer's chunk took from it too
+        assert_about(
+            limiter
+                .reserve(other, BandwidthDirection::Upload, 1000)
+                .await,
+            Duration::from_millis(1125),
+        );
+        assert_eq!(
+            limiter
+                .reserve(other, BandwidthDirection::Download, 1000)
+                .await,
+            Duration::ZERO
+        );
+
+        // No limits left for the peer, only the global ones apply
+        limiter
+            .set_peer_limits(peer, BandwidthLimits::default())
+            .await;
+        assert!(limiter.peers.lock().await.is_empty());
+        assert_eq!(
+            limiter
+                .reserve(peer, BandwidthDirection::Download, 1000)
+                .await,
+            Duration::ZERO
+        );
+    }
+}
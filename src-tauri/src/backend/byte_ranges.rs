@@ -0,0 +1,194 @@
+//! # Byte Ranges
+//!
+//! Bookkeeping of which bytes of a transfer have been received.
+//!
+//! Chunks are identified by their byte offset rather than their chunk ID, so the sender can
+//! change the chunk size mid-transfer, and retransmitted (or overlapping) chunks are never
+//! counted twice.
+
+use std::collections::BTreeMap;
+
+/// A set of disjoint, half-open byte ranges `[start, end)`.
+#[derive(Debug, Default, Clone)]
+pub struct ByteRanges {
+    /// Ranges keyed by start, with the end as value. Adjacent ranges are merged.
+    ranges: BTreeMap<u64, u64>,
+    /// Total number of bytes covered by the ranges
+    covered: u64,
+}
+
+impl ByteRanges {
+    /// Create an empty set of byte ranges
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Total number of bytes covered
+    pub fn covered(&self) -> u64 {
+        self.covered
+    }
+
+    /// Is every byte in `[0, len)` covered?
+    pub fn is_complete(&self, len: u64) -> bool {
+        len == 0 || self.ranges.get(&0).is_some_and(|end| *end >= len)
+    }
+
+    /// Is every byte in `[start, end)` covered?
+    pub fn contains(&self, start: u64, end: u64) -> bool {
+        start >= end
+            || self
+                .ranges
+                .range(..=start)
+                .next_back()
+                .is_some_and(|(_, range_end)| *range_end >= end)
+    }
+
+    /// The ranges of `[0, len)` not covered, in order.
+    pub fn gaps(&self, len: u64) -> Vec<(u64, u64)> {
+        let mut gaps = Vec::new();
+        let mut next = 0;
+        for (start, end) in &self.ranges {
+            if *start >= len {
+                break;
+            }
+            if *start > next {
+                gaps.push((next, *start));
+            }
+            next = next.max(*end);
+        }
+        if next < len {
+            gaps.push((next, len));
+        }
+        gaps
+    }
+
+    /// Insert the range `[start, end)`.
+    ///
+    /// Returns the number of bytes that were not covered before.
+    pub fn insert(&mut self, start: u64, end: u64) -> u64 {
+        if start >= end {
+            return 0;
+        }
+
+        let mut new_start = start;
+        let mut new_end = end;
+        let mut already_covered = 0;
+
+        // Merge with every range that overlaps or touches [start, end)
+        let overlapping: Vec<(u64, u64)> = self
+            .ranges
+            .range(..=end)
+            .rev()
+            .take_while(|(_, range_end)| **range_end >= start)
+            .map(|(range_start, range_end)| (*range_start, *range_end))
+            .collect();
+
+        for (range_start, range_end) in overlapping {
+            self.ranges.remove(&range_start);
+            already_covered += range_end.min(end).saturating_sub(range_start.max(start));
+            new_start = new_start.min(range_start);
+            new_end = new_end.max(range_end);
+        }
+
+        self.ranges.insert(new_start, new_end);
+
+        let newly_covered = (end - start) - already_covered;
+        self.covered += newly_covered;
+        newly_covered
+    }
+
+    /// Remove the range `[start, end)`.
+    ///
+    /// Returns the number of bytes that were covered.
+    pub fn remove(&mut self, start: u64, end: u64) -> u64 {
+        if start >= end {
+            return 0;
+        }
+
+        // Every range that overlaps [start, end)
+        let overlapping: Vec<(u64, u64)> = self
+            .ranges
+            .range(..end)
+            .rev()
+            .take_while(|(_, range_end)| **range_end > start)
+            .map(|(range_start, range_end)| (*range_start, *range_end))
+            .collect();
+
+        let mut removed = 0;
+        for (range_start, range_end) in overlapping {
+            self.ranges.remove(&range_start);
+            removed += range_end.min(end) - range_start.max(start);
+            // Keep what is left on either side
+            if range_start < start {
+                self.ranges.insert(range_start, start);
+            }
+            if range_end > end {
+                self.ranges.insert(end, range_end);
+            }
+        }
+
+        self.covered -= removed;
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(ranges: &[(u64, u64)]) -> ByteRanges {
+        let mut byte_ranges = ByteRanges::new();
+        for (start, end) in ranges {
+            byte_ranges.insert(*start, *end);
+        }
+        byte_ranges
+    }
+
+    #[test]
+    fn gaps_of_empty_ranges() {
+        assert_eq!(ByteRanges::new().gaps(100), vec![(0, 100)]);
+        assert_eq!(ByteRanges::new().gaps(0), vec![]);
+    }
+
+    #[test]
+    fn gaps_between_ranges() {
+        let byte_ranges = ranges(&[(10, 20), (30, 40)]);
+        assert_eq!(byte_ranges.gaps(50), vec![(0, 10), (20, 30), (40, 50)]);
+    }
+
+    #[test]
+    fn gaps_at_the_edges() {
+        // Covered from the start, and up to the end
+        assert_eq!(ranges(&[(0, 10), (20, 30)]).gaps(30), vec![(10, 20)]);
+        assert_eq!(ranges(&[(0, 30)]).gaps(30), vec![]);
+    }
+
+    #[test]
+    fn gaps_ignore_ranges_past_the_end() {
+        let byte_ranges = ranges(&[(10, 20), (60, 70)]);
+        assert_eq!(byte_ranges.gaps(50), vec![(0, 10), (20, 50)]);
+        // A range straddling the end
+        assert_eq!(ranges(&[(40, 70)]).gaps(50), vec![(0, 40)]);
+    }
+
+    #[test]
+    fn gaps_of_merged_ranges() {
+        // Adjacent and overlapping ranges are merged
+        let byte_ranges = ranges(&[(10, 20), (20, 25), (22, 30)]);
+        assert_eq!(byte_ranges.covered(), 20);
+        assert_eq!(byte_ranges.gaps(40), vec![(0, 10), (30, 40)]);
+    }
+
+    #[test]
+    fn gaps_after_remove() {
+        let mut byte_ranges = ranges(&[(0, 20), (30, 50)]);
+        // Splits the first range, and cuts the start of the second
+        assert_eq!(byte_ranges.remove(10, 35), 15);
+        assert_eq!(byte_ranges.covered(), 25);
+        assert_eq!(byte_ranges.gaps(50), vec![(10, 35)]);
+        // Nothing left to remove there
+        assert_eq!(byte_ranges.remove(10, 35), 0);
+        assert_eq!(byte_ranges.remove(0, 50), 25);
+        assert_eq!(byte_ranges.gaps(50), vec![(0, 50)]);
+    }
+}
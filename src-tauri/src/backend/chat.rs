@@ -0,0 +1,179 @@
+//! # Chat
+//!
+//! Text chat messages between peers.
+//!
+//! - A message has a unique ID, the time it was sent (sender's clock) and a body of at most
+//!   [MAX_CHAT_BODY_LEN] bytes.
+//! - The receiver acknowledges each message with `ChatMessageAck`, and the sender reports it to the
+//!   frontend as delivered. Acks for messages we did not send to that peer are ignored.
+//! - Messages to a peer that is not connected are queued, and sent once it is (see [super::chat_outbox]).
+//! - Messages may also be sent to a room, to every one of its members (see [super::rooms]).
+//! - Messages are recorded in the conversation with the peer or room (see [super::chat_store]).
+//!   A message received again (same ID) is acknowledged, but not passed on to the frontend twice.
+//! - A message may later be edited or deleted by its author, and reacted to by anyone in the
+//!   conversation (see [super::chat_updates]).
+//! - Typing indicators and read receipts are sent alongside, but not recorded (see [super::chat_signals]).
+
+use std::{net::SocketAddr, time::SystemTime};
+
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::js_api::backend_event::{
+    self, BackendEvent, ChatHistoryMessage, ChatMessageQueued, ChatMessageState,
+};
+
+use super::{
+    chat_outbox::{self, ChatAction, OutboxMessage},
+    chat_store::ConversationKey,
+    history::unix_millis,
+    peer_manager::{Peer, PeerInfo, PeerManager, PeerState},
+    protocol::Message,
+};
+
+/// Maximum length of a chat message body, in bytes
+pub const MAX_CHAT_BODY_LEN: usize = 64 * 1024;
+
+/// Check the body of a chat message, sent or received.
+pub fn check_body(body: &str) -> Result<(), String> {
+    if body.trim().is_empty() {
+        return Err("Empty chat message".to_string());
+    }
+    if body.len() > MAX_CHAT_BODY_LEN {
+        return Err(format!(
+            "Chat message is {} bytes, longer than the {} byte limit",
+            body.len(),
+            MAX_CHAT_BODY_LEN
+        ));
+    }
+    Ok(())
+}
+
+impl PeerManager {
+    /// Send a chat message to a peer, and notify the frontend.
+    ///
+    /// `peer` is the address of an authenticated peer, or the identity of a peer. If the peer is not
+    /// connected, the message is queued until it is (see [super::chat_outbox]).
+    /// The message is reported as delivered once the peer acknowledges it.
+    pub(crate) async fn send_chat_message(&self, peer: &str, body: String) -> Result<(), String> {
+        check_body(&body)?;
+        let (peer_identity, connected) = self.chat_peer(peer).await?;
+
+        let message = OutboxMessage {
+            id: Uuid::new_v4(),
+            timestamp: unix_millis(SystemTime::now()),
+            body,
+            room_id: None,
+            clock: 0,
+            encrypted: None,
+            action: ChatAction::Message,
+        };
+        // Queue the message first: the peer may ack it at once, or be gone already
+        {
+            let mut chat_outbox = self.chat_outbox.lock().await;
+            chat_outbox.push(&peer_identity, message.clone());
+            if let Err(e) = chat_outbox.save().await {
+                warn!("{}", e);
+            }
+        }
+        let key = ConversationKey::Peer(peer_identity.clone());
+        self.store_chat_message(
+            &key,
+            ChatHistoryMessage {
+                id: message.id.to_string(),
+                timestamp: message.timestamp,
+                body: message.body.clone(),
+                outgoing: true,
+                author: None,
+                author_name: None,
+                clock: 0,
+                state: ChatMessageState::Queued,
+                edited_at: None,
+                deleted: false,
+                reactions: Vec::new(),
+            },
+        )
+        .await;
+
+        if let Some((peer_addr, peer_tx, peer_info)) = connected
+            && peer_tx.send(message.to_message()).await.is_ok()
+        {
+            self.store_chat_sent(&key, &message.id.to_string()).await;
+            self.backend_event_tx
+                .send(BackendEvent::ChatMessageSent(backend_event::ChatMessage {
+                    peer: peer_info.into_connection_info(peer_addr),
+                    room_id: None,
+                    id: message.id.to_string(),
+                    timestamp: message.timestamp,
+                    body: message.body,
+                }))
+                .await
+                .expect("Failed to send ChatMessageSent event to the frontend");
+        } else {
+            debug!(
+                "Peer {} is not connected, chat message {} queued",
+                peer_identity, message.id
+            );
+            self.backend_event_tx
+                .send(BackendEvent::ChatMessageQueued(ChatMessageQueued {
+                    peer_identity,
+                    room_id: None,
+                    id: message.id.to_string(),
+                    timestamp: message.timestamp,
+                    body: message.body,
+                }))
+                .await
+                .expect("Failed to send ChatMessageQueued event to the frontend");
+        }
+        Ok(())
+    }
+
+    /// Find the peer a chat message is for: an authenticated peer by address, or a known peer
+    /// by identity (see [super::known_peers]).
+    ///
+    /// Returns the identity of the peer, and its address, message sender and info if it is connected.
+    pub(crate) async fn chat_peer(
+        &self,
+        peer: &str,
+    ) -> Result<
+        (
+            String,
+            Option<(SocketAddr, mpsc::Sender<Message>, PeerInfo)>,
+        ),
+        String,
+    > {
+        let active_peers = self.active_peers.lock().await;
+
+        if let Ok(peer_addr) = peer.parse::<SocketAddr>() {
+            return match active_peers.get(&peer_addr) {
+                Some(Peer {
+                    tx,
+                    state: PeerState::Authenticated { peer_info },
+                    ..
+                }) => Ok((
+                    peer_info.identity.clone(),
+                    Some((peer_addr, tx.clone(), peer_info.clone())),
+                )),
+                Some(_) => Err(format!("Peer {} is not authenticated", peer_addr)),
+                None => Err(format!("Peer {} is not connected", peer_addr)),
+            };
+        }
+
+        let connected =
+            active_peers
+                .iter()
+                .find_map(|(peer_addr, connected)| match &connected.state {
+                    PeerState::Authenticated { peer_info } if peer_info.identity == peer => {
+                        Some((*peer_addr, connected.tx.clone(), peer_info.clone()))
+                    }
+                    _ => None,
+                });
+        drop(active_peers);
+
+        if connected.is_none() {
+            chat_outbox::check_recipient(&*self.known_peers.lock().await, peer)?;
+        }
+        Ok((peer.to_string(), connected))
+    }
+}
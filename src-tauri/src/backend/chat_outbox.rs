@@ -0,0 +1,356 @@
+//! # Chat Outbox
+//!
+//! Store-and-forward of chat messages: a message is not lost if the peer is offline.
+//!
+//! Edits, deletions and reactions (see [super::chat_updates]) go through the outbox too,
+//! so they reach the peer in order with the messages they refer to.
+//!
+//! - Every chat message we send is kept in the outbox of the peer (by identity), in order,
+//!   until the peer acknowledges it. A message is only added once (by ID).
+//!   A message sent to a room is kept in the outbox of each member (see [super::rooms]).
+//! - Messages are only queued for peers that authenticated with us before (see [super::known_peers]).
+//!   Anything else is refused, rather than kept forever for a peer that does not exist.
+//! - A message to a peer that is not connected is queued. Once the peer authenticates again
+//!   (after our sender keys, see [super::sessions]), every message in its outbox is sent, in order:
+//!   the queued ones, and those sent before that were never acknowledged. The peer ignores
+//!   messages it already has (by ID), so a retry cannot produce a duplicate.
+//!
+//! The outbox is persisted to [CHAT_OUTBOX_FILE], so queued messages survive restarts.
+
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::js_api::backend_event::{self, BackendEvent};
+
+use super::{
+    chat_store::ConversationKey,
+    known_peers::KnownPeers,
+    peer_manager::{PeerManager, PeerState},
+    protocol::{ChatDelete, ChatEdit, ChatMessage, ChatReaction, EncryptedBody, Message},
+    sender_keys,
+};
+
+/// File the outbox is saved to, next to the log file.
+pub const CHAT_OUTBOX_FILE: &str = "kuaip2p-chat-outbox.json";
+
+/// What an outbox entry is: a chat message, or a change to an earlier one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChatAction {
+    #[default]
+    Message,
+    /// `body` is the new text of the message
+    Edit {
+        message_id: Uuid,
+    },
+    Delete {
+        message_id: Uuid,
+    },
+    /// `body` is the emoji
+    Reaction {
+        message_id: Uuid,
+        added: bool,
+    },
+}
+
+/// A chat message (or edit, deletion or reaction) waiting for the peer's ack.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxMessage {
+    pub id: Uuid,
+    /// When the message was sent (or queued), in milliseconds since the Unix epoch
+    pub timestamp: u64,
+    pub body: String,
+    /// The room the message was sent to, None if it was sent to the peer only
+    #[serde(default)]
+    pub room_id: Option<Uuid>,
+    /// Lamport clock of the room when the message was sent
+    #[serde(default)]
+    pub clock: u64,
+    /// The body of a room message, encrypted once for every member
+    #[serde(default)]
+    pub encrypted: Option<EncryptedBody>,
+    #[serde(default)]
+    pub action: ChatAction,
+}
+
+impl OutboxMessage {
+    /// The conversation the message was sent in.
+    pub fn conversation(&self, peer_identity: &str) -> ConversationKey {
+        match self.room_id {
+            Some(room_id) => ConversationKey::Room(room_id),
+            None => ConversationKey::Peer(peer_identity.to_string()),
+        }
+    }
+
+    /// Everything but the body, authenticated with it in a room (see [super::sender_keys]).
+    pub fn header(&self) -> Vec<u8> {
+        sender_keys::header(self.id, self.timestamp, self.clock, &self.action)
+    }
+
+    /// The protocol message to send to the peer.
+    pub fn to_message(&self) -> Message {
+        // Only the members of the room may read an encrypted body
+        let body = match self.encrypted {
+            Some(_) => String::new(),
+            None => self.body.clone(),
+        };
+        let (id, timestamp, room_id, clock, encrypted) = (
+            self.id,
+            self.timestamp,
+            self.room_id,
+            self.clock,
+            self.encrypted.clone(),
+        );
+        match self.action {
+            ChatAction::Message => Message::ChatMessage(ChatMessage {
+                id,
+                timestamp,
+                body,
+                room_id,
+                clock,
+                encrypted,
+            }),
+            ChatAction::Edit { message_id } => Message::ChatEdit(ChatEdit {
+                id,
+                message_id,
+                timestamp,
+                room_id,
+                clock,
+                body,
+                encrypted,
+            }),
+            ChatAction::Delete { message_id } => Message::ChatDelete(ChatDelete {
+                id,
+                message_id,
+                timestamp,
+                room_id,
+                clock,
+                encrypted,
+            }),
+            ChatAction::Reaction { message_id, added } => Message::ChatReaction(ChatReaction {
+                id,
+                message_id,
+                timestamp,
+                room_id,
+                clock,
+                emoji: body,
+                added,
+                encrypted,
+            }),
+        }
+    }
+}
+
+/// The chat messages waiting for each peer's ack, keyed by peer identity, oldest first.
+#[derive(Debug, Default)]
+pub struct ChatOutbox {
+    peers: HashMap<String, Vec<OutboxMessage>>,
+}
+
+impl ChatOutbox {
+    /// Load the outbox saved to [CHAT_OUTBOX_FILE]. Empty if there is none saved, or it is invalid.
+    pub fn load() -> Self {
+        let Ok(json) = std::fs::read_to_string(CHAT_OUTBOX_FILE) else {
+            return Self::default();
+        };
+        match serde_json::from_str::<HashMap<String, Vec<OutboxMessage>>>(&json) {
+            Ok(peers) => {
+                info!(
+                    "Loaded {} chat messages waiting to be delivered",
+                    peers.values().map(Vec::len).sum::<usize>()
+                );
+                Self { peers }
+            }
+            Err(e) => {
+                warn!("Ignoring invalid {}: {}", CHAT_OUTBOX_FILE, e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Save the outbox to [CHAT_OUTBOX_FILE].
+    pub async fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string(&self.peers)
+            .map_err(|e| format!("Failed to serialize chat outbox: {}", e))?;
+        tokio::fs::write(CHAT_OUTBOX_FILE, json)
+            .await
+            .map_err(|e| format!("Failed to save chat outbox: {}", e))
+    }
+
+    /// Add a message to the peer's outbox. Returns false if it is already there.
+    pub fn push(&mut self, peer_identity: &str, message: OutboxMessage) -> bool {
+        let messages = self.peers.entry(peer_identity.to_string()).or_default();
+        if messages.iter().any(|queued| queued.id == message.id) {
+            return false;
+        }
+        messages.push(message);
+        true
+    }
+
+    /// Remove an acknowledged message from the peer's outbox. Returns None if it was not there.
+    pub fn remove(&mut self, peer_identity: &str, id: Uuid) -> Option<OutboxMessage> {
+        let messages = self.peers.get_mut(peer_identity)?;
+        let index = messages.iter().position(|message| message.id == id)?;
+        let message = messages.remove(index);
+        if messages.is_empty() {
+            self.peers.remove(peer_identity);
+        }
+        Some(message)
+    }
+
+    /// Whether a message is still waiting for some peer's ack.
+    pub fn contains(&self, id: Uuid) -> bool {
+        self.peers
+            .values()
+            .flatten()
+            .any(|message| message.id == id)
+    }
+
+    /// The sender keys of the encrypted messages waiting for some peer's ack.
+    pub fn key_ids(&self) -> HashSet<Uuid> {
+        self.peers
+            .values()
+            .flatten()
+            .filter_map(|message| message.encrypted.as_ref())
+            .map(|encrypted| encrypted.key_id)
+            .collect()
+    }
+
+    /// The messages waiting for the peer's ack, oldest first.
+    pub fn messages(&self, peer_identity: &str) -> Vec<OutboxMessage> {
+        self.peers.get(peer_identity).cloned().unwrap_or_default()
+    }
+}
+
+/// Check that messages may be queued for a peer that is not connected.
+///
+/// Only known peers: the outbox cannot grow forever with messages for peers that do not exist.
+pub fn check_recipient(known_peers: &KnownPeers, peer_identity: &str) -> Result<(), String> {
+    if !known_peers.contains(peer_identity) {
+        return Err(format!("Unknown peer {}", peer_identity));
+    }
+    Ok(())
+}
+
+impl PeerManager {
+    /// Send every message in the outbox of a peer that just authenticated, in order.
+    pub(crate) async fn flush_chat_outbox(&self, peer_addr: SocketAddr) {
+        let (peer_tx, peer_info) = {
+            let active_peers = self.active_peers.lock().await;
+            match active_peers.get(&peer_addr) {
+                Some(peer) => match &peer.state {
+                    PeerState::Authenticated { peer_info } => (peer.tx.clone(), peer_info.clone()),
+                    _ => return,
+                },
+                None => return,
+            }
+        };
+        let messages = self.chat_outbox.lock().await.messages(&peer_info.identity);
+        if messages.is_empty() {
+            return;
+        }
+
+        debug!(
+            "Sending {} chat messages waiting for peer {}",
+            messages.len(),
+            peer_addr
+        );
+        for message in messages {
+            if peer_tx.send(message.to_message()).await.is_err() {
+                // Disconnected again, the rest waits for next time
+                return;
+            }
+
+            // Messages sent before (but never acknowledged) were already reported.
+            // Edits, deletions and reactions were reported when they were made.
+            if message.action == ChatAction::Message
+                && self
+                    .store_chat_sent(
+                        &message.conversation(&peer_info.identity),
+                        &message.id.to_string(),
+                    )
+                    .await
+            {
+                self.backend_event_tx
+                    .send(BackendEvent::ChatMessageSent(backend_event::ChatMessage {
+                        peer: peer_info.into_connection_info(peer_addr),
+                        room_id: message.room_id.map(|room_id| room_id.to_string()),
+                        id: message.id.to_string(),
+                        timestamp: message.timestamp,
+                        body: message.body,
+                    }))
+                    .await
+                    .expect("Failed to send ChatMessageSent event to the frontend");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outbox_message(id: Uuid) -> OutboxMessage {
+        OutboxMessage {
+            id,
+            timestamp: 1,
+            body: "hello".to_string(),
+            room_id: None,
+            clock: 0,
+            encrypted: None,
+            action: ChatAction::Message,
+        }
+    }
+
+    fn ids(outbox: &ChatOutbox, peer_identity: &str) -> Vec<Uuid> {
+        outbox
+            .messages(peer_identity)
+            .into_iter()
+            .map(|message| message.id)
+            .collect()
+    }
+
+    #[test]
+    fn messages_are_queued_once_in_order() {
+        let mut outbox = ChatOutbox::default();
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        assert!(outbox.push("alice", outbox_message(first)));
+        assert!(outbox.push("alice", outbox_message(second)));
+        assert!(!outbox.push("alice", outbox_message(first)));
+        // A room message is queued for every member
+        assert!(outbox.push("bob", outbox_message(first)));
+        assert_eq!(ids(&outbox, "alice"), vec![first, second]);
+        assert_eq!(ids(&outbox, "bob"), vec![first]);
+    }
+
+    #[test]
+    fn acks_remove_messages_from_one_peer_only() {
+        let mut outbox = ChatOutbox::default();
+        let id = Uuid::new_v4();
+        outbox.push("alice", outbox_message(id));
+        outbox.push("bob", outbox_message(id));
+
+        assert!(outbox.remove("carol", id).is_none());
+        assert!(outbox.remove("alice", id).is_some());
+        assert!(outbox.remove("alice", id).is_none());
+        assert!(outbox.contains(id));
+        assert!(outbox.remove("bob", id).is_some());
+        assert!(!outbox.contains(id));
+        assert!(outbox.peers.is_empty());
+    }
+
+    #[test]
+    fn messages_are_only_queued_for_known_peers() {
+        let mut known_peers = KnownPeers::default();
+        assert!(check_recipient(&known_peers, "alice").is_err());
+        known_peers.record("alice", "Alice");
+        assert!(check_recipient(&known_peers, "alice").is_ok());
+        // By identity, not by name
+        assert!(check_recipient(&known_peers, "Alice").is_err());
+    }
+}
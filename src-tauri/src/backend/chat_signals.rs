@@ -0,0 +1,384 @@
+//! # Chat Signals
+//!
+//! Typing indicators and read receipts: lightweight signals about a conversation with a peer or
+//! a room. Unlike chat messages, they are not persisted, queued or acknowledged. A signal for a
+//! peer that is not connected is dropped.
+//!
+//! - `Typing` tells the peer (or the members of the room) we are typing. The frontend sends
+//!   `SendTyping` while the user types, and one is sent at most every [TYPING_INTERVAL].
+//!   The receiver shows it for [TYPING_TIMEOUT], unless another one arrives.
+//! - `ReadUpTo` tells them we read the conversation up to the last message we received,
+//!   whenever the frontend marks it as read.
+//! - Both are rate-limited per conversation, when sent and when received (see [SignalLimiter]),
+//!   so a peer cannot flood the frontend.
+//! - Signals have a lower priority than every other message, file chunks included: they are
+//!   written to the connection when nothing else is waiting, or after [SIGNAL_TURN] other messages,
+//!   so a long file transfer cannot hold them back forever. If [SIGNAL_QUEUE_LEN] signals are
+//!   already waiting, new ones are dropped. A typing indicator that waited longer than
+//!   [TYPING_TIMEOUT] is dropped rather than written (see [is_stale]).
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::mpsc::error::TrySendError;
+use tracing::{debug, trace};
+
+use crate::js_api::backend_event::{BackendEvent, ChatReadUpTo, ConnectionInfo, PeerTyping};
+
+use super::{
+    chat_store::ConversationKey,
+    peer_manager::{PeerInfo, PeerManager, PeerState},
+    protocol::{Conversation, Message, ReadUpTo, Typing},
+};
+
+/// Minimum time between two typing indicators we send in a conversation
+pub const TYPING_INTERVAL: Duration = Duration::from_secs(3);
+
+/// How long a typing indicator received is shown, unless another one arrives
+pub const TYPING_TIMEOUT: Duration = Duration::from_secs(6);
+
+/// Minimum time between two typing indicators received from a peer in a conversation
+/// (after the first two, so indicators delayed by the network are not dropped)
+const RECEIVED_TYPING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Minimum time between two read receipts sent or received in a conversation,
+/// after a burst of [READ_RECEIPT_BURST]
+const READ_RECEIPT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Number of read receipts sent or received at once in a conversation, before they are limited
+const READ_RECEIPT_BURST: u32 = 5;
+
+/// Maximum number of signals waiting to be sent to a peer
+pub const SIGNAL_QUEUE_LEN: usize = 8;
+
+/// Number of other messages written to a peer at most while a signal waits
+pub const SIGNAL_TURN: u32 = 16;
+
+/// Number of rate limits kept, above which the expired ones are forgotten
+const MAX_LIMITS: usize = 1024;
+
+/// A kind of signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SignalKind {
+    Typing,
+    ReadUpTo,
+}
+
+impl SignalKind {
+    /// How many signals are let through at once, and then how often.
+    fn limit(self, sent: bool) -> (u32, Duration) {
+        match (self, sent) {
+            (Self::Typing, true) => (1, TYPING_INTERVAL),
+            (Self::Typing, false) => (2, RECEIVED_TYPING_INTERVAL),
+            (Self::ReadUpTo, _) => (READ_RECEIPT_BURST, READ_RECEIPT_INTERVAL),
+        }
+    }
+}
+
+/// Rate limits of the signals sent and received, per kind, sender and conversation.
+#[derive(Debug, Default)]
+pub struct SignalLimiter {
+    /// When the next signal is due, if they were let through evenly. Keyed by kind,
+    /// sender identity (None for us) and conversation.
+    due: HashMap<(SignalKind, Option<String>, ConversationKey), Instant>,
+}
+
+impl SignalLimiter {
+    /// Whether a signal may be sent by us (`from` is None) or received from a peer (by identity) now.
+    /// If so, it is counted against the limit.
+    pub fn allow(&mut self, kind: SignalKind, from: Option<&str>, key: &ConversationKey) -> bool {
+        self.allow_at(kind, from, key, Instant::now())
+    }
+
+    fn allow_at(
+        &mut self,
+        kind: SignalKind,
+        from: Option<&str>,
+        key: &ConversationKey,
+        now: Instant,
+    ) -> bool {
+        if self.due.len() > MAX_LIMITS {
+            self.due.retain(|_, due| *due > now);
+        }
+
+        let (burst, interval) = kind.limit(from.is_none());
+        let due = self
+            .due
+            .entry((kind, from.map(str::to_string), key.clone()))
+            .or_insert(now);
+        // Up to `burst` signals may be let through ahead of time
+        let next = (*due).max(now);
+        if next > now + interval * (burst - 1) {
+            return false;
+        }
+        *due = next + interval;
+        true
+    }
+}
+
+/// Whether a signal that was queued at `queued_at` is no longer worth writing: a typing indicator
+/// the receiver would have stopped showing already.
+pub fn is_stale(signal: &Message, queued_at: Instant) -> bool {
+    matches!(signal, Message::Typing(_)) && queued_at.elapsed() >= TYPING_TIMEOUT
+}
+
+impl PeerManager {
+    /// Tell the peer (or the members of the room) we are typing in a conversation.
+    ///
+    /// Does nothing if we did less than [TYPING_INTERVAL] ago.
+    pub(crate) async fn send_typing(&self, key: &ConversationKey) -> Result<(), String> {
+        let (conversation, recipients) = self.signal_recipients(key).await?;
+        if !self
+            .signal_limiter
+            .lock()
+            .await
+            .allow(SignalKind::Typing, None, key)
+        {
+            trace!("Typing indicator for {:?} sent too soon. Dropped.", key);
+            return Ok(());
+        }
+        self.send_signal(&recipients, || Message::Typing(Typing { conversation }))
+            .await;
+        Ok(())
+    }
+
+    /// Tell the peer (or the members of the room) we read a conversation up to the last message
+    /// we received in it.
+    pub(crate) async fn send_read_receipt(&self, key: &ConversationKey) {
+        let Ok((conversation, recipients)) = self.signal_recipients(key).await else {
+            return;
+        };
+        let last_received = self.chat_store.lock().await.last_received(key);
+        let Some(message_id) = last_received.and_then(|id| id.parse().ok()) else {
+            return;
+        };
+        if !self
+            .signal_limiter
+            .lock()
+            .await
+            .allow(SignalKind::ReadUpTo, None, key)
+        {
+            debug!("Read receipt for {:?} sent too soon. Dropped.", key);
+            return;
+        }
+        self.send_signal(&recipients, || {
+            Message::ReadUpTo(ReadUpTo {
+                conversation,
+                message_id,
+            })
+        })
+        .await;
+    }
+
+    /// The conversation of a signal, as the recipients see it, and the identities of the recipients.
+    async fn signal_recipients(
+        &self,
+        key: &ConversationKey,
+    ) -> Result<(Conversation, Vec<String>), String> {
+        match key {
+            ConversationKey::Peer(peer_identity) => {
+                Ok((Conversation::Direct, vec![peer_identity.clone()]))
+            }
+            ConversationKey::Room(room_id) => {
+                let rooms = self.rooms.lock().await;
+                let room = rooms
+                    .get(room_id)
+                    .filter(|room| room.joined)
+                    .ok_or_else(|| format!("Not a member of room {}", room_id))?;
+                Ok((
+                    Conversation::Room { room_id: *room_id },
+                    room.members.clone(),
+                ))
+            }
+        }
+    }
+
+    /// Send a signal to the recipients that are connected, behind every other message.
+    async fn send_signal(&self, recipients: &[String], signal: impl Fn() -> Message) {
+        let signal_txs: Vec<_> = self
+            .active_peers
+            .lock()
+            .await
+            .values()
+            .filter_map(|peer| match &peer.state {
+                PeerState::Authenticated { peer_info }
+                    if recipients.contains(&peer_info.identity) =>
+                {
+                    Some((peer_info.name.clone(), peer.signal_tx.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+
+        for (peer_name, signal_tx) in signal_txs {
+            // Signals are not worth waiting for: they are stale by the time there is room
+            if let Err(TrySendError::Full(_)) = signal_tx.try_send((Instant::now(), signal())) {
+                debug!("Too many signals waiting for peer {}. Dropped.", peer_name);
+            }
+        }
+    }
+
+    /// The conversation a signal received from a peer is about.
+    ///
+    /// Returns None if the peer is not in the conversation with us, or sent too many signals lately.
+    pub(crate) async fn accept_signal(
+        &self,
+        peer_info: &PeerInfo,
+        kind: SignalKind,
+        conversation: Conversation,
+    ) -> Option<ConversationKey> {
+        let key = match conversation {
+            Conversation::Direct => ConversationKey::Peer(peer_info.identity.clone()),
+            Conversation::Room { room_id } => {
+                let member =
+                    self.rooms.lock().await.get(&room_id).is_some_and(|room| {
+                        room.joined && room.members.contains(&peer_info.identity)
+                    });
+                if !member {
+                    debug!(
+                        "Peer {} sent {:?} for room {}, which we are not in with it. Ignoring.",
+                        peer_info.name, kind, room_id
+                    );
+                    return None;
+                }
+                ConversationKey::Room(room_id)
+            }
+        };
+
+        if !self
+            .signal_limiter
+            .lock()
+            .await
+            .allow(kind, Some(&peer_info.identity), &key)
+        {
+            debug!(
+                "Peer {} sent {:?} too often. Ignoring.",
+                peer_info.name, kind
+            );
+            return None;
+        }
+        Some(key)
+    }
+
+    /// Tell the frontend a peer is typing in a conversation.
+    pub(crate) async fn emit_peer_typing(&self, peer: ConnectionInfo, key: &ConversationKey) {
+        let (_, room_id) = key.to_event();
+        self.backend_event_tx
+            .send(BackendEvent::PeerTyping(PeerTyping {
+                peer,
+                room_id,
+                timeout_ms: TYPING_TIMEOUT.as_millis() as u64,
+            }))
+            .await
+            .expect("Failed to send PeerTyping event to the frontend");
+    }
+
+    /// Tell the frontend a peer read a conversation up to a message.
+    pub(crate) async fn emit_chat_read_up_to(
+        &self,
+        peer: ConnectionInfo,
+        key: &ConversationKey,
+        message_id: String,
+    ) {
+        let (_, room_id) = key.to_event();
+        self.backend_event_tx
+            .send(BackendEvent::ChatReadUpTo(ChatReadUpTo {
+                peer,
+                room_id,
+                message_id,
+            }))
+            .await
+            .expect("Failed to send ChatReadUpTo event to the frontend");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed(
+        limiter: &mut SignalLimiter,
+        kind: SignalKind,
+        from: Option<&str>,
+        key: &ConversationKey,
+        now: Instant,
+        count: u32,
+    ) -> u32 {
+        (0..count)
+            .filter(|_| limiter.allow_at(kind, from, key, now))
+            .count() as u32
+    }
+
+    #[test]
+    fn read_receipts_are_let_through_in_a_burst_then_evenly() {
+        let mut limiter = SignalLimiter::default();
+        let key = ConversationKey::Peer("identity".to_string());
+        let start = Instant::now();
+
+        let kind = SignalKind::ReadUpTo;
+        assert_eq!(
+            allowed(&mut limiter, kind, None, &key, start, 10),
+            READ_RECEIPT_BURST
+        );
+        // One more every interval, not a new burst
+        let later = start + READ_RECEIPT_INTERVAL;
+        assert_eq!(allowed(&mut limiter, kind, None, &key, later, 10), 1);
+        // A full burst again once the limiter caught up
+        let much_later = later + READ_RECEIPT_INTERVAL * (READ_RECEIPT_BURST + 1);
+        assert_eq!(
+            allowed(&mut limiter, kind, None, &key, much_later, 10),
+            READ_RECEIPT_BURST
+        );
+    }
+
+    #[test]
+    fn typing_indicators_are_limited_per_sender_and_conversation() {
+        let mut limiter = SignalLimiter::default();
+        let key = ConversationKey::Peer("identity".to_string());
+        let other_key = ConversationKey::Room(uuid::Uuid::new_v4());
+        let start = Instant::now();
+
+        let kind = SignalKind::Typing;
+        // Sent: one per interval
+        assert_eq!(allowed(&mut limiter, kind, None, &key, start, 3), 1);
+        let soon = start + TYPING_INTERVAL / 2;
+        assert_eq!(allowed(&mut limiter, kind, None, &key, soon, 3), 0);
+        let later = start + TYPING_INTERVAL;
+        assert_eq!(allowed(&mut limiter, kind, None, &key, later, 3), 1);
+        // Received: two at once, from each sender
+        assert_eq!(
+            allowed(&mut limiter, kind, Some("alice"), &key, start, 3),
+            2
+        );
+        assert_eq!(allowed(&mut limiter, kind, Some("bob"), &key, start, 3), 2);
+        assert_eq!(
+            allowed(&mut limiter, kind, Some("alice"), &other_key, start, 3),
+            2
+        );
+        let later = start + RECEIVED_TYPING_INTERVAL;
+        assert_eq!(
+            allowed(&mut limiter, kind, Some("alice"), &key, later, 3),
+            1
+        );
+    }
+
+    #[test]
+    fn only_old_typing_indicators_are_stale() {
+        let conversation = || Conversation::Direct;
+        let typing = Message::Typing(Typing {
+            conversation: conversation(),
+        });
+        let read_up_to = Message::ReadUpTo(ReadUpTo {
+            conversation: conversation(),
+            message_id: uuid::Uuid::new_v4(),
+        });
+        let now = Instant::now();
+        let old = now - TYPING_TIMEOUT;
+
+        assert!(!is_stale(&typing, now));
+        assert!(is_stale(&typing, old));
+        assert!(!is_stale(&read_up_to, old));
+    }
+}
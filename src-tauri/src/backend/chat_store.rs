@@ -0,0 +1,742 @@
+//! # Chat Store
+//!
+//! The chat history, one conversation per peer or room, so conversations survive restarts.
+//!
+//! - Conversations with a peer are keyed by the peer's identity (see [super::ecdsa_identity]),
+//!   so peers with the same name do not share a conversation. Room conversations are keyed by room ID.
+//! - Every message sent or received is recorded, with its delivery state: queued while the peer
+//!   is offline, sent, then delivered once the peer acknowledges it, for ours; unread, then read
+//!   once the frontend marks the conversation as read, for theirs.
+//!   A message received twice (same ID) is only recorded once.
+//! - Edits, deletions and reactions are applied to the message they refer to (see [super::chat_updates]).
+//!   Authors are told apart by identity: only the author of a message may edit or delete it,
+//!   and a deleted message keeps no text or reactions. Names are kept for display only.
+//! - Room messages are kept in Lamport clock order (see [super::rooms]), so every member sees
+//!   the same history. Other messages are kept in the order they were sent or received.
+//! - The frontend loads a conversation a page at a time, from the most recent message backwards,
+//!   and is told the number of unread messages of every conversation whenever it changes.
+//!
+//! Each conversation is an append-only log of JSON lines in [CHAT_DIR], replayed at startup.
+//! Its first line names the peer (or room).
+
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::{self, OpenOptions},
+    io::AsyncWriteExt,
+};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::js_api::backend_event::{
+    BackendEvent, ChatConversation, ChatConversations, ChatHistoryMessage, ChatHistoryPage,
+    ChatMessageReaction, ChatMessageState,
+};
+
+use super::{
+    known_peers::KnownPeers,
+    peer_manager::{PeerInfo, PeerManager},
+};
+
+/// Directory the conversations are saved to, next to the log file.
+pub const CHAT_DIR: &str = "kuaip2p-chat";
+
+/// Number of messages in a page, if the frontend does not say
+const DEFAULT_PAGE_LEN: u32 = 50;
+
+/// Maximum number of messages in a page
+const MAX_PAGE_LEN: u32 = 500;
+
+/// Who a conversation is with.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ConversationKey {
+    /// A peer, by identity
+    Peer(String),
+    /// A room, by ID
+    Room(Uuid),
+}
+
+impl ConversationKey {
+    /// The conversation a frontend request is about: exactly one of a peer identity and a room ID.
+    pub fn from_request(
+        peer_identity: Option<String>,
+        room_id: Option<String>,
+    ) -> Result<Self, String> {
+        match (peer_identity, room_id) {
+            (Some(peer_identity), None) => Ok(Self::Peer(peer_identity)),
+            (None, Some(room_id)) => room_id
+                .parse()
+                .map(Self::Room)
+                .map_err(|e| format!("Invalid room ID (UUID): {}", e)),
+            _ => Err("Either a peer identity or a room ID is required".to_string()),
+        }
+    }
+
+    /// The peer identity and room ID of the conversation, as sent to the frontend.
+    pub fn to_event(&self) -> (Option<String>, Option<String>) {
+        match self {
+            Self::Peer(peer_identity) => (Some(peer_identity.clone()), None),
+            Self::Room(room_id) => (None, Some(room_id.to_string())),
+        }
+    }
+}
+
+/// A line of a conversation log.
+#[derive(Debug, Serialize, Deserialize)]
+enum ChatLogEntry {
+    /// The first line: who the conversation is with (a peer or a room)
+    Conversation {
+        peer_identity: Option<String>,
+        #[serde(default)]
+        room_id: Option<Uuid>,
+    },
+    /// A message sent (or queued) or received
+    Message(ChatHistoryMessage),
+    /// A queued message was sent
+    Sent { id: String },
+    /// A message we sent was delivered
+    Delivered { id: String },
+    /// Every message received so far was read
+    Read,
+    /// A message was edited, deleted or reacted to
+    Updated {
+        id: String,
+        /// The identity of the peer who made the change. None if we did.
+        by: Option<String>,
+        /// The name of the peer who made the change, for display only
+        #[serde(default)]
+        by_name: Option<String>,
+        update: ChatUpdate,
+    },
+}
+
+/// A change to a chat message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ChatUpdate {
+    Edit { body: String, edited_at: u64 },
+    Delete,
+    Reaction { emoji: String, added: bool },
+}
+
+/// A conversation with a peer or a room.
+#[derive(Debug)]
+struct Conversation {
+    /// The log file of the conversation
+    path: PathBuf,
+    /// The messages, oldest first
+    messages: Vec<ChatHistoryMessage>,
+}
+
+impl Conversation {
+    /// Apply a log entry to the conversation. Returns false if it changes nothing.
+    fn apply(&mut self, entry: &ChatLogEntry) -> bool {
+        match entry {
+            ChatLogEntry::Conversation { .. } => false,
+            ChatLogEntry::Message(message) => {
+                if self.find(&message.id).is_some() {
+                    return false;
+                }
+                // Room messages are ordered by their Lamport clock, then ID
+                let index = match message.clock {
+                    0 => self.messages.len(),
+                    clock => self
+                        .messages
+                        .partition_point(|other| (other.clock, &other.id) < (clock, &message.id)),
+                };
+                self.messages.insert(index, message.clone());
+                true
+            }
+            ChatLogEntry::Sent { id } => match self.find(id) {
+                Some(index) if self.messages[index].state == ChatMessageState::Queued => {
+                    self.messages[index].state = ChatMessageState::Sent;
+                    true
+                }
+                _ => false,
+            },
+            // The ack may overtake recording the message as sent
+            ChatLogEntry::Delivered { id } => match self.find(id) {
+                Some(index)
+                    if matches!(
+                        self.messages[index].state,
+                        ChatMessageState::Queued | ChatMessageState::Sent
+                    ) =>
+                {
+                    self.messages[index].state = ChatMessageState::Delivered;
+                    true
+                }
+                _ => false,
+            },
+            ChatLogEntry::Read => {
+                let mut changed = false;
+                for message in &mut self.messages {
+                    if message.state == ChatMessageState::Unread {
+                        message.state = ChatMessageState::Read;
+                        changed = true;
+                    }
+                }
+                changed
+            }
+            ChatLogEntry::Updated {
+                id,
+                by,
+                by_name,
+                update,
+            } => {
+                let Some(index) = self.find(id) else {
+                    return false;
+                };
+                let message = &mut self.messages[index];
+                if message.deleted {
+                    return false;
+                }
+                match update {
+                    ChatUpdate::Edit { body, edited_at } => {
+                        if message.author != *by || message.body == *body {
+                            return false;
+                        }
+                        message.body = body.clone();
+                        message.edited_at = Some(*edited_at);
+                    }
+                    ChatUpdate::Delete => {
+                        if message.author != *by {
+                            return false;
+                        }
+                        message.body.clear();
+                        message.reactions.clear();
+                        message.deleted = true;
+                    }
+                    ChatUpdate::Reaction { emoji, added } => {
+                        let existing = message
+                            .reactions
+                            .iter()
+                            .position(|reaction| reaction.emoji == *emoji && reaction.by == *by);
+                        match (existing, added) {
+                            (None, true) => message.reactions.push(ChatMessageReaction {
+                                emoji: emoji.clone(),
+                                by: by.clone(),
+                                by_name: by_name.clone(),
+                            }),
+                            (Some(index), false) => {
+                                message.reactions.remove(index);
+                            }
+                            _ => return false,
+                        }
+                    }
+                }
+                true
+            }
+        }
+    }
+
+    fn find(&self, id: &str) -> Option<usize> {
+        // Recent messages are the most likely to be looked up
+        self.messages.iter().rposition(|message| message.id == id)
+    }
+
+    fn unread(&self) -> u32 {
+        self.messages
+            .iter()
+            .filter(|message| message.state == ChatMessageState::Unread)
+            .count() as u32
+    }
+}
+
+/// Every conversation.
+#[derive(Debug, Default)]
+pub struct ChatStore {
+    conversations: HashMap<ConversationKey, Conversation>,
+}
+
+impl ChatStore {
+    /// Load the conversations saved to [CHAT_DIR]. Invalid lines are skipped.
+    pub fn load() -> Self {
+        let mut store = Self::default();
+        let Ok(read_dir) = std::fs::read_dir(CHAT_DIR) else {
+            return store;
+        };
+
+        for path in read_dir.flatten().map(|entry| entry.path()) {
+            let Ok(log) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let mut entries = log
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(serde_json::from_str::<ChatLogEntry>);
+            let key = match entries.next() {
+                Some(Ok(ChatLogEntry::Conversation {
+                    room_id: Some(room_id),
+                    ..
+                })) => ConversationKey::Room(room_id),
+                Some(Ok(ChatLogEntry::Conversation {
+                    peer_identity: Some(peer_identity),
+                    ..
+                })) => ConversationKey::Peer(peer_identity),
+                _ => {
+                    warn!("Ignoring invalid chat log {}", path.display());
+                    continue;
+                }
+            };
+
+            let mut conversation = Conversation {
+                path: path.clone(),
+                messages: Vec::new(),
+            };
+            let mut invalid = 0;
+            for entry in entries {
+                match entry {
+                    Ok(entry) => {
+                        conversation.apply(&entry);
+                    }
+                    Err(_) => invalid += 1,
+                }
+            }
+            if invalid > 0 {
+                warn!("Skipped {} invalid lines of {}", invalid, path.display());
+            }
+            store.conversations.insert(key, conversation);
+        }
+
+        info!("Loaded {} chat conversations", store.conversations.len());
+        store
+    }
+
+    /// Record a message sent or received in the conversation.
+    ///
+    /// Returns false if a message with the same ID was already recorded.
+    pub async fn add_message(
+        &mut self,
+        key: &ConversationKey,
+        message: ChatHistoryMessage,
+    ) -> Result<bool, String> {
+        self.append(key, ChatLogEntry::Message(message)).await
+    }
+
+    /// Record that a queued message of the conversation was sent.
+    pub async fn set_sent(&mut self, key: &ConversationKey, id: &str) -> Result<bool, String> {
+        self.append(key, ChatLogEntry::Sent { id: id.to_string() })
+            .await
+    }
+
+    /// Record that a message we sent in the conversation was delivered.
+    pub async fn set_delivered(&mut self, key: &ConversationKey, id: &str) -> Result<bool, String> {
+        self.append(key, ChatLogEntry::Delivered { id: id.to_string() })
+            .await
+    }
+
+    /// Mark every message received in the conversation as read.
+    pub async fn mark_read(&mut self, key: &ConversationKey) -> Result<bool, String> {
+        self.append(key, ChatLogEntry::Read).await
+    }
+
+    /// Apply an edit, deletion or reaction to a message of the conversation,
+    /// made by a peer (or by us, if `by` is None).
+    ///
+    /// Returns false if it changes nothing: the message is unknown or deleted,
+    /// or `by` may not make this change.
+    pub async fn update(
+        &mut self,
+        key: &ConversationKey,
+        id: &str,
+        by: Option<&PeerInfo>,
+        update: ChatUpdate,
+    ) -> Result<bool, String> {
+        let entry = ChatLogEntry::Updated {
+            id: id.to_string(),
+            by: by.map(|peer_info| peer_info.identity.clone()),
+            by_name: by.map(|peer_info| peer_info.name.clone()),
+            update,
+        };
+        self.append(key, entry).await
+    }
+
+    /// Whether a message of the conversation was recorded.
+    pub fn contains(&self, key: &ConversationKey, id: &str) -> bool {
+        self.conversations
+            .get(key)
+            .is_some_and(|conversation| conversation.find(id).is_some())
+    }
+
+    /// A message of the conversation, as it is now.
+    pub fn message(&self, key: &ConversationKey, id: &str) -> Option<ChatHistoryMessage> {
+        let conversation = self.conversations.get(key)?;
+        conversation
+            .find(id)
+            .map(|index| conversation.messages[index].clone())
+    }
+
+    /// The ID of the last message of the conversation we received (rather than sent).
+    pub fn last_received(&self, key: &ConversationKey) -> Option<String> {
+        self.conversations
+            .get(key)?
+            .messages
+            .iter()
+            .rev()
+            .find(|message| !message.outgoing)
+            .map(|message| message.id.clone())
+    }
+
+    /// The latest Lamport clock of the messages of the conversation (0 if it has none).
+    pub fn last_clock(&self, key: &ConversationKey) -> u64 {
+        self.conversations
+            .get(key)
+            .and_then(|conversation| conversation.messages.iter().map(|m| m.clock).max())
+            .unwrap_or(0)
+    }
+
+    /// A page of the conversation: the messages just before `before`
+    /// (or the most recent ones), oldest first.
+    pub fn page(
+        &self,
+        key: &ConversationKey,
+        before: Option<&str>,
+        limit: Option<u32>,
+    ) -> ChatHistoryPage {
+        let messages = self.conversations.get(key).map_or(&[][..], |conversation| {
+            let end = before
+                .and_then(|before| conversation.find(before))
+                .unwrap_or(conversation.messages.len());
+            &conversation.messages[..end]
+        });
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LEN).min(MAX_PAGE_LEN) as usize;
+        let start = messages.len().saturating_sub(limit);
+
+        let (peer_identity, room_id) = key.to_event();
+        ChatHistoryPage {
+            peer_identity,
+            room_id,
+            messages: messages[start..].to_vec(),
+            has_more: start > 0,
+        }
+    }
+
+    /// Every conversation, with its number of unread messages, most recent first.
+    /// Peers are named after the last name they were known by.
+    pub fn conversations(&self, known_peers: &KnownPeers) -> ChatConversations {
+        let mut conversations: Vec<ChatConversation> = self
+            .conversations
+            .iter()
+            .map(|(key, conversation)| {
+                let (peer_identity, room_id) = key.to_event();
+                let peer_name = peer_identity
+                    .as_deref()
+                    .and_then(|identity| known_peers.name(identity))
+                    .map(str::to_string);
+                ChatConversation {
+                    peer_identity,
+                    peer_name,
+                    room_id,
+                    unread: conversation.unread(),
+                    last_timestamp: conversation
+                        .messages
+                        .last()
+                        .map(|message| message.timestamp),
+                }
+            })
+            .collect();
+        conversations.sort_by_key(|conversation| Reverse(conversation.last_timestamp));
+        ChatConversations { conversations }
+    }
+
+    /// Apply an entry to the conversation, and append it to its log if it changed anything.
+    async fn append(&mut self, key: &ConversationKey, entry: ChatLogEntry) -> Result<bool, String> {
+        let mut lines = String::new();
+        let new = !self.conversations.contains_key(key);
+        let conversation = self
+            .conversations
+            .entry(key.clone())
+            .or_insert_with(|| Conversation {
+                path: conversation_path(key),
+                messages: Vec::new(),
+            });
+        if !conversation.apply(&entry) {
+            if new {
+                self.conversations.remove(key);
+            }
+            return Ok(false);
+        }
+        if new {
+            let (peer_identity, room_id) = match key {
+                ConversationKey::Peer(peer_identity) => (Some(peer_identity.clone()), None),
+                ConversationKey::Room(room_id) => (None, Some(*room_id)),
+            };
+            lines.push_str(&log_line(&ChatLogEntry::Conversation {
+                peer_identity,
+                room_id,
+            })?);
+        }
+        lines.push_str(&log_line(&entry)?);
+
+        fs::create_dir_all(CHAT_DIR)
+            .await
+            .map_err(|e| format!("Failed to create {}: {}", CHAT_DIR, e))?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&conversation.path)
+            .await
+            .map_err(|e| format!("Failed to open {}: {}", conversation.path.display(), e))?;
+        // Flush, so the write is done before the next entry is appended
+        file.write_all(lines.as_bytes())
+            .await
+            .and(file.flush().await)
+            .map_err(|e| format!("Failed to save chat message: {}", e))?;
+        Ok(true)
+    }
+}
+
+/// A log entry as a JSON line.
+fn log_line(entry: &ChatLogEntry) -> Result<String, String> {
+    serde_json::to_string(entry)
+        .map(|line| line + "\n")
+        .map_err(|e| format!("Failed to serialize chat log entry: {}", e))
+}
+
+/// The log file of a new conversation. Peer identities (base64) may not be valid file names, so they are hashed.
+fn conversation_path(key: &ConversationKey) -> PathBuf {
+    match key {
+        ConversationKey::Peer(peer_identity) => {
+            let hash = blake3::hash(peer_identity.as_bytes()).to_hex();
+            Path::new(CHAT_DIR).join(format!("{}.jsonl", &hash[..32]))
+        }
+        ConversationKey::Room(room_id) => {
+            Path::new(CHAT_DIR).join(format!("room-{}.jsonl", room_id))
+        }
+    }
+}
+
+impl PeerManager {
+    /// Record a chat message in the conversation.
+    ///
+    /// Returns false if it was already recorded. A message that could not be saved is still recorded in memory.
+    pub(crate) async fn store_chat_message(
+        &self,
+        key: &ConversationKey,
+        message: ChatHistoryMessage,
+    ) -> bool {
+        let added = self.chat_store.lock().await.add_message(key, message).await;
+        added.unwrap_or_else(|e| {
+            warn!("{}", e);
+            true
+        })
+    }
+
+    /// Record that a queued chat message of the conversation was sent.
+    ///
+    /// Returns false if it was not queued (it was sent before, but not acknowledged).
+    pub(crate) async fn store_chat_sent(&self, key: &ConversationKey, id: &str) -> bool {
+        let sent = self.chat_store.lock().await.set_sent(key, id).await;
+        sent.unwrap_or_else(|e| {
+            warn!("{}", e);
+            true
+        })
+    }
+
+    /// Record that a chat message we sent in the conversation was delivered.
+    pub(crate) async fn store_chat_delivered(&self, key: &ConversationKey, id: &str) {
+        if let Err(e) = self.chat_store.lock().await.set_delivered(key, id).await {
+            warn!("{}", e);
+        }
+    }
+
+    /// Apply an edit, deletion or reaction to a chat message of the conversation.
+    ///
+    /// Returns the message after the change, or None if the change was not applied.
+    /// A change that could not be saved is still applied in memory.
+    pub(crate) async fn store_chat_update(
+        &self,
+        key: &ConversationKey,
+        id: &str,
+        by: Option<&PeerInfo>,
+        update: ChatUpdate,
+    ) -> Option<ChatHistoryMessage> {
+        let mut chat_store = self.chat_store.lock().await;
+        let updated = chat_store
+            .update(key, id, by, update)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("{}", e);
+                true
+            });
+        if !updated {
+            return None;
+        }
+        chat_store.message(key, id)
+    }
+
+    /// Report every conversation, with its number of unread messages, to the frontend.
+    pub(crate) async fn emit_chat_conversations(&self) {
+        let conversations = {
+            let known_peers = self.known_peers.lock().await;
+            self.chat_store.lock().await.conversations(&known_peers)
+        };
+        self.backend_event_tx
+            .send(BackendEvent::ChatConversations(conversations))
+            .await
+            .expect("Failed to send ChatConversations event to the frontend");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: &str, timestamp: u64, author: Option<&str>, clock: u64) -> ChatHistoryMessage {
+        ChatHistoryMessage {
+            id: id.to_string(),
+            timestamp,
+            body: format!("message {}", id),
+            outgoing: author.is_none(),
+            author: author.map(str::to_string),
+            author_name: author.map(str::to_string),
+            clock,
+            state: if author.is_none() {
+                ChatMessageState::Sent
+            } else {
+                ChatMessageState::Unread
+            },
+            edited_at: None,
+            deleted: false,
+            reactions: Vec::new(),
+        }
+    }
+
+    fn updated(id: &str, by: Option<&str>, update: ChatUpdate) -> ChatLogEntry {
+        ChatLogEntry::Updated {
+            id: id.to_string(),
+            by: by.map(str::to_string),
+            by_name: None,
+            update,
+        }
+    }
+
+    fn edit(body: &str) -> ChatUpdate {
+        ChatUpdate::Edit {
+            body: body.to_string(),
+            edited_at: 1,
+        }
+    }
+
+    fn conversation(messages: impl IntoIterator<Item = ChatHistoryMessage>) -> Conversation {
+        let mut conversation = Conversation {
+            path: PathBuf::new(),
+            messages: Vec::new(),
+        };
+        for message in messages {
+            conversation.apply(&ChatLogEntry::Message(message));
+        }
+        conversation
+    }
+
+    fn ids(messages: &[ChatHistoryMessage]) -> Vec<String> {
+        messages.iter().map(|message| message.id.clone()).collect()
+    }
+
+    #[test]
+    fn pages_go_back_from_the_most_recent_message() {
+        let key = ConversationKey::Peer("alice".to_string());
+        let mut store = ChatStore::default();
+        store.conversations.insert(
+            key.clone(),
+            conversation((0..120).map(|i| message(&i.to_string(), i, Some("alice"), 0))),
+        );
+        let range =
+            |range: std::ops::Range<u64>| -> Vec<String> { range.map(|i| i.to_string()).collect() };
+
+        let page = store.page(&key, None, None);
+        assert_eq!(ids(&page.messages), range(70..120));
+        assert!(page.has_more);
+        let page = store.page(&key, Some("70"), Some(30));
+        assert_eq!(ids(&page.messages), range(40..70));
+        assert!(page.has_more);
+        let page = store.page(&key, Some("10"), Some(30));
+        assert_eq!(ids(&page.messages), range(0..10));
+        assert!(!page.has_more);
+        assert_eq!(store.page(&key, None, Some(u32::MAX)).messages.len(), 120);
+
+        let other = ConversationKey::Peer("bob".to_string());
+        assert!(store.page(&other, None, None).messages.is_empty());
+    }
+
+    #[test]
+    fn only_unread_received_messages_are_counted() {
+        let mut alice = conversation([
+            message("1", 1, Some("alice"), 0),
+            message("2", 2, None, 0),
+            message("3", 3, Some("alice"), 0),
+            // Received twice
+            message("3", 3, Some("alice"), 0),
+        ]);
+        assert_eq!(alice.messages.len(), 3);
+        assert_eq!(alice.unread(), 2);
+        assert!(alice.apply(&ChatLogEntry::Read));
+        assert_eq!(alice.unread(), 0);
+        assert!(!alice.apply(&ChatLogEntry::Read));
+
+        let mut store = ChatStore::default();
+        store
+            .conversations
+            .insert(ConversationKey::Peer("alice".to_string()), alice);
+        store.conversations.insert(
+            ConversationKey::Peer("bob".to_string()),
+            conversation([message("4", 4, Some("bob"), 0)]),
+        );
+        let conversations = store.conversations(&KnownPeers::default()).conversations;
+        let unread: Vec<(Option<String>, u32)> = conversations
+            .into_iter()
+            .map(|conversation| (conversation.peer_identity, conversation.unread))
+            .collect();
+        assert_eq!(
+            unread,
+            vec![(Some("bob".to_string()), 1), (Some("alice".to_string()), 0)]
+        );
+    }
+
+    #[test]
+    fn only_the_author_may_edit_or_delete() {
+        let mut conversation = conversation([
+            message("theirs", 1, Some("alice"), 0),
+            message("ours", 2, None, 0),
+        ]);
+
+        assert!(!conversation.apply(&updated("theirs", Some("bob"), edit("bob's"))));
+        assert!(!conversation.apply(&updated("theirs", None, edit("ours"))));
+        assert!(conversation.apply(&updated("theirs", Some("alice"), edit("alice's"))));
+        assert!(!conversation.apply(&updated("ours", Some("alice"), edit("alice's"))));
+        assert!(conversation.apply(&updated("ours", None, edit("ours"))));
+        assert_eq!(conversation.messages[0].body, "alice's");
+        assert_eq!(conversation.messages[1].body, "ours");
+
+        let like = |added| ChatUpdate::Reaction {
+            emoji: "👍".to_string(),
+            added,
+        };
+        assert!(conversation.apply(&updated("theirs", Some("bob"), like(true))));
+        assert!(!conversation.apply(&updated("theirs", Some("bob"), like(true))));
+        assert!(!conversation.apply(&updated("theirs", Some("bob"), ChatUpdate::Delete)));
+        assert!(conversation.apply(&updated("theirs", Some("alice"), ChatUpdate::Delete)));
+        let deleted = &conversation.messages[0];
+        assert!(deleted.deleted && deleted.body.is_empty() && deleted.reactions.is_empty());
+
+        // Nothing changes a deleted message
+        assert!(!conversation.apply(&updated("theirs", Some("alice"), edit("again"))));
+        assert!(!conversation.apply(&updated("theirs", Some("bob"), like(true))));
+        assert!(!conversation.apply(&updated("unknown", None, ChatUpdate::Delete)));
+    }
+
+    #[test]
+    fn room_messages_are_ordered_by_clock() {
+        let conversation = conversation([
+            message("b", 1, Some("alice"), 2),
+            message("c", 2, Some("bob"), 3),
+            message("a", 3, None, 1),
+            message("a2", 4, Some("bob"), 2),
+        ]);
+        assert_eq!(ids(&conversation.messages), vec!["a", "a2", "b", "c"]);
+    }
+}
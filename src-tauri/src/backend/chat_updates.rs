@@ -0,0 +1,572 @@
+//! # Chat Updates
+//!
+//! Changes to a chat message sent earlier, referred to by its ID: `ChatEdit` replaces its text,
+//! `ChatDelete` deletes it, and `ChatReaction` adds or removes an emoji reaction.
+//!
+//! - Only the author of a message may edit or delete it. The receiver checks it against the
+//!   identity of the authenticated peer the change came from, and ignores it otherwise.
+//!   Anyone in the conversation may react.
+//! - Changes are applied to the chat history (see [super::chat_store]), and reported to the
+//!   frontend with `ChatMessageUpdated`.
+//! - They are acknowledged and queued like chat messages (see [super::chat_outbox]). In a room they
+//!   are sent to every member with the room's clock, encrypted and signed with our sender key
+//!   (see [super::sender_keys]).
+//! - A change may arrive before the message it changes (in a room, the message may come from
+//!   another member). It is kept, unacknowledged, until the message arrives, then applied and
+//!   acknowledged. At most [MAX_PENDING_UPDATES] changes are kept: the others are sent again
+//!   on the next connection.
+
+use std::{collections::HashMap, net::SocketAddr, time::SystemTime};
+
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::js_api::backend_event::{BackendEvent, ChatHistoryMessage, ChatMessageUpdated};
+
+use super::{
+    chat::check_body,
+    chat_outbox::{ChatAction, OutboxMessage},
+    chat_store::{ChatUpdate, ConversationKey},
+    history::unix_millis,
+    peer_manager::{PeerInfo, PeerManager},
+    protocol::{ChatDelete, ChatEdit, ChatMessageAck, ChatReaction, EncryptedBody, Message},
+    sender_keys,
+};
+
+/// Maximum length of a reaction emoji, in bytes (enough for any emoji sequence)
+pub const MAX_REACTION_LEN: usize = 64;
+
+/// Maximum number of changes kept until the message they change arrives
+pub const MAX_PENDING_UPDATES: usize = 1000;
+
+/// Check the emoji of a reaction, sent or received.
+pub fn check_emoji(emoji: &str) -> Result<(), String> {
+    if emoji.is_empty() || emoji.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err("Invalid reaction emoji".to_string());
+    }
+    if emoji.len() > MAX_REACTION_LEN {
+        return Err(format!(
+            "Reaction is {} bytes, longer than the {} byte limit",
+            emoji.len(),
+            MAX_REACTION_LEN
+        ));
+    }
+    Ok(())
+}
+
+/// Parse the ID of a chat message, as sent by the frontend.
+fn parse_message_id(message_id: &str) -> Result<Uuid, String> {
+    message_id
+        .parse()
+        .map_err(|e| format!("Invalid message ID (UUID): {}", e))
+}
+
+/// Check a change to a chat message, and turn it into the change to record.
+/// `text` is the new text of an edit, or the emoji of a reaction.
+///
+/// Returns the ID of the message changed, and the change.
+fn chat_update(
+    action: ChatAction,
+    text: String,
+    timestamp: u64,
+) -> Result<(Uuid, ChatUpdate), String> {
+    match action {
+        ChatAction::Message => Err("Not a change to a chat message".to_string()),
+        ChatAction::Edit { message_id } => {
+            check_body(&text)?;
+            Ok((
+                message_id,
+                ChatUpdate::Edit {
+                    body: text,
+                    edited_at: timestamp,
+                },
+            ))
+        }
+        ChatAction::Delete { message_id } => Ok((message_id, ChatUpdate::Delete)),
+        ChatAction::Reaction { message_id, added } => {
+            check_emoji(&text)?;
+            Ok((message_id, ChatUpdate::Reaction { emoji: text, added }))
+        }
+    }
+}
+
+/// An edit, deletion or reaction received from a peer.
+pub struct ReceivedUpdate {
+    id: Uuid,
+    timestamp: u64,
+    room_id: Option<Uuid>,
+    clock: u64,
+    action: ChatAction,
+    /// The new text of an edit, or the emoji of a reaction (empty if it is encrypted)
+    text: String,
+    encrypted: Option<EncryptedBody>,
+}
+
+impl From<ChatEdit> for ReceivedUpdate {
+    fn from(chat_edit: ChatEdit) -> Self {
+        Self {
+            id: chat_edit.id,
+            timestamp: chat_edit.timestamp,
+            room_id: chat_edit.room_id,
+            clock: chat_edit.clock,
+            action: ChatAction::Edit {
+                message_id: chat_edit.message_id,
+            },
+            text: chat_edit.body,
+            encrypted: chat_edit.encrypted,
+        }
+    }
+}
+
+impl From<ChatDelete> for ReceivedUpdate {
+    fn from(chat_delete: ChatDelete) -> Self {
+        Self {
+            id: chat_delete.id,
+            timestamp: chat_delete.timestamp,
+            room_id: chat_delete.room_id,
+            clock: chat_delete.clock,
+            action: ChatAction::Delete {
+                message_id: chat_delete.message_id,
+            },
+            text: String::new(),
+            encrypted: chat_delete.encrypted,
+        }
+    }
+}
+
+impl From<ChatReaction> for ReceivedUpdate {
+    fn from(chat_reaction: ChatReaction) -> Self {
+        Self {
+            id: chat_reaction.id,
+            timestamp: chat_reaction.timestamp,
+            room_id: chat_reaction.room_id,
+            clock: chat_reaction.clock,
+            action: ChatAction::Reaction {
+                message_id: chat_reaction.message_id,
+                added: chat_reaction.added,
+            },
+            text: chat_reaction.emoji,
+            encrypted: chat_reaction.encrypted,
+        }
+    }
+}
+
+/// A change a peer made to a chat message we do not have yet.
+#[derive(Debug)]
+struct PendingUpdate {
+    /// ID of the change, to acknowledge once it is applied
+    id: Uuid,
+    by: PeerInfo,
+    update: ChatUpdate,
+}
+
+/// Changes received before the message they change, keyed by conversation and message ID.
+#[derive(Debug, Default)]
+pub struct PendingUpdates {
+    updates: HashMap<(ConversationKey, Uuid), Vec<PendingUpdate>>,
+    len: usize,
+}
+
+impl PendingUpdates {
+    /// Keep a change until its message arrives. Returns false if too many changes are kept already.
+    fn push(&mut self, key: ConversationKey, message_id: Uuid, pending: PendingUpdate) -> bool {
+        let kept = self.updates.get(&(key.clone(), message_id));
+        // A change sent again (it was not acknowledged) is only kept once
+        if kept.is_some_and(|kept| kept.iter().any(|kept| kept.id == pending.id)) {
+            return true;
+        }
+        if self.len >= MAX_PENDING_UPDATES {
+            return false;
+        }
+        self.updates
+            .entry((key, message_id))
+            .or_default()
+            .push(pending);
+        self.len += 1;
+        true
+    }
+
+    /// Take the changes kept for a message, in the order they arrived.
+    fn take(&mut self, key: &ConversationKey, message_id: Uuid) -> Vec<PendingUpdate> {
+        let updates = self
+            .updates
+            .remove(&(key.clone(), message_id))
+            .unwrap_or_default();
+        self.len -= updates.len();
+        updates
+    }
+}
+
+impl PeerManager {
+    /// Edit a chat message we sent in a conversation.
+    pub(crate) async fn edit_chat_message(
+        &self,
+        key: &ConversationKey,
+        message_id: &str,
+        body: String,
+    ) -> Result<(), String> {
+        let message_id = parse_message_id(message_id)?;
+        self.send_chat_update(key, ChatAction::Edit { message_id }, body)
+            .await
+    }
+
+    /// Delete a chat message we sent in a conversation.
+    pub(crate) async fn delete_chat_message(
+        &self,
+        key: &ConversationKey,
+        message_id: &str,
+    ) -> Result<(), String> {
+        let message_id = parse_message_id(message_id)?;
+        self.send_chat_update(key, ChatAction::Delete { message_id }, String::new())
+            .await
+    }
+
+    /// Add (or remove) our emoji reaction to a chat message of a conversation.
+    pub(crate) async fn react_to_chat_message(
+        &self,
+        key: &ConversationKey,
+        message_id: &str,
+        emoji: String,
+        added: bool,
+    ) -> Result<(), String> {
+        let message_id = parse_message_id(message_id)?;
+        self.send_chat_update(key, ChatAction::Reaction { message_id, added }, emoji)
+            .await
+    }
+
+    /// Apply a change to a chat message, send it to the peer (or every member of the room),
+    /// and notify the frontend.
+    ///
+    /// Peers that are not connected get the change once they connect, after the messages before it.
+    async fn send_chat_update(
+        &self,
+        key: &ConversationKey,
+        action: ChatAction,
+        text: String,
+    ) -> Result<(), String> {
+        let timestamp = unix_millis(SystemTime::now());
+        let (message_id, update) = chat_update(action, text.clone(), timestamp)?;
+
+        let (recipients, room_id, clock, sender_key, message) = match key {
+            ConversationKey::Peer(peer_identity) => {
+                let message = self.apply_own_chat_update(key, message_id, update).await?;
+                (vec![peer_identity.clone()], None, 0, None, message)
+            }
+            ConversationKey::Room(room_id) => {
+                let mut rooms = self.rooms.lock().await;
+                let room = rooms
+                    .get_mut(room_id)
+                    .filter(|room| room.joined)
+                    .ok_or_else(|| format!("Not a member of room {}", room_id))?;
+                // Applied first: the clock only moves for a change that is sent
+                let clock = room.next_clock()?;
+                let message = self.apply_own_chat_update(key, message_id, update).await?;
+                room.clock = clock;
+                // As for messages, only a new sender key is saved
+                let created = room.sender_keys.is_empty();
+                let sent = (
+                    room.members.clone(),
+                    Some(*room_id),
+                    room.clock,
+                    Some(room.sender_key().clone()),
+                    message,
+                );
+                if created && let Err(e) = rooms.save().await {
+                    warn!("{}", e);
+                }
+                sent
+            }
+        };
+
+        let mut outbox_message = OutboxMessage {
+            id: Uuid::new_v4(),
+            timestamp,
+            body: text,
+            room_id,
+            clock,
+            encrypted: None,
+            action,
+        };
+        if let (Some(room_id), Some(sender_key)) = (room_id, sender_key) {
+            // Encrypted once, for every member
+            outbox_message.encrypted =
+                Some(sender_key.encrypt(room_id, &outbox_message.header(), &outbox_message.body));
+        }
+        // Queue the change first: the peers may ack it at once, or be gone already
+        {
+            let mut chat_outbox = self.chat_outbox.lock().await;
+            for recipient in &recipients {
+                chat_outbox.push(recipient, outbox_message.clone());
+            }
+            if let Err(e) = chat_outbox.save().await {
+                warn!("{}", e);
+            }
+        }
+
+        let connected = self.connected_peers().await;
+        for recipient in &recipients {
+            let mut sent = false;
+            for (_, peer_tx, _) in connected
+                .iter()
+                .filter(|(_, _, peer_info)| peer_info.identity == *recipient)
+            {
+                peer_tx.send(outbox_message.to_message()).await.ok(); // We ignore the error here, as it stays queued.
+                sent = true;
+            }
+            if !sent {
+                debug!(
+                    "Peer {} is not connected, change {} to chat message {} queued",
+                    recipient, outbox_message.id, message_id
+                );
+            }
+        }
+
+        let (peer_identity, room_id) = key.to_event();
+        self.backend_event_tx
+            .send(BackendEvent::ChatMessageUpdated(ChatMessageUpdated {
+                peer_identity,
+                room_id,
+                message,
+                by: None,
+                by_name: None,
+            }))
+            .await
+            .expect("Failed to send ChatMessageUpdated event to the frontend");
+        Ok(())
+    }
+
+    /// Apply a change we made to a chat message of a conversation.
+    async fn apply_own_chat_update(
+        &self,
+        key: &ConversationKey,
+        message_id: Uuid,
+        update: ChatUpdate,
+    ) -> Result<ChatHistoryMessage, String> {
+        self.store_chat_update(key, &message_id.to_string(), None, update)
+            .await
+            .ok_or_else(|| {
+                format!(
+                    "Chat message {} does not exist, or cannot be changed by us",
+                    message_id
+                )
+            })
+    }
+
+    /// Apply an edit, deletion or reaction a peer sent, if it may make it, and notify the frontend.
+    ///
+    /// The change is acknowledged like a chat message once applied (or found to change nothing).
+    /// It is not if it cannot be decrypted yet (it is sent again, after the key, on the next
+    /// connection), is invalid, or its message has not arrived yet (it is kept until it does).
+    pub(crate) async fn receive_chat_update(
+        &self,
+        received: ReceivedUpdate,
+        peer_addr: SocketAddr,
+        message_name: &str,
+    ) {
+        let Some((peer_tx, peer_info)) = self.authenticated_peer(peer_addr, message_name).await
+        else {
+            return;
+        };
+
+        // As for chat messages, changes in rooms we are not in with the peer are acknowledged, but ignored
+        let text = match received.room_id {
+            Some(room_id)
+                if self
+                    .accept_room_message(room_id, &peer_info.identity, received.clock)
+                    .await =>
+            {
+                let header = sender_keys::header(
+                    received.id,
+                    received.timestamp,
+                    received.clock,
+                    &received.action,
+                );
+                let decrypted = self.received_sender_keys.lock().await.decrypt(
+                    room_id,
+                    &peer_info.identity,
+                    &header,
+                    received.encrypted.as_ref(),
+                );
+                match decrypted {
+                    Ok(text) => Some(text),
+                    Err(e) => {
+                        warn!(
+                            "Failed to decrypt {} {} of peer {} to room {}: {}",
+                            message_name, received.id, peer_addr, room_id, e
+                        );
+                        return;
+                    }
+                }
+            }
+            Some(_) => None,
+            None => Some(received.text),
+        };
+
+        let update = match text
+            .map(|text| chat_update(received.action, text, received.timestamp))
+            .transpose()
+        {
+            Ok(update) => update,
+            Err(e) => {
+                warn!(
+                    "Peer {} sent an invalid {} {}: {}. Ignoring.",
+                    peer_addr, message_name, received.id, e
+                );
+                return;
+            }
+        };
+
+        let Some((message_id, update)) = update else {
+            debug!(
+                "Peer {} sent {} {} to a room we are not in with it. Ignoring.",
+                peer_addr, message_name, received.id
+            );
+            peer_tx
+                .send(Message::ChatMessageAck(ChatMessageAck { id: received.id }))
+                .await
+                .ok(); // We ignore the error here, as the peer may have already disconnected.
+            return;
+        };
+        let key = match received.room_id {
+            Some(room_id) => ConversationKey::Room(room_id),
+            None => ConversationKey::Peer(peer_info.identity.clone()),
+        };
+
+        // Kept if the message has not arrived yet. Checked with the chat store locked,
+        // so the message cannot arrive in between (see [Self::apply_pending_chat_updates]).
+        {
+            let chat_store = self.chat_store.lock().await;
+            if !chat_store.contains(&key, &message_id.to_string()) {
+                let pending = PendingUpdate {
+                    id: received.id,
+                    by: peer_info,
+                    update,
+                };
+                if self
+                    .pending_chat_updates
+                    .lock()
+                    .await
+                    .push(key, message_id, pending)
+                {
+                    debug!(
+                        "Peer {} sent {} {} of chat message {}, which has not arrived yet. Kept.",
+                        peer_addr, message_name, received.id, message_id
+                    );
+                } else {
+                    warn!(
+                        "Too many changes to chat messages that have not arrived. Peer {} sends {} {} again later.",
+                        peer_addr, message_name, received.id
+                    );
+                }
+                return;
+            }
+        }
+
+        peer_tx
+            .send(Message::ChatMessageAck(ChatMessageAck { id: received.id }))
+            .await
+            .ok(); // We ignore the error here, as the peer may have already disconnected.
+        self.apply_peer_chat_update(&key, message_id, &peer_info, update)
+            .await;
+    }
+
+    /// Apply the changes kept for a chat message that just arrived, and acknowledge them.
+    pub(crate) async fn apply_pending_chat_updates(&self, key: &ConversationKey, message_id: Uuid) {
+        let pending = {
+            // Locked as when the changes were kept (see [Self::receive_chat_update])
+            let _chat_store = self.chat_store.lock().await;
+            self.pending_chat_updates.lock().await.take(key, message_id)
+        };
+        if pending.is_empty() {
+            return;
+        }
+
+        let connected = self.connected_peers().await;
+        for pending in pending {
+            self.apply_peer_chat_update(key, message_id, &pending.by, pending.update)
+                .await;
+            for (_, peer_tx, _) in connected
+                .iter()
+                .filter(|(_, _, peer_info)| peer_info.identity == pending.by.identity)
+            {
+                peer_tx
+                    .send(Message::ChatMessageAck(ChatMessageAck { id: pending.id }))
+                    .await
+                    .ok(); // We ignore the error here, as the peer may have already disconnected.
+            }
+        }
+    }
+
+    /// Apply a change a peer made to a chat message we have, if it may make it, and notify the frontend.
+    async fn apply_peer_chat_update(
+        &self,
+        key: &ConversationKey,
+        message_id: Uuid,
+        by: &PeerInfo,
+        update: ChatUpdate,
+    ) {
+        // The author check is done against the identity of the authenticated peer, not anything it claims
+        let Some(message) = self
+            .store_chat_update(key, &message_id.to_string(), Some(by), update)
+            .await
+        else {
+            debug!(
+                "Peer {} sent a change to chat message {}, which changes nothing or is not its own. Ignoring.",
+                by.identity, message_id
+            );
+            return;
+        };
+
+        let (peer_identity, room_id) = key.to_event();
+        self.backend_event_tx
+            .send(BackendEvent::ChatMessageUpdated(ChatMessageUpdated {
+                peer_identity,
+                room_id,
+                message,
+                by: Some(by.identity.clone()),
+                by_name: Some(by.name.clone()),
+            }))
+            .await
+            .expect("Failed to send ChatMessageUpdated event to the frontend");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending(id: Uuid) -> PendingUpdate {
+        PendingUpdate {
+            id,
+            by: PeerInfo {
+                name: "alice".to_string(),
+                identity: "identity".to_string(),
+                backend_version: String::new(),
+                compression: Vec::new(),
+            },
+            update: ChatUpdate::Delete,
+        }
+    }
+
+    #[test]
+    fn pending_updates_are_kept_once_and_bounded() {
+        let mut pending_updates = PendingUpdates::default();
+        let key = ConversationKey::Peer("identity".to_string());
+        let (message_id, id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        assert!(pending_updates.push(key.clone(), message_id, pending(id)));
+        // Sent again before the message arrived
+        assert!(pending_updates.push(key.clone(), message_id, pending(id)));
+        assert!(pending_updates.take(&key, Uuid::new_v4()).is_empty());
+        let taken = pending_updates.take(&key, message_id);
+        assert_eq!(taken.len(), 1);
+        assert_eq!(taken[0].id, id);
+        assert!(pending_updates.take(&key, message_id).is_empty());
+
+        for _ in 0..MAX_PENDING_UPDATES {
+            assert!(pending_updates.push(key.clone(), Uuid::new_v4(), pending(Uuid::new_v4())));
+        }
+        assert!(!pending_updates.push(key.clone(), message_id, pending(id)));
+        assert!(pending_updates.take(&key, message_id).is_empty());
+    }
+}
@@ -0,0 +1,215 @@
+//! # Chunk Sizing
+//!
+//! Picks the length of outgoing file chunks.
+//!
+//! The maximum chunk length is negotiated in the `FileOffer`/`FileOfferResponse` exchange,
+//! bounded by [MIN_CHUNK_LEN] and [MAX_CHUNK_LEN] (which fits within [MAX_MESSAGE_SIZE]).
+//! Within those bounds the sender adapts the chunk length as the transfer progresses:
+//!
+//! - The initial length depends on the file size, and the link quality measured by previous
+//!   transfers with the same peer (if any).
+//! - Once throughput is measured, chunks are sized to take roughly [TARGET_CHUNK_TIME] on the wire.
+//!   Large chunks suit fast LAN links, small chunks suit slow links.
+//! - Retransmissions (lossy links) halve the chunk length, so less is resent per loss.
+//!
+//! Chunks are located by byte offset, so changing the length mid-transfer is always safe.
+//!
+//! [MIN_CHUNK_LEN]: super::protocol::MIN_CHUNK_LEN
+//! [MAX_CHUNK_LEN]: super::protocol::MAX_CHUNK_LEN
+//! [MAX_MESSAGE_SIZE]: super::protocol::MAX_MESSAGE_SIZE
+
+use std::time::Duration;
+
+use super::{
+    flow_control::SlidingWindow,
+    merkle::BLOCK_LEN,
+    protocol::{MAX_CHUNK_LEN, MIN_CHUNK_LEN},
+};
+
+/// How long a single chunk should take on the wire.
+pub const TARGET_CHUNK_TIME: Duration = Duration::from_millis(100);
+
+/// Chunk length used when nothing is known about the link (1 MB).
+pub const DEFAULT_CHUNK_LEN: u64 = 1024 * 1024;
+
+/// Chunk lengths are multiples of this (16 KB), the Merkle tree's block length.
+const CHUNK_LEN_ALIGN: u64 = BLOCK_LEN;
+
+/// Loss rate above which the link is considered lossy.
+const LOSSY_LINK: f64 = 0.02;
+
+/// Link quality measured by a previous transfer with a peer.
+#[derive(Debug, Clone, Copy)]
+pub struct LinkQuality {
+    /// Smoothed round trip time
+    pub srtt: Option<Duration>,
+    /// Throughput in bytes per second
+    pub throughput: f64,
+    /// Fraction of chunks that had to be retransmitted
+    pub loss_rate: f64,
+}
+
+impl From<&SlidingWindow> for LinkQuality {
+    fn from(window: &SlidingWindow) -> Self {
+        Self {
+            srtt: window.srtt(),
+            throughput: window.throughput(),
+            loss_rate: window.loss_rate(),
+        }
+    }
+}
+
+/// Adaptive chunk length for a single outgoing file transfer.
+#[derive(Debug)]
+pub struct ChunkSizer {
+    /// Current chunk length
+    chunk_len: u64,
+    /// Maximum chunk length negotiated with the peer
+    max_chunk_len: u64,
+}
+
+impl ChunkSizer {
+    /// Create a new chunk sizer for a file of `file_size` bytes.
+    pub fn new(file_size: u64, max_chunk_len: u64, link_quality: Option<LinkQuality>) -> Self {
+        // Chunks must stay aligned to the Merkle tree's blocks, whatever the peer agreed to
+        let max_chunk_len =
+            max_chunk_len.clamp(MIN_CHUNK_LEN, MAX_CHUNK_LEN) / CHUNK_LEN_ALIGN * CHUNK_LEN_ALIGN;
+
+        let chunk_len = match link_quality {
+            // Lossy link, start small
+            Some(link_quality) if link_quality.loss_rate > LOSSY_LINK => MIN_CHUNK_LEN * 4,
+            // Known link, size for the measured throughput
+            Some(link_quality) if link_quality.throughput > 0.0 => {
+                target_chunk_len(link_quality.throughput)
+            }
+            // Unknown link, use the default
+            _ => DEFAULT_CHUNK_LEN,
+        };
+
+        // No point in sending a small file in just one or two chunks, split it up a bit
+        // so the window (and progress) has something to work with.
+        let chunk_len = chunk_len.min(file_size.div_ceil(8));
+
+        let mut sizer = Self {
+            chunk_len: 0,
+            max_chunk_len,
+        };
+        sizer.set_chunk_len(chunk_len);
+        sizer
+    }
+
+    /// The current chunk length
+    pub fn chunk_len(&self) -> u64 {
+        self.chunk_len
+    }
+
+    /// Adapt the chunk length after an ack.
+    ///
+    /// Moves at most a factor of two towards the target per call, so a single noisy
+    /// throughput sample does not swing the chunk length wildly.
+    pub fn on_ack(&mut self, window: &SlidingWindow) {
+        if window.throughput() == 0.0 || window.loss_rate() > LOSSY_LINK {
+            return;
+        }
+
+        let target = target_chunk_len(window.throughput());
+        let chunk_len = if target > self.chunk_len {
+            target.min(self.chunk_len * 2)
+        } else {
+            target.max(self.chunk_len / 2)
+        };
+        self.set_chunk_len(chunk_len);
+    }
+
+    /// Halve the chunk length after an ack timeout.
+    pub fn on_timeout(&mut self) {
+        self.set_chunk_len(self.chunk_len / 2);
+    }
+
+    /// Set the chunk length, aligned and within bounds.
+    fn set_chunk_len(&mut self, chunk_len: u64) {
+        self.chunk_len = (chunk_len / CHUNK_LEN_ALIGN * CHUNK_LEN_ALIGN)
+            .clamp(MIN_CHUNK_LEN, self.max_chunk_len);
+    }
+}
+
+/// Chunk length that takes [TARGET_CHUNK_TIME] to send at `throughput` bytes per second.
+fn target_chunk_len(throughput: f64) -> u64 {
+    (throughput * TARGET_CHUNK_TIME.as_secs_f64()) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::flow_control::FlowControlConfig;
+
+    const GB: u64 = 1024 * 1024 * 1024;
+
+    fn link(throughput: f64, loss_rate: f64) -> Option<LinkQuality> {
+        Some(LinkQuality {
+            srtt: None,
+            throughput,
+            loss_rate,
+        })
+    }
+
+    #[test]
+    fn initial_length_depends_on_file_and_link() {
+        assert_eq!(
+            ChunkSizer::new(GB, MAX_CHUNK_LEN, None).chunk_len(),
+            DEFAULT_CHUNK_LEN
+        );
+        // About a hundred milliseconds at 10 MB/s, rounded down to whole blocks
+        assert_eq!(
+            ChunkSizer::new(GB, MAX_CHUNK_LEN, link(10_000_000.0, 0.0)).chunk_len(),
+            61 * BLOCK_LEN
+        );
+        assert_eq!(
+            ChunkSizer::new(GB, MAX_CHUNK_LEN, link(10_000_000.0, 0.1)).chunk_len(),
+            MIN_CHUNK_LEN * 4
+        );
+        // Small files are split in about 8 chunks, but never below the minimum
+        assert_eq!(
+            ChunkSizer::new(1024 * 1024, MAX_CHUNK_LEN, None).chunk_len(),
+            128 * 1024
+        );
+        assert_eq!(
+            ChunkSizer::new(1000, MAX_CHUNK_LEN, None).chunk_len(),
+            MIN_CHUNK_LEN
+        );
+    }
+
+    #[test]
+    fn lengths_are_aligned_and_within_limits() {
+        // An unaligned maximum from the peer is rounded down to whole blocks
+        assert_eq!(
+            ChunkSizer::new(GB, 100_000, None).chunk_len(),
+            6 * BLOCK_LEN
+        );
+        assert_eq!(ChunkSizer::new(GB, 1, None).chunk_len(), MIN_CHUNK_LEN);
+        assert_eq!(
+            ChunkSizer::new(GB, u64::MAX, link(1e12, 0.0)).chunk_len(),
+            MAX_CHUNK_LEN
+        );
+        for throughput in [1.0, 123_456.0, 3e6, 7.7e7, 1e10] {
+            let chunk_len = ChunkSizer::new(GB, MAX_CHUNK_LEN, link(throughput, 0.0)).chunk_len();
+            assert_eq!(chunk_len % BLOCK_LEN, 0);
+            assert!((MIN_CHUNK_LEN..=MAX_CHUNK_LEN).contains(&chunk_len));
+        }
+    }
+
+    #[test]
+    fn timeouts_halve_the_length() {
+        let mut sizer = ChunkSizer::new(GB, MAX_CHUNK_LEN, None);
+        sizer.on_timeout();
+        assert_eq!(sizer.chunk_len(), DEFAULT_CHUNK_LEN / 2);
+        for _ in 0..20 {
+            sizer.on_timeout();
+        }
+        assert_eq!(sizer.chunk_len(), MIN_CHUNK_LEN);
+
+        // Nothing measured yet, nothing to adapt to
+        sizer.on_ack(&SlidingWindow::new(FlowControlConfig::default()));
+        assert_eq!(sizer.chunk_len(), MIN_CHUNK_LEN);
+    }
+}
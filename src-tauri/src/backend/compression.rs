@@ -0,0 +1,231 @@
+//! # Compression
+//!
+//! Optional compression of `FileChunk` payloads.
+//!
+//! - Peers list the algorithms they support in the connect handshake (see [SUPPORTED]).
+//! - The sender picks one per transfer, announced in the `FileOffer`: the first algorithm we prefer
+//!   that the peer supports. None if every file of the transfer is already compressed,
+//!   judging by its magic bytes (archives, images, audio and video).
+//! - A chunk is only compressed if a sample of it does not look random (high byte entropy),
+//!   and is sent raw if compressing it does not make it smaller.
+//! - Chunks are verified against the Merkle root once decompressed.
+//!
+//! Progress reports both the file bytes transferred and the wire bytes (the chunk payloads sent).
+
+use std::{net::SocketAddr, path::Path};
+
+use tokio::{fs::File, io::AsyncReadExt};
+
+use super::{
+    manifest::Manifest,
+    peer_manager::{PeerManager, PeerState},
+    protocol::Compression,
+};
+
+/// The compression algorithms we support, preferred first
+pub const SUPPORTED: [Compression; 2] = [Compression::Zstd, Compression::Lz4];
+
+/// zstd compression level (fast, yet close to the default ratio)
+const ZSTD_LEVEL: i32 = 3;
+
+/// Entropy (in bits per byte) above which a chunk is not worth compressing
+const MAX_ENTROPY: f64 = 7.5;
+
+/// Number of bytes of a chunk sampled for its entropy
+const ENTROPY_SAMPLE_LEN: usize = 64 * 1024;
+
+/// Magic bytes of already compressed file types, and their offset in the file
+const COMPRESSED_MAGIC: &[(usize, &[u8])] = &[
+    (0, b"\x1f\x8b"),           // gzip
+    (0, b"PK\x03\x04"),         // zip (and docx, jar, apk...)
+    (0, b"\x28\xb5\x2f\xfd"),   // zstd
+    (0, b"\xfd7zXZ\x00"),       // xz
+    (0, b"BZh"),                // bzip2
+    (0, b"7z\xbc\xaf\x27\x1c"), // 7z
+    (0, b"Rar!\x1a\x07"),       // rar
+    (0, b"\x04\x22\x4d\x18"),   // lz4
+    (0, b"\x89PNG\r\n\x1a\n"),  // png
+    (0, b"\xff\xd8\xff"),       // jpeg
+    (0, b"GIF8"),               // gif
+    (0, b"ID3"),                // mp3
+    (0, b"OggS"),               // ogg
+    (0, b"fLaC"),               // flac
+    (0, b"\x1a\x45\xdf\xa3"),   // mkv, webm
+    (4, b"ftyp"),               // mp4, mov, heic
+    (8, b"WEBP"),               // webp
+];
+
+/// Pick the compression of a transfer to a peer supporting `peer_supported`.
+pub fn negotiate(peer_supported: &[Compression]) -> Option<Compression> {
+    SUPPORTED
+        .into_iter()
+        .find(|compression| peer_supported.contains(compression))
+}
+
+/// Is the file already compressed, judging by its magic bytes?
+async fn is_compressed_file(path: &Path) -> bool {
+    let Ok(mut file) = File::open(path).await else {
+        return false;
+    };
+    let mut header = [0u8; 16];
+    let mut len = 0;
+    while len < header.len() {
+        match file.read(&mut header[len..]).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => len += n,
+        }
+    }
+    let header = &header[..len];
+    COMPRESSED_MAGIC
+        .iter()
+        .any(|(offset, magic)| header.get(*offset..*offset + magic.len()) == Some(*magic))
+}
+
+/// Shannon entropy of the bytes of `data`, in bits per byte.
+fn entropy(data: &[u8]) -> f64 {
+    let mut counts = [0u64; 256];
+    for byte in data {
+        counts[*byte as usize] += 1;
+    }
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Compress a chunk. None if it is not worth it: the chunk looks random, or does not get smaller.
+pub fn compress(compression: Compression, data: &[u8]) -> Option<Vec<u8>> {
+    if data.is_empty() || entropy(&data[..data.len().min(ENTROPY_SAMPLE_LEN)]) > MAX_ENTROPY {
+        return None;
+    }
+    let compressed = match compression {
+        Compression::Zstd => zstd::bulk::compress(data, ZSTD_LEVEL).ok()?,
+        Compression::Lz4 => lz4_flex::block::compress_prepend_size(data),
+    };
+    (compressed.len() < data.len()).then_some(compressed)
+}
+
+/// Decompress a chunk of at most `max_len` bytes.
+pub fn decompress(compression: Compression, data: &[u8], max_len: u64) -> Result<Vec<u8>, String> {
+    match compression {
+        Compression::Zstd => zstd::bulk::decompress(data, max_len as usize)
+            .map_err(|e| format!("Invalid zstd data: {}", e)),
+        Compression::Lz4 => {
+            // The decompressed length is prepended, check it before allocating
+            let len = data
+                .get(..4)
+                .map(|len| u32::from_le_bytes(len.try_into().expect("4 bytes")))
+                .ok_or("Invalid lz4 data: too short")?;
+            if len as u64 > max_len {
+                return Err(format!("Invalid lz4 data: {} bytes is too long", len));
+            }
+            lz4_flex::block::decompress_size_prepended(data)
+                .map_err(|e| format!("Invalid lz4 data: {}", e))
+        }
+    }
+}
+
+impl PeerManager {
+    /// Pick the compression of an outgoing transfer, once its files are hashed.
+    pub(crate) async fn choose_compression(
+        &self,
+        peer_addr: SocketAddr,
+        manifest: &Manifest,
+    ) -> Option<Compression> {
+        let peer_supported = match &self.active_peers.lock().await.get(&peer_addr)?.state {
+            PeerState::Authenticated { peer_info } => peer_info.compression.clone(),
+            _ => return None,
+        };
+        let compression = negotiate(&peer_supported)?;
+
+        // Not worth it if every file is already compressed
+        for file in manifest.files.iter().filter(|file| file.size > 0) {
+            if !is_compressed_file(&file.local_path).await {
+                return Some(compression);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Text-like data, which compresses well.
+    fn text(len: usize) -> Vec<u8> {
+        b"the quick brown fox jumps over the lazy dog, "
+            .iter()
+            .copied()
+            .cycle()
+            .take(len)
+            .collect()
+    }
+
+    /// Pseudo-random data, which does not compress.
+    fn random(len: usize) -> Vec<u8> {
+        let mut state: u32 = 1;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = text(256 * 1024);
+        for compression in SUPPORTED {
+            let compressed = compress(compression, &data).expect("text compresses");
+            assert!(compressed.len() < data.len());
+            assert_eq!(
+                decompress(compression, &compressed, data.len() as u64).unwrap(),
+                data
+            );
+        }
+    }
+
+    #[test]
+    fn incompressible_data_is_sent_raw() {
+        for compression in SUPPORTED {
+            assert!(compress(compression, &random(64 * 1024)).is_none());
+            assert!(compress(compression, &[]).is_none());
+        }
+    }
+
+    #[test]
+    fn rejects_output_larger_than_chunk_len() {
+        let data = text(256 * 1024);
+        for compression in SUPPORTED {
+            let compressed = compress(compression, &data).unwrap();
+            assert!(decompress(compression, &compressed, data.len() as u64 - 1).is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_data() {
+        let data = text(64 * 1024);
+        for compression in SUPPORTED {
+            let mut compressed = compress(compression, &data).unwrap();
+            compressed.truncate(compressed.len() / 2);
+            assert!(decompress(compression, &compressed, data.len() as u64).is_err());
+        }
+        assert!(decompress(Compression::Lz4, &[1, 2], 1024).is_err());
+    }
+
+    #[test]
+    fn negotiates_our_preference() {
+        assert_eq!(
+            negotiate(&[Compression::Lz4, Compression::Zstd]),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(negotiate(&[Compression::Lz4]), Some(Compression::Lz4));
+        assert_eq!(negotiate(&[]), None);
+    }
+}
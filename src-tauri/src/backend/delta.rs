@@ -0,0 +1,661 @@
+//! # Delta Transfer
+//!
+//! rsync-style delta encoding of the files the receiver has an older version of.
+//!
+//! - When accepting an offer, the receiver looks for an existing file at the final path of every
+//!   file of at least [MIN_DELTA_SIZE] bytes: the older version the transfer would replace.
+//!   For each, it sends a `BlockSignatures` message before the `FileOfferResponse`, with a rolling
+//!   checksum and a strong (BLAKE3) checksum of every block of its copy.
+//! - The sender looks for those blocks at every byte offset of its own file, using the rolling
+//!   checksum, and sends the blocks found as `FileDelta` copy instructions before any chunk.
+//! - Everything else is sent as literal data, in the usual `FileChunk`s, so it is still verified
+//!   against the Merkle root. The data copied is verified by the file's checksum once complete.
+//! - If a file fails that check (its older version changed meanwhile), the receiver falls back to
+//!   receiving it in full: it asks the sender for the file again with a `RangeRequest`.
+//!   The sender waits for such requests until the transfer ends, even after `FileDone`.
+//!
+//! Files the receiver already has (see [super::hash_index]) and multi-source downloads do not use delta transfer.
+
+use std::{
+    collections::HashMap,
+    io::{BufReader, ErrorKind, Read},
+    path::{Path, PathBuf},
+};
+
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncSeekExt},
+    sync::mpsc,
+};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use super::{
+    byte_ranges::ByteRanges,
+    manifest::FileSet,
+    merkle::BLOCK_LEN,
+    peer_manager::PeerManager,
+    protocol::{BlockSignature, BlockSignatures, DeltaCopy, FileDelta, Message, RangeRequest},
+};
+
+/// Minimum size of a file for the receiver to send the block signatures of its older version (1 MB)
+pub const MIN_DELTA_SIZE: u64 = 1024 * 1024; // 1 MB
+
+/// Maximum number of block signatures of a file, so they fit in a message
+const MAX_BLOCKS: u64 = 100_000;
+
+/// Maximum length of a signed block (8 MB). Larger files are not delta encoded.
+const MAX_BLOCK_LEN: u64 = 1024 * 1024 * 8; // 8 MB
+
+/// Length of the strong checksum of a block (a truncated BLAKE3 hash)
+const STRONG_LEN: usize = 16;
+
+/// Maximum number of copy instructions per `FileDelta` message
+const MAX_COPIES_PER_MESSAGE: usize = 65_536;
+
+/// Minimum length of a read when looking for blocks in a file (1 MB)
+const MIN_READ_LEN: usize = 1024 * 1024; // 1 MB
+
+/// The rsync rolling checksum of a window of bytes, which can be moved by a byte in constant time.
+struct RollingChecksum {
+    a: u32,
+    b: u32,
+    len: u32,
+}
+
+impl RollingChecksum {
+    fn new(data: &[u8]) -> Self {
+        let len = data.len() as u32;
+        let mut a: u32 = 0;
+        let mut b: u32 = 0;
+        for (i, byte) in data.iter().enumerate() {
+            a = a.wrapping_add(*byte as u32);
+            b = b.wrapping_add((len - i as u32).wrapping_mul(*byte as u32));
+        }
+        Self { a, b, len }
+    }
+
+    /// Move the window by a byte: `out` leaves it, `byte` enters it.
+    fn roll(&mut self, out: u8, byte: u8) {
+        self.a = self.a.wrapping_sub(out as u32).wrapping_add(byte as u32);
+        self.b = self
+            .b
+            .wrapping_sub(self.len.wrapping_mul(out as u32))
+            .wrapping_add(self.a);
+    }
+
+    fn digest(&self) -> u32 {
+        (self.a & 0xffff) | (self.b << 16)
+    }
+}
+
+/// The strong checksum of a block.
+fn strong_checksum(data: &[u8]) -> Vec<u8> {
+    blake3::hash(data).as_bytes()[..STRONG_LEN].to_vec()
+}
+
+/// The block length used to sign a file of `size` bytes. None if the file is too large.
+fn block_len(size: u64) -> Option<u64> {
+    let block_len = size
+        .div_ceil(MAX_BLOCKS)
+        .next_multiple_of(1024)
+        .max(BLOCK_LEN);
+    (block_len <= MAX_BLOCK_LEN).then_some(block_len)
+}
+
+/// Are the block signatures the peer sent usable?
+pub fn check_signatures(signatures: &BlockSignatures) -> bool {
+    (BLOCK_LEN..=MAX_BLOCK_LEN).contains(&signatures.block_len)
+        && signatures.blocks.len() as u64 <= MAX_BLOCKS
+        && signatures
+            .blocks
+            .iter()
+            .all(|block| block.strong.len() == STRONG_LEN)
+}
+
+/// Sign every full block of a file.
+fn sign_file(path: &Path, block_len: u64) -> std::io::Result<Vec<BlockSignature>> {
+    let mut reader = BufReader::new(std::fs::File::open(path)?);
+    let mut block = vec![0u8; block_len as usize];
+    let mut blocks = Vec::new();
+    loop {
+        match reader.read_exact(&mut block) {
+            Ok(()) => blocks.push(BlockSignature {
+                weak: RollingChecksum::new(&block).digest(),
+                strong: strong_checksum(&block),
+            }),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(blocks),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// A file read in large pieces, through a window moved along it.
+struct FileWindow<R> {
+    reader: R,
+    buf: Vec<u8>,
+    /// Start of the window in `buf`
+    start: usize,
+    read_len: usize,
+}
+
+impl<R: Read> FileWindow<R> {
+    fn new(reader: R, block_len: usize) -> Self {
+        let read_len = block_len.max(MIN_READ_LEN);
+        Self {
+            reader,
+            buf: Vec::with_capacity(block_len + read_len),
+            start: 0,
+            read_len,
+        }
+    }
+
+    /// Make sure `len` bytes are read from the start of the window. False at the end of the file.
+    fn fill(&mut self, len: usize) -> std::io::Result<bool> {
+        if self.buf.len() - self.start >= len {
+            return Ok(true);
+        }
+        // Drop what the window has left behind, then read more
+        self.buf.drain(..self.start);
+        self.start = 0;
+        while self.buf.len() < len {
+            let filled = self.buf.len();
+            self.buf.resize(filled + self.read_len, 0);
+            let n = loop {
+                match self.reader.read(&mut self.buf[filled..]) {
+                    Ok(n) => break n,
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        self.buf.truncate(filled);
+                        return Err(e);
+                    }
+                }
+            };
+            self.buf.truncate(filled + n);
+            if n == 0 {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// The `len` bytes from the start of the window, once filled.
+    fn get(&self, len: usize) -> &[u8] {
+        &self.buf[self.start..self.start + len]
+    }
+
+    fn advance(&mut self, n: usize) {
+        self.start += n;
+    }
+}
+
+/// Find the blocks of the peer's older version in a file, at any byte offset.
+///
+/// Returns the copy instructions, in order, with adjacent copies merged.
+fn find_copies(path: &Path, signatures: &BlockSignatures) -> std::io::Result<Vec<DeltaCopy>> {
+    find_copies_in(std::fs::File::open(path)?, signatures)
+}
+
+fn find_copies_in(
+    reader: impl Read,
+    signatures: &BlockSignatures,
+) -> std::io::Result<Vec<DeltaCopy>> {
+    let block_len = signatures.block_len;
+    let mut by_weak: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (i, block) in signatures.blocks.iter().enumerate() {
+        by_weak.entry(block.weak).or_default().push(i);
+    }
+
+    let len = block_len as usize;
+    let mut window = FileWindow::new(reader, len);
+    let mut offset = 0;
+    let mut copies: Vec<DeltaCopy> = Vec::new();
+    'blocks: loop {
+        // The window on the next block
+        if !window.fill(len)? {
+            break;
+        }
+
+        let mut rolling = RollingChecksum::new(window.get(len));
+        loop {
+            let source = by_weak.get(&rolling.digest()).and_then(|candidates| {
+                let strong = strong_checksum(window.get(len));
+                candidates
+                    .iter()
+                    .find(|i| signatures.blocks[**i].strong == strong)
+            });
+            if let Some(source) = source {
+                let source_offset = *source as u64 * block_len;
+                match copies.last_mut() {
+                    Some(last)
+                        if last.offset + last.len == offset
+                            && last.source_offset + last.len == source_offset =>
+                    {
+                        last.len += block_len;
+                    }
+                    _ => copies.push(DeltaCopy {
+                        offset,
+                        source_offset,
+                        len: block_len,
+                    }),
+                }
+                offset += block_len;
+                window.advance(len);
+                continue 'blocks;
+            }
+
+            // No block here: the first byte of the window is literal data
+            if !window.fill(len + 1)? {
+                break 'blocks;
+            }
+            let bytes = window.get(len + 1);
+            rolling.roll(bytes[0], bytes[len]);
+            window.advance(1);
+            offset += 1;
+        }
+    }
+    Ok(copies)
+}
+
+/// Copy ranges of an older version of a file into the file being received, starting at `file_offset` of the stream.
+pub(crate) async fn apply_copies(
+    files: &FileSet,
+    base: &Path,
+    file_offset: u64,
+    copies: &[DeltaCopy],
+) -> std::io::Result<()> {
+    let mut base = File::open(base).await?;
+    let mut buf = vec![0u8; 1024 * 1024];
+    for copy in copies {
+        base.seek(std::io::SeekFrom::Start(copy.source_offset))
+            .await?;
+        let mut done = 0;
+        while done < copy.len {
+            let n = (copy.len - done).min(buf.len() as u64) as usize;
+            base.read_exact(&mut buf[..n]).await?;
+            files
+                .write_at(file_offset + copy.offset + done, &buf[..n])
+                .await?;
+            done += n as u64;
+        }
+    }
+    Ok(())
+}
+
+impl PeerManager {
+    /// Fall back to receiving a file in full, after the data copied from its older version failed
+    /// to verify: forget what was received of it, and ask the peer for it again.
+    ///
+    /// Returns false if nothing was copied to the file, or it was already asked for again.
+    pub(crate) async fn receive_without_delta(&self, unique_id: Uuid, index: usize) -> bool {
+        let (peer_addr, offset, len) = {
+            let mut active_transfers = self.active_transfers.lock().await;
+            let Some(transfer) = active_transfers.get_mut(&unique_id) else {
+                return false;
+            };
+            if transfer.delta_bases.remove(&index).is_none() {
+                return false;
+            }
+            transfer.resent_files.insert(index);
+            let Some(file) = transfer.manifest.files.get(index) else {
+                return false;
+            };
+            let (start, end) = (file.offset, file.offset + file.size);
+            transfer.bytes_transferred -= transfer.received_ranges.remove(start, end);
+            // Ranges are requested in whole blocks
+            let start = start - start % BLOCK_LEN;
+            let end = end.next_multiple_of(BLOCK_LEN).min(transfer.total_size);
+            (transfer.peer_addr, start, end - start)
+        };
+
+        let Some(peer_tx) = self
+            .active_peers
+            .lock()
+            .await
+            .get(&peer_addr)
+            .map(|peer| peer.tx.clone())
+        else {
+            return false;
+        };
+        peer_tx
+            .send(Message::RangeRequest(RangeRequest {
+                unique_id,
+                offset,
+                len,
+            }))
+            .await
+            .is_ok()
+    }
+
+    /// Send the block signatures of the older versions we have of the files of an accepted offer.
+    ///
+    /// To be sent before the `FileOfferResponse`. Files in `have_files` are skipped.
+    pub(crate) async fn send_block_signatures(
+        &self,
+        unique_id: Uuid,
+        peer_tx: &mpsc::Sender<Message>,
+        have_files: &[u32],
+    ) {
+        let candidates: Vec<(usize, PathBuf)> = self
+            .active_transfers
+            .lock()
+            .await
+            .get(&unique_id)
+            .map(|transfer| {
+                transfer
+                    .manifest
+                    .files
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, file)| {
+                        file.size >= MIN_DELTA_SIZE && !have_files.contains(&(*index as u32))
+                    })
+                    .map(|(index, file)| (index, file.local_path.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        for (index, path) in candidates {
+            // The older version is the file the transfer would replace
+            let Some(block_len) = fs::metadata(&path)
+                .await
+                .ok()
+                .filter(|metadata| metadata.is_file())
+                .and_then(|metadata| block_len(metadata.len()))
+            else {
+                continue;
+            };
+            let base = path.clone();
+            let blocks =
+                match tokio::task::spawn_blocking(move || sign_file(&base, block_len)).await {
+                    Ok(Ok(blocks)) if !blocks.is_empty() => blocks,
+                    Ok(Ok(_)) => continue,
+                    Ok(Err(e)) => {
+                        warn!("Failed to sign {}: {}", path.display(), e);
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!("Failed to sign {}: {}", path.display(), e);
+                        continue;
+                    }
+                };
+
+            match self.active_transfers.lock().await.get_mut(&unique_id) {
+                Some(transfer) => {
+                    transfer.delta_bases.insert(index, path.clone());
+                }
+                None => return,
+            }
+            debug!(
+                "Sending {} block signatures of {} for file transfer {}",
+                blocks.len(),
+                path.display(),
+                unique_id
+            );
+            peer_tx
+                .send(Message::BlockSignatures(BlockSignatures {
+                    unique_id,
+                    file_index: index as u32,
+                    block_len,
+                    blocks,
+                }))
+                .await
+                .ok(); // We ignore the error here, as the peer may have already disconnected.
+        }
+    }
+
+    /// Send the copy instructions of every file the peer sent block signatures of.
+    ///
+    /// The ranges copied are added to `skip`, so they are not sent as chunks.
+    pub(crate) async fn send_file_deltas(
+        &self,
+        unique_id: Uuid,
+        peer_tx: &mpsc::Sender<Message>,
+        skip: &mut ByteRanges,
+    ) {
+        let deltas: Vec<(BlockSignatures, PathBuf, u64, u64)> = {
+            let mut active_transfers = self.active_transfers.lock().await;
+            let Some(transfer) = active_transfers.get_mut(&unique_id) else {
+                return;
+            };
+            std::mem::take(&mut transfer.block_signatures)
+                .into_values()
+                .filter_map(|signatures| {
+                    let file = transfer
+                        .manifest
+                        .files
+                        .get(signatures.file_index as usize)?;
+                    Some((signatures, file.local_path.clone(), file.offset, file.size))
+                })
+                .collect()
+        };
+
+        for (signatures, path, file_offset, size) in deltas {
+            // The peer already has the file
+            if skip.contains(file_offset, file_offset + size) {
+                continue;
+            }
+
+            let file_index = signatures.file_index;
+            let source = path.clone();
+            let copies = match tokio::task::spawn_blocking(move || {
+                find_copies(&source, &signatures)
+            })
+            .await
+            {
+                Ok(Ok(copies)) => copies,
+                Ok(Err(e)) => {
+                    warn!("Failed to delta encode {}: {}", path.display(), e);
+                    continue;
+                }
+                Err(e) => {
+                    warn!("Failed to delta encode {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            // The file may have grown since it was offered
+            let copies: Vec<DeltaCopy> = copies
+                .into_iter()
+                .filter(|copy| copy.offset + copy.len <= size)
+                .collect();
+            if copies.is_empty() {
+                continue;
+            }
+
+            let copied: u64 = copies.iter().map(|copy| copy.len).sum();
+            info!(
+                "File transfer {}: {} of {} bytes of {} are copied from the peer's older version",
+                unique_id,
+                copied,
+                size,
+                path.display()
+            );
+            for copy in &copies {
+                skip.insert(
+                    file_offset + copy.offset,
+                    file_offset + copy.offset + copy.len,
+                );
+            }
+            for copies in copies.chunks(MAX_COPIES_PER_MESSAGE) {
+                if peer_tx
+                    .send(Message::FileDelta(FileDelta {
+                        unique_id,
+                        file_index,
+                        copies: copies.to_vec(),
+                    }))
+                    .await
+                    .is_err()
+                {
+                    // The peer disconnected, sending the chunks fails as well
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{manifest::Manifest, protocol::FileMetadata};
+
+    const BLOCK: usize = 1024;
+
+    /// Pseudo-random bytes, so blocks do not repeat.
+    fn random_bytes(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(2_654_435_761).wrapping_add(1);
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    fn signatures(old: &[u8]) -> BlockSignatures {
+        BlockSignatures {
+            unique_id: Uuid::nil(),
+            file_index: 1,
+            block_len: BLOCK as u64,
+            blocks: old
+                .chunks_exact(BLOCK)
+                .map(|block| BlockSignature {
+                    weak: RollingChecksum::new(block).digest(),
+                    strong: strong_checksum(block),
+                })
+                .collect(),
+        }
+    }
+
+    /// A new version of `old`: some bytes inserted, a block changed, and a block dropped.
+    fn new_version(old: &[u8]) -> Vec<u8> {
+        let mut new = random_bytes(100, 7);
+        new.extend_from_slice(&old[..3 * BLOCK]);
+        new.extend_from_slice(&random_bytes(BLOCK, 8));
+        new.extend_from_slice(&old[4 * BLOCK..6 * BLOCK]);
+        new.extend_from_slice(&old[7 * BLOCK..]);
+        new.extend_from_slice(&random_bytes(10, 9));
+        new
+    }
+
+    #[test]
+    fn roll_matches_new() {
+        let data = random_bytes(4 * BLOCK, 1);
+        let mut rolling = RollingChecksum::new(&data[..BLOCK]);
+        for start in 1..=data.len() - BLOCK {
+            rolling.roll(data[start - 1], data[start + BLOCK - 1]);
+            assert_eq!(
+                rolling.digest(),
+                RollingChecksum::new(&data[start..start + BLOCK]).digest()
+            );
+        }
+    }
+
+    #[test]
+    fn find_copies_at_any_offset() {
+        let old = random_bytes(10 * BLOCK + 300, 2);
+        let new = new_version(&old);
+        let copies = find_copies_in(new.as_slice(), &signatures(&old)).unwrap();
+
+        // Adjacent blocks are merged: the runs of blocks kept, at the offsets they moved to
+        let found: Vec<(u64, u64, u64)> = copies
+            .iter()
+            .map(|copy| (copy.offset, copy.source_offset, copy.len))
+            .collect();
+        let block = BLOCK as u64;
+        assert_eq!(
+            found,
+            vec![
+                (100, 0, 3 * block),
+                (100 + 4 * block, 4 * block, 2 * block),
+                (100 + 6 * block, 7 * block, 3 * block),
+            ]
+        );
+        for copy in &copies {
+            let (offset, source_offset, len) = (
+                copy.offset as usize,
+                copy.source_offset as usize,
+                copy.len as usize,
+            );
+            assert_eq!(
+                new[offset..offset + len],
+                old[source_offset..source_offset + len]
+            );
+        }
+    }
+
+    #[test]
+    fn find_copies_in_unrelated_data() {
+        let old = random_bytes(4 * BLOCK, 3);
+        let new = random_bytes(4 * BLOCK, 4);
+        assert!(
+            find_copies_in(new.as_slice(), &signatures(&old))
+                .unwrap()
+                .is_empty()
+        );
+        // Shorter than a block
+        assert!(
+            find_copies_in(&old[..BLOCK - 1], &signatures(&old))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn apply_copies_round_trip() {
+        let dir = std::env::temp_dir().join(format!("kuaip2p-delta-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).await.unwrap();
+        let base = dir.join("old");
+        let old = random_bytes(10 * BLOCK + 300, 5);
+        fs::write(&base, &old).await.unwrap();
+
+        // The file received comes second in the stream, so copies are offset by the first file
+        let new = new_version(&old);
+        let first = random_bytes(1000, 6);
+        let mut manifest = Manifest::default();
+        for (name, data) in [("first", &first), ("new", &new)] {
+            manifest.files.push(crate::backend::manifest::ManifestFile {
+                path: name.to_string(),
+                local_path: dir.join(name),
+                size: data.len() as u64,
+                offset: manifest.total_size(),
+                checksum: blake3::hash(data).as_bytes().to_vec(),
+                verified: false,
+                metadata: FileMetadata::default(),
+            });
+        }
+        let files = FileSet::create(&manifest, uuid::Uuid::new_v4())
+            .await
+            .unwrap();
+
+        let signatures = BlockSignatures {
+            blocks: sign_file(&base, BLOCK as u64).unwrap(),
+            ..signatures(&old)
+        };
+        let copies = find_copies_in(new.as_slice(), &signatures).unwrap();
+        let file_offset = first.len() as u64;
+        apply_copies(&files, &base, file_offset, &copies)
+            .await
+            .unwrap();
+
+        // The rest is sent as literal data
+        let mut copied = ByteRanges::new();
+        for copy in &copies {
+            copied.insert(copy.offset, copy.offset + copy.len);
+        }
+        files.write_at(0, &first).await.unwrap();
+        for (start, end) in copied.gaps(new.len() as u64) {
+            files
+                .write_at(file_offset + start, &new[start as usize..end as usize])
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            files.finish_file(1).await.unwrap().as_bytes().as_slice(),
+            manifest.files[1].checksum
+        );
+        files.discard().await;
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -1 +1,490 @@
+//! # ECDSA Identity
+//!
+//! Our long-term identity, and the handshake proving each peer's identity to the other.
+//!
+//! - The identity is a secp256k1 key pair, created on first start and saved to [IDENTITY_FILE].
+//!   A peer is identified by its public key (SEC1, compressed), shown to the frontend in base64.
+//!   Names are only for display: anything that decides what a peer may do uses its identity.
+//! - The handshake:
+//!   1. The initiator sends `ConnectRequest`, with its public key and a nonce: a new ephemeral
+//!      public key, for the end-to-end encrypted session (see [super::sessions]).
+//!   2. The responder answers with `ConnectChallenge`: its own public key and nonce, and its
+//!      signature of the handshake (see [transcript]).
+//!   3. The initiator checks the signature, and sends `ConnectProof` with its own signature.
+//!   4. The responder checks it, and only then asks the frontend to accept the connection.
+//!
+//!   The signatures cover both public keys and both nonces, so neither can be replayed on
+//!   another connection, and the session derived from the nonces is bound to both identities.
+//!   A peer that fails the handshake is disconnected.
+//!
+//! ## Limitation
+//!
+//! The handshake proves who is at the other end of the connection *when it is made*. The
+//! messages sent after it are neither signed nor encrypted (only the secrets sealed with the
+//! session are, see [super::sessions]), so an attacker on the path between two peers can let
+//! the handshake through, then inject or change messages. Trusting a peer by identity
+//! (auto-accept rules, shares limited to some peers, room membership) therefore only holds
+//! against peers that are not on the path, such as other hosts on the LAN spoofing a name.
 
+use std::net::SocketAddr;
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use k256::{
+    PublicKey, SecretKey,
+    ecdsa::{
+        Signature, SigningKey, VerifyingKey,
+        signature::{Signer, Verifier},
+    },
+};
+use tracing::{info, warn};
+
+use crate::js_api::backend_event;
+
+use super::{
+    compression,
+    peer_manager::{PeerManager, PeerState},
+    protocol::{ConnectProof, ConnectionInfo, EcdsaConnectionInfo, Message, PROTOCOL_VERSION},
+    sessions::{PeerSession, derive_session_key, public_key_bytes, random_secret},
+};
+
+/// File our identity (secret key) is saved to, next to the log file.
+pub const IDENTITY_FILE: &str = "kuaip2p-identity.key";
+
+/// Context of the handshake signatures
+const HANDSHAKE_CONTEXT: &[u8] = b"kuaip2p 2025-06 identity handshake";
+
+/// Our long-term identity.
+pub struct Identity {
+    signing_key: SigningKey,
+}
+
+impl std::fmt::Debug for Identity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Keep the secret out of the logs
+        write!(f, "Identity({})", self.identity())
+    }
+}
+
+impl Identity {
+    /// Load our identity from [IDENTITY_FILE], or create (and save) a new one.
+    pub fn load() -> Self {
+        match std::fs::read(IDENTITY_FILE) {
+            Ok(bytes) => match SigningKey::from_slice(&bytes) {
+                Ok(signing_key) => {
+                    let identity = Self { signing_key };
+                    info!("Loaded identity {}", identity.identity());
+                    return identity;
+                }
+                Err(e) => warn!("Ignoring invalid {}: {}", IDENTITY_FILE, e),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to read {}: {}", IDENTITY_FILE, e),
+        }
+
+        let identity = Self {
+            signing_key: SigningKey::from(random_secret()),
+        };
+        info!("Created identity {}", identity.identity());
+        if let Err(e) = save_secret(&identity.signing_key.to_bytes()) {
+            warn!(
+                "Failed to save {}: {}. A new identity is created on the next start.",
+                IDENTITY_FILE, e
+            );
+        }
+        identity
+    }
+
+    /// Our public key, as sent to peers (SEC1, compressed).
+    pub fn public_key(&self) -> Vec<u8> {
+        self.signing_key
+            .verifying_key()
+            .to_encoded_point(true)
+            .as_bytes()
+            .to_vec()
+    }
+
+    /// Our identity, as peers see it.
+    pub fn identity(&self) -> String {
+        encode_identity(&self.public_key())
+    }
+
+    /// Sign a message with our identity.
+    pub fn sign(&self, message: &[u8]) -> Vec<u8> {
+        let signature: Signature = self.signing_key.sign(message);
+        signature.to_bytes().to_vec()
+    }
+}
+
+/// Save our secret key, readable by us only.
+fn save_secret(secret: &[u8]) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    std::io::Write::write_all(&mut options.open(IDENTITY_FILE)?, secret)
+}
+
+/// The identity of a public key, as shown to the frontend.
+pub fn encode_identity(public_key: &[u8]) -> String {
+    BASE64_STANDARD.encode(public_key)
+}
+
+/// Check a peer's signature of a message with its public key.
+pub fn verify(public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<(), String> {
+    let verifying_key = VerifyingKey::from_sec1_bytes(public_key)
+        .map_err(|e| format!("Invalid identity public key: {}", e))?;
+    let signature =
+        Signature::from_slice(signature).map_err(|e| format!("Invalid signature: {}", e))?;
+    verifying_key
+        .verify(message, &signature)
+        .map_err(|_| "Invalid signature".to_string())
+}
+
+/// A new ephemeral key pair for a handshake: the secret we keep, and the public key sent as our nonce.
+fn handshake_key() -> (SecretKey, Vec<u8>) {
+    let secret = random_secret();
+    let nonce = public_key_bytes(&secret);
+    (secret, nonce)
+}
+
+/// Which side of the handshake signs
+#[derive(Debug, Clone, Copy)]
+pub enum Role {
+    Initiator,
+    Responder,
+}
+
+/// What each side of the handshake signs: the role of the signer, then the public key and nonce
+/// of the initiator, then those of the responder.
+pub fn transcript(
+    role: Role,
+    initiator: &EcdsaConnectionInfo,
+    responder: &EcdsaConnectionInfo,
+) -> Vec<u8> {
+    let mut transcript = HANDSHAKE_CONTEXT.to_vec();
+    transcript.push(match role {
+        Role::Initiator => 0,
+        Role::Responder => 1,
+    });
+    transcript.extend_from_slice(&handshake_parts(initiator, responder));
+    transcript
+}
+
+/// The public key and nonce of the initiator, then those of the responder, each prefixed with its length.
+pub fn handshake_parts(
+    initiator: &EcdsaConnectionInfo,
+    responder: &EcdsaConnectionInfo,
+) -> Vec<u8> {
+    let mut parts = Vec::new();
+    for part in [
+        &initiator.public_key,
+        &initiator.nonce,
+        &responder.public_key,
+        &responder.nonce,
+    ] {
+        parts.extend_from_slice(&(part.len() as u32).to_be_bytes());
+        parts.extend_from_slice(part);
+    }
+    parts
+}
+
+/// Check the public key and nonce a peer sent, before they are used in the handshake.
+fn check_identity_info(info: &EcdsaConnectionInfo) -> Result<(), String> {
+    VerifyingKey::from_sec1_bytes(&info.public_key)
+        .map_err(|e| format!("Invalid identity public key: {}", e))?;
+    PublicKey::from_sec1_bytes(&info.nonce).map_err(|e| format!("Invalid handshake key: {}", e))?;
+    Ok(())
+}
+
+/// The handshake with a peer, while it is in progress.
+pub enum Handshake {
+    /// We sent `ConnectRequest`, and wait for the challenge
+    Initiator {
+        ours: EcdsaConnectionInfo,
+        /// The ephemeral secret of our nonce
+        secret: SecretKey,
+    },
+    /// We sent `ConnectChallenge`, and wait for the proof
+    Responder {
+        ours: EcdsaConnectionInfo,
+        /// The ephemeral secret of our nonce
+        secret: SecretKey,
+        /// The connection info the peer sent, not verified yet
+        peer: ConnectionInfo,
+    },
+}
+
+impl std::fmt::Debug for Handshake {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Keep the secrets out of the logs
+        match self {
+            Self::Initiator { .. } => write!(f, "Initiator"),
+            Self::Responder { .. } => write!(f, "Responder"),
+        }
+    }
+}
+
+impl PeerManager {
+    /// Our connection info, without a handshake nonce or signature.
+    pub(crate) fn connection_info(&self) -> ConnectionInfo {
+        ConnectionInfo {
+            protocol_version: PROTOCOL_VERSION,
+            name: "todo!".to_string(),
+            backend_version: env!("CARGO_PKG_VERSION").to_string(),
+            compression: compression::SUPPORTED.to_vec(),
+            identity: EcdsaConnectionInfo {
+                public_key: self.identity.public_key(),
+                signature: Vec::new(),
+                nonce: Vec::new(),
+            },
+        }
+    }
+
+    /// Start the handshake with a peer we connected to: send it `ConnectRequest`.
+    ///
+    /// Returns false if the peer is not connected (yet).
+    pub(crate) async fn start_handshake(&self, peer_addr: SocketAddr) -> bool {
+        let (secret, nonce) = handshake_key();
+        let mut connection_info = self.connection_info();
+        connection_info.identity.nonce = nonce;
+        let peer_tx = {
+            let mut active_peers = self.active_peers.lock().await;
+            let Some(peer) = active_peers.get_mut(&peer_addr) else {
+                return false;
+            };
+            peer.handshake = Some(Handshake::Initiator {
+                ours: EcdsaConnectionInfo {
+                    public_key: connection_info.identity.public_key.clone(),
+                    signature: Vec::new(),
+                    nonce: connection_info.identity.nonce.clone(),
+                },
+                secret,
+            });
+            peer.tx.clone()
+        };
+        peer_tx
+            .send(Message::ConnectRequest(connection_info))
+            .await
+            .ok(); // We ignore the error here, as the peer may have already disconnected.
+        true
+    }
+
+    /// Answer a peer's `ConnectRequest` with our signed `ConnectChallenge`.
+    pub(crate) async fn challenge_peer(
+        &self,
+        peer_addr: SocketAddr,
+        peer_info: ConnectionInfo,
+    ) -> Result<(), String> {
+        check_identity_info(&peer_info.identity)?;
+
+        let (secret, nonce) = handshake_key();
+        let mut connection_info = self.connection_info();
+        connection_info.identity.nonce = nonce;
+        connection_info.identity.signature = self.identity.sign(&transcript(
+            Role::Responder,
+            &peer_info.identity,
+            &connection_info.identity,
+        ));
+
+        let peer_tx = {
+            let mut active_peers = self.active_peers.lock().await;
+            let Some(peer) = active_peers.get_mut(&peer_addr) else {
+                return Ok(());
+            };
+            if !matches!(peer.state, PeerState::Connected { peer_info: None })
+                || peer.handshake.is_some()
+            {
+                return Err("Unexpected ConnectRequest".to_string());
+            }
+            peer.handshake = Some(Handshake::Responder {
+                ours: EcdsaConnectionInfo {
+                    public_key: connection_info.identity.public_key.clone(),
+                    signature: Vec::new(),
+                    nonce: connection_info.identity.nonce.clone(),
+                },
+                secret,
+                peer: peer_info,
+            });
+            peer.tx.clone()
+        };
+        peer_tx
+            .send(Message::ConnectChallenge(connection_info))
+            .await
+            .ok(); // We ignore the error here, as the peer may have already disconnected.
+        Ok(())
+    }
+
+    /// Check the responder's `ConnectChallenge`, remember who it is, derive the session,
+    /// and send our `ConnectProof`.
+    pub(crate) async fn answer_challenge(
+        &self,
+        peer_addr: SocketAddr,
+        peer_info: ConnectionInfo,
+    ) -> Result<(), String> {
+        check_identity_info(&peer_info.identity)?;
+
+        let (peer_tx, signature) = {
+            let mut active_peers = self.active_peers.lock().await;
+            let Some(peer) = active_peers.get_mut(&peer_addr) else {
+                return Ok(());
+            };
+            let Some(Handshake::Initiator { ours, secret }) = peer.handshake.take() else {
+                return Err("Unexpected ConnectChallenge".to_string());
+            };
+            verify(
+                &peer_info.identity.public_key,
+                &transcript(Role::Responder, &ours, &peer_info.identity),
+                &peer_info.identity.signature,
+            )?;
+            let key = derive_session_key(
+                &secret,
+                &peer_info.identity.nonce,
+                &ours,
+                &peer_info.identity,
+            )?;
+            let signature =
+                self.identity
+                    .sign(&transcript(Role::Initiator, &ours, &peer_info.identity));
+
+            // Verified: the peer is who it says it is. It still has to accept the connection.
+            peer.session = Some(PeerSession { key });
+            peer.state = PeerState::Connected {
+                peer_info: Some(peer_info.into()),
+            };
+            (peer.tx.clone(), signature)
+        };
+        peer_tx
+            .send(Message::ConnectProof(ConnectProof { signature }))
+            .await
+            .ok(); // We ignore the error here, as the peer may have already disconnected.
+        Ok(())
+    }
+
+    /// Check the initiator's `ConnectProof`. Once it passes, the peer is known to own its identity,
+    /// and the session is derived.
+    ///
+    /// Returns the verified connection info of the peer, to ask the frontend about.
+    pub(crate) async fn check_proof(
+        &self,
+        peer_addr: SocketAddr,
+        proof: ConnectProof,
+    ) -> Result<Option<backend_event::ConnectionInfo>, String> {
+        let mut active_peers = self.active_peers.lock().await;
+        let Some(peer) = active_peers.get_mut(&peer_addr) else {
+            return Ok(None);
+        };
+        let Some(Handshake::Responder {
+            ours,
+            secret,
+            peer: peer_info,
+        }) = peer.handshake.take()
+        else {
+            return Err("Unexpected ConnectProof".to_string());
+        };
+        verify(
+            &peer_info.identity.public_key,
+            &transcript(Role::Initiator, &peer_info.identity, &ours),
+            &proof.signature,
+        )?;
+        let key = derive_session_key(
+            &secret,
+            &peer_info.identity.nonce,
+            &peer_info.identity,
+            &ours,
+        )?;
+
+        let connection_info = backend_event::ConnectionInfo {
+            name: peer_info.name.clone(),
+            ip: peer_addr.to_string(),
+            backend_version: peer_info.backend_version.clone(),
+            identity: encode_identity(&peer_info.identity.public_key),
+        };
+        peer.session = Some(PeerSession { key });
+        peer.state = PeerState::Connected {
+            peer_info: Some(peer_info.into()),
+        };
+        Ok(Some(connection_info))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity() -> Identity {
+        Identity {
+            signing_key: SigningKey::from(random_secret()),
+        }
+    }
+
+    fn info(identity: &Identity) -> EcdsaConnectionInfo {
+        EcdsaConnectionInfo {
+            public_key: identity.public_key(),
+            signature: Vec::new(),
+            nonce: handshake_key().1,
+        }
+    }
+
+    #[test]
+    fn handshake_signature_verifies() {
+        let (initiator, responder) = (identity(), identity());
+        let (ours, theirs) = (info(&initiator), info(&responder));
+        let signature = responder.sign(&transcript(Role::Responder, &ours, &theirs));
+
+        assert!(check_identity_info(&theirs).is_ok());
+        assert!(
+            verify(
+                &theirs.public_key,
+                &transcript(Role::Responder, &ours, &theirs),
+                &signature
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn handshake_signature_is_bound_to_role_nonces_and_key() {
+        let (initiator, responder) = (identity(), identity());
+        let (ours, theirs) = (info(&initiator), info(&responder));
+        let signature = responder.sign(&transcript(Role::Responder, &ours, &theirs));
+
+        // Reflected as the initiator's proof
+        assert!(
+            verify(
+                &theirs.public_key,
+                &transcript(Role::Initiator, &ours, &theirs),
+                &signature
+            )
+            .is_err()
+        );
+        // Replayed on another connection
+        let other = info(&initiator);
+        assert!(
+            verify(
+                &theirs.public_key,
+                &transcript(Role::Responder, &other, &theirs),
+                &signature
+            )
+            .is_err()
+        );
+        // Claimed by another identity
+        assert!(
+            verify(
+                &ours.public_key,
+                &transcript(Role::Responder, &ours, &theirs),
+                &signature
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn invalid_identity_info_is_refused() {
+        let mut info = info(&identity());
+        info.nonce.pop();
+        assert!(check_identity_info(&info).is_err());
+        info.public_key = vec![0; 33];
+        info.nonce = handshake_key().1;
+        assert!(check_identity_info(&info).is_err());
+    }
+}
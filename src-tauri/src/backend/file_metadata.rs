@@ -0,0 +1,180 @@
+//! # File Metadata
+//!
+//! Modification time and permissions of the files of a transfer.
+//!
+//! - The sender reads them when the transfer is queued, and sends them in each `ManifestEntry`.
+//! - The receiver applies them once the transfer completes (every file verified and moved into
+//!   place), as far as its [MetadataPolicy] allows. Failing to apply them does not fail the transfer.
+//! - The Unix mode is only applied if the user opts in. It only ever keeps the permission bits
+//!   (no setuid, setgid or sticky bit), and never makes a file group or world writable.
+//!   If read-only files are not honoured, the owner may always write the file.
+//!
+//! The policy can be changed at runtime with `FrontendEvent::SetMetadataPolicy`.
+
+use std::{
+    fs::{self, OpenOptions},
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use tracing::warn;
+use uuid::Uuid;
+
+use super::{
+    hash_index::modified_nanos,
+    peer_manager::{FileTransferDirection, PeerManager},
+    protocol::FileMetadata,
+};
+
+/// Permission bits kept from the sender's Unix mode
+const PERMISSION_BITS: u32 = 0o777;
+
+/// Group and other write permission bits, never applied
+const GROUP_OTHER_WRITE: u32 = 0o022;
+
+/// Owner write permission bit
+const OWNER_WRITE: u32 = 0o200;
+
+/// Which metadata of received files is applied.
+#[derive(Debug, Clone)]
+pub struct MetadataPolicy {
+    /// Apply the modification time
+    pub modified: bool,
+    /// Apply the Unix mode (permissions and executable bits). Off by default.
+    pub mode: bool,
+    /// Make files read-only if they were on the sender
+    pub read_only: bool,
+}
+
+impl Default for MetadataPolicy {
+    fn default() -> Self {
+        Self {
+            modified: true,
+            mode: false,
+            read_only: true,
+        }
+    }
+}
+
+impl FileMetadata {
+    /// The metadata of a local file, to be sent.
+    pub fn read(metadata: &fs::Metadata) -> Self {
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            Some(metadata.permissions().mode() & PERMISSION_BITS)
+        };
+        #[cfg(not(unix))]
+        let mode = None;
+
+        Self {
+            modified: modified_nanos(metadata),
+            mode,
+            read_only: metadata.permissions().readonly(),
+        }
+    }
+
+    /// Apply the metadata to a received file, as far as `policy` allows.
+    pub fn apply(&self, path: &Path, policy: &MetadataPolicy) -> std::io::Result<()> {
+        // Before the permissions, the file may not be writable afterwards
+        if policy.modified
+            && let Some(modified) = self.modified
+        {
+            OpenOptions::new()
+                .write(true)
+                .open(path)?
+                .set_modified(UNIX_EPOCH + Duration::from_nanos(modified))?;
+        }
+
+        let mut permissions = fs::metadata(path)?.permissions();
+        let original = permissions.clone();
+        #[cfg(unix)]
+        if policy.mode
+            && let Some(mode) = self.mode
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mut mode = mode & PERMISSION_BITS & !GROUP_OTHER_WRITE;
+            if !policy.read_only {
+                mode |= OWNER_WRITE;
+            }
+            permissions.set_mode(mode);
+        }
+        if policy.read_only && self.read_only {
+            permissions.set_readonly(true);
+        }
+        if permissions != original {
+            fs::set_permissions(path, permissions)?;
+        }
+        Ok(())
+    }
+}
+
+impl PeerManager {
+    /// Apply the metadata sent with the files of a completed incoming transfer.
+    pub(crate) async fn apply_received_metadata(&self, unique_id: Uuid) {
+        let received: Vec<(PathBuf, FileMetadata)> = self
+            .active_transfers
+            .lock()
+            .await
+            .get(&unique_id)
+            .filter(|transfer| transfer.direction == FileTransferDirection::Receiving)
+            .map(|transfer| {
+                transfer
+                    .manifest
+                    .files
+                    .iter()
+                    .map(|file| (file.local_path.clone(), file.metadata.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if received.is_empty() {
+            return;
+        }
+        let policy = self.metadata_policy.lock().await.clone();
+
+        tokio::task::spawn_blocking(move || {
+            for (path, metadata) in received {
+                if let Err(e) = metadata.apply(&path, &policy) {
+                    warn!("Failed to apply metadata to {}: {}", path.display(), e);
+                }
+            }
+        })
+        .await
+        .ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn mode_is_opt_in_and_masked() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("kuaip2p-metadata-{}", Uuid::new_v4()));
+        fs::write(&path, b"data").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        let mode = || fs::metadata(&path).unwrap().permissions().mode() & 0o7777;
+        let metadata = FileMetadata {
+            modified: None,
+            mode: Some(0o4777),
+            read_only: false,
+        };
+
+        // Not applied by default
+        metadata.apply(&path, &MetadataPolicy::default()).unwrap();
+        assert_eq!(mode(), 0o644);
+
+        // No setuid, and no group or world write
+        let policy = MetadataPolicy {
+            mode: true,
+            ..MetadataPolicy::default()
+        };
+        metadata.apply(&path, &policy).unwrap();
+        assert_eq!(mode(), 0o755);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,409 @@
+//! # File Sender
+//!
+//! Streams the chunks of an accepted outgoing file transfer to the peer,
+//! paced by the [SlidingWindow] flow control and the upload bandwidth limits,
+//! and sized by the [ChunkSizer].
+//!
+//! Every chunk carries its Merkle proof (see [super::merkle]). A chunk the peer fails to verify
+//! is nacked, and resent at once.
+//!
+//! The files the peer already has (see [super::hash_index]) are skipped. Since chunks stay aligned
+//! to the Merkle tree's blocks, the blocks a skipped file shares with others are still sent.
+//!
+//! A ranged transfer (to a multi-source download, see [super::swarm]) sends nothing on its own:
+//! it sends the ranges the peer requests with `RangeRequest`, until the peer ends the transfer.
+//! Any other transfer still sends the ranges the peer requests after `FileDone` (a file copied
+//! from the peer's older version failed to verify, see [super::delta]), then `FileDone` again.
+//!
+//! Chunks are compressed with the transfer's compression, if any, when it makes them smaller
+//! (see [super::compression]). The upload bandwidth limits apply to the compressed payloads.
+//!
+//! While the transfer is paused no new chunks are sent, but the chunks already in flight are
+//! still acknowledged (and retransmitted if need be), so the window drains before the sender waits.
+
+use std::{collections::VecDeque, ops::Range};
+
+use tokio::sync::{mpsc, watch};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::js_api::backend_event::{BackendEvent, FileTransferProgress};
+
+use super::{
+    bandwidth::BandwidthDirection,
+    byte_ranges::ByteRanges,
+    chunk_sizing::ChunkSizer,
+    compression,
+    flow_control::{AckTimeout, ChunkReply, SlidingWindow},
+    manifest::FileSet,
+    merkle::{BLOCK_LEN, Hash},
+    peer_manager::PeerManager,
+    protocol::{Compression, FileChunk, FileDone, Message},
+};
+
+/// Maximum number of requested ranges waiting to be sent.
+/// A multi-source download only requests a few segments from a source at a time.
+const MAX_PENDING_RANGES: usize = 16;
+
+impl PeerManager {
+    /// Send the chunks of an accepted outgoing file transfer to the peer, followed by `FileDone`.
+    ///
+    /// Acks, nacks and requested ranges are received through `ack_rx`, which is fed by the
+    /// `FileChunkAck`, `FileChunkNack` and `RangeRequest` message handlers.
+    /// Pauses are received through `pause_rx`.
+    /// The byte ranges in `skip` are not sent, the peer already has them.
+    /// If the transfer fails, the frontend is notified with a `FileTransferError` event.
+    pub(crate) async fn send_file_chunks(
+        &self,
+        unique_id: Uuid,
+        peer_tx: mpsc::Sender<Message>,
+        ack_rx: mpsc::Receiver<ChunkReply>,
+        pause_rx: watch::Receiver<bool>,
+        skip: ByteRanges,
+    ) {
+        if let Err(message) = self
+            .run_file_sender(unique_id, &peer_tx, ack_rx, pause_rx, skip)
+            .await
+        {
+            self.fail_file_transfer(unique_id, message).await;
+        }
+    }
+
+    async fn run_file_sender(
+        &self,
+        unique_id: Uuid,
+        peer_tx: &mpsc::Sender<Message>,
+        mut ack_rx: mpsc::Receiver<ChunkReply>,
+        mut pause_rx: watch::Receiver<bool>,
+        skip: ByteRanges,
+    ) -> Result<(), String> {
+        // Grab what we need from the transfer state, then release the lock
+        let (files, merkle_tree, peer_addr, total_size, max_chunk_len, ranged, compression) = {
+            let active_transfers = self.active_transfers.lock().await;
+            let transfer = active_transfers
+                .get(&unique_id)
+                .ok_or("File transfer state not found")?;
+            match (transfer.status.files(), &transfer.merkle_tree) {
+                (Some(files), Some(merkle_tree)) => (
+                    files.clone(),
+                    merkle_tree.clone(),
+                    transfer.peer_addr,
+                    transfer.total_size,
+                    transfer.chunk_len,
+                    transfer.ranged,
+                    transfer.compression,
+                ),
+                _ => return Err("File transfer is not in progress".to_string()),
+            }
+        };
+
+        // Start from what the last transfer to this peer measured, if anything
+        let link_quality = self
+            .active_peers
+            .lock()
+            .await
+            .get(&peer_addr)
+            .and_then(|peer| peer.link_quality);
+
+        let mut sizer = ChunkSizer::new(total_size, max_chunk_len, link_quality);
+        let mut window = SlidingWindow::new(self.flow_control);
+        let mut retransmit_queue = VecDeque::new();
+        let mut next_chunk_id = 0;
+        let mut wire_bytes = 0;
+        // Whether `FileDone` was sent since the last chunk
+        let mut done_sent = false;
+
+        // The byte ranges left to send: everything the peer does not have, or else the ranges the peer requests.
+        // The gaps between the skipped ranges are widened to whole blocks.
+        let mut pending_ranges: VecDeque<Range<u64>> = VecDeque::new();
+        if !ranged {
+            for (start, end) in skip.gaps(total_size) {
+                let start = start - start % BLOCK_LEN;
+                let end = end.next_multiple_of(BLOCK_LEN).min(total_size);
+                match pending_ranges.back_mut() {
+                    Some(last) if last.end >= start => last.end = last.end.max(end),
+                    _ => pending_ranges.push_back(start..end),
+                }
+            }
+        }
+        let mut bytes_acked = if ranged {
+            0
+        } else {
+            total_size
+                - pending_ranges
+                    .iter()
+                    .map(|range| range.end - range.start)
+                    .sum::<u64>()
+        };
+
+        info!(
+            "Sending file {} ({} bytes), starting with {} byte chunks",
+            unique_id,
+            total_size,
+            sizer.chunk_len()
+        );
+
+        loop {
+            // Retransmissions are sent regardless of the window, they are already in flight.
+            // A chunk keeps its byte range when retransmitted, even if the chunk length changed since.
+            while let Some(chunk_id) = retransmit_queue.pop_front() {
+                let Some((offset, len)) = window.chunk_range(chunk_id) else {
+                    continue;
+                };
+                let (data, compressed) =
+                    encode_chunk(compression, read_chunk(&files, offset, len).await?).await;
+                self.bandwidth
+                    .acquire(peer_addr, BandwidthDirection::Upload, data.len() as u64)
+                    .await;
+                window.on_retransmit(chunk_id);
+                wire_bytes += data.len() as u64;
+                let proof = merkle_tree.proof(offset, len);
+                send_chunk(
+                    peer_tx, unique_id, chunk_id, offset, data, proof, compressed,
+                )
+                .await?;
+            }
+
+            // Fill the window with new chunks, unless paused
+            let paused = *pause_rx.borrow();
+            while !paused
+                && window.can_send()
+                && let Some(range) = pending_ranges.front_mut()
+            {
+                let offset = range.start;
+                let len = sizer.chunk_len().min(range.end - offset);
+                range.start += len;
+                if range.is_empty() {
+                    pending_ranges.pop_front();
+                }
+
+                let (data, compressed) =
+                    encode_chunk(compression, read_chunk(&files, offset, len).await?).await;
+                self.bandwidth
+                    .acquire(peer_addr, BandwidthDirection::Upload, data.len() as u64)
+                    .await;
+                window.on_sent(next_chunk_id, offset, len);
+                wire_bytes += data.len() as u64;
+                let proof = merkle_tree.proof(offset, len);
+                send_chunk(
+                    peer_tx,
+                    unique_id,
+                    next_chunk_id,
+                    offset,
+                    data,
+                    proof,
+                    compressed,
+                )
+                .await?;
+                next_chunk_id += 1;
+            }
+
+            // Every chunk has been sent and acknowledged: tell the peer we are done.
+            // The peer verifies every file against the checksums in the manifest.
+            // Then wait for more requests, until the peer ends the transfer (ranged transfers only wait).
+            if !ranged && !done_sent && pending_ranges.is_empty() && window.is_empty() {
+                // Remember the link quality for the next transfer to this peer
+                if let Some(peer) = self.active_peers.lock().await.get_mut(&peer_addr) {
+                    peer.link_quality = Some((&window).into());
+                }
+                peer_tx
+                    .send(Message::FileDone(FileDone { unique_id }))
+                    .await
+                    .map_err(|e| format!("Failed to send FileDone message to the peer: {}", e))?;
+                done_sent = true;
+            }
+
+            // Paused, and every chunk in flight is acknowledged. Wait until resumed.
+            if paused && window.is_empty() {
+                debug!(
+                    "File transfer {} paused after {} bytes",
+                    unique_id, bytes_acked
+                );
+                pause_rx
+                    .wait_for(|paused| !paused)
+                    .await
+                    .map_err(|_| "File transfer was dropped".to_string())?;
+                window.on_resume();
+                debug!(
+                    "File transfer {} resumed after {} bytes",
+                    unique_id, bytes_acked
+                );
+                continue;
+            }
+
+            let deadline = window.next_deadline();
+            tokio::select! {
+                reply = ack_rx.recv() => {
+                    match reply {
+                        Some(ChunkReply::Ack(chunk_id)) => {
+                            if let Some(len) = window.on_ack(chunk_id) {
+                                sizer.on_ack(&window);
+                                bytes_acked = (bytes_acked + len).min(total_size);
+                                self.update_send_progress(
+                                    unique_id,
+                                    bytes_acked,
+                                    wire_bytes,
+                                    total_size,
+                                )
+                                .await;
+                            }
+                        }
+                        Some(ChunkReply::Nack(chunk_id)) => match window.on_nack(chunk_id) {
+                            Some(AckTimeout::Retransmit(chunk_id)) => {
+                                warn!(
+                                    "Chunk {} of file {} failed verification on the peer's side, resending",
+                                    chunk_id, unique_id
+                                );
+                                retransmit_queue.push_back(chunk_id);
+                            }
+                            Some(AckTimeout::Failed(chunk_id)) => {
+                                return Err(format!(
+                                    "Chunk {} failed verification after {} retries",
+                                    chunk_id, self.flow_control.max_retries
+                                ));
+                            }
+                            None => {}
+                        },
+                        Some(ChunkReply::Range { offset, len }) => {
+                            // Sent again after `FileDone`: `FileDone` follows again
+                            done_sent = false;
+                            let end = offset + len;
+                            if pending_ranges
+                                .iter()
+                                .any(|range| range.start < end && offset < range.end)
+                            {
+                                debug!(
+                                    "Range {}..{} of file {} is already pending, ignoring",
+                                    offset, end, unique_id
+                                );
+                            } else if pending_ranges.len() >= MAX_PENDING_RANGES {
+                                warn!(
+                                    "Too many pending ranges for file {}, ignoring range {}..{}",
+                                    unique_id, offset, end
+                                );
+                            } else {
+                                pending_ranges.push_back(offset..end);
+                            }
+                        }
+                        // The peer's `FileDoneResult` completed the transfer
+                        None if done_sent => return Ok(()),
+                        None => return Err("File transfer was dropped".to_string()),
+                    }
+                }
+                _ = async {
+                    match deadline {
+                        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    let timeouts = window.on_timeout();
+                    if !timeouts.is_empty() {
+                        sizer.on_timeout();
+                    }
+                    for timeout in timeouts {
+                        match timeout {
+                            AckTimeout::Retransmit(chunk_id) => {
+                                debug!(
+                                    "Ack for chunk {} of file {} timed out, retransmitting (window: {}, rto: {:?}, chunk length: {})",
+                                    chunk_id,
+                                    unique_id,
+                                    window.window(),
+                                    window.rto(),
+                                    sizer.chunk_len()
+                                );
+                                retransmit_queue.push_back(chunk_id);
+                            }
+                            AckTimeout::Failed(chunk_id) => {
+                                return Err(format!(
+                                    "Chunk {} was not acknowledged after {} retries",
+                                    chunk_id, self.flow_control.max_retries
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Update the bytes transferred (and sent over the wire) of an outgoing transfer, and notify the frontend.
+    async fn update_send_progress(
+        &self,
+        unique_id: Uuid,
+        bytes_acked: u64,
+        wire_bytes: u64,
+        total_size: u64,
+    ) {
+        let (sending, rate) = {
+            let mut active_transfers = self.active_transfers.lock().await;
+            let Some(transfer) = active_transfers.get_mut(&unique_id) else {
+                return;
+            };
+            transfer
+                .rate
+                .record(bytes_acked.saturating_sub(transfer.bytes_transferred));
+            transfer.bytes_transferred = bytes_acked;
+            transfer.wire_bytes = wire_bytes;
+            ((&transfer.direction).into(), transfer.rate.rate())
+        };
+
+        self.backend_event_tx
+            .send(BackendEvent::FileTransferProgress(FileTransferProgress {
+                unique_id: unique_id.to_string(),
+                bytes_transferred: bytes_acked,
+                total_bytes: total_size,
+                wire_bytes,
+                rate,
+                sending,
+            }))
+            .await
+            .expect("Failed to send FileTransferProgress event to the frontend");
+    }
+}
+
+/// Read `len` bytes at `offset` of the files being sent.
+async fn read_chunk(files: &FileSet, offset: u64, len: u64) -> Result<Vec<u8>, String> {
+    files
+        .read_at(offset, len)
+        .await
+        .map_err(|e| format!("Failed to read file: {}", e))
+}
+
+/// Compress a chunk with the transfer's compression, if it is worth it.
+///
+/// Returns the payload, and whether it is compressed.
+async fn encode_chunk(compression: Option<Compression>, data: Vec<u8>) -> (Vec<u8>, bool) {
+    let Some(compression) = compression else {
+        return (data, false);
+    };
+    // Compressing a large chunk takes a while, do not hold up the runtime
+    tokio::task::spawn_blocking(move || match compression::compress(compression, &data) {
+        Some(compressed) => (compressed, true),
+        None => (data, false),
+    })
+    .await
+    .expect("Chunk compression panicked")
+}
+
+/// Send a chunk of the file to the peer.
+async fn send_chunk(
+    peer_tx: &mpsc::Sender<Message>,
+    unique_id: Uuid,
+    chunk_id: u64,
+    offset: u64,
+    data: Vec<u8>,
+    proof: Vec<Hash>,
+    compressed: bool,
+) -> Result<(), String> {
+    peer_tx
+        .send(Message::FileChunk(FileChunk {
+            unique_id,
+            chunk_id,
+            offset,
+            data,
+            proof,
+            compressed,
+        }))
+        .await
+        .map_err(|e| format!("Failed to send FileChunk message to the peer: {}", e))
+}
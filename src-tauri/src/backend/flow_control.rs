@@ -0,0 +1,478 @@
+//! # Flow Control
+//!
+//! Sliding-window flow control for outgoing file chunks.
+//!
+//! The sender keeps at most [SlidingWindow::window] unacknowledged chunks in flight.
+//! Every `FileChunkAck` slides the window forward, and chunks that are not acknowledged
+//! within the retransmission timeout (RTO) are retransmitted, up to [FlowControlConfig::max_retries] times.
+//!
+//! ## Adaptation
+//!
+//! - RTT is estimated as per RFC 6298 (smoothed RTT + RTT variance), which also drives the RTO.
+//! - Throughput is an exponentially weighted moving average of acknowledged bytes per second.
+//! - The window grows by one chunk per ack while the link is not queueing (slow start),
+//!   then by one chunk per window (congestion avoidance).
+//! - If the RTT grows well past the minimum RTT seen, the link is queueing, so the window is
+//!   shrunk towards the bandwidth-delay product instead of flooding the link.
+//! - A timeout halves the window.
+
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+/// Flow control configuration for outgoing file transfers.
+#[derive(Debug, Clone, Copy)]
+pub struct FlowControlConfig {
+    /// Number of chunks allowed in flight when a transfer starts.
+    pub initial_window: usize,
+    /// Minimum number of chunks allowed in flight.
+    pub min_window: usize,
+    /// Maximum number of chunks allowed in flight.
+    /// Bounds the memory used by a single transfer to `max_window * chunk_len`.
+    pub max_window: usize,
+    /// Lower bound of the ack timeout.
+    pub min_ack_timeout: Duration,
+    /// Upper bound of the ack timeout.
+    pub max_ack_timeout: Duration,
+    /// Number of times a chunk is retransmitted before the transfer fails.
+    pub max_retries: u32,
+}
+
+impl Default for FlowControlConfig {
+    fn default() -> Self {
+        Self {
+            initial_window: 4,
+            min_window: 1,
+            max_window: 64,
+            min_ack_timeout: Duration::from_millis(500),
+            max_ack_timeout: Duration::from_secs(30),
+            max_retries: 5,
+        }
+    }
+}
+
+/// A chunk that has been sent, but not yet acknowledged.
+#[derive(Debug)]
+struct InFlightChunk {
+    /// When the chunk was (last) sent
+    sent_at: Instant,
+    /// The byte offset of the chunk within the file
+    offset: u64,
+    /// The length of the chunk in bytes
+    len: u64,
+    /// Number of times the chunk has been retransmitted
+    retries: u32,
+}
+
+/// The peer's reply to a chunk, forwarded to the chunk sender task.
+#[derive(Debug, Clone, Copy)]
+pub enum ChunkReply {
+    /// The chunk was received and verified
+    Ack(u64),
+    /// The chunk failed verification, and must be resent
+    Nack(u64),
+    /// The peer requests a byte range of a ranged transfer (multi-source downloads)
+    Range { offset: u64, len: u64 },
+}
+
+/// What to do with a chunk whose ack timed out.
+#[derive(Debug, PartialEq)]
+pub enum AckTimeout {
+    /// Retransmit the chunk
+    Retransmit(u64),
+    /// The chunk ran out of retries, the transfer has failed
+    Failed(u64),
+}
+
+/// Sliding window over the chunks of a single outgoing file transfer.
+#[derive(Debug)]
+pub struct SlidingWindow {
+    /// Flow control configuration
+    config: FlowControlConfig,
+    /// Chunks in flight, keyed by chunk_id
+    in_flight: BTreeMap<u64, InFlightChunk>,
+    /// Current window size in chunks (fractional for additive increase)
+    window: f64,
+    /// Slow start threshold in chunks
+    ssthresh: f64,
+    /// Smoothed round trip time
+    srtt: Option<Duration>,
+    /// Round trip time variance
+    rttvar: Duration,
+    /// Minimum round trip time seen
+    min_rtt: Option<Duration>,
+    /// Current retransmission timeout
+    rto: Duration,
+    /// Smoothed throughput of acknowledged bytes per second
+    throughput: f64,
+    /// When the last ack arrived (for throughput measurement)
+    last_ack_at: Option<Instant>,
+    /// Number of chunks sent for the first time
+    chunks_sent: u64,
+    /// Number of chunks retransmitted
+    chunks_retransmitted: u64,
+}
+
+impl SlidingWindow {
+    /// Create a new sliding window
+    pub fn new(config: FlowControlConfig) -> Self {
+        Self {
+            config,
+            in_flight: BTreeMap::new(),
+            window: config
+                .initial_window
+                .clamp(config.min_window, config.max_window) as f64,
+            ssthresh: config.max_window as f64,
+            srtt: None,
+            rttvar: Duration::ZERO,
+            min_rtt: None,
+            // RFC 6298 initial RTO is 1 second
+            rto: Duration::from_secs(1).clamp(config.min_ack_timeout, config.max_ack_timeout),
+            throughput: 0.0,
+            last_ack_at: None,
+            chunks_sent: 0,
+            chunks_retransmitted: 0,
+        }
+    }
+
+    /// Current window size in chunks
+    pub fn window(&self) -> usize {
+        self.window as usize
+    }
+
+    /// Number of chunks in flight
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Are there no chunks in flight?
+    pub fn is_empty(&self) -> bool {
+        self.in_flight.is_empty()
+    }
+
+    /// Can another chunk be sent without exceeding the window?
+    pub fn can_send(&self) -> bool {
+        self.in_flight.len() < self.window()
+    }
+
+    /// Smoothed round trip time, if measured yet
+    pub fn srtt(&self) -> Option<Duration> {
+        self.srtt
+    }
+
+    /// Current retransmission timeout
+    pub fn rto(&self) -> Duration {
+        self.rto
+    }
+
+    /// Smoothed throughput in bytes per second
+    pub fn throughput(&self) -> f64 {
+        self.throughput
+    }
+
+    /// Fraction of chunks that had to be retransmitted
+    pub fn loss_rate(&self) -> f64 {
+        if self.chunks_sent == 0 {
+            return 0.0;
+        }
+        self.chunks_retransmitted as f64 / (self.chunks_sent + self.chunks_retransmitted) as f64
+    }
+
+    /// The byte range `(offset, len)` of a chunk in flight
+    pub fn chunk_range(&self, chunk_id: u64) -> Option<(u64, u64)> {
+        self.in_flight
+            .get(&chunk_id)
+            .map(|chunk| (chunk.offset, chunk.len))
+    }
+
+    /// Record that a chunk has been sent for the first time.
+    pub fn on_sent(&mut self, chunk_id: u64, offset: u64, len: u64) {
+        self.chunks_sent += 1;
+        self.in_flight.insert(
+            chunk_id,
+            InFlightChunk {
+                sent_at: Instant::now(),
+                offset,
+                len,
+                retries: 0,
+            },
+        );
+    }
+
+    /// Record that a chunk has been retransmitted.
+    pub fn on_retransmit(&mut self, chunk_id: u64) {
+        if let Some(chunk) = self.in_flight.get_mut(&chunk_id) {
+            self.chunks_retransmitted += 1;
+            chunk.sent_at = Instant::now();
+            chunk.retries += 1;
+        }
+    }
+
+    /// Record an ack for a chunk.
+    ///
+    /// Returns the number of bytes acknowledged, or `None` if the chunk was not in flight
+    /// (duplicate or unknown ack).
+    pub fn on_ack(&mut self, chunk_id: u64) -> Option<u64> {
+        let chunk = self.in_flight.remove(&chunk_id)?;
+        let now = Instant::now();
+
+        // Karn's algorithm: only sample the RTT of chunks that were not retransmitted
+        if chunk.retries == 0 {
+            self.sample_rtt(now.duration_since(chunk.sent_at));
+        }
+
+        // Throughput EWMA over inter-ack intervals
+        if let Some(last_ack_at) = self.last_ack_at {
+            let elapsed = now.duration_since(last_ack_at).as_secs_f64();
+            if elapsed > 0.0 {
+                let sample = chunk.len as f64 / elapsed;
+                self.throughput = if self.throughput == 0.0 {
+                    sample
+                } else {
+                    0.875 * self.throughput + 0.125 * sample
+                };
+            }
+        }
+        self.last_ack_at = Some(now);
+
+        self.grow_or_shrink(chunk.len);
+
+        Some(chunk.len)
+    }
+
+    /// The transfer resumes after a pause.
+    ///
+    /// The gap since the last ack says nothing about the link, so it is not a throughput sample.
+    pub fn on_resume(&mut self) {
+        self.last_ack_at = None;
+    }
+
+    /// A chunk failed verification on the peer's side. Resend it at once, unless out of retries.
+    ///
+    /// Corruption is not congestion, so the window and the RTO are left alone.
+    pub fn on_nack(&mut self, chunk_id: u64) -> Option<AckTimeout> {
+        let chunk = self.in_flight.get(&chunk_id)?;
+        Some(if chunk.retries >= self.config.max_retries {
+            AckTimeout::Failed(chunk_id)
+        } else {
+            AckTimeout::Retransmit(chunk_id)
+        })
+    }
+
+    /// When the oldest chunk in flight times out, if any chunks are in flight.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.in_flight
+            .values()
+            .map(|chunk| chunk.sent_at + self.rto)
+            .min()
+    }
+
+    /// Collect every chunk whose ack has timed out.
+    ///
+    /// A timeout halves the window and backs off the RTO.
+    pub fn on_timeout(&mut self) -> Vec<AckTimeout> {
+        let now = Instant::now();
+        let expired: Vec<AckTimeout> = self
+            .in_flight
+            .iter()
+            .filter(|(_, chunk)| now >= chunk.sent_at + self.rto)
+            .map(|(chunk_id, chunk)| {
+                if chunk.retries >= self.config.max_retries {
+                    AckTimeout::Failed(*chunk_id)
+                } else {
+                    AckTimeout::Retransmit(*chunk_id)
+                }
+            })
+            .collect();
+
+        if !expired.is_empty() {
+            self.ssthresh = (self.window / 2.0).max(self.config.min_window as f64);
+            self.window = self.ssthresh;
+            self.rto = (self.rto * 2).min(self.config.max_ack_timeout);
+        }
+
+        expired
+    }
+
+    /// Update the RTT estimate and RTO as per RFC 6298.
+    fn sample_rtt(&mut self, rtt: Duration) {
+        self.min_rtt = Some(self.min_rtt.map_or(rtt, |min_rtt| min_rtt.min(rtt)));
+
+        match self.srtt {
+            None => {
+                self.srtt = Some(rtt);
+                self.rttvar = rtt / 2;
+            }
+            Some(srtt) => {
+                let delta = srtt.abs_diff(rtt);
+                self.rttvar = self.rttvar.mul_f64(0.75) + delta.mul_f64(0.25);
+                self.srtt = Some(srtt.mul_f64(0.875) + rtt.mul_f64(0.125));
+            }
+        }
+
+        let srtt = self.srtt.unwrap_or(rtt);
+        self.rto = (srtt + self.rttvar * 4)
+            .clamp(self.config.min_ack_timeout, self.config.max_ack_timeout);
+    }
+
+    /// Adjust the window after an ack.
+    fn grow_or_shrink(&mut self, chunk_len: u64) {
+        let min = self.config.min_window as f64;
+        let max = self.config.max_window as f64;
+
+        // Is the link queueing? (RTT well above the minimum RTT)
+        let queueing = match (self.srtt, self.min_rtt) {
+            (Some(srtt), Some(min_rtt)) => srtt > min_rtt * 2 && srtt > Duration::from_millis(20),
+            _ => false,
+        };
+
+        if queueing {
+            // Shrink towards the bandwidth-delay product, we are only filling buffers
+            let bdp = self.bdp_chunks(chunk_len).unwrap_or(self.window);
+            self.ssthresh = bdp.max(min);
+            self.window = (self.window * 0.9).max(bdp).clamp(min, max);
+        } else if self.window < self.ssthresh {
+            // Slow start
+            self.window = (self.window + 1.0).clamp(min, max);
+        } else {
+            // Congestion avoidance
+            self.window = (self.window + 1.0 / self.window).clamp(min, max);
+        }
+    }
+
+    /// Bandwidth-delay product in chunks, if enough has been measured.
+    fn bdp_chunks(&self, chunk_len: u64) -> Option<f64> {
+        let min_rtt = self.min_rtt?;
+        if self.throughput == 0.0 || chunk_len == 0 {
+            return None;
+        }
+        Some((self.throughput * min_rtt.as_secs_f64() / chunk_len as f64).ceil())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> FlowControlConfig {
+        FlowControlConfig {
+            min_ack_timeout: Duration::ZERO,
+            ..FlowControlConfig::default()
+        }
+    }
+
+    /// Pretend a chunk in flight was sent `ago`.
+    fn sent_ago(window: &mut SlidingWindow, chunk_id: u64, ago: Duration) {
+        window.in_flight.get_mut(&chunk_id).unwrap().sent_at = Instant::now() - ago;
+    }
+
+    #[test]
+    fn rto_follows_rfc_6298() {
+        let mut window = SlidingWindow::new(config());
+        assert_eq!(window.rto(), Duration::from_secs(1));
+
+        // First sample: SRTT = R, RTTVAR = R/2, RTO = SRTT + 4 * RTTVAR
+        window.sample_rtt(Duration::from_millis(100));
+        assert_eq!(window.srtt(), Some(Duration::from_millis(100)));
+        assert_eq!(window.rto(), Duration::from_millis(300));
+
+        // Next samples: RTTVAR = 3/4 RTTVAR + 1/4 |SRTT - R|, SRTT = 7/8 SRTT + 1/8 R
+        window.sample_rtt(Duration::from_millis(200));
+        assert_eq!(window.srtt(), Some(Duration::from_micros(112_500)));
+        assert_eq!(window.rto(), Duration::from_micros(112_500 + 4 * 62_500));
+
+        // The RTO stays within the configured bounds
+        window.sample_rtt(Duration::from_secs(60));
+        assert_eq!(window.rto(), config().max_ack_timeout);
+        let mut window = SlidingWindow::new(FlowControlConfig::default());
+        window.sample_rtt(Duration::from_millis(1));
+        assert_eq!(window.rto(), FlowControlConfig::default().min_ack_timeout);
+    }
+
+    #[test]
+    fn retransmitted_chunks_are_not_rtt_samples() {
+        let mut window = SlidingWindow::new(config());
+        window.on_sent(0, 0, 100);
+        window.on_retransmit(0);
+        assert_eq!(window.on_ack(0), Some(100));
+        assert_eq!(window.srtt(), None);
+
+        // Duplicate or unknown acks are ignored
+        assert_eq!(window.on_ack(0), None);
+        assert_eq!(window.on_ack(7), None);
+        assert!(window.is_empty());
+    }
+
+    #[test]
+    fn window_grows_with_acks() {
+        let mut window = SlidingWindow::new(config());
+        assert_eq!(window.window(), 4);
+        for chunk_id in 0..4 {
+            assert!(window.can_send());
+            window.on_sent(chunk_id, chunk_id * 100, 100);
+        }
+        assert!(!window.can_send());
+
+        // Slow start: one more chunk per ack
+        for chunk_id in 0..4 {
+            window.on_ack(chunk_id);
+        }
+        assert_eq!(window.window(), 8);
+
+        // Congestion avoidance past the threshold: one more chunk per window
+        window.ssthresh = 8.0;
+        for chunk_id in 4..12 {
+            window.on_sent(chunk_id, chunk_id * 100, 100);
+            window.on_ack(chunk_id);
+        }
+        assert_eq!(window.window(), 8);
+        window.on_sent(12, 1200, 100);
+        window.on_ack(12);
+        assert_eq!(window.window(), 9);
+    }
+
+    #[test]
+    fn window_shrinks_towards_bdp_when_queueing() {
+        let mut window = SlidingWindow::new(config());
+        window.window = 40.0;
+        window.sample_rtt(Duration::from_millis(10));
+        for _ in 0..50 {
+            window.sample_rtt(Duration::from_millis(100));
+        }
+        // 1 MB/s over a 10 ms minimum RTT is 10 chunks of 1 kB
+        window.throughput = 1_000_000.0;
+
+        window.grow_or_shrink(1000);
+        assert_eq!(window.window(), 36);
+        for _ in 0..50 {
+            window.grow_or_shrink(1000);
+        }
+        assert_eq!(window.window(), 10);
+    }
+
+    #[test]
+    fn timeouts_halve_the_window_and_back_off() {
+        let mut window = SlidingWindow::new(config());
+        window.window = 16.0;
+        window.on_sent(0, 0, 100);
+        window.on_sent(1, 100, 100);
+        assert_eq!(window.on_timeout(), vec![]);
+
+        sent_ago(&mut window, 0, Duration::from_secs(2));
+        assert_eq!(window.on_timeout(), vec![AckTimeout::Retransmit(0)]);
+        assert_eq!(window.window(), 8);
+        assert_eq!(window.rto(), Duration::from_secs(2));
+        window.on_retransmit(0);
+
+        // Out of retries, the transfer fails
+        for _ in 0..config().max_retries {
+            window.on_retransmit(1);
+        }
+        assert_eq!(window.on_nack(1), Some(AckTimeout::Failed(1)));
+        sent_ago(&mut window, 1, Duration::from_secs(10));
+        assert_eq!(window.on_timeout(), vec![AckTimeout::Failed(1)]);
+        assert_eq!(window.window(), 4);
+        assert_eq!(window.on_nack(0), Some(AckTimeout::Retransmit(0)));
+    }
+}
@@ -0,0 +1,51 @@
+use uuid::Uuid;
+
+use crate::{
+    backend::frontend_manager::FrontendManager,
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{CancelFileTransfer, FrontendEvent},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_cancel_file_transfer(
+        &mut self,
+        cancel_file_transfer: CancelFileTransfer,
+    ) {
+        // Cancel a queued, offered, in-progress or paused file transfer.
+        // The peer is notified (if the offer was sent), and any partly received files are removed.
+
+        // Parse the unique ID
+        let unique_id: Uuid = match cancel_file_transfer.unique_id.parse() {
+            Ok(unique_id) => unique_id,
+            Err(_) => {
+                // Invalid unique ID
+                self.peer_manager
+                    .backend_event_tx
+                    .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                        event: FrontendEvent::CancelFileTransfer(cancel_file_transfer),
+                        error: "Invalid unique ID".to_string(),
+                    }))
+                    .await
+                    .expect("Failed to send BadFrontendEvent event to the backend");
+                return;
+            }
+        };
+
+        if let Err(e) = self
+            .peer_manager
+            .cancel_file_transfer(unique_id, cancel_file_transfer.message.clone(), false)
+            .await
+        {
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::CancelFileTransfer(cancel_file_transfer),
+                    error: e,
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+        }
+    }
+}
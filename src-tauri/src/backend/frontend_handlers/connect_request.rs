@@ -3,10 +3,7 @@ use std::net::SocketAddr;
 use tracing::{debug, warn};
 
 use crate::{
-    backend::{
-        frontend_manager::FrontendManager,
-        protocol::{ConnectionInfo, Message},
-    },
+    backend::frontend_manager::FrontendManager,
     js_api::{
         backend_event::{BackendEvent, BadFrontendEvent},
         frontend_event::{ConnectRequest, FrontendEvent},
@@ -38,25 +35,12 @@ impl FrontendManager {
         match self.peer_manager.connect(peer_addr).await {
             Ok(_) => {
                 // Connection successful
-                // Send a `ConnectionRequest` to the peer
+                // Send a `ConnectionRequest` to the peer, starting the identity handshake
                 // Retry 20 times if the peer is not found in the active peers list (500ms * 20 = 10s timeout)
                 // Note that we drop the lock after each iteration to prevent deadlocks.
                 let mut success = false;
                 for _ in 0..20 {
-                    let peers = self.peer_manager.active_peers.lock().await;
-                    if let Some(peer) = peers.get(&peer_addr) {
-                        peer.tx
-                            .send(Message::ConnectRequest(ConnectionInfo {
-                                name: "todo!".to_string(),
-                                backend_version: env!("CARGO_PKG_VERSION").to_string(),
-                                // identitiy: EcdsaConnectionInfo {
-                                //     public_key: vec![], // todo!(),
-                                //     signature: vec![],  // todo!(),
-                                //     nonce: vec![],      // todo!(),
-                                // },
-                            }))
-                            .await
-                            .expect("Failed to send ConnectRequest message to the peer");
+                    if self.peer_manager.start_handshake(peer_addr).await {
                         success = true;
                         break;
                     }
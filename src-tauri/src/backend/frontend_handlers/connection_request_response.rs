@@ -4,7 +4,7 @@ use crate::{
     backend::{
         frontend_manager::FrontendManager,
         peer_manager::PeerState,
-        protocol::{ConnectionInfo, ConnectionPermit, ConnectionResponse, Message},
+        protocol::{ConnectionPermit, ConnectionResponse, Message},
     },
     js_api::{
         backend_event::{BackendEvent, BadFrontendEvent},
@@ -39,48 +39,61 @@ impl FrontendManager {
             }
         };
 
+        let mut authenticated = false;
+        // The peer is dropped once the lock is released, with this reason
+        let mut dropped: Option<Option<String>> = None;
         let mut peers = self.peer_manager.active_peers.lock().await;
 
         if let Some(peer) = peers.get_mut(&peer_addr) {
             if connection_request_response.accept {
                 // Connection accepted, change state to `Authenticated` and send a `ConnectResponse` with `Permit` message
                 // peer.state = PeerState::Authenticated;
-                if let PeerState::Connected { peer_info } = &peer.state {
-                    let peer_info = peer_info.as_ref().expect(
-                        "Peer info was not set when handling the connection request response???",
-                    );
-                    let connection_response = ConnectionResponse {
-                        permit: ConnectionPermit::Permit {
-                            identitiy: ConnectionInfo {
-                                name: "todo!".to_string(),
-                                backend_version: env!("CARGO_PKG_VERSION").to_string(),
-                                // identitiy: EcdsaConnectionInfo {
-                                //     public_key: vec![], // TODO: Implement this
-                                //     signature: vec![],  // TODO: Implement this
-                                //     nonce: vec![],      // TODO: Implement this
-                                // },
+                match &peer.state {
+                    PeerState::Connected {
+                        peer_info: Some(peer_info),
+                    } if peer.handshake.is_none() => {
+                        let connection_response = ConnectionResponse {
+                            permit: ConnectionPermit::Permit {
+                                identitiy: self.peer_manager.connection_info(),
                             },
-                        },
-                        message: connection_request_response.message.clone(),
-                    };
+                            message: connection_request_response.message.clone(),
+                        };
 
-                    // Update state to `Authenticated`
-                    peer.state = PeerState::Authenticated {
-                        peer_info: peer_info.clone(),
-                    };
+                        // Update state to `Authenticated`
+                        peer.state = PeerState::Authenticated {
+                            peer_info: peer_info.clone(),
+                        };
+                        authenticated = true;
 
-                    // Send the connection response
-                    peer.tx
-                        .send(Message::ConnectResponse(connection_response))
-                        .await
-                        .expect("Failed to send ConnectResponse message to the peer");
+                        // Send the connection response
+                        peer.tx
+                            .send(Message::ConnectResponse(connection_response))
+                            .await
+                            .expect("Failed to send ConnectResponse message to the peer");
+                    }
+                    // The peer has not proved its identity yet
+                    PeerState::Connected { .. } => {
+                        self.peer_manager
+                            .backend_event_tx
+                            .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                                event: FrontendEvent::ConnectionRequestResponse(
+                                    connection_request_response,
+                                ),
+                                error: "Peer has not proved its identity yet".to_string(),
+                            }))
+                            .await
+                            .expect("Failed to send BadFrontendEvent event to the backend");
+                        return;
+                    }
+                    _ => {}
                 }
             } else {
                 // Connection rejected, send a `ConnectResponse` with `Deny` message
                 // This packet is treated as a disconnect request
                 match &peer.state {
-                    PeerState::Connected { peer_info } => {
-                        let peer_info = peer_info.as_ref().expect("Peer info was not set when handling the connection request response???");
+                    PeerState::Connected {
+                        peer_info: Some(peer_info),
+                    } => {
                         let connection_response = ConnectionResponse {
                             permit: ConnectionPermit::Deny,
                             message: connection_request_response.message.clone(),
@@ -109,12 +122,7 @@ impl FrontendManager {
                     _ => {
                         // Peer is in an invalid state.
                         // Drop the peer.
-                        self.peer_manager
-                            .drop_peer(
-                                peer_addr,
-                                "Peer is not in the connecting state".to_string().into(),
-                            )
-                            .await;
+                        dropped = Some("Peer is not in the connecting state".to_string().into());
                     }
                 }
             }
@@ -129,5 +137,20 @@ impl FrontendManager {
                 .await
                 .expect("Failed to send BadFrontendEvent event to the backend");
         }
+        drop(peers);
+
+        if let Some(reason) = dropped {
+            self.peer_manager.drop_peer(peer_addr, reason).await;
+            return;
+        }
+
+        // Remember the peer, send the rooms it is in, our sender keys for them (sealed with the
+        // session derived in the handshake), then the chat messages queued while it was offline.
+        if authenticated {
+            self.peer_manager.remember_peer(peer_addr).await;
+            self.peer_manager.sync_rooms(peer_addr).await;
+            self.peer_manager.send_sender_keys(peer_addr).await;
+            self.peer_manager.flush_chat_outbox(peer_addr).await;
+        }
     }
 }
@@ -0,0 +1,27 @@
+use crate::{
+    backend::frontend_manager::FrontendManager,
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{CreateRoom, FrontendEvent},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_create_room(&mut self, create_room: CreateRoom) {
+        // Create the room, and invite its members (those not connected once they connect)
+        if let Err(e) = self
+            .peer_manager
+            .create_room(create_room.name.clone(), create_room.members.clone())
+            .await
+        {
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::CreateRoom(create_room),
+                    error: e,
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+        }
+    }
+}
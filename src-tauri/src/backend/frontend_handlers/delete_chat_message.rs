@@ -0,0 +1,38 @@
+use crate::{
+    backend::{chat_store::ConversationKey, frontend_manager::FrontendManager},
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{DeleteChatMessage, FrontendEvent},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_delete_chat_message(
+        &mut self,
+        delete_chat_message: DeleteChatMessage,
+    ) {
+        // Delete a message we sent, and send the deletion to the peer (or every member of the room).
+        // Only the author of a message may delete it.
+        let result = match ConversationKey::from_request(
+            delete_chat_message.peer_identity.clone(),
+            delete_chat_message.room_id.clone(),
+        ) {
+            Ok(key) => {
+                self.peer_manager
+                    .delete_chat_message(&key, &delete_chat_message.message_id)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::DeleteChatMessage(delete_chat_message),
+                    error: e,
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+        }
+    }
+}
@@ -39,76 +39,21 @@ impl FrontendManager {
             }
         };
 
-        let mut peers = self.peer_manager.active_peers.lock().await;
+        // Do not hold the lock while dropping the peer: `drop_peer` takes it again
+        let peer = {
+            let mut peers = self.peer_manager.active_peers.lock().await;
+            peers.get_mut(&peer_addr).map(|peer| {
+                let peer_info = match &peer.state {
+                    PeerState::Connected { peer_info } => peer_info.clone(),
+                    PeerState::Authenticated { peer_info } => Some(peer_info.clone()),
+                    // Peer is already disconnecting
+                    PeerState::Disconnecting { .. } => None,
+                };
+                (peer.tx.clone(), peer_info)
+            })
+        };
 
-        if let Some(peer) = peers.get_mut(&peer_addr) {
-            // Peer is connected
-            // Send a `DisconnectRequest` message to the peer
-            match peer
-                .tx
-                .send(Message::DisconnectRequest(MessageDisconnectRequest {
-                    message: handle_disconnect_request.message.clone(),
-                }))
-                .await
-            {
-                Ok(_) => {
-                    // Message sent successfully
-                    // Change state to `Disconnecting`
-                    peer.state = PeerState::Disconnecting {
-                        reason: handle_disconnect_request.message.clone(),
-                        peer_info: {
-                            match &peer.state {
-                                PeerState::Connected { peer_info } => {
-                                    if let Some(peer_info) = peer_info {
-                                        peer_info.clone()
-                                    } else {
-                                        // Peer info not set?
-                                        self.peer_manager
-                                            .drop_peer(
-                                                peer_addr,
-                                                Some(
-                                                    "Peer info not set when handling DisconnectRequest"
-                                                        .to_string(),
-                                                ),
-                                            )
-                                            .await;
-                                        return;
-                                    }
-                                }
-                                PeerState::Authenticated { peer_info } => peer_info.clone(),
-                                PeerState::Disconnecting { .. } => {
-                                    // Peer is already disconnecting, but they sent another disconnect request?
-                                    // Disconnect the peer
-                                    self.peer_manager
-                                        .drop_peer(
-                                            peer_addr,
-                                            Some("Peer is not connected".to_string()),
-                                        )
-                                        .await;
-                                    return;
-                                }
-                            }
-                        },
-                    };
-                }
-                Err(e) => {
-                    // Failed to send the message
-                    // Disconnect the peer except override the message with the error
-                    warn!(
-                        ?e,
-                        "Failed to send DisconnectRequest message to the peer. Disconnecting the peer with an error message."
-                    );
-                    self.peer_manager
-                        .drop_peer(
-                            peer_addr,
-                            Some(
-                                "Failed to send DisconnectRequest message to the peer".to_string(),
-                            ),
-                        )
-                        .await;
-                }
-            }
-        } else {
+        let Some((peer_tx, peer_info)) = peer else {
             // Peer is not connected
             // Ignore the request
             warn!("Tried to disconnect a peer that isn't connected (DisconnectRequest).");
@@ -122,6 +67,56 @@ impl FrontendManager {
                 }))
                 .await
                 .expect("Failed to send BadFrontendEvent event to the backend");
+            return;
+        };
+
+        let Some(peer_info) = peer_info else {
+            // Peer info not set, or the peer is already disconnecting?
+            // Disconnect the peer
+            self.peer_manager
+                .drop_peer(peer_addr, Some("Peer is not connected".to_string()))
+                .await;
+            return;
+        };
+
+        // Peer is connected
+        // Send a `DisconnectRequest` message to the peer
+        match peer_tx
+            .send(Message::DisconnectRequest(MessageDisconnectRequest {
+                message: handle_disconnect_request.message.clone(),
+            }))
+            .await
+        {
+            Ok(_) => {
+                // Message sent successfully
+                // Change state to `Disconnecting`
+                if let Some(peer) = self
+                    .peer_manager
+                    .active_peers
+                    .lock()
+                    .await
+                    .get_mut(&peer_addr)
+                {
+                    peer.state = PeerState::Disconnecting {
+                        reason: handle_disconnect_request.message.clone(),
+                        peer_info,
+                    };
+                }
+            }
+            Err(e) => {
+                // Failed to send the message
+                // Disconnect the peer except override the message with the error
+                warn!(
+                    ?e,
+                    "Failed to send DisconnectRequest message to the peer. Disconnecting the peer with an error message."
+                );
+                self.peer_manager
+                    .drop_peer(
+                        peer_addr,
+                        Some("Failed to send DisconnectRequest message to the peer".to_string()),
+                    )
+                    .await;
+            }
         }
     }
 }
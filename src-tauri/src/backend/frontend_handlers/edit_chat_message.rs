@@ -0,0 +1,39 @@
+use crate::{
+    backend::{chat_store::ConversationKey, frontend_manager::FrontendManager},
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{EditChatMessage, FrontendEvent},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_edit_chat_message(&mut self, edit_chat_message: EditChatMessage) {
+        // Edit a message we sent, and send the edit to the peer (or every member of the room).
+        // Only the author of a message may edit it.
+        let result = match ConversationKey::from_request(
+            edit_chat_message.peer_identity.clone(),
+            edit_chat_message.room_id.clone(),
+        ) {
+            Ok(key) => {
+                self.peer_manager
+                    .edit_chat_message(
+                        &key,
+                        &edit_chat_message.message_id,
+                        edit_chat_message.body.clone(),
+                    )
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::EditChatMessage(edit_chat_message),
+                    error: e,
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+        }
+    }
+}
@@ -1,9 +1,11 @@
-use tokio::fs;
+use std::sync::Arc;
+
 use uuid::Uuid;
 
 use crate::{
     backend::{
         frontend_manager::FrontendManager,
+        manifest::FileSet,
         peer_manager::{FileTransferDirection, FileTransferStatus},
         protocol::{self, Message},
     },
@@ -38,11 +40,15 @@ impl FrontendManager {
             }
         };
 
+        // An accepted offer is answered in the background, once the files we already have are placed,
+        // and the block signatures of the older versions we have are sent (see `PeerManager::spawn_offer_acceptance`)
+        let mut accepted = None;
+        let mut rejected = false;
         let mut active_transfers = self.peer_manager.active_transfers.lock().await;
 
         if let Some(transfer) = active_transfers.get_mut(&unique_id) {
             // We cannot "accept" a file offer if we are the one sending the file.
-            if let FileTransferDirection::Sending { .. } = transfer.direction {
+            if let FileTransferDirection::Sending = transfer.direction {
                 self.peer_manager
                     .backend_event_tx
                     .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
@@ -67,41 +73,58 @@ impl FrontendManager {
                 .await
                 .get_mut(&transfer.peer_addr)
             {
-                peer.tx
-                    .send(Message::FileOfferResponse(protocol::FileOfferResponse {
-                        unique_id: transfer.unique_id,
-                        accept: file_offer_response.accept,
-                    }))
-                    .await
-                    .expect("Failed to send FileOfferResponse message to the peer");
-
-                if file_offer_response.accept {
-                    // Accepted!
-                    // We can accept file chunks from the peer now!
-                    // Create a file handle for the incoming file transfer
-                    let file_handle = match fs::File::create(&transfer.filename).await {
-                        Ok(file_handle) => file_handle,
+                // Accepted?
+                // Create the files (and folders) under the download directory first,
+                // so we can still reject the offer if that fails.
+                let mut accept = file_offer_response.accept;
+                let mut create_error = None;
+                if accept {
+                    let download_dir = self.peer_manager.download_dir.lock().await.clone();
+                    transfer.manifest.set_download_dir(&download_dir);
+                    match FileSet::create(&transfer.manifest, transfer.unique_id).await {
+                        Ok(files) => {
+                            // We can accept file chunks from the peer now!
+                            transfer.status = FileTransferStatus::InProgress {
+                                files: Arc::new(files),
+                            };
+                            accepted = Some((peer.tx.clone(), transfer.chunk_len));
+                        }
                         Err(e) => {
-                            // Notify frontend of error
-                            self.peer_manager
-                                .backend_event_tx
-                                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
-                                    event: FrontendEvent::FileOfferResponse(file_offer_response),
-                                    error: format!("Failed to create file handle: {}", e),
-                                }))
-                                .await
-                                .expect("Failed to send BadFrontendEvent event to the backend");
-                            return;
+                            accept = false;
+                            create_error = Some(format!("Failed to create files: {}", e));
                         }
-                    };
-
-                    transfer.status = FileTransferStatus::InProgress {
-                        file_handle: file_handle.into(),
-                    };
-                } else {
+                    }
+                }
+                if !accept {
                     // Rejected.
                     // Change the transfer state to "Rejected"
                     transfer.status = FileTransferStatus::Rejected;
+                    rejected = true;
+                }
+
+                if !accept {
+                    peer.tx
+                        .send(Message::FileOfferResponse(protocol::FileOfferResponse {
+                            unique_id: transfer.unique_id,
+                            accept,
+                            max_chunk_len: transfer.chunk_len,
+                            reason: create_error.clone(),
+                            have_files: Vec::new(),
+                        }))
+                        .await
+                        .expect("Failed to send FileOfferResponse message to the peer");
+                }
+
+                if let Some(error) = create_error {
+                    // Notify frontend of error
+                    self.peer_manager
+                        .backend_event_tx
+                        .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                            event: FrontendEvent::FileOfferResponse(file_offer_response),
+                            error,
+                        }))
+                        .await
+                        .expect("Failed to send BadFrontendEvent event to the backend");
                 }
             } else {
                 // Peer is not connected, remove the transfer state
@@ -127,5 +150,18 @@ impl FrontendManager {
                 .await
                 .expect("Failed to send BadFrontendEvent event to the backend");
         }
+        drop(active_transfers);
+
+        if rejected {
+            self.peer_manager.release_file_offer(unique_id).await;
+            self.peer_manager.record_transfer_history(unique_id).await;
+        }
+
+        if let Some((peer_tx, max_chunk_len)) = accepted {
+            // Count the offer towards the peer's daily limit
+            self.peer_manager.confirm_file_offer(unique_id).await;
+            self.peer_manager
+                .spawn_offer_acceptance(unique_id, peer_tx, max_chunk_len);
+        }
     }
 }
@@ -0,0 +1,72 @@
+use uuid::Uuid;
+
+use crate::{
+    backend::frontend_manager::FrontendManager,
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FileRequestResponse, FrontendEvent},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_file_request_response(
+        &mut self,
+        file_request_response: FileRequestResponse,
+    ) {
+        // Approve or deny a file request from a peer.
+        // If approved, the file is queued to be sent like any other file transfer.
+
+        let request = match file_request_response.request_id.parse::<Uuid>() {
+            Ok(request_id) => self
+                .peer_manager
+                .received_file_requests
+                .lock()
+                .await
+                .remove(&request_id)
+                .map(|request| (request_id, request))
+                .ok_or("Invalid file request ID: ID does not exist.".to_string()),
+            Err(e) => Err(format!("Invalid File Request ID (UUID): {}", e)),
+        };
+        let (request_id, request) = match request {
+            Ok(request) => request,
+            Err(error) => {
+                self.peer_manager
+                    .backend_event_tx
+                    .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                        event: FrontendEvent::FileRequestResponse(file_request_response),
+                        error,
+                    }))
+                    .await
+                    .expect("Failed to send BadFrontendEvent event to the backend");
+                return;
+            }
+        };
+
+        let peer_addr = request.peer_addr;
+        if !file_request_response.accept {
+            self.peer_manager
+                .deny_file_request(request_id, peer_addr, "Request denied".to_string())
+                .await;
+            return;
+        }
+
+        if let Err(error) = self
+            .peer_manager
+            .approve_file_request(request_id, request)
+            .await
+        {
+            // Tell the peer, and the frontend
+            self.peer_manager
+                .deny_file_request(request_id, peer_addr, error.clone())
+                .await;
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::FileRequestResponse(file_request_response),
+                    error,
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+        }
+    }
+}
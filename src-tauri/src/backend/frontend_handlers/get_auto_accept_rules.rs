@@ -0,0 +1,16 @@
+use crate::{
+    backend::frontend_manager::FrontendManager,
+    js_api::backend_event::{AutoAcceptRulesInfo, BackendEvent},
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_get_auto_accept_rules(&mut self) {
+        // Send the auto-accept rules to the frontend
+        let rules = self.peer_manager.auto_accept_rules.lock().await.rules();
+        self.peer_manager
+            .backend_event_tx
+            .send(BackendEvent::AutoAcceptRules(AutoAcceptRulesInfo { rules }))
+            .await
+            .expect("Failed to send AutoAcceptRules event to the frontend");
+    }
+}
@@ -0,0 +1,8 @@
+use crate::backend::frontend_manager::FrontendManager;
+
+impl FrontendManager {
+    pub(crate) async fn handle_get_chat_conversations(&mut self) {
+        // Send every conversation and its number of unread messages to the frontend
+        self.peer_manager.emit_chat_conversations().await;
+    }
+}
@@ -0,0 +1,40 @@
+use crate::{
+    backend::{chat_store::ConversationKey, frontend_manager::FrontendManager},
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FrontendEvent, GetChatHistory},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_get_chat_history(&mut self, get_chat_history: GetChatHistory) {
+        // Send the page of the conversation with the peer (or room) to the frontend
+        let key = match ConversationKey::from_request(
+            get_chat_history.peer_identity.clone(),
+            get_chat_history.room_id.clone(),
+        ) {
+            Ok(key) => key,
+            Err(e) => {
+                self.peer_manager
+                    .backend_event_tx
+                    .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                        event: FrontendEvent::GetChatHistory(get_chat_history),
+                        error: e,
+                    }))
+                    .await
+                    .expect("Failed to send BadFrontendEvent event to the backend");
+                return;
+            }
+        };
+        let page = self.peer_manager.chat_store.lock().await.page(
+            &key,
+            get_chat_history.before.as_deref(),
+            get_chat_history.limit,
+        );
+        self.peer_manager
+            .backend_event_tx
+            .send(BackendEvent::ChatHistory(page))
+            .await
+            .expect("Failed to send ChatHistory event to the frontend");
+    }
+}
@@ -0,0 +1,13 @@
+use crate::{backend::frontend_manager::FrontendManager, js_api::backend_event::BackendEvent};
+
+impl FrontendManager {
+    pub(crate) async fn handle_get_hash_index(&mut self) {
+        // Send the library folders and the size of the hash index to the frontend
+        let info = self.peer_manager.hash_index.lock().await.info();
+        self.peer_manager
+            .backend_event_tx
+            .send(BackendEvent::HashIndex(info))
+            .await
+            .expect("Failed to send HashIndex event to the frontend");
+    }
+}
@@ -0,0 +1,16 @@
+use crate::{backend::frontend_manager::FrontendManager, js_api::backend_event::BackendEvent};
+
+impl FrontendManager {
+    pub(crate) async fn handle_get_rooms(&mut self) {
+        // Send every room we know of to the frontend
+        let rooms = {
+            let known_peers = self.peer_manager.known_peers.lock().await;
+            self.peer_manager.rooms.lock().await.info(&known_peers)
+        };
+        self.peer_manager
+            .backend_event_tx
+            .send(BackendEvent::Rooms(rooms))
+            .await
+            .expect("Failed to send Rooms event to the frontend");
+    }
+}
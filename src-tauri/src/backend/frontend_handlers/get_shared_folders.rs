@@ -0,0 +1,22 @@
+use crate::{
+    backend::frontend_manager::FrontendManager,
+    js_api::backend_event::{BackendEvent, SharedFoldersInfo},
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_get_shared_folders(&mut self) {
+        // Send the shared folders to the frontend
+        let shares = self
+            .peer_manager
+            .shared_folders
+            .lock()
+            .await
+            .shares()
+            .to_vec();
+        self.peer_manager
+            .backend_event_tx
+            .send(BackendEvent::SharedFolders(SharedFoldersInfo { shares }))
+            .await
+            .expect("Failed to send SharedFolders event to the frontend");
+    }
+}
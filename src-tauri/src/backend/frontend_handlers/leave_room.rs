@@ -0,0 +1,23 @@
+use crate::{
+    backend::frontend_manager::FrontendManager,
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FrontendEvent, LeaveRoom},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_leave_room(&mut self, leave_room: LeaveRoom) {
+        // Leave the room, and tell its members. Its history is kept.
+        if let Err(e) = self.peer_manager.leave_room(&leave_room.room_id).await {
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::LeaveRoom(leave_room),
+                    error: e,
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+        }
+    }
+}
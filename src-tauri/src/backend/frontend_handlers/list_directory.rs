@@ -0,0 +1,78 @@
+use std::time::Instant;
+
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::{
+    backend::{
+        frontend_manager::FrontendManager,
+        protocol::{self, Message},
+        shares::{FileRequestState, expire_file_requests},
+    },
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FrontendEvent, ListDirectory},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_list_directory(&mut self, list_directory: ListDirectory) {
+        // Ask the peer for the listing of a directory it shares.
+        // The listing is sent to the frontend as a `DirectoryListing` event.
+
+        // Parse the IP address, and find the peer
+        let (peer_addr, peer_tx) = match self.peer_manager.frontend_peer(&list_directory.ip).await {
+            Ok(peer_tx) => peer_tx,
+            Err(error) => {
+                self.peer_manager
+                    .backend_event_tx
+                    .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                        event: FrontendEvent::ListDirectory(list_directory),
+                        error,
+                    }))
+                    .await
+                    .expect("Failed to send BadFrontendEvent event to the backend");
+                return;
+            }
+        };
+
+        // Remember the request, to match the peer's listing
+        let request_id = Uuid::new_v4();
+        let mut directory_requests = self.peer_manager.directory_requests.lock().await;
+        expire_file_requests(&mut directory_requests);
+        directory_requests.insert(
+            request_id,
+            FileRequestState {
+                peer_addr,
+                path: list_directory.path.clone(),
+                local_path: None,
+                ranged: false,
+                created_at: Instant::now(),
+            },
+        );
+        drop(directory_requests);
+
+        debug!("Listing {:?} of {}", list_directory.path, peer_addr);
+        if let Err(e) = peer_tx
+            .send(Message::ListDirectory(protocol::ListDirectory {
+                request_id,
+                path: list_directory.path.clone(),
+            }))
+            .await
+        {
+            self.peer_manager
+                .directory_requests
+                .lock()
+                .await
+                .remove(&request_id);
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::ListDirectory(list_directory),
+                    error: format!("Failed to send ListDirectory message to the peer: {}", e),
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+        }
+    }
+}
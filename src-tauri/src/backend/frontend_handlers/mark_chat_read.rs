@@ -0,0 +1,48 @@
+use tracing::warn;
+
+use crate::{
+    backend::{chat_store::ConversationKey, frontend_manager::FrontendManager},
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FrontendEvent, MarkChatRead},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_mark_chat_read(&mut self, mark_chat_read: MarkChatRead) {
+        // Mark the conversation as read, report the new unread counts,
+        // and tell the peer (or the members of the room) what we read
+        let key = match ConversationKey::from_request(
+            mark_chat_read.peer_identity.clone(),
+            mark_chat_read.room_id.clone(),
+        ) {
+            Ok(key) => key,
+            Err(e) => {
+                self.peer_manager
+                    .backend_event_tx
+                    .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                        event: FrontendEvent::MarkChatRead(mark_chat_read),
+                        error: e,
+                    }))
+                    .await
+                    .expect("Failed to send BadFrontendEvent event to the backend");
+                return;
+            }
+        };
+        let marked = self
+            .peer_manager
+            .chat_store
+            .lock()
+            .await
+            .mark_read(&key)
+            .await;
+        match marked {
+            Ok(true) => {
+                self.peer_manager.emit_chat_conversations().await;
+                self.peer_manager.send_read_receipt(&key).await;
+            }
+            Ok(false) => {}
+            Err(e) => warn!("{}", e),
+        }
+    }
+}
@@ -1,6 +1,40 @@
+pub mod cancel_file_transfer;
 pub mod connect_request;
 pub mod connection_request_response;
+pub mod create_room;
+pub mod delete_chat_message;
 pub mod disconnect_request;
+pub mod edit_chat_message;
 pub mod file_offer_response;
+pub mod file_request_response;
 pub mod frontend_ready;
+pub mod get_auto_accept_rules;
+pub mod get_chat_conversations;
+pub mod get_chat_history;
+pub mod get_hash_index;
+pub mod get_rooms;
+pub mod get_shared_folders;
+pub mod leave_room;
+pub mod list_directory;
+pub mod mark_chat_read;
+pub mod pause_file_transfer;
+pub mod query_transfer_history;
+pub mod react_to_chat_message;
+pub mod reorder_file_transfer;
+pub mod request_file;
+pub mod resume_file_transfer;
+pub mod send_chat_message;
+pub mod send_room_message;
+pub mod send_typing;
+pub mod set_auto_accept_rules;
+pub mod set_bandwidth_limits;
+pub mod set_download_dir;
+pub mod set_file_transfer_priority;
+pub mod set_library_folders;
+pub mod set_metadata_policy;
+pub mod set_offer_policy;
+pub mod set_room_members;
+pub mod set_shared_folders;
+pub mod set_transfer_concurrency;
+pub mod swarm_download;
 pub mod transmit_file;
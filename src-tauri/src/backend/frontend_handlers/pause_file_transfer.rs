@@ -0,0 +1,64 @@
+use uuid::Uuid;
+
+use crate::{
+    backend::frontend_manager::FrontendManager,
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FrontendEvent, PauseFileTransfer},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_pause_file_transfer(
+        &mut self,
+        pause_file_transfer: PauseFileTransfer,
+    ) {
+        // Pause a file transfer.
+        // A queued transfer keeps its place in the queue, but is not started until resumed.
+        // An in-progress transfer stops sending new chunks, and the peer is notified.
+
+        // Parse the unique ID
+        let unique_id: Uuid = match pause_file_transfer.unique_id.parse() {
+            Ok(unique_id) => unique_id,
+            Err(_) => {
+                // Invalid unique ID
+                self.peer_manager
+                    .backend_event_tx
+                    .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                        event: FrontendEvent::PauseFileTransfer(pause_file_transfer),
+                        error: "Invalid unique ID".to_string(),
+                    }))
+                    .await
+                    .expect("Failed to send BadFrontendEvent event to the backend");
+                return;
+            }
+        };
+
+        // Is the transfer still queued?
+        if self
+            .peer_manager
+            .scheduler
+            .lock()
+            .await
+            .set_paused(unique_id, true)
+        {
+            return;
+        }
+
+        // Otherwise, it must be in progress (or paused)
+        if let Err(e) = self
+            .peer_manager
+            .set_file_transfer_paused(unique_id, true, false)
+            .await
+        {
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::PauseFileTransfer(pause_file_transfer),
+                    error: e,
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+        }
+    }
+}
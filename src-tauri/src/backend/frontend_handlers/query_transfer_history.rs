@@ -0,0 +1,24 @@
+use crate::{
+    backend::frontend_manager::FrontendManager,
+    js_api::{backend_event::BackendEvent, frontend_event::QueryTransferHistory},
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_query_transfer_history(
+        &mut self,
+        query_transfer_history: QueryTransferHistory,
+    ) {
+        // Send the page of the transfer history matching the query to the frontend
+        let page = self
+            .peer_manager
+            .transfer_history
+            .lock()
+            .await
+            .query(&query_transfer_history);
+        self.peer_manager
+            .backend_event_tx
+            .send(BackendEvent::TransferHistory(page))
+            .await
+            .expect("Failed to send TransferHistory event to the frontend");
+    }
+}
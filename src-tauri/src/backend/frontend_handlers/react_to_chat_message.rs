@@ -0,0 +1,42 @@
+use crate::{
+    backend::{chat_store::ConversationKey, frontend_manager::FrontendManager},
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FrontendEvent, ReactToChatMessage},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_react_to_chat_message(
+        &mut self,
+        react_to_chat_message: ReactToChatMessage,
+    ) {
+        // Add (or remove) our reaction to a message, and send it to the peer (or every member of the room).
+        let result = match ConversationKey::from_request(
+            react_to_chat_message.peer_identity.clone(),
+            react_to_chat_message.room_id.clone(),
+        ) {
+            Ok(key) => {
+                self.peer_manager
+                    .react_to_chat_message(
+                        &key,
+                        &react_to_chat_message.message_id,
+                        react_to_chat_message.emoji.clone(),
+                        react_to_chat_message.added,
+                    )
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::ReactToChatMessage(react_to_chat_message),
+                    error: e,
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    backend::frontend_manager::FrontendManager,
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FrontendEvent, ReorderFileTransfer},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_reorder_file_transfer(
+        &mut self,
+        reorder_file_transfer: ReorderFileTransfer,
+    ) {
+        // Move a queued file transfer to another position in the queue
+
+        // Parse the unique ID, and move the transfer
+        let reordered = match reorder_file_transfer.unique_id.parse::<Uuid>() {
+            Ok(unique_id) => self
+                .peer_manager
+                .scheduler
+                .lock()
+                .await
+                .reorder(unique_id, reorder_file_transfer.position as usize),
+            Err(_) => false,
+        };
+
+        if !reordered {
+            // Invalid unique ID, or the transfer is not queued
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::ReorderFileTransfer(reorder_file_transfer),
+                    error: "No such queued file transfer".to_string(),
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+            return;
+        }
+
+        info!(
+            "Moved queued file transfer {} to position {}",
+            reorder_file_transfer.unique_id, reorder_file_transfer.position
+        );
+    }
+}
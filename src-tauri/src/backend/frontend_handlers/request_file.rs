@@ -0,0 +1,82 @@
+use std::time::Instant;
+
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    backend::{
+        frontend_manager::FrontendManager,
+        protocol::{FileRequest, Message},
+        shares::{FileRequestState, expire_file_requests},
+    },
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FrontendEvent, RequestFile},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_request_file(&mut self, request_file: RequestFile) {
+        // Ask the peer for a file it shares.
+        // If the peer approves, its file offer is accepted automatically.
+
+        // Parse the IP address, and find the peer
+        let (peer_addr, peer_tx) = match self.peer_manager.frontend_peer(&request_file.ip).await {
+            Ok(peer_tx) => peer_tx,
+            Err(error) => {
+                self.peer_manager
+                    .backend_event_tx
+                    .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                        event: FrontendEvent::RequestFile(request_file),
+                        error,
+                    }))
+                    .await
+                    .expect("Failed to send BadFrontendEvent event to the backend");
+                return;
+            }
+        };
+
+        // Remember the request, to accept the peer's offer
+        let request_id = Uuid::new_v4();
+        let mut sent_file_requests = self.peer_manager.sent_file_requests.lock().await;
+        expire_file_requests(&mut sent_file_requests);
+        sent_file_requests.insert(
+            request_id,
+            FileRequestState {
+                peer_addr,
+                path: request_file.path.clone(),
+                local_path: None,
+                ranged: false,
+                created_at: Instant::now(),
+            },
+        );
+        drop(sent_file_requests);
+
+        info!(
+            "Requesting {} from {} (request {})",
+            request_file.path, peer_addr, request_id
+        );
+        if let Err(e) = peer_tx
+            .send(Message::FileRequest(FileRequest {
+                request_id,
+                path: request_file.path.clone(),
+                ranged: false,
+            }))
+            .await
+        {
+            self.peer_manager
+                .sent_file_requests
+                .lock()
+                .await
+                .remove(&request_id);
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::RequestFile(request_file),
+                    error: format!("Failed to send FileRequest message to the peer: {}", e),
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use uuid::Uuid;
+
+use crate::{
+    backend::frontend_manager::FrontendManager,
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FrontendEvent, ResumeFileTransfer},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_resume_file_transfer(
+        &mut self,
+        resume_file_transfer: ResumeFileTransfer,
+    ) {
+        // Resume a paused file transfer.
+        // A queued transfer is started as soon as it is next in the queue, and there is room.
+        // An in-progress transfer carries on where it stopped, and the peer is notified.
+
+        // Parse the unique ID
+        let unique_id: Uuid = match resume_file_transfer.unique_id.parse() {
+            Ok(unique_id) => unique_id,
+            Err(_) => {
+                // Invalid unique ID
+                self.peer_manager
+                    .backend_event_tx
+                    .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                        event: FrontendEvent::ResumeFileTransfer(resume_file_transfer),
+                        error: "Invalid unique ID".to_string(),
+                    }))
+                    .await
+                    .expect("Failed to send BadFrontendEvent event to the backend");
+                return;
+            }
+        };
+
+        // Is the transfer still queued?
+        if self
+            .peer_manager
+            .scheduler
+            .lock()
+            .await
+            .set_paused(unique_id, false)
+        {
+            return;
+        }
+
+        // Otherwise, it must be in progress (or paused)
+        if let Err(e) = self
+            .peer_manager
+            .set_file_transfer_paused(unique_id, false, false)
+            .await
+        {
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::ResumeFileTransfer(resume_file_transfer),
+                    error: e,
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use crate::{
+    backend::frontend_manager::FrontendManager,
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FrontendEvent, SendChatMessage},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_send_chat_message(&mut self, send_chat_message: SendChatMessage) {
+        // Send a chat message to the peer, or queue it if the peer is not connected.
+        // The frontend is told once it is sent (or queued), and again once the peer acknowledges it.
+        if let Err(e) = self
+            .peer_manager
+            .send_chat_message(&send_chat_message.peer, send_chat_message.body.clone())
+            .await
+        {
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::SendChatMessage(send_chat_message),
+                    error: e,
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use crate::{
+    backend::frontend_manager::FrontendManager,
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FrontendEvent, SendRoomMessage},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_send_room_message(&mut self, send_room_message: SendRoomMessage) {
+        // Send a chat message to every member of the room, queued for those not connected.
+        // The frontend is told once it is sent (or queued) to each member, and again once each acknowledges it.
+        if let Err(e) = self
+            .peer_manager
+            .send_room_message(&send_room_message.room_id, send_room_message.body.clone())
+            .await
+        {
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::SendRoomMessage(send_room_message),
+                    error: e,
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use crate::{
+    backend::{chat_store::ConversationKey, frontend_manager::FrontendManager},
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FrontendEvent, SendTyping},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_send_typing(&mut self, send_typing: SendTyping) {
+        // Tell the peer (or the members of the room) we are typing.
+        // The frontend sends this repeatedly while the user types, the backend limits how often it is sent.
+        let result = match ConversationKey::from_request(
+            send_typing.peer_identity.clone(),
+            send_typing.room_id.clone(),
+        ) {
+            Ok(key) => self.peer_manager.send_typing(&key).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::SendTyping(send_typing),
+                    error: e,
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use tracing::info;
+
+use crate::{
+    backend::{auto_accept::AutoAcceptRules, frontend_manager::FrontendManager},
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FrontendEvent, SetAutoAcceptRules},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_set_auto_accept_rules(
+        &mut self,
+        set_auto_accept_rules: SetAutoAcceptRules,
+    ) {
+        // Replace the auto-accept rules, and save them.
+        // Applies to offers received from now on.
+
+        // Check the rules (patterns and folders)
+        let rules = match AutoAcceptRules::new(set_auto_accept_rules.rules.clone()) {
+            Ok(rules) => rules,
+            Err(error) => {
+                self.peer_manager
+                    .backend_event_tx
+                    .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                        event: FrontendEvent::SetAutoAcceptRules(set_auto_accept_rules),
+                        error,
+                    }))
+                    .await
+                    .expect("Failed to send BadFrontendEvent event to the backend");
+                return;
+            }
+        };
+
+        info!(
+            "Setting {} auto-accept rules",
+            set_auto_accept_rules.rules.len()
+        );
+        let save_result = {
+            let mut auto_accept_rules = self.peer_manager.auto_accept_rules.lock().await;
+            *auto_accept_rules = rules;
+            auto_accept_rules.save().await
+        };
+
+        // The rules apply even if they could not be saved
+        if let Err(error) = save_result {
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::SetAutoAcceptRules(set_auto_accept_rules),
+                    error,
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+        }
+
+        self.handle_get_auto_accept_rules().await;
+    }
+}
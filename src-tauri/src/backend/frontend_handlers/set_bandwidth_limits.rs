@@ -0,0 +1,60 @@
+use std::net::SocketAddr;
+
+use tracing::info;
+
+use crate::{
+    backend::{bandwidth::BandwidthLimits, frontend_manager::FrontendManager},
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FrontendEvent, SetBandwidthLimits},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_set_bandwidth_limits(
+        &mut self,
+        set_bandwidth_limits: SetBandwidthLimits,
+    ) {
+        // Change the bandwidth limits, globally or for a single peer.
+        // The new limits apply to the very next chunk of every transfer.
+
+        let limits = BandwidthLimits {
+            upload: set_bandwidth_limits.upload.filter(|limit| *limit > 0),
+            download: set_bandwidth_limits.download.filter(|limit| *limit > 0),
+        };
+
+        match &set_bandwidth_limits.ip {
+            None => {
+                info!("Setting global bandwidth limits: {:?}", limits);
+                self.peer_manager.bandwidth.set_global_limits(limits).await;
+            }
+            Some(ip) => {
+                // Parse the IP address
+                let peer_addr: SocketAddr = match ip.parse() {
+                    Ok(peer_addr) => peer_addr,
+                    Err(_) => {
+                        // Invalid IP address
+                        self.peer_manager
+                            .backend_event_tx
+                            .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                                event: FrontendEvent::SetBandwidthLimits(set_bandwidth_limits),
+                                error: "Invalid IP address".to_string(),
+                            }))
+                            .await
+                            .expect("Failed to send BadFrontendEvent event to the backend");
+                        return;
+                    }
+                };
+
+                info!(
+                    "Setting bandwidth limits for peer {}: {:?}",
+                    peer_addr, limits
+                );
+                self.peer_manager
+                    .bandwidth
+                    .set_peer_limits(peer_addr, limits)
+                    .await;
+            }
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use std::path::PathBuf;
+
+use tracing::info;
+
+use crate::{
+    backend::frontend_manager::FrontendManager,
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FrontendEvent, SetDownloadDir},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_set_download_dir(&mut self, set_download_dir: SetDownloadDir) {
+        // Change the directory incoming files are saved to.
+        // Applies to offers accepted from now on.
+
+        // Make sure the directory exists
+        let path = PathBuf::from(&set_download_dir.path);
+        if !tokio::fs::metadata(&path)
+            .await
+            .is_ok_and(|metadata| metadata.is_dir())
+        {
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::SetDownloadDir(set_download_dir),
+                    error: "Download directory does not exist".to_string(),
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+            return;
+        }
+
+        info!("Setting download directory: {}", path.display());
+        *self.peer_manager.download_dir.lock().await = path;
+
+        // Index the files already in the new directory
+        self.peer_manager.spawn_hash_index_rescan();
+    }
+}
@@ -0,0 +1,49 @@
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    backend::frontend_manager::FrontendManager,
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FrontendEvent, SetFileTransferPriority},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_set_file_transfer_priority(
+        &mut self,
+        set_file_transfer_priority: SetFileTransferPriority,
+    ) {
+        // Change the priority of a queued file transfer.
+        // The transfer moves behind every other transfer of the same or higher priority.
+
+        // Parse the unique ID, and change the priority
+        let changed = match set_file_transfer_priority.unique_id.parse::<Uuid>() {
+            Ok(unique_id) => self
+                .peer_manager
+                .scheduler
+                .lock()
+                .await
+                .set_priority(unique_id, set_file_transfer_priority.priority),
+            Err(_) => false,
+        };
+
+        if !changed {
+            // Invalid unique ID, or the transfer is not queued
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::SetFileTransferPriority(set_file_transfer_priority),
+                    error: "No such queued file transfer".to_string(),
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+            return;
+        }
+
+        info!(
+            "Changed priority of queued file transfer {} to {}",
+            set_file_transfer_priority.unique_id, set_file_transfer_priority.priority
+        );
+    }
+}
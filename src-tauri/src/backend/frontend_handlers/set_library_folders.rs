@@ -0,0 +1,64 @@
+use std::path::PathBuf;
+
+use tracing::info;
+
+use crate::{
+    backend::frontend_manager::FrontendManager,
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FrontendEvent, SetLibraryFolders},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_set_library_folders(
+        &mut self,
+        set_library_folders: SetLibraryFolders,
+    ) {
+        // Replace the library folders, save them, and rescan.
+        // The frontend gets the new index once scanned.
+
+        // Make sure every folder exists
+        let mut folders = Vec::new();
+        for folder in &set_library_folders.folders {
+            let path = PathBuf::from(folder);
+            if !path.is_absolute()
+                || !tokio::fs::metadata(&path)
+                    .await
+                    .is_ok_and(|metadata| metadata.is_dir())
+            {
+                self.peer_manager
+                    .backend_event_tx
+                    .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                        error: format!("Library folder does not exist: {}", folder),
+                        event: FrontendEvent::SetLibraryFolders(set_library_folders),
+                    }))
+                    .await
+                    .expect("Failed to send BadFrontendEvent event to the backend");
+                return;
+            }
+            folders.push(path);
+        }
+
+        info!("Indexing {} library folders", folders.len());
+        let save_result = {
+            let mut hash_index = self.peer_manager.hash_index.lock().await;
+            hash_index.set_libraries(folders);
+            hash_index.save().await
+        };
+
+        // The folders apply even if they could not be saved
+        if let Err(error) = save_result {
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::SetLibraryFolders(set_library_folders),
+                    error,
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+        }
+
+        self.peer_manager.spawn_hash_index_rescan();
+    }
+}
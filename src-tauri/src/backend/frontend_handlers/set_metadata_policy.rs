@@ -0,0 +1,24 @@
+use tracing::info;
+
+use crate::{
+    backend::{file_metadata::MetadataPolicy, frontend_manager::FrontendManager},
+    js_api::frontend_event::SetMetadataPolicy,
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_set_metadata_policy(
+        &mut self,
+        set_metadata_policy: SetMetadataPolicy,
+    ) {
+        // Choose which metadata of received files is applied.
+        // Applies to transfers completing from now on.
+        let metadata_policy = MetadataPolicy {
+            modified: set_metadata_policy.modified,
+            mode: set_metadata_policy.mode,
+            read_only: set_metadata_policy.read_only,
+        };
+
+        info!("Setting metadata policy: {:?}", metadata_policy);
+        *self.peer_manager.metadata_policy.lock().await = metadata_policy;
+    }
+}
@@ -0,0 +1,22 @@
+use tracing::info;
+
+use crate::{backend::frontend_manager::FrontendManager, js_api::frontend_event::SetOfferPolicy};
+
+impl FrontendManager {
+    pub(crate) async fn handle_set_offer_policy(&mut self, set_offer_policy: SetOfferPolicy) {
+        // Change the size limits for incoming file offers.
+        // Applies to offers received from now on. A limit of 0 is unlimited.
+        let max_file_size = set_offer_policy.max_file_size.filter(|limit| *limit > 0);
+        let max_daily_bytes_per_peer = set_offer_policy
+            .max_daily_bytes_per_peer
+            .filter(|limit| *limit > 0);
+
+        info!(
+            "Setting offer policy: max file size {:?}, max daily bytes per peer {:?}",
+            max_file_size, max_daily_bytes_per_peer
+        );
+        let mut offer_policy = self.peer_manager.offer_policy.lock().await;
+        offer_policy.max_file_size = max_file_size;
+        offer_policy.max_daily_bytes_per_peer = max_daily_bytes_per_peer;
+    }
+}
@@ -0,0 +1,27 @@
+use crate::{
+    backend::frontend_manager::FrontendManager,
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FrontendEvent, SetRoomMembers},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_set_room_members(&mut self, set_room_members: SetRoomMembers) {
+        // Change the members of the room, and tell the old and new members
+        if let Err(e) = self
+            .peer_manager
+            .set_room_members(&set_room_members.room_id, set_room_members.members.clone())
+            .await
+        {
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::SetRoomMembers(set_room_members),
+                    error: e,
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+        }
+    }
+}
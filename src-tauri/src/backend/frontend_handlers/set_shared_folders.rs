@@ -0,0 +1,53 @@
+use tracing::info;
+
+use crate::{
+    backend::{frontend_manager::FrontendManager, shares::SharedFolders},
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FrontendEvent, SetSharedFolders},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_set_shared_folders(&mut self, set_shared_folders: SetSharedFolders) {
+        // Replace the shared folders, and save them.
+        // Applies to requests received from now on.
+
+        // Check the shares (names and folders)
+        let shares = match SharedFolders::new(set_shared_folders.shares.clone()) {
+            Ok(shares) => shares,
+            Err(error) => {
+                self.peer_manager
+                    .backend_event_tx
+                    .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                        event: FrontendEvent::SetSharedFolders(set_shared_folders),
+                        error,
+                    }))
+                    .await
+                    .expect("Failed to send BadFrontendEvent event to the backend");
+                return;
+            }
+        };
+
+        info!("Sharing {} folders", set_shared_folders.shares.len());
+        let save_result = {
+            let mut shared_folders = self.peer_manager.shared_folders.lock().await;
+            *shared_folders = shares;
+            shared_folders.save().await
+        };
+
+        // The shares apply even if they could not be saved
+        if let Err(error) = save_result {
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::SetSharedFolders(set_shared_folders),
+                    error,
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+        }
+
+        self.handle_get_shared_folders().await;
+    }
+}
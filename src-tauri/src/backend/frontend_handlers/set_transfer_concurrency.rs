@@ -0,0 +1,25 @@
+use tracing::info;
+
+use crate::{
+    backend::frontend_manager::FrontendManager, js_api::frontend_event::SetTransferConcurrency,
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_set_transfer_concurrency(
+        &mut self,
+        set_transfer_concurrency: SetTransferConcurrency,
+    ) {
+        // Change the concurrency limits of the transfer queue.
+        // Lowering the limits does not stop running transfers, it only holds back queued ones.
+
+        info!(
+            "Setting file transfer concurrency: {} at once, {} per peer",
+            set_transfer_concurrency.max_concurrent,
+            set_transfer_concurrency.max_concurrent_per_peer
+        );
+        self.peer_manager.scheduler.lock().await.set_limits(
+            set_transfer_concurrency.max_concurrent as usize,
+            set_transfer_concurrency.max_concurrent_per_peer as usize,
+        );
+    }
+}
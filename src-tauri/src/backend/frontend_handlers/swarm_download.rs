@@ -0,0 +1,64 @@
+use std::{collections::HashSet, net::SocketAddr};
+
+use tokio::sync::mpsc;
+
+use crate::{
+    backend::{frontend_manager::FrontendManager, protocol::Message},
+    js_api::{
+        backend_event::{BackendEvent, BadFrontendEvent},
+        frontend_event::{FrontendEvent, SwarmDownloadRequest},
+    },
+};
+
+impl FrontendManager {
+    pub(crate) async fn handle_swarm_download(&mut self, swarm_download: SwarmDownloadRequest) {
+        // Download the same content from several peers at once.
+        // Every source is asked for its path. The first offer starts the download, the others join it.
+
+        let sources = self.resolve_swarm_sources(&swarm_download).await;
+        let result = match sources {
+            Ok(sources) => self
+                .peer_manager
+                .start_swarm_download(sources)
+                .await
+                .map(|_| ()),
+            Err(error) => Err(error),
+        };
+
+        if let Err(error) = result {
+            self.peer_manager
+                .backend_event_tx
+                .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
+                    event: FrontendEvent::SwarmDownload(swarm_download),
+                    error,
+                }))
+                .await
+                .expect("Failed to send BadFrontendEvent event to the backend");
+        }
+    }
+
+    /// Find every source of the download: at least one, each a different connected peer.
+    async fn resolve_swarm_sources(
+        &self,
+        swarm_download: &SwarmDownloadRequest,
+    ) -> Result<Vec<(SocketAddr, mpsc::Sender<Message>, String)>, String> {
+        if swarm_download.sources.is_empty() {
+            return Err("No sources to download from".to_string());
+        }
+
+        let mut seen = HashSet::new();
+        let mut sources = Vec::new();
+        for source in &swarm_download.sources {
+            let (peer_addr, peer_tx) = self
+                .peer_manager
+                .frontend_peer(&source.ip)
+                .await
+                .map_err(|error| format!("Source {}: {}", source.ip, error))?;
+            if !seen.insert(peer_addr) {
+                return Err(format!("Source {} is listed twice", source.ip));
+            }
+            sources.push((peer_addr, peer_tx, source.path.clone()));
+        }
+        Ok(sources)
+    }
+}
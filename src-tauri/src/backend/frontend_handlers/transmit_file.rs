@@ -1,15 +1,10 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, path::PathBuf};
 
-use tokio::fs::File;
 use tracing::warn;
 use uuid::Uuid;
 
 use crate::{
-    backend::{
-        frontend_manager::FrontendManager,
-        peer_manager::{FileTransferDirection, FileTransferStatus},
-        protocol::{FileOffer, Message},
-    },
+    backend::{frontend_manager::FrontendManager, manifest::Manifest},
     js_api::{
         backend_event::{BackendEvent, BadFrontendEvent},
         frontend_event::{FrontendEvent, TransmitFile},
@@ -37,90 +32,33 @@ impl FrontendManager {
             }
         };
 
-        // Open the file
-        let file = match File::open(&transmit_file.path).await {
-            Ok(f) => f,
+        // List the files to send (walking any folders)
+        let paths: Vec<PathBuf> = transmit_file.paths.iter().map(PathBuf::from).collect();
+        let manifest = match Manifest::from_local_paths(&paths, &transmit_file.filename).await {
+            Ok(manifest) => manifest,
             Err(e) => {
                 // Notify frontend of error
                 self.peer_manager
                     .backend_event_tx
                     .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
                         event: FrontendEvent::TransmitFile(transmit_file.clone()),
-                        error: format!("Failed to open file: {}", e),
+                        error: e,
                     }))
                     .await
                     .expect("Failed to send BadFrontendEvent");
                 return;
             }
         };
-        let metadata = match file.metadata().await {
-            Ok(m) => m,
-            Err(e) => {
-                // Notify frontend of error
-                self.peer_manager
-                    .backend_event_tx
-                    .send(BackendEvent::BadFrontendEvent(BadFrontendEvent {
-                        event: FrontendEvent::TransmitFile(transmit_file.clone()),
-                        error: format!("Failed to get file metadata: {}", e),
-                    }))
-                    .await
-                    .expect("Failed to send BadFrontendEvent");
-                return;
-            }
-        };
-        let size = metadata.len();
-        let chunk_len = 1024 * 1024; // 1 MB for now
         let unique_id = Uuid::new_v4();
 
-        let mut peers = self.peer_manager.active_peers.lock().await;
-
-        if let Some(peer) = peers.get_mut(&peer_addr) {
-            // Peer is connected
-            // Send a `TransmitFile` message to the peer
-            // Send FileOfferRequest
-            let offer = FileOffer {
-                filename: transmit_file.filename.clone(),
-                unique_id,
-                size,
-                chunk_len,
-            };
-
-            match peer.tx.send(Message::FileOfferRequest(offer)).await {
-                Ok(_) => {
-                    // Message sent successfully
-                    // Store transfer state
-                    self.peer_manager.active_transfers.lock().await.insert(
-                        unique_id,
-                        crate::backend::peer_manager::FileTransferState {
-                            unique_id,
-                            peer_addr,
-                            direction: FileTransferDirection::Sending {
-                                file_path: transmit_file.path,
-                            },
-                            filename: transmit_file.filename,
-                            total_size: size,
-                            bytes_transferred: 0,
-                            chunk_len,
-                            status: FileTransferStatus::WaitingForPeerResponse,
-                        },
-                    );
-                }
-                Err(e) => {
-                    // Failed to send the message
-                    // Disconnect the peer except override the message with the error
-                    warn!(
-                        ?e,
-                        "Failed to send TransmitFile message to the peer. Disconnecting the peer with an error message."
-                    );
-                    self.peer_manager
-                        .drop_peer(
-                            peer_addr,
-                            Some("Failed to send TransmitFile message to the peer".to_string()),
-                        )
-                        .await;
-                }
-            }
-        } else {
+        // Make sure the peer is connected
+        if !self
+            .peer_manager
+            .active_peers
+            .lock()
+            .await
+            .contains_key(&peer_addr)
+        {
             // Peer is not connected
             // Ignore the request
             warn!(
@@ -137,6 +75,20 @@ impl FrontendManager {
                 }))
                 .await
                 .expect("Failed to send BadFrontendEvent event to the backend");
+            return;
         }
+
+        // Queue the transfer
+        // The scheduler sends the `FileOfferRequest` once there is room for the transfer
+        self.peer_manager
+            .queue_file_transfer(
+                unique_id,
+                peer_addr,
+                transmit_file.filename,
+                manifest,
+                transmit_file.priority.unwrap_or_default(),
+                false,
+            )
+            .await;
     }
 }
@@ -104,7 +104,115 @@ impl FrontendManager {
             FrontendEvent::FileOfferResponse(file_offer_response) => {
                 self.handle_file_offer_response(file_offer_response).await;
             }
-            FrontendEvent::CancelFileTransfer(_cancel_file_transfer) => todo!(),
+            FrontendEvent::CancelFileTransfer(cancel_file_transfer) => {
+                self.handle_cancel_file_transfer(cancel_file_transfer).await;
+            }
+            FrontendEvent::ReorderFileTransfer(reorder_file_transfer) => {
+                self.handle_reorder_file_transfer(reorder_file_transfer)
+                    .await;
+            }
+            FrontendEvent::SetFileTransferPriority(set_file_transfer_priority) => {
+                self.handle_set_file_transfer_priority(set_file_transfer_priority)
+                    .await;
+            }
+            FrontendEvent::PauseFileTransfer(pause_file_transfer) => {
+                self.handle_pause_file_transfer(pause_file_transfer).await;
+            }
+            FrontendEvent::ResumeFileTransfer(resume_file_transfer) => {
+                self.handle_resume_file_transfer(resume_file_transfer).await;
+            }
+            FrontendEvent::SetTransferConcurrency(set_transfer_concurrency) => {
+                self.handle_set_transfer_concurrency(set_transfer_concurrency)
+                    .await;
+            }
+            FrontendEvent::SetBandwidthLimits(set_bandwidth_limits) => {
+                self.handle_set_bandwidth_limits(set_bandwidth_limits).await;
+            }
+            FrontendEvent::SetDownloadDir(set_download_dir) => {
+                self.handle_set_download_dir(set_download_dir).await;
+            }
+            FrontendEvent::SetOfferPolicy(set_offer_policy) => {
+                self.handle_set_offer_policy(set_offer_policy).await;
+            }
+            FrontendEvent::SetMetadataPolicy(set_metadata_policy) => {
+                self.handle_set_metadata_policy(set_metadata_policy).await;
+            }
+            FrontendEvent::SetAutoAcceptRules(set_auto_accept_rules) => {
+                self.handle_set_auto_accept_rules(set_auto_accept_rules)
+                    .await;
+            }
+            FrontendEvent::GetAutoAcceptRules => {
+                self.handle_get_auto_accept_rules().await;
+            }
+            FrontendEvent::SetSharedFolders(set_shared_folders) => {
+                self.handle_set_shared_folders(set_shared_folders).await;
+            }
+            FrontendEvent::GetSharedFolders => {
+                self.handle_get_shared_folders().await;
+            }
+            FrontendEvent::RequestFile(request_file) => {
+                self.handle_request_file(request_file).await;
+            }
+            FrontendEvent::ListDirectory(list_directory) => {
+                self.handle_list_directory(list_directory).await;
+            }
+            FrontendEvent::SwarmDownload(swarm_download) => {
+                self.handle_swarm_download(swarm_download).await;
+            }
+            FrontendEvent::SetLibraryFolders(set_library_folders) => {
+                self.handle_set_library_folders(set_library_folders).await;
+            }
+            FrontendEvent::SendChatMessage(send_chat_message) => {
+                self.handle_send_chat_message(send_chat_message).await;
+            }
+            FrontendEvent::SendTyping(send_typing) => {
+                self.handle_send_typing(send_typing).await;
+            }
+            FrontendEvent::EditChatMessage(edit_chat_message) => {
+                self.handle_edit_chat_message(edit_chat_message).await;
+            }
+            FrontendEvent::DeleteChatMessage(delete_chat_message) => {
+                self.handle_delete_chat_message(delete_chat_message).await;
+            }
+            FrontendEvent::ReactToChatMessage(react_to_chat_message) => {
+                self.handle_react_to_chat_message(react_to_chat_message)
+                    .await;
+            }
+            FrontendEvent::GetChatHistory(get_chat_history) => {
+                self.handle_get_chat_history(get_chat_history).await;
+            }
+            FrontendEvent::MarkChatRead(mark_chat_read) => {
+                self.handle_mark_chat_read(mark_chat_read).await;
+            }
+            FrontendEvent::GetChatConversations => {
+                self.handle_get_chat_conversations().await;
+            }
+            FrontendEvent::CreateRoom(create_room) => {
+                self.handle_create_room(create_room).await;
+            }
+            FrontendEvent::SetRoomMembers(set_room_members) => {
+                self.handle_set_room_members(set_room_members).await;
+            }
+            FrontendEvent::LeaveRoom(leave_room) => {
+                self.handle_leave_room(leave_room).await;
+            }
+            FrontendEvent::SendRoomMessage(send_room_message) => {
+                self.handle_send_room_message(send_room_message).await;
+            }
+            FrontendEvent::GetRooms => {
+                self.handle_get_rooms().await;
+            }
+            FrontendEvent::QueryTransferHistory(query_transfer_history) => {
+                self.handle_query_transfer_history(query_transfer_history)
+                    .await;
+            }
+            FrontendEvent::GetHashIndex => {
+                self.handle_get_hash_index().await;
+            }
+            FrontendEvent::FileRequestResponse(file_request_response) => {
+                self.handle_file_request_response(file_request_response)
+                    .await;
+            }
             FrontendEvent::FrontendReady(backend_startup_config) => {
                 // We are already beyond the program initialization stage.
                 // We are not expecting this event.
@@ -0,0 +1,652 @@
+//! # Hash Index
+//!
+//! An index of the files we already have, by BLAKE3 checksum, so files we already have are not
+//! transferred again.
+//!
+//! - The index covers the download directory and the library folders set by the frontend.
+//!   It is rescanned at startup, and whenever either changes. A file is only hashed again
+//!   if its size or modification time changed. Files received are indexed as they complete,
+//!   along with the peer they were received from.
+//! - When an offer is accepted, every file found in the index is copied from the local copy
+//!   before answering, and listed in `FileOfferResponse::have_files`. The sender skips them.
+//!   If we have every file, nothing is sent, and the transfer completes at once.
+//!   This runs in the background, so neither the frontend nor the peer's messages wait for it.
+//! - An indexed file is only used if its size and modification time still match the index,
+//!   and the copy is hashed again before it counts as verified.
+//! - `have_files` tells the sender which of its files we have. So only the files in the library
+//!   folders, or received from the same peer, are used: the sender cannot probe for the other
+//!   files we have.
+//!
+//! The index is persisted to [HASH_INDEX_FILE], and the library folders are set with
+//! `FrontendEvent::SetLibraryFolders`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::{self, File},
+    sync::mpsc,
+};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::js_api::backend_event::{BackendEvent, FileTransferDeduplicated, HashIndexInfo};
+
+use super::{
+    byte_ranges::ByteRanges,
+    manifest::{FileSet, Manifest, PART_EXTENSION, hash_file},
+    peer_manager::{FileTransferDirection, PeerManager},
+    protocol::{FileOfferResponse, Message},
+};
+
+/// File the index is saved to, next to the log file.
+pub const HASH_INDEX_FILE: &str = "kuaip2p-hash-index.json";
+
+/// A file we have, as it was when it was hashed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedFile {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Last modification time, in nanoseconds since the UNIX epoch
+    pub modified: Option<u64>,
+    /// BLAKE3 checksum (hex)
+    pub checksum: String,
+    /// Identity of the peer the file was received from, if it was received
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+impl IndexedFile {
+    /// Is the file still as it was when it was hashed (same size and modification time)?
+    fn is_unchanged(&self, metadata: &std::fs::Metadata) -> bool {
+        self.size == metadata.len() && self.modified == modified_nanos(metadata)
+    }
+}
+
+/// The index, as saved to [HASH_INDEX_FILE].
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SavedHashIndex {
+    libraries: Vec<PathBuf>,
+    files: Vec<IndexedFile>,
+}
+
+/// The files we have in the download directory and the library folders, by checksum.
+#[derive(Debug, Default)]
+pub struct HashIndex {
+    /// Folders indexed besides the download directory
+    libraries: Vec<PathBuf>,
+    files: HashMap<PathBuf, IndexedFile>,
+    /// Paths of the indexed files, by checksum
+    by_checksum: HashMap<String, HashSet<PathBuf>>,
+    /// Bumped whenever a scan starts, so an older scan still running is dropped
+    generation: u64,
+}
+
+impl HashIndex {
+    /// Load the index saved to [HASH_INDEX_FILE]. An empty index if there is none saved, or it is invalid.
+    pub fn load() -> Self {
+        let Ok(json) = std::fs::read_to_string(HASH_INDEX_FILE) else {
+            return Self::default();
+        };
+        match serde_json::from_str::<SavedHashIndex>(&json) {
+            Ok(saved) => {
+                info!("Loaded hash index of {} files", saved.files.len());
+                let mut index = Self {
+                    libraries: saved.libraries,
+                    ..Self::default()
+                };
+                saved.files.into_iter().for_each(|file| index.insert(file));
+                index
+            }
+            Err(e) => {
+                warn!("Ignoring invalid {}: {}", HASH_INDEX_FILE, e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Save the index to [HASH_INDEX_FILE].
+    pub async fn save(&self) -> Result<(), String> {
+        let saved = SavedHashIndex {
+            libraries: self.libraries.clone(),
+            files: self.files.values().cloned().collect(),
+        };
+        let json = serde_json::to_string(&saved)
+            .map_err(|e| format!("Failed to serialize hash index: {}", e))?;
+        fs::write(HASH_INDEX_FILE, json)
+            .await
+            .map_err(|e| format!("Failed to save hash index: {}", e))
+    }
+
+    /// Replace the library folders. The index is only updated by the next scan.
+    pub fn set_libraries(&mut self, libraries: Vec<PathBuf>) {
+        self.libraries = libraries;
+    }
+
+    /// Add a file to the index, or update it.
+    pub fn insert(&mut self, file: IndexedFile) {
+        self.remove(&file.path.clone());
+        self.by_checksum
+            .entry(file.checksum.clone())
+            .or_default()
+            .insert(file.path.clone());
+        self.files.insert(file.path.clone(), file);
+    }
+
+    fn remove(&mut self, path: &Path) {
+        let Some(file) = self.files.remove(path) else {
+            return;
+        };
+        if let Some(paths) = self.by_checksum.get_mut(&file.checksum) {
+            paths.remove(path);
+            if paths.is_empty() {
+                self.by_checksum.remove(&file.checksum);
+            }
+        }
+    }
+
+    /// The indexed files with this checksum and size that the peer may know we have:
+    /// the files in the library folders, and the files received from the peer.
+    pub fn find(&self, checksum: &[u8], size: u64, peer_identity: &str) -> Vec<IndexedFile> {
+        let Ok(checksum) = <[u8; blake3::OUT_LEN]>::try_from(checksum) else {
+            return Vec::new();
+        };
+        self.by_checksum
+            .get(blake3::Hash::from_bytes(checksum).to_hex().as_str())
+            .into_iter()
+            .flatten()
+            .filter_map(|path| self.files.get(path))
+            .filter(|file| file.size == size)
+            .filter(|file| {
+                file.source.as_deref() == Some(peer_identity)
+                    || self
+                        .libraries
+                        .iter()
+                        .any(|library| file.path.starts_with(library))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// The library folders, and what is indexed.
+    pub fn info(&self) -> HashIndexInfo {
+        HashIndexInfo {
+            libraries: self
+                .libraries
+                .iter()
+                .map(|library| library.display().to_string())
+                .collect(),
+            files: self.files.len() as u64,
+            bytes: self.files.values().map(|file| file.size).sum(),
+        }
+    }
+}
+
+impl PeerManager {
+    /// Rescan the download directory and the library folders in the background.
+    pub(crate) fn spawn_hash_index_rescan(&self) {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            manager.rescan_hash_index().await;
+        });
+    }
+
+    /// Rescan the download directory and the library folders, hashing the files that are new or changed.
+    /// Then save the index, and tell the frontend.
+    async fn rescan_hash_index(&self) {
+        let (generation, mut roots, known) = {
+            let mut index = self.hash_index.lock().await;
+            index.generation += 1;
+            (
+                index.generation,
+                index.libraries.clone(),
+                index.files.clone(),
+            )
+        };
+        roots.insert(0, self.download_dir.lock().await.clone());
+        info!("Scanning {} folders for the hash index", roots.len());
+
+        let files = scan_folders(roots, &known).await;
+
+        let info = {
+            let mut index = self.hash_index.lock().await;
+            if index.generation != generation {
+                debug!("Another scan of the hash index started, dropping this one");
+                return;
+            }
+            index.files.clear();
+            index.by_checksum.clear();
+            files.into_values().for_each(|file| index.insert(file));
+            if let Err(e) = index.save().await {
+                warn!("{}", e);
+            }
+            index.info()
+        };
+
+        info!(
+            "Hash index updated: {} files, {} bytes",
+            info.files, info.bytes
+        );
+        self.backend_event_tx
+            .send(BackendEvent::HashIndex(info))
+            .await
+            .expect("Failed to send HashIndex event to the frontend");
+    }
+
+    /// Answer an accepted offer in the background: place the files we already have, send the block
+    /// signatures of the older versions we have (unless it is a multi-source download), then accept
+    /// the offer. Then request the first segments of a multi-source download.
+    ///
+    /// Copying and hashing the local copies takes a while, and must not hold up the frontend
+    /// or the peer's messages.
+    pub(crate) fn spawn_offer_acceptance(
+        &self,
+        unique_id: Uuid,
+        peer_tx: mpsc::Sender<Message>,
+        max_chunk_len: u64,
+    ) {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let Some((manifest, files, peer_identity, swarm)) = manager
+                .active_transfers
+                .lock()
+                .await
+                .get(&unique_id)
+                .and_then(|transfer| {
+                    Some((
+                        transfer.manifest.clone(),
+                        transfer.status.files()?.clone(),
+                        transfer.peer_identity.clone(),
+                        transfer.swarm.is_some(),
+                    ))
+                })
+            else {
+                // Cancelled in the meantime
+                return;
+            };
+
+            let have_files = manager
+                .deduplicate_files(&manifest, &files, &peer_identity)
+                .await;
+            if !swarm {
+                manager
+                    .send_block_signatures(unique_id, &peer_tx, &have_files)
+                    .await;
+            }
+            peer_tx
+                .send(Message::FileOfferResponse(FileOfferResponse {
+                    unique_id,
+                    accept: true,
+                    max_chunk_len,
+                    reason: None,
+                    have_files: have_files.clone(),
+                }))
+                .await
+                .ok(); // We ignore the error here, as the peer may have already disconnected.
+            manager
+                .finish_deduplicated_files(unique_id, &have_files)
+                .await;
+
+            if swarm {
+                manager.send_swarm_sources(unique_id).await;
+                manager.request_swarm_ranges(unique_id).await;
+            }
+        });
+    }
+
+    /// Place the files of an accepted offer that we already have, from their local copies.
+    ///
+    /// Returns the indices of the files placed.
+    async fn deduplicate_files(
+        &self,
+        manifest: &Manifest,
+        files: &FileSet,
+        peer_identity: &str,
+    ) -> Vec<u32> {
+        let candidates: Vec<(usize, Vec<IndexedFile>)> = {
+            let index = self.hash_index.lock().await;
+            manifest
+                .files
+                .iter()
+                .enumerate()
+                .filter(|(_, file)| file.size > 0)
+                .map(|(i, file)| (i, index.find(&file.checksum, file.size, peer_identity)))
+                .filter(|(_, candidates)| !candidates.is_empty())
+                .collect()
+        };
+
+        // Only files still as they were indexed, and whose copy still matches the checksum
+        let mut placed = Vec::new();
+        for (i, candidates) in candidates {
+            for candidate in candidates {
+                let unchanged = fs::metadata(&candidate.path)
+                    .await
+                    .is_ok_and(|metadata| candidate.is_unchanged(&metadata));
+                if !unchanged {
+                    continue;
+                }
+                match files
+                    .place_file(i, &candidate.path, &manifest.files[i].checksum)
+                    .await
+                {
+                    Ok(()) => {
+                        debug!(
+                            "{} placed from {}",
+                            manifest.files[i].path,
+                            candidate.path.display()
+                        );
+                        placed.push(i as u32);
+                        break;
+                    }
+                    Err(e) => warn!(
+                        "Failed to place {} from {}: {}",
+                        manifest.files[i].path,
+                        candidate.path.display(),
+                        e
+                    ),
+                }
+            }
+        }
+        placed
+    }
+
+    /// Mark the files placed from local copies as received and verified (the copies were hashed
+    /// again), and tell the frontend.
+    ///
+    /// If we had every file, the transfer completes at once.
+    async fn finish_deduplicated_files(&self, unique_id: Uuid, have: &[u32]) {
+        if have.is_empty() {
+            return;
+        }
+
+        let (paths, bytes, complete) = {
+            let mut active_transfers = self.active_transfers.lock().await;
+            let Some(transfer) = active_transfers.get_mut(&unique_id) else {
+                return;
+            };
+            let mut paths = Vec::new();
+            let mut bytes = 0;
+            let mut had = ByteRanges::new();
+            for index in have {
+                let Some(file) = transfer.manifest.files.get_mut(*index as usize) else {
+                    continue;
+                };
+                file.verified = true;
+                paths.push(file.path.clone());
+                had.insert(file.offset, file.offset + file.size);
+                let newly_received = transfer
+                    .received_ranges
+                    .insert(file.offset, file.offset + file.size);
+                transfer.bytes_transferred += newly_received;
+                bytes += newly_received;
+            }
+            // The peer only sends nothing if we had every byte. Otherwise, `FileDone` completes the transfer.
+            let complete = had.is_complete(transfer.total_size);
+            // A multi-source download must not be finished by a chunk as well
+            if let Some(swarm) = transfer.swarm.as_mut() {
+                swarm.finishing |= complete;
+            }
+            (paths, bytes, complete)
+        };
+
+        info!(
+            "Already had {} files ({} bytes) of file transfer {}",
+            paths.len(),
+            bytes,
+            unique_id
+        );
+        self.backend_event_tx
+            .send(BackendEvent::FileTransferDeduplicated(
+                FileTransferDeduplicated {
+                    unique_id: unique_id.to_string(),
+                    paths,
+                    bytes,
+                },
+            ))
+            .await
+            .expect("Failed to send FileTransferDeduplicated event to the frontend");
+
+        // The peer completes the transfer on its side
+        if complete {
+            match self.commit_received_files(unique_id).await {
+                Ok(()) => self.complete_file_transfer(unique_id).await,
+                Err(message) => self.fail_file_transfer(unique_id, message).await,
+            }
+        }
+    }
+
+    /// Add the files of a completed incoming transfer to the index.
+    pub(crate) async fn index_received_files(&self, unique_id: Uuid) {
+        let (received, peer_identity): (Vec<(PathBuf, Vec<u8>)>, String) = self
+            .active_transfers
+            .lock()
+            .await
+            .get(&unique_id)
+            .filter(|transfer| transfer.direction == FileTransferDirection::Receiving)
+            .map(|transfer| {
+                (
+                    transfer
+                        .manifest
+                        .files
+                        .iter()
+                        .filter(|file| file.size > 0)
+                        .map(|file| (file.local_path.clone(), file.checksum.clone()))
+                        .collect(),
+                    transfer.peer_identity.clone(),
+                )
+            })
+            .unwrap_or_default();
+        if received.is_empty() {
+            return;
+        }
+
+        let mut files = Vec::new();
+        for (path, checksum) in received {
+            let (Ok(metadata), Ok(checksum)) = (
+                fs::metadata(&path).await,
+                <[u8; blake3::OUT_LEN]>::try_from(checksum.as_slice()),
+            ) else {
+                continue;
+            };
+            files.push(IndexedFile {
+                path,
+                size: metadata.len(),
+                modified: modified_nanos(&metadata),
+                checksum: blake3::Hash::from_bytes(checksum).to_hex().to_string(),
+                source: Some(peer_identity.clone()),
+            });
+        }
+
+        let mut index = self.hash_index.lock().await;
+        files.into_iter().for_each(|file| index.insert(file));
+        if let Err(e) = index.save().await {
+            warn!("{}", e);
+        }
+    }
+}
+
+/// Scan folders recursively, hashing the files that are not in `known` or changed since.
+///
+/// Returns every (non-empty) file found, by path.
+async fn scan_folders(
+    roots: Vec<PathBuf>,
+    known: &HashMap<PathBuf, IndexedFile>,
+) -> HashMap<PathBuf, IndexedFile> {
+    let mut files = HashMap::new();
+    let mut dirs = roots;
+    while let Some(dir) = dirs.pop() {
+        let mut read_dir = match fs::read_dir(&dir).await {
+            Ok(read_dir) => read_dir,
+            Err(e) => {
+                warn!("Failed to scan {}: {}", dir.display(), e);
+                continue;
+            }
+        };
+        while let Ok(Some(entry)) = read_dir.next_entry().await {
+            let path = entry.path();
+            // Symlinks are not followed, like folder transfers
+            let Ok(metadata) = fs::symlink_metadata(&path).await else {
+                continue;
+            };
+            if metadata.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            if !metadata.is_file()
+                || metadata.len() == 0
+                || path
+                    .extension()
+                    .is_some_and(|extension| extension == PART_EXTENSION)
+                || files.contains_key(&path)
+            {
+                continue;
+            }
+
+            let file = match known.get(&path).filter(|file| file.is_unchanged(&metadata)) {
+                Some(file) => file.clone(),
+                None => match hash_path(&path).await {
+                    Ok(checksum) => IndexedFile {
+                        path: path.clone(),
+                        size: metadata.len(),
+                        modified: modified_nanos(&metadata),
+                        checksum,
+                        source: None,
+                    },
+                    Err(e) => {
+                        warn!("Failed to hash {}: {}", path.display(), e);
+                        continue;
+                    }
+                },
+            };
+            files.insert(path, file);
+        }
+    }
+    files
+}
+
+/// Compute the BLAKE3 checksum (hex) of a file.
+async fn hash_path(path: &Path) -> std::io::Result<String> {
+    let mut file = File::open(path).await?;
+    Ok(hash_file(&mut file, None).await?.to_hex().to_string())
+}
+
+/// Last modification time, in nanoseconds since the UNIX epoch.
+pub(super) fn modified_nanos(metadata: &std::fs::Metadata) -> Option<u64> {
+    metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .and_then(|modified| u64::try_from(modified.as_nanos()).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checksum_of(data: &[u8]) -> String {
+        blake3::hash(data).to_hex().to_string()
+    }
+
+    fn indexed(path: &str, data: &[u8], source: Option<&str>) -> IndexedFile {
+        IndexedFile {
+            path: PathBuf::from(path),
+            size: data.len() as u64,
+            modified: None,
+            checksum: checksum_of(data),
+            source: source.map(str::to_string),
+        }
+    }
+
+    fn found(index: &HashIndex, data: &[u8], peer_identity: &str) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = index
+            .find(
+                blake3::hash(data).as_bytes(),
+                data.len() as u64,
+                peer_identity,
+            )
+            .into_iter()
+            .map(|file| file.path)
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn only_files_the_peer_may_know_of_are_found() {
+        let mut index = HashIndex::default();
+        index.set_libraries(vec![PathBuf::from("/library")]);
+        index.insert(indexed("/library/a", b"data", None));
+        index.insert(indexed("/downloads/b", b"data", Some("alice")));
+        index.insert(indexed("/downloads/c", b"data", None));
+
+        assert_eq!(
+            found(&index, b"data", "alice"),
+            vec![PathBuf::from("/downloads/b"), PathBuf::from("/library/a")]
+        );
+        assert_eq!(
+            found(&index, b"data", "bob"),
+            vec![PathBuf::from("/library/a")]
+        );
+        // The size must match too, and invalid checksums find nothing
+        assert!(
+            index
+                .find(blake3::hash(b"data").as_bytes(), 5, "alice")
+                .is_empty()
+        );
+        assert!(index.find(&[0; 3], 4, "alice").is_empty());
+    }
+
+    #[test]
+    fn updated_files_are_found_by_their_new_checksum_only() {
+        let mut index = HashIndex::default();
+        index.set_libraries(vec![PathBuf::from("/library")]);
+        index.insert(indexed("/library/a", b"old", None));
+        index.insert(indexed("/library/a", b"new", None));
+
+        assert!(found(&index, b"old", "alice").is_empty());
+        assert_eq!(
+            found(&index, b"new", "alice"),
+            vec![PathBuf::from("/library/a")]
+        );
+        assert!(!index.by_checksum.contains_key(&checksum_of(b"old")));
+        assert_eq!(index.info().files, 1);
+    }
+
+    #[tokio::test]
+    async fn scans_only_hash_new_or_changed_files() {
+        let dir = std::env::temp_dir().join(format!("kuaip2p-hash-index-{}", Uuid::new_v4()));
+        fs::create_dir_all(dir.join("sub")).await.unwrap();
+        let path = dir.join("sub/a");
+        fs::write(&path, b"first").await.unwrap();
+        fs::write(dir.join("empty"), b"").await.unwrap();
+        fs::write(dir.join(format!("b.{}", PART_EXTENSION)), b"partial")
+            .await
+            .unwrap();
+
+        let files = scan_folders(vec![dir.clone()], &HashMap::new()).await;
+        assert_eq!(files.keys().collect::<Vec<_>>(), vec![&path]);
+        assert_eq!(files[&path].checksum, checksum_of(b"first"));
+
+        // An unchanged file is not hashed again, and keeps where it came from
+        let mut known = files.clone();
+        let file = known.get_mut(&path).unwrap();
+        file.checksum = "not hashed again".to_string();
+        file.source = Some("alice".to_string());
+        let files = scan_folders(vec![dir.clone()], &known).await;
+        assert_eq!(files[&path].checksum, "not hashed again");
+        assert_eq!(files[&path].source.as_deref(), Some("alice"));
+
+        // A changed file is
+        fs::write(&path, b"second!").await.unwrap();
+        let files = scan_folders(vec![dir.clone()], &known).await;
+        assert_eq!(files[&path].checksum, checksum_of(b"second!"));
+        assert_eq!(files[&path].source, None);
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
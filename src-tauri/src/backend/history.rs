@@ -0,0 +1,385 @@
+//! # Transfer History
+//!
+//! A local record of every finished file transfer, so it can be looked up later
+//! (e.g. "that file Bob sent last week").
+//!
+//! - A transfer is recorded once it ends: completed, cancelled, rejected or failed.
+//!   Offers rejected by an auto-accept rule are recorded as rejected, but offers rejected before
+//!   they were shown to the frontend (by the offer policy) are not recorded.
+//! - A record has the peer, the name and size of the offer, the direction, when it started and ended,
+//!   how it ended, the checksum of every file, and where the files were saved to (or sent from).
+//! - The frontend searches the history with `FrontendEvent::QueryTransferHistory`, by peer, name,
+//!   direction, outcome and time, a page at a time. Pages are most recent first.
+//!
+//! The history is an append-only log of JSON lines ([HISTORY_FILE]), loaded at startup.
+//! Only the last [MAX_RECORDS] records are kept in memory, and searched.
+
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::js_api::{
+    backend_event::{TransferHistoryPage, TransferOutcome, TransferRecord, TransferRecordFile},
+    frontend_event::QueryTransferHistory,
+};
+
+use super::{
+    manifest::ManifestFile,
+    peer_manager::{FileTransferDirection, FileTransferStatus, PeerManager},
+};
+
+/// File the history is appended to, next to the log file.
+pub const HISTORY_FILE: &str = "kuaip2p-history.jsonl";
+
+/// Number of records in a page, if the query does not say
+const DEFAULT_PAGE_LEN: u32 = 50;
+
+/// Maximum number of records in a page
+const MAX_PAGE_LEN: u32 = 500;
+
+/// Maximum number of records kept in memory. Older records are only left in [HISTORY_FILE].
+const MAX_RECORDS: usize = 10_000;
+
+/// The last finished file transfers, oldest first.
+#[derive(Debug, Default)]
+pub struct TransferHistory {
+    records: VecDeque<TransferRecord>,
+}
+
+impl TransferHistory {
+    /// Load the history from [HISTORY_FILE]. Invalid lines are skipped.
+    pub fn load() -> Self {
+        let Ok(log) = std::fs::read_to_string(HISTORY_FILE) else {
+            return Self::default();
+        };
+        let mut invalid = 0;
+        let mut records: VecDeque<TransferRecord> = log
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| {
+                serde_json::from_str(line)
+                    .inspect_err(|_| invalid += 1)
+                    .ok()
+            })
+            .collect();
+        if invalid > 0 {
+            warn!("Skipped {} invalid lines of {}", invalid, HISTORY_FILE);
+        }
+        if records.len() > MAX_RECORDS {
+            records.drain(..records.len() - MAX_RECORDS);
+        }
+        info!("Loaded {} transfer history records", records.len());
+        Self { records }
+    }
+
+    /// Append a record to the history, and to [HISTORY_FILE].
+    pub async fn append(&mut self, record: TransferRecord) -> Result<(), String> {
+        let mut line = serde_json::to_string(&record)
+            .map_err(|e| format!("Failed to serialize transfer history record: {}", e))?;
+        line.push('\n');
+        self.push(record);
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(HISTORY_FILE)
+            .await
+            .map_err(|e| format!("Failed to open {}: {}", HISTORY_FILE, e))?;
+        // Flush, so the write is done before the next record is appended
+        file.write_all(line.as_bytes())
+            .await
+            .and(file.flush().await)
+            .map_err(|e| format!("Failed to save transfer history record: {}", e))
+    }
+
+    /// Add a record, dropping the oldest one beyond [MAX_RECORDS].
+    fn push(&mut self, record: TransferRecord) {
+        if self.records.len() == MAX_RECORDS {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    /// A page of the records matching a query, most recent first.
+    pub fn query(&self, query: &QueryTransferHistory) -> TransferHistoryPage {
+        let filename = query
+            .filename
+            .as_ref()
+            .map(|filename| filename.to_lowercase());
+        let matching: Vec<&TransferRecord> = self
+            .records
+            .iter()
+            .rev()
+            .filter(|record| {
+                query
+                    .peer_name
+                    .as_ref()
+                    .is_none_or(|peer_name| *peer_name == record.peer_name)
+                    && query
+                        .direction
+                        .is_none_or(|direction| direction == record.direction)
+                    && query
+                        .outcome
+                        .is_none_or(|outcome| outcome == record.outcome)
+                    && query.since.is_none_or(|since| record.ended_at >= since)
+                    && query.until.is_none_or(|until| record.ended_at < until)
+                    && filename.as_ref().is_none_or(|filename| {
+                        record.filename.to_lowercase().contains(filename)
+                            || record
+                                .files
+                                .iter()
+                                .any(|file| file.path.to_lowercase().contains(filename))
+                    })
+            })
+            .collect();
+
+        let offset = query.offset.unwrap_or_default();
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_LEN).min(MAX_PAGE_LEN);
+        TransferHistoryPage {
+            records: matching
+                .iter()
+                .skip(offset as usize)
+                .take(limit as usize)
+                .map(|record| (*record).clone())
+                .collect(),
+            offset,
+            total: matching.len() as u32,
+        }
+    }
+}
+
+/// Milliseconds since the Unix epoch.
+pub fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+/// Where the file (or folder) of a transfer is on disk, from one of its files.
+fn root_path(file: &ManifestFile) -> Option<PathBuf> {
+    if file.local_path.as_os_str().is_empty() {
+        return None;
+    }
+    // Strip the folders of the file's path inside the transfer
+    let depth = file.path.split('/').count();
+    file.local_path
+        .ancestors()
+        .nth(depth - 1)
+        .map(Path::to_path_buf)
+}
+
+impl PeerManager {
+    /// Record a transfer that has just ended in the history.
+    pub(crate) async fn record_transfer_history(&self, unique_id: Uuid) {
+        let record = {
+            let active_transfers = self.active_transfers.lock().await;
+            let Some(transfer) = active_transfers.get(&unique_id) else {
+                return;
+            };
+            let (outcome, reason) = match &transfer.status {
+                FileTransferStatus::Completed => (TransferOutcome::Completed, None),
+                FileTransferStatus::Cancelled => (TransferOutcome::Cancelled, None),
+                FileTransferStatus::Rejected => (TransferOutcome::Rejected, None),
+                FileTransferStatus::Error(message) => {
+                    (TransferOutcome::Failed, Some(message.clone()))
+                }
+                _ => return,
+            };
+            // Incoming files are only saved once the transfer completes
+            let saved = transfer.direction == FileTransferDirection::Sending
+                || outcome == TransferOutcome::Completed;
+
+            TransferRecord {
+                unique_id: unique_id.to_string(),
+                peer_name: transfer.peer_name.clone(),
+                peer_ip: transfer.peer_addr.ip().to_string(),
+                filename: transfer.filename.clone(),
+                size: transfer.total_size,
+                bytes_transferred: transfer.bytes_transferred,
+                direction: (&transfer.direction).into(),
+                started_at: unix_millis(transfer.started_at),
+                ended_at: unix_millis(SystemTime::now()),
+                outcome,
+                reason,
+                local_path: transfer
+                    .manifest
+                    .files
+                    .first()
+                    .filter(|_| saved)
+                    .and_then(root_path)
+                    .map(|path| path.display().to_string()),
+                files: transfer
+                    .manifest
+                    .files
+                    .iter()
+                    .map(|file| TransferRecordFile {
+                        path: file.path.clone(),
+                        size: file.size,
+                        checksum: <[u8; blake3::OUT_LEN]>::try_from(file.checksum.as_slice())
+                            .ok()
+                            .map(|checksum| {
+                                blake3::Hash::from_bytes(checksum).to_hex().to_string()
+                            }),
+                    })
+                    .collect(),
+            }
+        };
+
+        if let Err(e) = self.transfer_history.lock().await.append(record).await {
+            warn!("{}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::js_api::backend_event::FileTransferDirection;
+
+    fn record(i: u64, peer_name: &str, filename: &str) -> TransferRecord {
+        TransferRecord {
+            unique_id: Uuid::new_v4().to_string(),
+            peer_name: peer_name.to_string(),
+            peer_ip: "192.168.1.2".to_string(),
+            filename: filename.to_string(),
+            size: 1,
+            bytes_transferred: 1,
+            direction: if i.is_multiple_of(2) {
+                FileTransferDirection::Sending
+            } else {
+                FileTransferDirection::Receiving
+            },
+            started_at: i * 1000,
+            ended_at: i * 1000 + 500,
+            outcome: if i.is_multiple_of(3) {
+                TransferOutcome::Failed
+            } else {
+                TransferOutcome::Completed
+            },
+            reason: None,
+            local_path: None,
+            files: vec![TransferRecordFile {
+                path: format!("{}/file-{}.txt", filename, i),
+                size: 1,
+                checksum: None,
+            }],
+        }
+    }
+
+    fn query() -> QueryTransferHistory {
+        QueryTransferHistory {
+            peer_name: None,
+            filename: None,
+            direction: None,
+            outcome: None,
+            since: None,
+            until: None,
+            offset: None,
+            limit: None,
+        }
+    }
+
+    /// The `ended_at` of the records of a page, in seconds.
+    fn ended(page: &TransferHistoryPage) -> Vec<u64> {
+        page.records
+            .iter()
+            .map(|record| record.ended_at / 1000)
+            .collect()
+    }
+
+    fn history() -> TransferHistory {
+        let mut history = TransferHistory::default();
+        for i in 0..10u64 {
+            let peer_name = if i < 5 { "Alice" } else { "Bob" };
+            let filename = if i.is_multiple_of(4) {
+                "Photos"
+            } else {
+                "notes"
+            };
+            history.push(record(i, peer_name, filename));
+        }
+        history
+    }
+
+    #[test]
+    fn queries_filter_the_records() {
+        let history = history();
+        let page = history.query(&query());
+        assert_eq!(ended(&page), (0..10).rev().collect::<Vec<_>>());
+        assert_eq!(page.total, 10);
+
+        let by_peer = QueryTransferHistory {
+            peer_name: Some("Bob".to_string()),
+            ..query()
+        };
+        assert_eq!(ended(&history.query(&by_peer)), vec![9, 8, 7, 6, 5]);
+        // Case-insensitive, on the name or the path of a file
+        let by_name = QueryTransferHistory {
+            filename: Some("PHOTOS".to_string()),
+            ..query()
+        };
+        assert_eq!(ended(&history.query(&by_name)), vec![8, 4, 0]);
+        let by_path = QueryTransferHistory {
+            filename: Some("file-7".to_string()),
+            ..query()
+        };
+        assert_eq!(ended(&history.query(&by_path)), vec![7]);
+        let by_direction_and_outcome = QueryTransferHistory {
+            direction: Some(FileTransferDirection::Receiving),
+            outcome: Some(TransferOutcome::Failed),
+            ..query()
+        };
+        assert_eq!(ended(&history.query(&by_direction_and_outcome)), vec![9, 3]);
+        // From `since`, up to but not including `until`
+        let by_time = QueryTransferHistory {
+            since: Some(3500),
+            until: Some(6500),
+            ..query()
+        };
+        assert_eq!(ended(&history.query(&by_time)), vec![5, 4, 3]);
+    }
+
+    #[test]
+    fn queries_are_paged() {
+        let history = history();
+        let page = history.query(&QueryTransferHistory {
+            offset: Some(3),
+            limit: Some(4),
+            ..query()
+        });
+        assert_eq!(ended(&page), vec![6, 5, 4, 3]);
+        assert_eq!((page.offset, page.total), (3, 10));
+
+        let past_the_end = history.query(&QueryTransferHistory {
+            offset: Some(20),
+            ..query()
+        });
+        assert!(past_the_end.records.is_empty());
+        assert_eq!(past_the_end.total, 10);
+    }
+
+    #[test]
+    fn pages_and_records_are_capped() {
+        let mut history = TransferHistory::default();
+        for i in 0..MAX_RECORDS as u64 + 5 {
+            history.push(record(i, "Alice", "notes"));
+        }
+        assert_eq!(history.records.len(), MAX_RECORDS);
+        assert_eq!(history.records[0].ended_at / 1000, 5);
+
+        let page = history.query(&QueryTransferHistory {
+            limit: Some(u32::MAX),
+            ..query()
+        });
+        assert_eq!(page.records.len(), MAX_PAGE_LEN as usize);
+        assert_eq!(
+            history.query(&query()).records.len(),
+            DEFAULT_PAGE_LEN as usize
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! # Known Peers
+//!
+//! Every peer that ever authenticated with us, by identity (see [super::ecdsa_identity]),
+//! with the last name it used.
+//!
+//! - A peer is recorded whenever it authenticates, and its name updated if it changed.
+//! - Chat messages are only queued for known peers (see [super::chat_outbox]), so the outbox
+//!   cannot fill up with messages for peers that do not exist.
+//! - Names are only shown to the frontend. Peers are always told apart by identity.
+//!
+//! The known peers are persisted to [KNOWN_PEERS_FILE], so they survive restarts.
+
+use std::{collections::HashMap, net::SocketAddr};
+
+use tracing::{info, warn};
+
+use super::peer_manager::{PeerManager, PeerState};
+
+/// File the known peers are saved to, next to the log file.
+pub const KNOWN_PEERS_FILE: &str = "kuaip2p-known-peers.json";
+
+/// The last name of every peer we know, keyed by identity.
+#[derive(Debug, Default)]
+pub struct KnownPeers {
+    names: HashMap<String, String>,
+}
+
+impl KnownPeers {
+    /// Load the peers saved to [KNOWN_PEERS_FILE]. Empty if there are none saved, or they are invalid.
+    pub fn load() -> Self {
+        let Ok(json) = std::fs::read_to_string(KNOWN_PEERS_FILE) else {
+            return Self::default();
+        };
+        match serde_json::from_str::<HashMap<String, String>>(&json) {
+            Ok(names) => {
+                info!("Loaded {} known peers", names.len());
+                Self { names }
+            }
+            Err(e) => {
+                warn!("Ignoring invalid {}: {}", KNOWN_PEERS_FILE, e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Save the peers to [KNOWN_PEERS_FILE].
+    pub async fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string(&self.names)
+            .map_err(|e| format!("Failed to serialize known peers: {}", e))?;
+        tokio::fs::write(KNOWN_PEERS_FILE, json)
+            .await
+            .map_err(|e| format!("Failed to save known peers: {}", e))
+    }
+
+    /// Record a peer, with its current name. Returns false if it was already known by that name.
+    pub fn record(&mut self, identity: &str, name: &str) -> bool {
+        if self.name(identity) == Some(name) {
+            return false;
+        }
+        self.names.insert(identity.to_string(), name.to_string());
+        true
+    }
+
+    pub fn contains(&self, identity: &str) -> bool {
+        self.names.contains_key(identity)
+    }
+
+    /// The last name of a peer, if we know it.
+    pub fn name(&self, identity: &str) -> Option<&str> {
+        self.names.get(identity).map(String::as_str)
+    }
+}
+
+impl PeerManager {
+    /// Record a peer that just authenticated as known, with its current name.
+    pub(crate) async fn remember_peer(&self, peer_addr: SocketAddr) {
+        let Some(peer_info) = self
+            .active_peers
+            .lock()
+            .await
+            .get(&peer_addr)
+            .and_then(|peer| match &peer.state {
+                PeerState::Authenticated { peer_info } => Some(peer_info.clone()),
+                _ => None,
+            })
+        else {
+            return;
+        };
+
+        let mut known_peers = self.known_peers.lock().await;
+        if known_peers.record(&peer_info.identity, &peer_info.name)
+            && let Err(e) = known_peers.save().await
+        {
+            warn!("{}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_keeps_the_last_name() {
+        let mut known_peers = KnownPeers::default();
+        assert!(!known_peers.contains("identity"));
+
+        assert!(known_peers.record("identity", "alice"));
+        assert!(!known_peers.record("identity", "alice"));
+        assert!(known_peers.record("identity", "bob"));
+        assert_eq!(known_peers.name("identity"), Some("bob"));
+
+        // Peers with the same name are still told apart
+        assert!(known_peers.record("other identity", "bob"));
+        assert_eq!(known_peers.name("other identity"), Some("bob"));
+        assert_eq!(known_peers.name("unknown"), None);
+    }
+}
@@ -0,0 +1,809 @@
+//! # Manifest
+//!
+//! A file transfer carries one or more files, listed in a manifest in the `FileOffer`.
+//!
+//! - Each entry has a relative path (`/`-separated), a size and a BLAKE3 checksum.
+//! - The files are laid out back to back in a single byte stream, in manifest order.
+//!   Chunks are located by their offset within that stream, and may span several files.
+//!   So flow control, chunk sizing and progress work the same for one file or a whole folder.
+//! - The receiver rebuilds the tree under its download directory. Paths are sanitized first:
+//!   absolute paths, `..`, and anything else that could escape the download directory are rejected.
+//!
+//! Empty directories are not transferred. Each file's modification time and permissions are sent
+//! along, and applied once it is received (see [super::file_metadata]).
+//!
+//! Incoming files are staged as `<name>.<id>.kuaip2p-part` (see [PART_EXTENSION]) while they are
+//! being received, `<id>` being the start of the transfer's unique ID, so two transfers of the
+//! same name never share a part file. Part files are always new: an existing file is never reused. Only once every file is verified are they fsynced and atomically renamed to
+//! their final names. A failed, cancelled or dropped transfer removes its `.kuaip2p-part` files,
+//! so a truncated file never looks complete.
+
+use std::{
+    collections::HashSet,
+    io::SeekFrom,
+    path::{Component, Path, PathBuf},
+};
+
+use tokio::{
+    fs::{self, File, OpenOptions},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+    sync::Mutex,
+};
+
+use tracing::warn;
+use uuid::Uuid;
+
+use super::{
+    merkle::{LeafHasher, MerkleTree},
+    protocol::{FileMetadata, ManifestEntry},
+};
+
+/// Length of a BLAKE3 checksum
+const CHECKSUM_LEN: usize = blake3::OUT_LEN;
+
+/// Extension appended to incoming files until they are verified
+pub const PART_EXTENSION: &str = "kuaip2p-part";
+
+/// A file of a transfer.
+#[derive(Debug, Clone)]
+pub struct ManifestFile {
+    /// Path relative to the root of the transfer (`/`-separated)
+    pub path: String,
+    /// Where the file is on disk. For incoming transfers, set once the transfer is accepted.
+    pub local_path: PathBuf,
+    /// Size of the file in bytes
+    pub size: u64,
+    /// Offset of the file within the transfer's byte stream
+    pub offset: u64,
+    /// BLAKE3 checksum of the file. For outgoing transfers, computed just before the offer is sent.
+    pub checksum: Vec<u8>,
+    /// Has the file been received and verified? (receiving only)
+    pub verified: bool,
+    /// Modification time and permissions, applied once the file is received
+    pub metadata: FileMetadata,
+}
+
+/// The files of a transfer.
+#[derive(Debug, Clone, Default)]
+pub struct Manifest {
+    pub files: Vec<ManifestFile>,
+}
+
+impl Manifest {
+    /// Build the manifest of the files and folders selected to be sent.
+    ///
+    /// A file is sent as its own name, a folder as its name followed by every file inside it.
+    /// A single file is sent as `filename` instead, so the frontend may rename it.
+    /// Checksums are left empty, see [Manifest::compute_checksums].
+    pub async fn from_local_paths(paths: &[PathBuf], filename: &str) -> Result<Self, String> {
+        let mut files = Vec::new();
+        let mut single_file = paths.len() == 1;
+
+        for path in paths {
+            let metadata = fs::metadata(path)
+                .await
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or(format!("Invalid file name: {}", path.display()))?;
+
+            if metadata.is_dir() {
+                single_file = false;
+                walk_dir(path, name, &mut files).await?;
+            } else {
+                files.push((
+                    name.to_string(),
+                    path.clone(),
+                    metadata.len(),
+                    FileMetadata::read(&metadata),
+                ));
+            }
+        }
+
+        if single_file && let Some((path, _, _, _)) = files.first_mut() {
+            *path = filename.to_string();
+        }
+
+        if files.is_empty() {
+            return Err("Nothing to send: no files selected".to_string());
+        }
+
+        let mut manifest = Self::default();
+        let mut seen = HashSet::new();
+        for (path, local_path, size, metadata) in files {
+            if !seen.insert(path.clone()) {
+                return Err(format!("Two files would be sent as {}", path));
+            }
+            manifest.push(path, local_path, size, Vec::new(), metadata);
+        }
+        Ok(manifest)
+    }
+
+    /// Build the manifest of an incoming offer, rejecting anything unsafe or inconsistent.
+    ///
+    /// Local paths are left empty, see [Manifest::set_download_dir].
+    pub fn from_offer(entries: Vec<ManifestEntry>, total_size: u64) -> Result<Self, String> {
+        if entries.is_empty() {
+            return Err("Empty manifest".to_string());
+        }
+
+        let mut manifest = Self::default();
+        let mut files = HashSet::new();
+        let mut dirs = HashSet::new();
+        for entry in entries {
+            let path = sanitize_path(&entry.path)
+                .ok_or(format!("Unsafe path in manifest: {:?}", entry.path))?;
+            if path
+                .extension()
+                .is_some_and(|extension| extension == PART_EXTENSION)
+            {
+                return Err(format!("Reserved file name in manifest: {:?}", entry.path));
+            }
+            if entry.checksum.len() != CHECKSUM_LEN {
+                return Err(format!("Invalid checksum for {:?}", entry.path));
+            }
+
+            // A path may not be used twice, nor as both a file and a directory
+            for dir in path.ancestors().skip(1) {
+                if files.contains(dir) {
+                    return Err(format!("Conflicting paths in manifest: {:?}", entry.path));
+                }
+                dirs.insert(dir.to_path_buf());
+            }
+            if dirs.contains(&path) || !files.insert(path) {
+                return Err(format!("Conflicting paths in manifest: {:?}", entry.path));
+            }
+
+            if manifest.total_size().checked_add(entry.size).is_none() {
+                return Err("Manifest is too large".to_string());
+            }
+            manifest.push(
+                entry.path,
+                PathBuf::new(),
+                entry.size,
+                entry.checksum,
+                entry.metadata,
+            );
+        }
+
+        if manifest.total_size() != total_size {
+            return Err(format!(
+                "Manifest adds up to {} bytes, but {} bytes were offered",
+                manifest.total_size(),
+                total_size
+            ));
+        }
+        Ok(manifest)
+    }
+
+    fn push(
+        &mut self,
+        path: String,
+        local_path: PathBuf,
+        size: u64,
+        checksum: Vec<u8>,
+        metadata: FileMetadata,
+    ) {
+        let offset = self.total_size();
+        self.files.push(ManifestFile {
+            path,
+            local_path,
+            size,
+            offset,
+            checksum,
+            verified: false,
+            metadata,
+        });
+    }
+
+    /// Total size of every file
+    pub fn total_size(&self) -> u64 {
+        self.files.last().map_or(0, |file| file.offset + file.size)
+    }
+
+    /// Place every (already sanitized) file under the download directory.
+    pub fn set_download_dir(&mut self, download_dir: &Path) {
+        for file in &mut self.files {
+            // Paths were sanitized when the offer came in
+            file.local_path = download_dir.join(sanitize_path(&file.path).unwrap_or_default());
+        }
+    }
+
+    /// Compute the checksum of every file to be sent, and the Merkle tree over their byte stream.
+    pub async fn compute_checksums(&mut self) -> Result<MerkleTree, String> {
+        let mut leaves = LeafHasher::default();
+        for file in &mut self.files {
+            let mut handle = File::open(&file.local_path)
+                .await
+                .map_err(|e| format!("Failed to open {}: {}", file.local_path.display(), e))?;
+            let size = handle
+                .metadata()
+                .await
+                .map_err(|e| format!("Failed to read {}: {}", file.local_path.display(), e))?
+                .len();
+            if size != file.size {
+                return Err(format!(
+                    "{} changed size since it was queued",
+                    file.local_path.display()
+                ));
+            }
+            file.checksum = hash_file(&mut handle, Some(&mut leaves))
+                .await
+                .map_err(|e| format!("Failed to hash {}: {}", file.local_path.display(), e))?
+                .as_bytes()
+                .to_vec();
+        }
+        Ok(leaves.finish())
+    }
+
+    /// The manifest as sent in a `FileOffer`
+    pub fn to_entries(&self) -> Vec<ManifestEntry> {
+        self.files
+            .iter()
+            .map(|file| ManifestEntry {
+                path: file.path.clone(),
+                size: file.size,
+                checksum: file.checksum.clone(),
+                metadata: file.metadata.clone(),
+            })
+            .collect()
+    }
+
+    /// Indices of the files overlapping the byte range `[start, end)` of the stream.
+    pub fn files_in_range(&self, start: u64, end: u64) -> impl Iterator<Item = usize> + '_ {
+        // Files are sorted by offset, so skip those that end before the range
+        let first = self
+            .files
+            .partition_point(|file| file.offset + file.size <= start);
+        self.files[first..]
+            .iter()
+            .take_while(move |file| file.offset < end)
+            .enumerate()
+            .map(move |(i, _)| first + i)
+    }
+}
+
+/// Collect every file under `dir`, with paths relative to the parent of `dir`.
+///
+/// Symbolic links are skipped, so a link cannot send files outside the selected folder
+/// (or loop forever).
+async fn walk_dir(
+    dir: &Path,
+    name: &str,
+    files: &mut Vec<(String, PathBuf, u64, FileMetadata)>,
+) -> Result<(), String> {
+    let mut pending = vec![(dir.to_path_buf(), name.to_string())];
+
+    while let Some((dir, relative)) = pending.pop() {
+        let mut read_dir = fs::read_dir(&dir)
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+
+        let mut entries = Vec::new();
+        while let Some(entry) = read_dir
+            .next_entry()
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?
+        {
+            entries.push(entry);
+        }
+        // Keep the manifest order stable
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let Some(entry_name) = entry.file_name().to_str().map(str::to_string) else {
+                return Err(format!("Invalid file name: {}", entry.path().display()));
+            };
+            let file_type = entry
+                .file_type()
+                .await
+                .map_err(|e| format!("Failed to read {}: {}", entry.path().display(), e))?;
+            let entry_relative = format!("{}/{}", relative, entry_name);
+
+            if file_type.is_dir() {
+                pending.push((entry.path(), entry_relative));
+            } else if file_type.is_file() {
+                let metadata = entry
+                    .metadata()
+                    .await
+                    .map_err(|e| format!("Failed to read {}: {}", entry.path().display(), e))?;
+                files.push((
+                    entry_relative,
+                    entry.path(),
+                    metadata.len(),
+                    FileMetadata::read(&metadata),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Turn a `/`-separated relative path from a peer into a safe relative path.
+///
+/// Returns None if the path is empty, absolute, or has any `..`, `.`, empty,
+/// or otherwise special component (including backslashes and drive letters).
+pub fn sanitize_path(path: &str) -> Option<PathBuf> {
+    let mut sanitized = PathBuf::new();
+
+    for component in path.split('/') {
+        if component.is_empty()
+            || component == "."
+            || component == ".."
+            || component.contains(['\\', ':', '\0'])
+        {
+            return None;
+        }
+
+        // Whatever the platform, the component must be a plain file name
+        let mut components = Path::new(component).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(_)), None) => sanitized.push(component),
+            _ => return None,
+        }
+    }
+
+    (!sanitized.as_os_str().is_empty()).then_some(sanitized)
+}
+
+/// Compute the BLAKE3 hash of a file, from the start.
+///
+/// The file is also fed to `leaves`, if any, as the next bytes of the transfer's stream.
+pub async fn hash_file(
+    file: &mut File,
+    mut leaves: Option<&mut LeafHasher>,
+) -> std::io::Result<blake3::Hash> {
+    file.seek(SeekFrom::Start(0)).await?;
+
+    let mut hasher = blake3::Hasher::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        if let Some(leaves) = leaves.as_deref_mut() {
+            leaves.update(&buf[..n]);
+        }
+    }
+
+    Ok(hasher.finalize())
+}
+
+/// An open file of a transfer.
+#[derive(Debug)]
+struct OpenFile {
+    /// Where the file is read from, or written to (the `.kuaip2p-part` file when receiving)
+    path: PathBuf,
+    /// Where the file is renamed to once verified (receiving only)
+    final_path: Option<PathBuf>,
+    offset: u64,
+    size: u64,
+    /// Opened on first use, closed once the file is done with
+    handle: Mutex<Option<File>>,
+}
+
+/// The files of an in-progress transfer, read and written through the transfer's byte stream.
+#[derive(Debug)]
+pub struct FileSet {
+    files: Vec<OpenFile>,
+    /// Are the files being written?
+    writable: bool,
+}
+
+impl FileSet {
+    /// Open the files of an outgoing transfer for reading.
+    ///
+    /// Files are opened lazily, so a folder with many files does not run out of file handles.
+    pub fn open(manifest: &Manifest) -> Self {
+        Self::new(manifest, false)
+    }
+
+    /// Create the `.kuaip2p-part` files (and directories) of an incoming transfer.
+    ///
+    /// Fails if a part file already exists, rather than sharing it with another transfer.
+    pub async fn create(manifest: &Manifest, unique_id: Uuid) -> std::io::Result<Self> {
+        let mut file_set = Self::new(manifest, true);
+        for file in &mut file_set.files {
+            file.path = part_path(&file.path, unique_id);
+        }
+        for file in &file_set.files {
+            // Fail now rather than once the files are received (see [Self::commit])
+            if let Some(final_path) = &file.final_path
+                && fs::try_exists(final_path).await?
+            {
+                file_set.discard().await;
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("{} already exists", final_path.display()),
+                ));
+            }
+            if let Some(parent) = file.path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            if let Err(e) = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&file.path)
+                .await
+            {
+                // Do not leave the files created so far behind (only those, not the existing one)
+                file_set.discard_created(&file.path).await;
+                return Err(e);
+            }
+        }
+        Ok(file_set)
+    }
+
+    fn new(manifest: &Manifest, writable: bool) -> Self {
+        Self {
+            files: manifest
+                .files
+                .iter()
+                .map(|file| OpenFile {
+                    path: file.local_path.clone(),
+                    final_path: writable.then(|| file.local_path.clone()),
+                    offset: file.offset,
+                    size: file.size,
+                    handle: Mutex::new(None),
+                })
+                .collect(),
+            writable,
+        }
+    }
+
+    /// Read `len` bytes at `offset` of the stream.
+    pub async fn read_at(&self, offset: u64, len: u64) -> std::io::Result<Vec<u8>> {
+        let mut data = vec![0u8; len as usize];
+
+        let mut done = 0;
+        for file in self.files_in_range(offset, offset + len) {
+            let (file_offset, n) = file.overlap(offset + done, len - done);
+            let mut handle = self.lock(file).await?;
+            let handle = handle.as_mut().expect("file was just opened");
+            handle.seek(SeekFrom::Start(file_offset)).await?;
+            handle
+                .read_exact(&mut data[done as usize..(done + n) as usize])
+                .await?;
+            done += n;
+        }
+
+        Ok(data)
+    }
+
+    /// Write `data` at `offset` of the stream.
+    pub async fn write_at(&self, offset: u64, data: &[u8]) -> std::io::Result<()> {
+        let len = data.len() as u64;
+
+        let mut done = 0;
+        for file in self.files_in_range(offset, offset + len) {
+            let (file_offset, n) = file.overlap(offset + done, len - done);
+            let mut handle = self.lock(file).await?;
+            let handle = handle.as_mut().expect("file was just opened");
+            handle.seek(SeekFrom::Start(file_offset)).await?;
+            handle
+                .write_all(&data[done as usize..(done + n) as usize])
+                .await?;
+            done += n;
+        }
+
+        Ok(())
+    }
+
+    /// Fill a `.kuaip2p-part` file from a local copy of the same content (see [super::hash_index]).
+    ///
+    /// The file is copied rather than hardlinked, so later changes to one do not show in the other.
+    /// The copy is hashed again: if it does not match `checksum` (the local copy changed since it
+    /// was indexed), the file is emptied again and an error returned.
+    pub async fn place_file(
+        &self,
+        index: usize,
+        src: &Path,
+        checksum: &[u8],
+    ) -> std::io::Result<()> {
+        let file = &self.files[index];
+        file.handle.lock().await.take();
+        let placed = async {
+            fs::copy(src, &file.path).await?;
+            let mut reader = File::open(&file.path).await?;
+            if hash_file(&mut reader, None).await?.as_bytes().as_slice() != checksum {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "the local copy changed since it was indexed",
+                ));
+            }
+            Ok(())
+        }
+        .await;
+        if placed.is_err() {
+            File::create(&file.path).await?;
+        }
+        placed
+    }
+
+    /// Flush a written file to disk, close it, and compute its BLAKE3 hash.
+    pub async fn finish_file(&self, index: usize) -> std::io::Result<blake3::Hash> {
+        let file = &self.files[index];
+        let mut handle = file.handle.lock().await;
+        if let Some(mut open) = handle.take() {
+            open.flush().await?;
+            open.sync_all().await?;
+        }
+
+        let mut reader = File::open(&file.path).await?;
+        hash_file(&mut reader, None).await
+    }
+
+    /// Rename every (verified) `.kuaip2p-part` file to its final name.
+    ///
+    /// Fails if a file already exists under one of the final names. If any rename fails, the files
+    /// renamed so far are renamed back, so [Self::discard] removes them all.
+    pub async fn commit(&self) -> std::io::Result<()> {
+        let mut renamed: Vec<(&PathBuf, &PathBuf)> = Vec::new();
+        for file in &self.files {
+            let Some(final_path) = &file.final_path else {
+                continue;
+            };
+            if let Err(e) = Self::commit_file(file, final_path).await {
+                for (path, final_path) in renamed.into_iter().rev() {
+                    if let Err(e) = fs::rename(final_path, path).await {
+                        warn!("Failed to rename {} back: {}", final_path.display(), e);
+                    }
+                }
+                return Err(e);
+            }
+            renamed.push((&file.path, final_path));
+        }
+        Ok(())
+    }
+
+    async fn commit_file(file: &OpenFile, final_path: &Path) -> std::io::Result<()> {
+        // Make sure the data is on disk before the rename makes it visible
+        if let Some(mut open) = file.handle.lock().await.take() {
+            open.flush().await?;
+            open.sync_all().await?;
+        }
+        rename_new(&file.path, final_path).await?;
+
+        // Make sure the rename itself is on disk
+        #[cfg(unix)]
+        if let Some(parent) = final_path.parent() {
+            File::open(parent).await?.sync_all().await?;
+        }
+        Ok(())
+    }
+
+    /// Close and remove every `.kuaip2p-part` file of a failed or cancelled transfer.
+    pub async fn discard(&self) {
+        self.discard_until(None).await;
+    }
+
+    /// Remove the `.kuaip2p-part` files created before `failed`, which could not be created.
+    async fn discard_created(&self, failed: &Path) {
+        self.discard_until(Some(failed)).await;
+    }
+
+    async fn discard_until(&self, failed: Option<&Path>) {
+        for file in &self.files {
+            if Some(file.path.as_path()) == failed {
+                break;
+            }
+            if file.final_path.is_none() {
+                continue;
+            }
+            file.handle.lock().await.take();
+            if let Err(e) = fs::remove_file(&file.path).await
+                && e.kind() != std::io::ErrorKind::NotFound
+            {
+                warn!("Failed to remove {}: {}", file.path.display(), e);
+            }
+        }
+    }
+
+    fn files_in_range(&self, start: u64, end: u64) -> impl Iterator<Item = &OpenFile> {
+        self.files.iter().filter(move |file| {
+            file.size > 0 && file.offset < end && start < file.offset + file.size
+        })
+    }
+
+    /// Lock a file's handle, opening it if need be.
+    async fn lock<'a>(
+        &self,
+        file: &'a OpenFile,
+    ) -> std::io::Result<tokio::sync::MutexGuard<'a, Option<File>>> {
+        let mut handle = file.handle.lock().await;
+        if handle.is_none() {
+            *handle = Some(
+                OpenOptions::new()
+                    .read(true)
+                    .write(self.writable)
+                    .open(&file.path)
+                    .await?,
+            );
+        }
+        Ok(handle)
+    }
+}
+
+/// Rename a file, failing instead of replacing `to` if it already exists.
+async fn rename_new(from: &Path, to: &Path) -> std::io::Result<()> {
+    let already_exists = || {
+        std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} already exists", to.display()),
+        )
+    };
+
+    // Unlike a rename, a hard link never replaces its target
+    match fs::hard_link(from, to).await {
+        Ok(()) => fs::remove_file(from).await,
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Err(already_exists()),
+        // The filesystem has no hard links, or not across these directories
+        Err(e) if hard_links_unsupported(&e) => {
+            if fs::try_exists(to).await? {
+                return Err(already_exists());
+            }
+            fs::rename(from, to).await
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Whether a hard link failed because the filesystem cannot make it, rather than because of
+/// the files themselves.
+fn hard_links_unsupported(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::Unsupported | std::io::ErrorKind::CrossesDevices
+    )
+}
+
+/// The `.kuaip2p-part` path of a file of an incoming transfer.
+fn part_path(path: &Path, unique_id: Uuid) -> PathBuf {
+    let mut part_path = path.as_os_str().to_owned();
+    part_path.push(".");
+    part_path.push(&unique_id.simple().to_string()[..8]);
+    part_path.push(".");
+    part_path.push(PART_EXTENSION);
+    PathBuf::from(part_path)
+}
+
+impl OpenFile {
+    /// Offset within this file, and length within this file, of `len` bytes at `stream_offset`.
+    fn overlap(&self, stream_offset: u64, len: u64) -> (u64, u64) {
+        let file_offset = stream_offset - self.offset;
+        (file_offset, len.min(self.size - file_offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, size: u64) -> ManifestEntry {
+        ManifestEntry {
+            path: path.to_string(),
+            size,
+            checksum: vec![0; CHECKSUM_LEN],
+            metadata: FileMetadata::default(),
+        }
+    }
+
+    #[test]
+    fn sanitize_path_keeps_plain_relative_paths() {
+        assert_eq!(sanitize_path("a.txt"), Some(PathBuf::from("a.txt")));
+        assert_eq!(
+            sanitize_path("dir/sub/a.txt"),
+            Some(["dir", "sub", "a.txt"].iter().collect::<PathBuf>())
+        );
+        assert_eq!(
+            sanitize_path("..a/b.."),
+            Some(PathBuf::from("..a").join("b.."))
+        );
+    }
+
+    #[test]
+    fn sanitize_path_rejects_escapes() {
+        for path in [
+            // Parent and current directories
+            "..",
+            "../a",
+            "a/../../b",
+            "a/./b",
+            // Absolute paths
+            "/etc/passwd",
+            // Windows drives and UNC prefixes
+            "C:/Windows",
+            "C:",
+            "c:a",
+            "\\\\server\\share\\a",
+            "//server/share/a",
+            "a\\..\\b",
+            // Empty components
+            "",
+            "a//b",
+            "a/",
+            "nul\0",
+        ] {
+            assert_eq!(sanitize_path(path), None, "{:?}", path);
+        }
+    }
+
+    #[test]
+    fn from_offer_lays_files_out_back_to_back() {
+        let manifest =
+            Manifest::from_offer(vec![entry("a", 3), entry("dir/b", 0), entry("dir/c", 5)], 8)
+                .unwrap();
+        let offsets: Vec<u64> = manifest.files.iter().map(|file| file.offset).collect();
+        assert_eq!(offsets, [0, 3, 3]);
+        assert_eq!(manifest.total_size(), 8);
+    }
+
+    #[test]
+    fn from_offer_rejects_unsafe_or_inconsistent_manifests() {
+        let cases = [
+            (vec![], 0),
+            (vec![entry("../a", 1)], 1),
+            (vec![entry("/a", 1)], 1),
+            (vec![entry("a.kuaip2p-part", 1)], 1),
+            // Duplicate paths, and a path used as both a file and a directory
+            (vec![entry("a", 1), entry("a", 1)], 2),
+            (vec![entry("a", 1), entry("a/b", 1)], 2),
+            (vec![entry("a/b", 1), entry("a", 1)], 2),
+            // Sizes that do not add up, or overflow
+            (vec![entry("a", 1)], 2),
+            (vec![entry("a", u64::MAX), entry("b", 1)], 0),
+        ];
+        for (entries, total_size) in cases {
+            let paths: Vec<String> = entries.iter().map(|entry| entry.path.clone()).collect();
+            assert!(
+                Manifest::from_offer(entries, total_size).is_err(),
+                "{:?}",
+                paths
+            );
+        }
+
+        let mut invalid_checksum = entry("a", 1);
+        invalid_checksum.checksum.pop();
+        assert!(Manifest::from_offer(vec![invalid_checksum], 1).is_err());
+    }
+
+    #[tokio::test]
+    async fn transfers_never_share_or_replace_files() {
+        let dir = std::env::temp_dir().join(format!("kuaip2p-manifest-{}", Uuid::new_v4()));
+        let mut manifest = Manifest::from_offer(vec![entry("a", 3)], 3).unwrap();
+        manifest.set_download_dir(&dir);
+
+        // Two offers of the same file each get their own part file
+        let first = FileSet::create(&manifest, Uuid::new_v4()).await.unwrap();
+        let second = FileSet::create(&manifest, Uuid::new_v4()).await.unwrap();
+        first.write_at(0, b"one").await.unwrap();
+        second.write_at(0, b"two").await.unwrap();
+        first.finish_file(0).await.unwrap();
+        second.finish_file(0).await.unwrap();
+
+        // An existing part file is left alone
+        let unique_id = Uuid::new_v4();
+        fs::write(part_path(&dir.join("a"), unique_id), b"old")
+            .await
+            .unwrap();
+        assert!(FileSet::create(&manifest, unique_id).await.is_err());
+        assert_eq!(
+            fs::read(part_path(&dir.join("a"), unique_id))
+                .await
+                .unwrap(),
+            b"old"
+        );
+
+        // The first to finish wins, and the second does not replace it
+        first.commit().await.unwrap();
+        assert!(second.commit().await.is_err());
+        assert_eq!(fs::read(dir.join("a")).await.unwrap(), b"one");
+        second.discard().await;
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
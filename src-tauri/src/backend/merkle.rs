@@ -0,0 +1,409 @@
+//! # Merkle Tree
+//!
+//! Every chunk of a transfer is verified as it arrives, against the root of a Merkle tree
+//! over the transfer's byte stream (as in BLAKE3/Bao). The root is sent in the `FileOffer`,
+//! so it also identifies the content of the transfer, whatever the file names.
+//!
+//! - The stream is split into [BLOCK_LEN] blocks. Each block is a leaf of the tree.
+//!   Chunk lengths are multiples of [BLOCK_LEN] (see [super::chunk_sizing]), so every chunk
+//!   covers whole blocks (bar the last block of the stream).
+//! - The tree is left-balanced: the left subtree of every node is a complete power of two.
+//!   Leaves and parents are hashed with distinct prefixes, so neither can pass for the other.
+//! - Each `FileChunk` carries a proof: the hashes of the subtrees outside its blocks, in pre-order.
+//!   Together with the hashes of its own blocks, they rebuild the root.
+//!
+//! A chunk that does not rebuild the root is rejected with `FileChunkNack`, and resent at once.
+//! The sender keeps the whole tree in memory (64 bytes per block, 4 MB per GB).
+
+/// A BLAKE3 hash.
+pub type Hash = [u8; 32];
+
+/// Length of a block (a leaf of the tree). Chunk boundaries are always block aligned.
+pub const BLOCK_LEN: u64 = super::protocol::MIN_CHUNK_LEN;
+
+/// Hash of a block.
+pub fn leaf_hash(data: &[u8]) -> Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[0]);
+    hasher.update(data);
+    *hasher.finalize().as_bytes()
+}
+
+/// Hash of a parent node.
+fn parent_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[1]);
+    hasher.update(left);
+    hasher.update(right);
+    *hasher.finalize().as_bytes()
+}
+
+/// Number of blocks (leaves) of a stream. An empty stream has a single empty block.
+fn block_count(total_size: u64) -> u64 {
+    total_size.div_ceil(BLOCK_LEN).max(1)
+}
+
+/// Height of the tree over `blocks` leaves.
+fn height(blocks: u64) -> u32 {
+    blocks.next_power_of_two().trailing_zeros()
+}
+
+/// Hashes the blocks of a stream fed in arbitrary pieces (e.g. file by file).
+#[derive(Debug, Default)]
+pub struct LeafHasher {
+    leaves: Vec<Hash>,
+    block: Vec<u8>,
+}
+
+impl LeafHasher {
+    /// Feed the next bytes of the stream.
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let take = (BLOCK_LEN as usize - self.block.len()).min(data.len());
+            self.block.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.block.len() == BLOCK_LEN as usize {
+                self.leaves.push(leaf_hash(&self.block));
+                self.block.clear();
+            }
+        }
+    }
+
+    /// Build the tree over every block fed.
+    pub fn finish(mut self) -> MerkleTree {
+        if !self.block.is_empty() || self.leaves.is_empty() {
+            self.leaves.push(leaf_hash(&self.block));
+        }
+        MerkleTree::from_leaves(self.leaves)
+    }
+}
+
+/// The whole tree of an outgoing transfer, to prove its chunks.
+#[derive(Debug)]
+pub struct MerkleTree {
+    /// Every level of the tree, from the leaves to the root.
+    /// The last node of an odd level is carried up as is.
+    levels: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    fn from_leaves(leaves: Vec<Hash>) -> Self {
+        let mut levels = vec![leaves];
+        while let Some(level) = levels.last().filter(|level| level.len() > 1) {
+            let parents = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => parent_hash(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(parents);
+        }
+        Self { levels }
+    }
+
+    /// The root hash.
+    pub fn root(&self) -> Hash {
+        self.levels
+            .last()
+            .and_then(|level| level.first())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The proof of the chunk at `offset`, `len` bytes long.
+    pub fn proof(&self, offset: u64, len: u64) -> Vec<Hash> {
+        let blocks = self.levels[0].len() as u64;
+        let range = block_range(offset, len);
+        let mut proof = Vec::new();
+        walk(height(blocks), 0, blocks, &range, &mut |level, index| {
+            proof.push(self.levels[level as usize][index as usize]);
+        });
+        proof
+    }
+}
+
+/// Verify a chunk of a stream of `total_size` bytes against the root of its tree.
+pub fn verify_chunk(
+    root: &Hash,
+    total_size: u64,
+    offset: u64,
+    data: &[u8],
+    proof: &[Hash],
+) -> bool {
+    let len = data.len() as u64;
+    let end = offset.saturating_add(len);
+    // Whole blocks only, bar the end of the stream
+    if !offset.is_multiple_of(BLOCK_LEN)
+        || (!len.is_multiple_of(BLOCK_LEN) && end != total_size)
+        || end > total_size
+    {
+        return false;
+    }
+
+    let blocks = block_count(total_size);
+    let range = block_range(offset, len);
+    let leaves: Vec<Hash> = if data.is_empty() {
+        vec![leaf_hash(&[])]
+    } else {
+        data.chunks(BLOCK_LEN as usize).map(leaf_hash).collect()
+    };
+
+    let mut proof = proof.iter();
+    let rebuilt = rebuild(height(blocks), 0, blocks, &range, &leaves, &mut proof);
+    rebuilt.is_some_and(|rebuilt| rebuilt == *root) && proof.next().is_none()
+}
+
+/// The blocks `[start, end)` covered by a chunk. An empty chunk covers the single empty block.
+fn block_range(offset: u64, len: u64) -> std::ops::Range<u64> {
+    let start = offset / BLOCK_LEN;
+    start..(offset + len).div_ceil(BLOCK_LEN).max(start + 1)
+}
+
+/// The leaves `[start, end)` under the node at `level`, `index`.
+fn node_range(level: u32, index: u64, blocks: u64) -> (u64, u64) {
+    let start = index << level;
+    (start, (start + (1 << level)).min(blocks))
+}
+
+/// Visit the nodes outside `range` whose hashes make up its proof, in pre-order.
+fn walk(
+    level: u32,
+    index: u64,
+    blocks: u64,
+    range: &std::ops::Range<u64>,
+    visit: &mut impl FnMut(u32, u64),
+) {
+    let (start, end) = node_range(level, index, blocks);
+    if end <= range.start || start >= range.end {
+        visit(level, index);
+    } else if level > 0 && !(range.start <= start && end <= range.end) {
+        walk(level - 1, index * 2, blocks, range, visit);
+        if node_range(level - 1, index * 2 + 1, blocks).0 < blocks {
+            walk(level - 1, index * 2 + 1, blocks, range, visit);
+        }
+    }
+}
+
+/// Rebuild the hash of the node at `level`, `index` from the leaves in `range` and the proof.
+fn rebuild<'a>(
+    level: u32,
+    index: u64,
+    blocks: u64,
+    range: &std::ops::Range<u64>,
+    leaves: &[Hash],
+    proof: &mut impl Iterator<Item = &'a Hash>,
+) -> Option<Hash> {
+    let (start, end) = node_range(level, index, blocks);
+    if end <= range.start || start >= range.end {
+        return proof.next().copied();
+    }
+    if level == 0 {
+        return leaves.get((start - range.start) as usize).copied();
+    }
+
+    let left = rebuild(level - 1, index * 2, blocks, range, leaves, proof)?;
+    if node_range(level - 1, index * 2 + 1, blocks).0 >= blocks {
+        // Odd node, carried up as is
+        return Some(left);
+    }
+    let right = rebuild(level - 1, index * 2 + 1, blocks, range, leaves, proof)?;
+    Some(parent_hash(&left, &right))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK: usize = BLOCK_LEN as usize;
+
+    fn stream(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn tree(data: &[u8]) -> MerkleTree {
+        let mut hasher = LeafHasher::default();
+        // Fed in pieces that do not line up with the blocks
+        for piece in data.chunks(1000) {
+            hasher.update(piece);
+        }
+        hasher.finish()
+    }
+
+    /// Every chunk of `chunk_blocks` blocks verifies against the root.
+    fn assert_chunks_verify(total_size: usize, chunk_blocks: usize) {
+        let data = stream(total_size);
+        let tree = tree(&data);
+        let root = tree.root();
+        let chunk_len = chunk_blocks * BLOCK;
+        let mut offset = 0;
+        loop {
+            let chunk = &data[offset..(offset + chunk_len).min(total_size)];
+            let proof = tree.proof(offset as u64, chunk.len() as u64);
+            assert!(
+                verify_chunk(&root, total_size as u64, offset as u64, chunk, &proof),
+                "chunk at {} of {} bytes does not verify",
+                offset,
+                total_size
+            );
+            offset += chunk_len;
+            if offset >= total_size {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn proofs_verify() {
+        // One block, a power of two, odd leaf counts, and short last blocks
+        for blocks in [1, 2, 3, 4, 5, 7, 8, 9, 13] {
+            for total_size in [blocks * BLOCK, blocks * BLOCK - 100] {
+                for chunk_blocks in [1, 2, 3, 4] {
+                    assert_chunks_verify(total_size, chunk_blocks);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn empty_stream_verifies() {
+        let tree = tree(&[]);
+        assert_eq!(tree.root(), leaf_hash(&[]));
+        assert!(verify_chunk(&tree.root(), 0, 0, &[], &tree.proof(0, 0)));
+    }
+
+    #[test]
+    fn whole_stream_needs_no_proof() {
+        let data = stream(5 * BLOCK + 10);
+        let tree = tree(&data);
+        let proof = tree.proof(0, data.len() as u64);
+        assert!(proof.is_empty());
+        assert!(verify_chunk(
+            &tree.root(),
+            data.len() as u64,
+            0,
+            &data,
+            &proof
+        ));
+    }
+
+    #[test]
+    fn tampered_chunk_is_rejected() {
+        let data = stream(5 * BLOCK + 10);
+        let tree = tree(&data);
+        let offset = 2 * BLOCK;
+        let mut chunk = data[offset..offset + BLOCK].to_vec();
+        let proof = tree.proof(offset as u64, BLOCK as u64);
+        chunk[7] ^= 1;
+        assert!(!verify_chunk(
+            &tree.root(),
+            data.len() as u64,
+            offset as u64,
+            &chunk,
+            &proof
+        ));
+    }
+
+    #[test]
+    fn tampered_proof_is_rejected() {
+        let data = stream(5 * BLOCK + 10);
+        let tree = tree(&data);
+        let offset = 3 * BLOCK;
+        let chunk = &data[offset..offset + BLOCK];
+        let proof = tree.proof(offset as u64, BLOCK as u64);
+        assert!(!proof.is_empty());
+
+        for index in 0..proof.len() {
+            let mut tampered = proof.clone();
+            tampered[index][0] ^= 1;
+            assert!(!verify_chunk(
+                &tree.root(),
+                data.len() as u64,
+                offset as u64,
+                chunk,
+                &tampered
+            ));
+        }
+        // Too short, and too long
+        assert!(!verify_chunk(
+            &tree.root(),
+            data.len() as u64,
+            offset as u64,
+            chunk,
+            &proof[1..]
+        ));
+        let mut longer = proof.clone();
+        longer.push([0; 32]);
+        assert!(!verify_chunk(
+            &tree.root(),
+            data.len() as u64,
+            offset as u64,
+            chunk,
+            &longer
+        ));
+        // The proof of another chunk
+        let other = tree.proof(BLOCK as u64, BLOCK as u64);
+        assert!(!verify_chunk(
+            &tree.root(),
+            data.len() as u64,
+            offset as u64,
+            chunk,
+            &other
+        ));
+    }
+
+    #[test]
+    fn wrong_root_is_rejected() {
+        let data = stream(5 * BLOCK + 10);
+        let tree = tree(&data);
+        let other = self::tree(&stream(5 * BLOCK + 11));
+        assert_ne!(tree.root(), other.root());
+
+        let chunk = &data[..BLOCK];
+        let proof = tree.proof(0, BLOCK as u64);
+        assert!(!verify_chunk(
+            &other.root(),
+            data.len() as u64,
+            0,
+            chunk,
+            &proof
+        ));
+    }
+
+    #[test]
+    fn unaligned_chunks_are_rejected() {
+        let data = stream(5 * BLOCK + 10);
+        let tree = tree(&data);
+        let total_size = data.len() as u64;
+        // Not at a block boundary
+        let proof = tree.proof(0, BLOCK as u64);
+        assert!(!verify_chunk(
+            &tree.root(),
+            total_size,
+            1,
+            &data[1..BLOCK + 1],
+            &proof
+        ));
+        // Partial block before the end of the stream
+        assert!(!verify_chunk(
+            &tree.root(),
+            total_size,
+            0,
+            &data[..BLOCK - 1],
+            &proof
+        ));
+        // Past the end of the stream
+        let offset = 5 * BLOCK;
+        let proof = tree.proof(offset as u64, 10);
+        let mut chunk = data[offset..].to_vec();
+        chunk.push(0);
+        assert!(!verify_chunk(
+            &tree.root(),
+            total_size,
+            offset as u64,
+            &chunk,
+            &proof
+        ));
+    }
+}
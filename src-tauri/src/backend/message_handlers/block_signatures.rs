@@ -0,0 +1,60 @@
+use std::net::SocketAddr;
+
+use tracing::warn;
+
+use crate::backend::{
+    delta,
+    peer_manager::{FileTransferDirection, FileTransferStatus, PeerManager},
+    protocol::BlockSignatures,
+};
+
+impl PeerManager {
+    /// # Message Handler: `BlockSignatures`
+    ///
+    /// The peer has an older version of a file we offered, and is about to accept the offer.
+    /// Keep the signatures of its blocks, to send the file as a delta once accepted (see [delta]).
+    pub async fn handle_block_signatures(
+        &self,
+        block_signatures: BlockSignatures,
+        peer_addr: SocketAddr,
+    ) {
+        if self
+            .authenticated_peer_tx(peer_addr, "BlockSignatures")
+            .await
+            .is_none()
+        {
+            return;
+        }
+
+        let mut active_transfers = self.active_transfers.lock().await;
+        let Some(transfer) = active_transfers
+            .get_mut(&block_signatures.unique_id)
+            .filter(|transfer| {
+                transfer.peer_addr == peer_addr
+                    && !transfer.ranged
+                    && transfer.direction == FileTransferDirection::Sending
+                    && matches!(transfer.status, FileTransferStatus::WaitingForPeerResponse)
+            })
+        else {
+            warn!(
+                "Peer {} sent BlockSignatures for an unknown file offer {}. Ignoring.",
+                peer_addr, block_signatures.unique_id
+            );
+            return;
+        };
+
+        if (block_signatures.file_index as usize) >= transfer.manifest.files.len()
+            || !delta::check_signatures(&block_signatures)
+        {
+            warn!(
+                "Peer {} sent invalid BlockSignatures for file {} of file offer {}. Ignoring.",
+                peer_addr, block_signatures.file_index, block_signatures.unique_id
+            );
+            return;
+        }
+
+        transfer
+            .block_signatures
+            .insert(block_signatures.file_index as usize, block_signatures);
+    }
+}
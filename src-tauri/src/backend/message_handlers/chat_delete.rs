@@ -0,0 +1,14 @@
+use std::net::SocketAddr;
+
+use crate::backend::{peer_manager::PeerManager, protocol::ChatDelete};
+
+impl PeerManager {
+    /// # Message Handler: `ChatDelete`
+    ///
+    /// The peer deleted a chat message it sent us, or sent to a room we are in.
+    /// Apply it to the chat history if the peer is the author, and tell the frontend.
+    pub async fn handle_chat_delete(&self, chat_delete: ChatDelete, peer_addr: SocketAddr) {
+        self.receive_chat_update(chat_delete.into(), peer_addr, "ChatDelete")
+            .await;
+    }
+}
@@ -0,0 +1,14 @@
+use std::net::SocketAddr;
+
+use crate::backend::{peer_manager::PeerManager, protocol::ChatEdit};
+
+impl PeerManager {
+    /// # Message Handler: `ChatEdit`
+    ///
+    /// The peer edited a chat message it sent us, or sent to a room we are in.
+    /// Apply it to the chat history if the peer is the author, and tell the frontend.
+    pub async fn handle_chat_edit(&self, chat_edit: ChatEdit, peer_addr: SocketAddr) {
+        self.receive_chat_update(chat_edit.into(), peer_addr, "ChatEdit")
+            .await;
+    }
+}
@@ -0,0 +1,136 @@
+use std::net::SocketAddr;
+
+use tracing::{debug, warn};
+
+use crate::{
+    backend::{
+        chat,
+        chat_outbox::ChatAction,
+        chat_store::ConversationKey,
+        peer_manager::PeerManager,
+        protocol::{ChatMessage, ChatMessageAck, Message},
+        sender_keys,
+    },
+    js_api::backend_event::{self, BackendEvent, ChatHistoryMessage, ChatMessageState},
+};
+
+impl PeerManager {
+    /// # Message Handler: `ChatMessage`
+    ///
+    /// The peer sent us a chat message, to us or to a room (encrypted). Acknowledge it, pass it on to the frontend,
+    /// and apply the changes to it that arrived before it.
+    pub async fn handle_chat_message(&self, chat_message: ChatMessage, peer_addr: SocketAddr) {
+        let Some((peer_tx, peer_info)) = self.authenticated_peer(peer_addr, "ChatMessage").await
+        else {
+            return;
+        };
+
+        // Room messages are decrypted with the peer's sender key for the room. Those of rooms
+        // we are not in with the peer are acknowledged, but ignored.
+        let body = match chat_message.room_id {
+            Some(room_id)
+                if self
+                    .accept_room_message(room_id, &peer_info.identity, chat_message.clock)
+                    .await =>
+            {
+                let header = sender_keys::header(
+                    chat_message.id,
+                    chat_message.timestamp,
+                    chat_message.clock,
+                    &ChatAction::Message,
+                );
+                let decrypted = self.received_sender_keys.lock().await.decrypt(
+                    room_id,
+                    &peer_info.identity,
+                    &header,
+                    chat_message.encrypted.as_ref(),
+                );
+                match decrypted {
+                    Ok(body) => Some(body),
+                    Err(e) => {
+                        // Not acknowledged: it is sent again, after the key, on the next connection
+                        warn!(
+                            "Failed to decrypt chat message {} of peer {} to room {}: {}",
+                            chat_message.id, peer_addr, room_id, e
+                        );
+                        return;
+                    }
+                }
+            }
+            Some(_) => None,
+            None => Some(chat_message.body.clone()),
+        };
+
+        if let Some(body) = &body
+            && let Err(e) = chat::check_body(body)
+        {
+            warn!(
+                "Peer {} sent an invalid chat message {}: {}. Ignoring.",
+                peer_addr, chat_message.id, e
+            );
+            return;
+        }
+
+        peer_tx
+            .send(Message::ChatMessageAck(ChatMessageAck {
+                id: chat_message.id,
+            }))
+            .await
+            .ok(); // We ignore the error here, as the peer may have already disconnected.
+
+        let Some(body) = body else {
+            debug!(
+                "Peer {} sent chat message {} to a room we are not in with it. Ignoring.",
+                peer_addr, chat_message.id
+            );
+            return;
+        };
+        let key = match chat_message.room_id {
+            Some(room_id) => ConversationKey::Room(room_id),
+            None => ConversationKey::Peer(peer_info.identity.clone()),
+        };
+
+        // The peer may resend a message whose ack it did not get
+        if !self
+            .store_chat_message(
+                &key,
+                ChatHistoryMessage {
+                    id: chat_message.id.to_string(),
+                    timestamp: chat_message.timestamp,
+                    body: body.clone(),
+                    outgoing: false,
+                    author: Some(peer_info.identity.clone()),
+                    author_name: Some(peer_info.name.clone()),
+                    clock: chat_message.clock,
+                    state: ChatMessageState::Unread,
+                    edited_at: None,
+                    deleted: false,
+                    reactions: Vec::new(),
+                },
+            )
+            .await
+        {
+            debug!(
+                "Peer {} sent chat message {} again. Ignoring.",
+                peer_addr, chat_message.id
+            );
+            return;
+        }
+
+        self.backend_event_tx
+            .send(BackendEvent::ChatMessageReceived(
+                backend_event::ChatMessage {
+                    peer: peer_info.into_connection_info(peer_addr),
+                    room_id: chat_message.room_id.map(|room_id| room_id.to_string()),
+                    id: chat_message.id.to_string(),
+                    timestamp: chat_message.timestamp,
+                    body,
+                },
+            ))
+            .await
+            .expect("Failed to send ChatMessageReceived event to the frontend");
+        // Changes to the message may have arrived before it
+        self.apply_pending_chat_updates(&key, chat_message.id).await;
+        self.emit_chat_conversations().await;
+    }
+}
@@ -0,0 +1,62 @@
+use std::net::SocketAddr;
+
+use tracing::warn;
+
+use crate::{
+    backend::{chat_outbox::ChatAction, peer_manager::PeerManager, protocol::ChatMessageAck},
+    js_api::backend_event::{BackendEvent, ChatMessageDelivered},
+};
+
+impl PeerManager {
+    /// # Message Handler: `ChatMessageAck`
+    ///
+    /// The peer received a chat message (or edit, deletion or reaction) we sent.
+    /// Tell the frontend the message was delivered.
+    pub async fn handle_chat_message_ack(
+        &self,
+        chat_message_ack: ChatMessageAck,
+        peer_addr: SocketAddr,
+    ) {
+        let Some((_, peer_info)) = self.authenticated_peer(peer_addr, "ChatMessageAck").await
+        else {
+            return;
+        };
+
+        // Only the peer the message was sent to may acknowledge it, and only once
+        let (message, delivered) = {
+            let mut chat_outbox = self.chat_outbox.lock().await;
+            let Some(message) = chat_outbox.remove(&peer_info.identity, chat_message_ack.id) else {
+                warn!(
+                    "Peer {} acknowledged an unknown chat message {}. Ignoring.",
+                    peer_addr, chat_message_ack.id
+                );
+                return;
+            };
+            // A room message is delivered once every member acknowledged it
+            let delivered = !chat_outbox.contains(message.id);
+            if let Err(e) = chat_outbox.save().await {
+                warn!("{}", e);
+            }
+            (message, delivered)
+        };
+        // Edits, deletions and reactions are not reported
+        if message.action != ChatAction::Message {
+            return;
+        }
+        if delivered {
+            self.store_chat_delivered(
+                &message.conversation(&peer_info.identity),
+                &chat_message_ack.id.to_string(),
+            )
+            .await;
+        }
+
+        self.backend_event_tx
+            .send(BackendEvent::ChatMessageDelivered(ChatMessageDelivered {
+                peer: peer_info.into_connection_info(peer_addr),
+                id: chat_message_ack.id.to_string(),
+            }))
+            .await
+            .expect("Failed to send ChatMessageDelivered event to the frontend");
+    }
+}
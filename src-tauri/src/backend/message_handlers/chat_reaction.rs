@@ -0,0 +1,14 @@
+use std::net::SocketAddr;
+
+use crate::backend::{peer_manager::PeerManager, protocol::ChatReaction};
+
+impl PeerManager {
+    /// # Message Handler: `ChatReaction`
+    ///
+    /// The peer added (or removed) an emoji reaction to a chat message of our conversation, or of a room we are in.
+    /// Apply it to the chat history, and tell the frontend.
+    pub async fn handle_chat_reaction(&self, chat_reaction: ChatReaction, peer_addr: SocketAddr) {
+        self.receive_chat_update(chat_reaction.into(), peer_addr, "ChatReaction")
+            .await;
+    }
+}
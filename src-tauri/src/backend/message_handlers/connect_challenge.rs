@@ -0,0 +1,44 @@
+use std::net::SocketAddr;
+
+use tracing::warn;
+
+use crate::backend::{
+    peer_manager::PeerManager,
+    protocol::{ConnectionInfo, PROTOCOL_VERSION},
+};
+
+impl PeerManager {
+    /// # Message Handler: `ConnectChallenge`
+    ///
+    /// The peer we asked to connect to proved its identity. Prove ours.
+    pub async fn handle_connect_challenge(
+        &self,
+        connection_info: ConnectionInfo,
+        peer_addr: SocketAddr,
+    ) {
+        // Refuse a peer of another protocol version
+        // Check the peer's signature of the handshake
+        // If it is valid, send our `ConnectProof`, and wait for the peer's `ConnectResponse`
+        // Else, disconnect the peer
+
+        if connection_info.protocol_version != PROTOCOL_VERSION {
+            self.drop_incompatible_peer(
+                peer_addr,
+                format!(
+                    "it speaks protocol version {}, ours is {}",
+                    connection_info.protocol_version, PROTOCOL_VERSION
+                ),
+            )
+            .await;
+            return;
+        }
+
+        if let Err(e) = self.answer_challenge(peer_addr, connection_info).await {
+            warn!(
+                "Peer {} failed to prove its identity: {}. Disconnecting.",
+                peer_addr, e
+            );
+            self.drop_peer(peer_addr, e.into()).await;
+        }
+    }
+}
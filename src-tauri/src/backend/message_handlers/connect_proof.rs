@@ -0,0 +1,57 @@
+use std::net::SocketAddr;
+
+use tracing::warn;
+
+use crate::{
+    backend::{
+        peer_manager::PeerManager,
+        protocol::{ConnectProof, Message},
+    },
+    js_api::backend_event::BackendEvent,
+};
+
+impl PeerManager {
+    /// # Message Handler: `ConnectProof`
+    ///
+    /// The peer that asked to connect to us proved its identity.
+    pub async fn handle_connect_proof(&self, connect_proof: ConnectProof, peer_addr: SocketAddr) {
+        // Check the peer's signature of the handshake
+        // If it is valid, prompt the frontend to accept or reject the connection
+        // Else, disconnect the peer
+
+        let connection_info = match self.check_proof(peer_addr, connect_proof).await {
+            Ok(Some(connection_info)) => connection_info,
+            Ok(None) => return,
+            Err(e) => {
+                warn!(
+                    "Peer {} failed to prove its identity: {}. Disconnecting.",
+                    peer_addr, e
+                );
+                self.drop_peer(peer_addr, e.into()).await;
+                return;
+            }
+        };
+
+        // Prompt the frontend to accept or reject the connection
+        self.backend_event_tx
+            .send(BackendEvent::ConnectRequest(connection_info))
+            .await
+            .expect("Failed to send ConnectRequest event to the frontend");
+
+        // This is the most we can do for now. The frontend will respond with a `ConnectResponse` message, and
+        // the specific handler will continue the process.
+        // Let's just begin the keep-alive ping-pong to keep the connection alive.
+        let peer_tx = self
+            .active_peers
+            .lock()
+            .await
+            .get(&peer_addr)
+            .map(|peer| peer.tx.clone());
+        if let Some(peer_tx) = peer_tx {
+            peer_tx
+                .send(Message::KeepAlive)
+                .await
+                .expect("Failed to send KeepAlive message to the peer");
+        }
+    }
+}
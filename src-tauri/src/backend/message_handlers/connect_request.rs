@@ -1,13 +1,10 @@
 use std::net::SocketAddr;
 
-// use base64::{Engine, prelude::BASE64_STANDARD};
+use tracing::warn;
 
-use crate::{
-    backend::{
-        peer_manager::{PeerManager, PeerState},
-        protocol::{ConnectionInfo, Message},
-    },
-    js_api::backend_event::{self, BackendEvent},
+use crate::backend::{
+    peer_manager::PeerManager,
+    protocol::{ConnectionInfo, PROTOCOL_VERSION},
 };
 
 impl PeerManager {
@@ -20,43 +17,28 @@ impl PeerManager {
         peer_addr: SocketAddr,
     ) {
         // Peer wants to connect to us
-        // Prompt the frontend to accept or reject the connection
-        // If accepted, change state to `Authenticated` and send a `ConnectResponse` message
+        // Refuse a peer of another protocol version
+        // Challenge the peer to prove its identity (see `ecdsa_identity`)
+        // Once it does, the frontend is prompted to accept or reject the connection
 
-        // Prompt the frontend to accept or reject the connection
-        self.backend_event_tx
-            .send(BackendEvent::ConnectRequest(
-                backend_event::ConnectionInfo {
-                    name: connection_info.name.clone(),
-                    ip: peer_addr.to_string(),
-                    backend_version: connection_info.backend_version.clone(),
-                    // identitiy: BASE64_STANDARD.encode(connection_info.identitiy.public_key.clone()),
-                },
-            ))
-            .await
-            .expect("Failed to send ConnectRequest event to the frontend");
-
-        // Update the state of the peer to include the connection info
-        {
-            let mut peers = self.active_peers.lock().await;
-            if let Some(peer) = peers.get_mut(&peer_addr) {
-                peer.state = PeerState::Connected {
-                    peer_info: Some(connection_info.into()),
-                };
-            }
+        if connection_info.protocol_version != PROTOCOL_VERSION {
+            self.drop_incompatible_peer(
+                peer_addr,
+                format!(
+                    "it speaks protocol version {}, ours is {}",
+                    connection_info.protocol_version, PROTOCOL_VERSION
+                ),
+            )
+            .await;
+            return;
         }
 
-        // This is the most we can do for now. The frontend will respond with a `ConnectResponse` message, and
-        // the specific handler will continue the process.
-        // Let's just begin the keep-alive ping-pong to keep the connection alive.
-        {
-            let mut peers = self.active_peers.lock().await;
-            if let Some(peer) = peers.get_mut(&peer_addr) {
-                peer.tx
-                    .send(Message::KeepAlive)
-                    .await
-                    .expect("Failed to send KeepAlive message to the peer");
-            }
+        if let Err(e) = self.challenge_peer(peer_addr, connection_info).await {
+            warn!(
+                "Peer {} sent an invalid ConnectRequest: {}. Disconnecting.",
+                peer_addr, e
+            );
+            self.drop_peer(peer_addr, e.into()).await;
         }
     }
 }
@@ -2,6 +2,7 @@ use std::net::SocketAddr;
 
 use crate::{
     backend::{
+        ecdsa_identity::encode_identity,
         peer_manager::{PeerManager, PeerState},
         protocol::{ConnectionPermit, ConnectionResponse, Message},
     },
@@ -18,17 +19,28 @@ impl PeerManager {
         // If accepted, change state to `Authenticated` and send a `ConnectResponse` message
         // If rejected, reply with a `DisconnectAck` message and close the connection
 
+        let mut authenticated = false;
+        // The peer is dropped once the lock is released, with this reason
+        let mut dropped: Option<Option<String>> = None;
         let mut peers = self.active_peers.lock().await;
         if let Some(peer) = peers.get_mut(&peer_addr) {
             match connect_response.permit {
                 ConnectionPermit::Permit { identitiy } => {
                     // Connection accepted, change state to `Authenticated` and notify frontend
+                    // The peer must be the one that proved its identity in the challenge
 
-                    if let PeerState::Connected { .. } = &mut peer.state {
+                    let identity = encode_identity(&identitiy.identity.public_key);
+                    if let PeerState::Connected {
+                        peer_info: Some(peer_info),
+                    } = &peer.state
+                        && peer.handshake.is_none()
+                        && peer_info.identity == identity
+                    {
                         // Update the peer state to `Authenticated`
                         peer.state = PeerState::Authenticated {
                             peer_info: identitiy.into(),
                         };
+                        authenticated = true;
 
                         // Send an event to the frontend to notify the user that the connection was accepted.
                         self.backend_event_tx
@@ -45,11 +57,7 @@ impl PeerManager {
                             );
                     } else {
                         // Unexpected state. Disconnect the peer
-                        self.drop_peer(
-                            peer_addr,
-                            "Unexpected state. Disconnecting peer.".to_string().into(),
-                        )
-                        .await;
+                        dropped = Some("Unexpected state. Disconnecting peer.".to_string().into());
                     }
                 }
                 ConnectionPermit::Deny => {
@@ -74,9 +82,24 @@ impl PeerManager {
                         .expect("Failed to send DisconnectAck message to peer");
 
                     // Close the connection
-                    self.drop_peer(peer_addr, None).await;
+                    dropped = Some(None);
                 }
             }
         }
+        drop(peers);
+
+        if let Some(reason) = dropped {
+            self.drop_peer(peer_addr, reason).await;
+            return;
+        }
+
+        // Remember the peer, send the rooms it is in, our sender keys for them (sealed with the
+        // session derived in the handshake), then the chat messages queued while it was offline.
+        if authenticated {
+            self.remember_peer(peer_addr).await;
+            self.sync_rooms(peer_addr).await;
+            self.send_sender_keys(peer_addr).await;
+            self.flush_chat_outbox(peer_addr).await;
+        }
     }
 }
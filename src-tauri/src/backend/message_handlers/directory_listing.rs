@@ -0,0 +1,63 @@
+use std::net::SocketAddr;
+
+use tracing::warn;
+
+use crate::{
+    backend::{peer_manager::PeerManager, protocol},
+    js_api::backend_event::{BackendEvent, DirectoryEntry, DirectoryListing},
+};
+
+impl PeerManager {
+    /// # Message Handler: `DirectoryListing`
+    ///
+    /// The peer replied to our `ListDirectory`. Pass the listing on to the frontend.
+    pub async fn handle_directory_listing(
+        &self,
+        directory_listing: protocol::DirectoryListing,
+        peer_addr: SocketAddr,
+    ) {
+        let Some((_, peer_info)) = self.authenticated_peer(peer_addr, "DirectoryListing").await
+        else {
+            return;
+        };
+
+        let request = {
+            let mut directory_requests = self.directory_requests.lock().await;
+            if directory_requests
+                .get(&directory_listing.request_id)
+                .is_some_and(|request| request.peer_addr == peer_addr)
+            {
+                directory_requests.remove(&directory_listing.request_id)
+            } else {
+                None
+            }
+        };
+        let Some(request) = request else {
+            warn!(
+                "Peer {} sent DirectoryListing for an unknown request {}. Ignoring.",
+                peer_addr, directory_listing.request_id
+            );
+            return;
+        };
+
+        self.backend_event_tx
+            .send(BackendEvent::DirectoryListing(DirectoryListing {
+                peer: peer_info.into_connection_info(peer_addr),
+                path: request.path,
+                entries: directory_listing
+                    .entries
+                    .into_iter()
+                    .map(|entry| DirectoryEntry {
+                        name: entry.name,
+                        size: entry.size,
+                        modified: entry.modified,
+                        is_dir: entry.is_dir,
+                    })
+                    .collect(),
+                truncated: directory_listing.truncated,
+                error: directory_listing.error,
+            }))
+            .await
+            .expect("Failed to send DirectoryListing event to the frontend");
+    }
+}
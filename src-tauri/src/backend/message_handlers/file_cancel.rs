@@ -0,0 +1,52 @@
+use std::net::SocketAddr;
+
+use tracing::warn;
+
+use crate::backend::{peer_manager::PeerManager, protocol::FileCancel};
+
+impl PeerManager {
+    /// # Message Handler: `FileCancel`
+    ///
+    /// The peer cancels a file transfer with us.
+    pub async fn handle_file_cancel(&self, file_cancel: FileCancel, peer_addr: SocketAddr) {
+        if self
+            .authenticated_peer_tx(peer_addr, "FileCancel")
+            .await
+            .is_none()
+        {
+            return;
+        }
+
+        // Only the peer of the transfer may cancel it.
+        // A source of a multi-source download only leaves it.
+        let (ours, swarm) = self
+            .active_transfers
+            .lock()
+            .await
+            .get(&file_cancel.unique_id)
+            .map_or((false, false), |transfer| {
+                (transfer.has_peer(peer_addr), transfer.swarm.is_some())
+            });
+        if !ours {
+            warn!(
+                "Peer {} sent FileCancel for an unknown file transfer {}. Ignoring.",
+                peer_addr, file_cancel.unique_id
+            );
+            return;
+        }
+
+        if swarm {
+            let reason = file_cancel
+                .message
+                .unwrap_or("Cancelled by the source".to_string());
+            self.remove_swarm_source(file_cancel.unique_id, peer_addr, &reason)
+                .await;
+            return;
+        }
+
+        // The transfer may have just ended on our side, that is fine
+        self.cancel_file_transfer(file_cancel.unique_id, file_cancel.message, true)
+            .await
+            .ok();
+    }
+}
@@ -0,0 +1,243 @@
+use std::net::SocketAddr;
+
+use tracing::warn;
+
+use crate::{
+    backend::{
+        bandwidth::BandwidthDirection,
+        compression, merkle,
+        peer_manager::{FileTransferDirection, PeerManager},
+        protocol::{FileChunk, FileChunkAck, Message},
+    },
+    js_api::backend_event::{BackendEvent, FileTransferProgress},
+};
+
+impl PeerManager {
+    /// # Message Handler: `FileChunk`
+    ///
+    /// Write a chunk of an incoming file, and acknowledge it.
+    pub async fn handle_file_chunk(&self, file_chunk: FileChunk, peer_addr: SocketAddr) {
+        // We got a file chunk from a peer.
+        // Check if the peer is authenticated, and we accepted the file transfer
+        // Write the chunk to the file(s), then send a `FileChunkAck` message
+        // Chunks that do not match the offered Merkle root are nacked, and not written
+        // Retransmitted chunks are written and acknowledged again, but not counted twice
+        // Verify every file the chunk completed
+        // For a multi-source download, request more segments, or finish it once every byte is received
+
+        let Some(peer_tx) = self.authenticated_peer_tx(peer_addr, "FileChunk").await else {
+            return;
+        };
+
+        let (files, offset, root_hash, total_size, compression, chunk_len) = {
+            let active_transfers = self.active_transfers.lock().await;
+            let Some(transfer) = active_transfers
+                .get(&file_chunk.unique_id)
+                .filter(|transfer| transfer.has_peer(peer_addr))
+            else {
+                warn!(
+                    "Peer {} sent a chunk for an unknown file transfer {}. Ignoring.",
+                    peer_addr, file_chunk.unique_id
+                );
+                return;
+            };
+
+            // Chunks that were in flight when the transfer was paused are still accepted
+            let files = match (&transfer.direction, transfer.status.files()) {
+                (FileTransferDirection::Receiving, Some(files)) => files.clone(),
+                _ => {
+                    warn!(
+                        "Peer {} sent a chunk for file transfer {} which is not receiving. Ignoring.",
+                        peer_addr, file_chunk.unique_id
+                    );
+                    return;
+                }
+            };
+
+            // Make sure the chunk fits within the offered files, and the negotiated chunk length
+            let len = file_chunk.data.len() as u64;
+            if len > transfer.chunk_len
+                || file_chunk.offset.saturating_add(len) > transfer.total_size
+            {
+                drop(active_transfers);
+                self.fail_file_transfer(
+                    file_chunk.unique_id,
+                    format!(
+                        "Peer sent chunk {} which does not fit within the offered files",
+                        file_chunk.chunk_id
+                    ),
+                )
+                .await;
+                return;
+            }
+
+            let Some(root_hash) = transfer.root_hash else {
+                drop(active_transfers);
+                self.fail_file_transfer(
+                    file_chunk.unique_id,
+                    "The offer has no root hash to verify chunks against".to_string(),
+                )
+                .await;
+                return;
+            };
+
+            (
+                files,
+                file_chunk.offset,
+                root_hash,
+                transfer.total_size,
+                transfer.compression,
+                transfer.chunk_len,
+            )
+        };
+
+        // Decompress the chunk, then verify it against the root hash of the offer
+        let wire_len = file_chunk.data.len() as u64;
+        let data = match (file_chunk.compressed, compression) {
+            (false, _) => Ok(file_chunk.data),
+            (true, Some(compression)) => {
+                let data = file_chunk.data;
+                tokio::task::spawn_blocking(move || {
+                    compression::decompress(compression, &data, chunk_len)
+                })
+                .await
+                .expect("Chunk decompression panicked")
+            }
+            (true, None) => Err("The offer announced no compression".to_string()),
+        };
+        let data = match data {
+            Ok(data)
+                if merkle::verify_chunk(
+                    &root_hash,
+                    total_size,
+                    offset,
+                    &data,
+                    &file_chunk.proof,
+                ) =>
+            {
+                data
+            }
+            result => {
+                warn!(
+                    "Chunk {} of file transfer {} from peer {} failed verification{}. Asking for it again.",
+                    file_chunk.chunk_id,
+                    file_chunk.unique_id,
+                    peer_addr,
+                    result.err().map_or(String::new(), |e| format!(" ({})", e))
+                );
+                peer_tx
+                    .send(Message::FileChunkNack(FileChunkAck {
+                        unique_id: file_chunk.unique_id,
+                        chunk_id: file_chunk.chunk_id,
+                    }))
+                    .await
+                    .ok(); // We ignore the error here, as the peer may have already disconnected.
+                return;
+            }
+        };
+
+        // Take the chunk from the download bandwidth limits. The ack (and so the next chunks
+        // from the peer) is held back until we are allowed more, without holding back this loop.
+        let ack_delay = self
+            .bandwidth
+            .reserve(peer_addr, BandwidthDirection::Download, wire_len)
+            .await;
+
+        // Write the chunk to the file(s)
+        if let Err(e) = files.write_at(offset, &data).await {
+            self.fail_file_transfer(
+                file_chunk.unique_id,
+                format!("Failed to write chunk {}: {}", file_chunk.chunk_id, e),
+            )
+            .await;
+            return;
+        }
+
+        // Update the progress (unless this is a retransmitted chunk)
+        let end = offset + data.len() as u64;
+        let (progress, completed_files, swarm_complete) = {
+            let mut active_transfers = self.active_transfers.lock().await;
+            let Some(transfer) = active_transfers.get_mut(&file_chunk.unique_id) else {
+                return;
+            };
+            let newly_received = transfer.received_ranges.insert(offset, end);
+            transfer.wire_bytes += wire_len;
+            let complete = transfer.received_ranges.is_complete(transfer.total_size);
+
+            // Only the first chunk completing a multi-source download finishes it
+            let swarm_complete = match transfer.swarm.as_mut() {
+                Some(swarm) => {
+                    swarm.record(peer_addr, end - offset);
+                    let finish = complete && !swarm.finishing;
+                    swarm.finishing |= complete;
+                    Some(finish)
+                }
+                None => None,
+            };
+
+            // Files that are now fully received, but not verified yet
+            let completed_files: Vec<usize> = transfer
+                .manifest
+                .files_in_range(offset, end)
+                .filter(|index| {
+                    let file = &transfer.manifest.files[*index];
+                    !file.verified
+                        && transfer
+                            .received_ranges
+                            .contains(file.offset, file.offset + file.size)
+                })
+                .collect();
+
+            let progress = if newly_received > 0 {
+                transfer.bytes_transferred += newly_received;
+                transfer.rate.record(newly_received);
+                Some(FileTransferProgress {
+                    unique_id: transfer.unique_id.to_string(),
+                    bytes_transferred: transfer.bytes_transferred,
+                    total_bytes: transfer.total_size,
+                    wire_bytes: transfer.wire_bytes,
+                    rate: transfer.rate.rate(),
+                    sending: (&transfer.direction).into(),
+                })
+            } else {
+                None
+            };
+            (progress, completed_files, swarm_complete)
+        };
+
+        // Acknowledge the chunk, once the download limits allow it
+        let ack = Message::FileChunkAck(FileChunkAck {
+            unique_id: file_chunk.unique_id,
+            chunk_id: file_chunk.chunk_id,
+        });
+        if ack_delay.is_zero() {
+            peer_tx.send(ack).await.ok(); // We ignore the error here, as the peer may have already disconnected.
+        } else {
+            tokio::spawn(async move {
+                tokio::time::sleep(ack_delay).await;
+                peer_tx.send(ack).await.ok(); // We ignore the error here, as the peer may have already disconnected.
+            });
+        }
+
+        if let Some(progress) = progress {
+            self.backend_event_tx
+                .send(BackendEvent::FileTransferProgress(progress))
+                .await
+                .expect("Failed to send FileTransferProgress event to the frontend");
+        }
+
+        if let Err(message) = self
+            .verify_received_files(file_chunk.unique_id, &files, completed_files)
+            .await
+        {
+            self.fail_file_transfer(file_chunk.unique_id, message).await;
+            return;
+        }
+
+        match swarm_complete {
+            Some(true) => self.finish_swarm_download(file_chunk.unique_id).await,
+            Some(false) => self.request_swarm_ranges(file_chunk.unique_id).await,
+            None => {}
+        }
+    }
+}
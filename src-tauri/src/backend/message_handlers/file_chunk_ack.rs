@@ -0,0 +1,35 @@
+use std::net::SocketAddr;
+
+use crate::backend::{flow_control::ChunkReply, peer_manager::PeerManager, protocol::FileChunkAck};
+
+impl PeerManager {
+    /// # Message Handler: `FileChunkAck`
+    ///
+    /// Forward a chunk ack to the sender task of the file transfer, sliding its window forward.
+    pub async fn handle_file_chunk_ack(&self, file_chunk_ack: FileChunkAck, peer_addr: SocketAddr) {
+        if self
+            .authenticated_peer_tx(peer_addr, "FileChunkAck")
+            .await
+            .is_none()
+        {
+            return;
+        }
+
+        // Do not hold the lock while forwarding the ack
+        let ack_tx = self
+            .active_transfers
+            .lock()
+            .await
+            .get(&file_chunk_ack.unique_id)
+            .filter(|transfer| transfer.peer_addr == peer_addr)
+            .and_then(|transfer| transfer.ack_tx.clone());
+
+        // If there is no sender task, the ack is late (or bogus), ignore it.
+        if let Some(ack_tx) = ack_tx {
+            ack_tx
+                .send(ChunkReply::Ack(file_chunk_ack.chunk_id))
+                .await
+                .ok();
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use std::net::SocketAddr;
+
+use crate::backend::{flow_control::ChunkReply, peer_manager::PeerManager, protocol::FileChunkAck};
+
+impl PeerManager {
+    /// # Message Handler: `FileChunkNack`
+    ///
+    /// Forward a chunk nack to the sender task of the file transfer, so it resends the chunk.
+    pub async fn handle_file_chunk_nack(
+        &self,
+        file_chunk_nack: FileChunkAck,
+        peer_addr: SocketAddr,
+    ) {
+        if self
+            .authenticated_peer_tx(peer_addr, "FileChunkNack")
+            .await
+            .is_none()
+        {
+            return;
+        }
+
+        // Do not hold the lock while forwarding the nack
+        let ack_tx = self
+            .active_transfers
+            .lock()
+            .await
+            .get(&file_chunk_nack.unique_id)
+            .filter(|transfer| transfer.peer_addr == peer_addr)
+            .and_then(|transfer| transfer.ack_tx.clone());
+
+        // If there is no sender task, the nack is late (or bogus), ignore it.
+        if let Some(ack_tx) = ack_tx {
+            ack_tx
+                .send(ChunkReply::Nack(file_chunk_nack.chunk_id))
+                .await
+                .ok();
+        }
+    }
+}
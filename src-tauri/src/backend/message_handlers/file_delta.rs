@@ -0,0 +1,126 @@
+use std::net::SocketAddr;
+
+use tracing::{debug, warn};
+
+use crate::{
+    backend::{
+        delta,
+        peer_manager::{FileTransferDirection, PeerManager},
+        protocol::FileDelta,
+    },
+    js_api::backend_event::{BackendEvent, FileTransferProgress},
+};
+
+impl PeerManager {
+    /// # Message Handler: `FileDelta`
+    ///
+    /// Copy ranges of a file from the older version we sent the block signatures of (see [delta]),
+    /// instead of receiving them.
+    ///
+    /// The peer sends them before any chunk. The data copied is verified by the file's checksum once complete.
+    pub async fn handle_file_delta(&self, file_delta: FileDelta, peer_addr: SocketAddr) {
+        if self
+            .authenticated_peer_tx(peer_addr, "FileDelta")
+            .await
+            .is_none()
+        {
+            return;
+        }
+
+        let (files, base, file_offset) = {
+            let active_transfers = self.active_transfers.lock().await;
+            let Some(transfer) = active_transfers
+                .get(&file_delta.unique_id)
+                .filter(|transfer| {
+                    transfer.peer_addr == peer_addr
+                        && transfer.direction == FileTransferDirection::Receiving
+                })
+            else {
+                warn!(
+                    "Peer {} sent FileDelta for an unknown file transfer {}. Ignoring.",
+                    peer_addr, file_delta.unique_id
+                );
+                return;
+            };
+
+            let index = file_delta.file_index as usize;
+            let (Some(files), Some(base), Some(file)) = (
+                transfer.status.files(),
+                transfer.delta_bases.get(&index),
+                transfer.manifest.files.get(index),
+            ) else {
+                warn!(
+                    "Peer {} sent FileDelta for file {} of file transfer {}, which we sent no signatures of. Ignoring.",
+                    peer_addr, file_delta.file_index, file_delta.unique_id
+                );
+                return;
+            };
+
+            // Make sure every copy fits within the file
+            if file_delta
+                .copies
+                .iter()
+                .any(|copy| copy.len == 0 || copy.offset.saturating_add(copy.len) > file.size)
+            {
+                drop(active_transfers);
+                self.fail_file_transfer(
+                    file_delta.unique_id,
+                    format!(
+                        "Peer sent copies for file {} which do not fit within the file",
+                        file_delta.file_index
+                    ),
+                )
+                .await;
+                return;
+            }
+
+            (files.clone(), base.clone(), file.offset)
+        };
+
+        // Copy from the older version, without holding the lock
+        if let Err(e) = delta::apply_copies(&files, &base, file_offset, &file_delta.copies).await {
+            self.fail_file_transfer(
+                file_delta.unique_id,
+                format!("Failed to copy from {}: {}", base.display(), e),
+            )
+            .await;
+            return;
+        }
+
+        // Count the ranges copied as received
+        let progress = {
+            let mut active_transfers = self.active_transfers.lock().await;
+            let Some(transfer) = active_transfers.get_mut(&file_delta.unique_id) else {
+                return;
+            };
+            let mut newly_received = 0;
+            for copy in &file_delta.copies {
+                newly_received += transfer.received_ranges.insert(
+                    file_offset + copy.offset,
+                    file_offset + copy.offset + copy.len,
+                );
+            }
+            transfer.bytes_transferred += newly_received;
+            debug!(
+                "Copied {} bytes of file {} of file transfer {} from {}",
+                newly_received,
+                file_delta.file_index,
+                file_delta.unique_id,
+                base.display()
+            );
+            FileTransferProgress {
+                unique_id: transfer.unique_id.to_string(),
+                bytes_transferred: transfer.bytes_transferred,
+                total_bytes: transfer.total_size,
+                wire_bytes: transfer.wire_bytes,
+                rate: transfer.rate.rate(),
+                sending: (&transfer.direction).into(),
+            }
+        };
+
+        self.backend_event_tx
+            .send(BackendEvent::FileTransferProgress(progress))
+            .await
+            .expect("Failed to send FileTransferProgress event to the frontend");
+    }
+}
@@ -0,0 +1,245 @@
+use std::net::SocketAddr;
+
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    backend::{
+        manifest::FileSet,
+        peer_manager::{FileTransferDirection, FileTransferStatus, PeerManager},
+        protocol::{FileDone, FileDoneResult, Message},
+    },
+    js_api::backend_event::{BackendEvent, FileTransferComplete, FileTransferFileComplete},
+};
+
+impl PeerManager {
+    /// # Message Handler: `FileDone`
+    ///
+    /// The peer has sent every chunk. Verify the files, and reply with a `FileDoneResult`.
+    pub async fn handle_file_done(&self, file_done: FileDone, peer_addr: SocketAddr) {
+        let Some(peer_tx) = self.authenticated_peer_tx(peer_addr, "FileDone").await else {
+            return;
+        };
+
+        let (files, complete, resending, bytes_transferred, total_size, unverified) = {
+            let active_transfers = self.active_transfers.lock().await;
+            let Some(transfer) = active_transfers
+                .get(&file_done.unique_id)
+                .filter(|transfer| transfer.peer_addr == peer_addr)
+            else {
+                warn!(
+                    "Peer {} sent FileDone for an unknown file transfer {}. Ignoring.",
+                    peer_addr, file_done.unique_id
+                );
+                return;
+            };
+
+            // A transfer paused at the very end may still receive `FileDone`
+            match (&transfer.direction, transfer.status.files()) {
+                (FileTransferDirection::Receiving, Some(files)) => (
+                    files.clone(),
+                    transfer.received_ranges.is_complete(transfer.total_size),
+                    // Only files asked for again are missing: they may still be on their way
+                    // (see `PeerManager::receive_without_delta`), and `FileDone` follows again
+                    transfer
+                        .received_ranges
+                        .gaps(transfer.total_size)
+                        .iter()
+                        .all(|(start, end)| {
+                            transfer.resent_files.iter().any(|index| {
+                                let file = &transfer.manifest.files[*index];
+                                file.offset <= *start && *end <= file.offset + file.size
+                            })
+                        }),
+                    transfer.bytes_transferred,
+                    transfer.total_size,
+                    // Files are verified as they complete, but empty files never get a chunk
+                    (0..transfer.manifest.files.len())
+                        .filter(|index| !transfer.manifest.files[*index].verified)
+                        .collect::<Vec<_>>(),
+                ),
+                _ => {
+                    warn!(
+                        "Peer {} sent FileDone for file transfer {} which is not receiving. Ignoring.",
+                        peer_addr, file_done.unique_id
+                    );
+                    return;
+                }
+            }
+        };
+
+        if !complete && resending {
+            debug!(
+                "Ignoring FileDone for file transfer {}, files asked for again are still being received",
+                file_done.unique_id
+            );
+            return;
+        }
+
+        // Verify the files: every byte received, and every checksum matches
+        let result = if !complete {
+            Err(format!(
+                "File incomplete: received {} of {} bytes",
+                bytes_transferred, total_size
+            ))
+        } else {
+            match self
+                .verify_received_files(file_done.unique_id, &files, unverified)
+                .await
+            {
+                Ok(()) => {
+                    // A file copied from its older version failed to verify:
+                    // the peer sends it again, then `FileDone` again
+                    if self
+                        .active_transfers
+                        .lock()
+                        .await
+                        .get(&file_done.unique_id)
+                        .is_some_and(|transfer| {
+                            !transfer.received_ranges.is_complete(transfer.total_size)
+                        })
+                    {
+                        return;
+                    }
+                    // Every file is verified, move them into place
+                    files
+                        .commit()
+                        .await
+                        .map_err(|e| format!("Failed to save files: {}", e))
+                }
+                Err(e) => Err(e),
+            }
+        };
+
+        peer_tx
+            .send(Message::FileDoneResult(FileDoneResult {
+                unique_id: file_done.unique_id,
+                success: result.is_ok(),
+                message: result.as_ref().err().cloned(),
+            }))
+            .await
+            .ok(); // We ignore the error here, as the peer may have already disconnected.
+
+        match result {
+            Ok(()) => {
+                info!("File transfer {} completed", file_done.unique_id);
+                self.complete_file_transfer(file_done.unique_id).await;
+            }
+            Err(message) => {
+                self.fail_file_transfer(file_done.unique_id, message).await;
+            }
+        }
+    }
+
+    /// Verify the files of a transfer received in full, then move them into place.
+    pub(crate) async fn commit_received_files(&self, unique_id: Uuid) -> Result<(), String> {
+        let (files, unverified) = {
+            let active_transfers = self.active_transfers.lock().await;
+            let Some((transfer, files)) = active_transfers
+                .get(&unique_id)
+                .and_then(|transfer| Some((transfer, transfer.status.files()?.clone())))
+            else {
+                return Err("File transfer state not found".to_string());
+            };
+            (
+                files,
+                (0..transfer.manifest.files.len())
+                    .filter(|index| !transfer.manifest.files[*index].verified)
+                    .collect::<Vec<_>>(),
+            )
+        };
+
+        self.verify_received_files(unique_id, &files, unverified)
+            .await?;
+        files
+            .commit()
+            .await
+            .map_err(|e| format!("Failed to save files: {}", e))
+    }
+
+    /// Verify received files against the checksums in the manifest, and notify the frontend
+    /// of every file completed. Files already verified are skipped.
+    ///
+    /// A file copied from its older version that fails is received again in full instead
+    /// (see [crate::backend::delta]), and left unverified.
+    pub(crate) async fn verify_received_files(
+        &self,
+        unique_id: Uuid,
+        files: &FileSet,
+        indices: Vec<usize>,
+    ) -> Result<(), String> {
+        for index in indices {
+            let (path, expected) = {
+                let active_transfers = self.active_transfers.lock().await;
+                let Some(file) = active_transfers
+                    .get(&unique_id)
+                    .and_then(|transfer| transfer.manifest.files.get(index))
+                else {
+                    return Err("File transfer state not found".to_string());
+                };
+                if file.verified {
+                    continue;
+                }
+                (file.path.clone(), file.checksum.clone())
+            };
+
+            // Flush the file to disk and hash it, without holding the lock
+            match files.finish_file(index).await {
+                Ok(checksum) if checksum.as_bytes().as_slice() == expected => {}
+                Ok(_) if self.receive_without_delta(unique_id, index).await => {
+                    warn!(
+                        "File {} of transfer {} copied from its older version failed to verify, receiving it in full",
+                        path, unique_id
+                    );
+                    continue;
+                }
+                Ok(_) => return Err(format!("Checksum mismatch: {}", path)),
+                Err(e) => return Err(format!("Failed to verify {}: {}", path, e)),
+            }
+
+            if let Some(file) = self
+                .active_transfers
+                .lock()
+                .await
+                .get_mut(&unique_id)
+                .and_then(|transfer| transfer.manifest.files.get_mut(index))
+            {
+                file.verified = true;
+            }
+
+            debug!("File {} of transfer {} verified", path, unique_id);
+            self.backend_event_tx
+                .send(BackendEvent::FileTransferFileComplete(
+                    FileTransferFileComplete {
+                        unique_id: unique_id.to_string(),
+                        path,
+                    },
+                ))
+                .await
+                .expect("Failed to send FileTransferFileComplete event to the frontend");
+        }
+
+        Ok(())
+    }
+
+    /// Mark the transfer as completed, and notify the frontend.
+    pub(crate) async fn complete_file_transfer(&self, unique_id: Uuid) {
+        if let Some(transfer) = self.active_transfers.lock().await.get_mut(&unique_id) {
+            transfer.status = FileTransferStatus::Completed;
+            transfer.ack_tx = None;
+            transfer.pause_tx = None;
+        }
+        self.release_transfer_slot(unique_id).await;
+        // Before indexing, the modification time is part of the index
+        self.apply_received_metadata(unique_id).await;
+        self.index_received_files(unique_id).await;
+        self.record_transfer_history(unique_id).await;
+
+        self.backend_event_tx
+            .send(BackendEvent::FileTransferComplete(FileTransferComplete {
+                unique_id: unique_id.to_string(),
+            }))
+            .await
+            .expect("Failed to send FileTransferComplete event to the frontend");
+    }
+}
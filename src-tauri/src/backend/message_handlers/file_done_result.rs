@@ -0,0 +1,52 @@
+use std::net::SocketAddr;
+
+use crate::backend::{
+    peer_manager::{FileTransferDirection, PeerManager},
+    protocol::FileDoneResult,
+};
+
+impl PeerManager {
+    /// # Message Handler: `FileDoneResult`
+    ///
+    /// The peer has verified the file we sent. Complete or fail the transfer accordingly.
+    pub async fn handle_file_done_result(
+        &self,
+        file_done_result: FileDoneResult,
+        peer_addr: SocketAddr,
+    ) {
+        if self
+            .authenticated_peer_tx(peer_addr, "FileDoneResult")
+            .await
+            .is_none()
+        {
+            return;
+        }
+
+        // Only the peer we are sending the file to can complete the transfer
+        let sending = self
+            .active_transfers
+            .lock()
+            .await
+            .get(&file_done_result.unique_id)
+            .is_some_and(|transfer| {
+                transfer.peer_addr == peer_addr
+                    && matches!(transfer.direction, FileTransferDirection::Sending)
+            });
+        if !sending {
+            return;
+        }
+
+        if file_done_result.success {
+            self.complete_file_transfer(file_done_result.unique_id)
+                .await;
+        } else {
+            self.fail_file_transfer(
+                file_done_result.unique_id,
+                file_done_result
+                    .message
+                    .unwrap_or("Peer failed to verify the file".to_string()),
+            )
+            .await;
+        }
+    }
+}
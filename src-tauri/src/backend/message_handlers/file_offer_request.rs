@@ -1,13 +1,29 @@
-use std::net::SocketAddr;
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    sync::Arc,
+    time::SystemTime,
+};
+
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+use uuid::Uuid;
 
 use crate::{
     backend::{
-        peer_manager::{
-            FileTransferDirection, FileTransferState, FileTransferStatus, PeerManager, PeerState,
+        bandwidth::RateMeter,
+        byte_ranges::ByteRanges,
+        manifest::Manifest,
+        peer_manager::{FileTransferDirection, FileTransferState, FileTransferStatus, PeerManager},
+        protocol::{self, FileOfferResponse, MAX_CHUNK_LEN, MIN_CHUNK_LEN, Message},
+        swarm::SwarmDownload,
+    },
+    js_api::{
+        backend_event::{
+            BackendEvent, FileOffer, FileOfferAutoHandled, FileOfferEntry, FileOfferRejected,
         },
-        protocol,
+        frontend_event::{AutoAcceptAction, AutoAcceptRule},
     },
-    js_api::backend_event::{BackendEvent, FileOffer},
 };
 
 impl PeerManager {
@@ -17,59 +33,295 @@ impl PeerManager {
         peer_addr: SocketAddr,
     ) {
         // We got a file offer request from a peer.
-        // Check if the peer is connected
-        // If the peer is connected, add the file transfer state to the PeerManager
-        // Send a backend event to the frontend with the file offer request
+        // Check if the peer is authenticated
+        // Reject an offer whose unique ID is already used by another transfer
+        // Check the manifest, then find the auto-accept rule matching the offer, if any
+        // Check the free disk space of the folder the offer would be saved to, and the offer policy.
+        // If any check fails, reject the offer with the reason, and tell the frontend
+        // If the offer comes from a source of a multi-source download that has started, join it
+        // Else, add the file transfer state to the PeerManager
+        // If an auto-accept rule matched, accept or reject the offer, and tell the frontend which rule applied
+        // Else, send a backend event to the frontend with the file offer request
         // If the peer is not connected, ignore the request
 
-        let mut peers = self.active_peers.lock().await;
-
-        if let Some(peer) = peers.get_mut(&peer_addr) {
-            match &peer.state {
-                PeerState::Connected { .. } => {
-                    // Peer is not authenticated yet, but they sent a file offer request?
-                    // Disconnect the peer
-                    self.drop_peer(
-                        peer_addr,
-                        Some("Peer sent a file offer request before authentication".to_string()),
-                    )
-                    .await;
-                }
-                PeerState::Authenticated { peer_info } => {
-                    // Peer is authenticated.
-                    // Send a backend event to the frontend with the file offer request
-                    // Add the file transfer state to the PeerManager
-                    self.backend_event_tx
-                        .send(BackendEvent::FileOffer(FileOffer {
-                            peer: peer_info.into_connection_info(peer_addr),
-                            filename: file_offer.filename.clone(),
-                            unique_id: file_offer.unique_id.to_string(),
-                            size: file_offer.size,
-                        }))
-                        .await
-                        .expect("Failed to send FileOfferRequest event to the frontend");
-
-                    // Store transfer state
-                    self.active_transfers.lock().await.insert(
-                        file_offer.unique_id,
-                        FileTransferState {
-                            unique_id: file_offer.unique_id,
-                            peer_addr,
-                            direction: FileTransferDirection::Receiving,
-                            filename: file_offer.filename,
-                            total_size: file_offer.size,
-                            bytes_transferred: 0,
-                            chunk_len: file_offer.chunk_len,
-                            status: FileTransferStatus::WaitingForPeerResponse,
+        let Some((peer_tx, peer_info)) =
+            self.authenticated_peer(peer_addr, "FileOfferRequest").await
+        else {
+            return;
+        };
+
+        // A source of one of our multi-source downloads
+        let swarm_request = self
+            .take_swarm_request(file_offer.unique_id, peer_addr)
+            .await;
+        if swarm_request.is_some()
+            && self
+                .join_swarm_download(&file_offer, peer_addr, &peer_tx)
+                .await
+        {
+            return;
+        }
+
+        // The unique ID keys the transfer, so it must not replace one in progress
+        if self
+            .active_transfers
+            .lock()
+            .await
+            .contains_key(&file_offer.unique_id)
+        {
+            warn!(
+                "Peer {} offered file transfer {} with a unique ID already in use. Rejecting.",
+                peer_addr, file_offer.unique_id
+            );
+            reject_file_offer(
+                &peer_tx,
+                file_offer.unique_id,
+                "Unique ID already in use".to_string(),
+            )
+            .await;
+            return;
+        }
+
+        // Check the manifest, and reject the offer outright if it is unsafe
+        let mut manifest = match Manifest::from_offer(file_offer.manifest, file_offer.size) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                warn!(
+                    "Peer {} offered file transfer {} with an invalid manifest: {}. Rejecting.",
+                    peer_addr, file_offer.unique_id, e
+                );
+                reject_file_offer(&peer_tx, file_offer.unique_id, e).await;
+                return;
+            }
+        };
+
+        // Accept a file we requested, or else apply the first auto-accept rule matching the offer, if any
+        let requested = match swarm_request {
+            Some(request) => Some(request),
+            None => {
+                let mut sent_file_requests = self.sent_file_requests.lock().await;
+                sent_file_requests
+                    .get(&file_offer.unique_id)
+                    .is_some_and(|request| request.peer_addr == peer_addr && !request.expired())
+                    .then(|| sent_file_requests.remove(&file_offer.unique_id))
+                    .flatten()
+            }
+        };
+        // The first offer of a multi-source download starts it
+        let swarm = requested
+            .as_ref()
+            .filter(|request| request.ranged)
+            .map(|_| {
+                let mut swarm = SwarmDownload::new(file_offer.size);
+                swarm.add_source(peer_addr);
+                swarm
+            });
+        let rule = match requested {
+            Some(request) => Some(AutoAcceptRule {
+                name: if request.ranged {
+                    format!("Multi-source download of {}", request.path)
+                } else {
+                    format!("File request for {}", request.path)
+                },
+                peer_identity: Some(peer_info.identity.clone()),
+                extensions: Vec::new(),
+                max_size: None,
+                pattern: None,
+                action: AutoAcceptAction::Accept { folder: None },
+            }),
+            None => self
+                .auto_accept_rules
+                .lock()
+                .await
+                .find(&peer_info.identity, &manifest)
+                .cloned(),
+        };
+
+        // Check the free disk space where the offer would be saved, and the offer policy
+        let folder = self.offer_folder(rule.as_ref()).await;
+        let space_warning = match self
+            .preflight_file_offer(
+                file_offer.unique_id,
+                &peer_info.identity,
+                &manifest,
+                folder.clone(),
+            )
+            .await
+        {
+            Ok(space_warning) => space_warning,
+            Err(reason) => {
+                warn!(
+                    "Rejecting file transfer {} from peer {}: {}",
+                    file_offer.unique_id, peer_addr, reason
+                );
+                reject_file_offer(&peer_tx, file_offer.unique_id, reason.clone()).await;
+
+                self.backend_event_tx
+                    .send(BackendEvent::FileOfferRejected(FileOfferRejected {
+                        peer: peer_info.into_connection_info(peer_addr),
+                        filename: file_offer.filename,
+                        unique_id: file_offer.unique_id.to_string(),
+                        size: file_offer.size,
+                        reason,
+                    }))
+                    .await
+                    .expect("Failed to send FileOfferRejected event to the frontend");
+                return;
+            }
+        };
+
+        let files: Vec<FileOfferEntry> = manifest
+            .files
+            .iter()
+            .map(|file| FileOfferEntry {
+                path: file.path.clone(),
+                size: file.size,
+            })
+            .collect();
+        let chunk_len = file_offer.max_chunk_len.clamp(MIN_CHUNK_LEN, MAX_CHUNK_LEN);
+
+        let (status, saved_folder) = match rule.as_ref().map(|rule| &rule.action) {
+            Some(AutoAcceptAction::Accept { .. }) => {
+                match self
+                    .create_auto_accepted_files(file_offer.unique_id, &mut manifest, &folder)
+                    .await
+                {
+                    Ok(files) => (
+                        FileTransferStatus::InProgress {
+                            files: Arc::new(files),
                         },
-                    );
-                }
-                PeerState::Disconnecting { .. } => {
-                    // Peer is already disconnecting, but they sent a file offer request?
-                    // Disconnect the peer
-                    self.drop_peer(peer_addr, None).await;
+                        Some(folder.display().to_string()),
+                    ),
+                    Err(reason) => {
+                        warn!(
+                            "Failed to auto-accept file transfer {} from peer {}: {}",
+                            file_offer.unique_id, peer_addr, reason
+                        );
+                        reject_file_offer(&peer_tx, file_offer.unique_id, reason.clone()).await;
+                        self.release_file_offer(file_offer.unique_id).await;
+
+                        self.backend_event_tx
+                            .send(BackendEvent::FileOfferRejected(FileOfferRejected {
+                                peer: peer_info.into_connection_info(peer_addr),
+                                filename: file_offer.filename,
+                                unique_id: file_offer.unique_id.to_string(),
+                                size: file_offer.size,
+                                reason,
+                            }))
+                            .await
+                            .expect("Failed to send FileOfferRejected event to the frontend");
+                        return;
+                    }
                 }
             }
+            Some(AutoAcceptAction::Reject) => (FileTransferStatus::Rejected, None),
+            Some(AutoAcceptAction::Prompt) | None => {
+                (FileTransferStatus::WaitingForPeerResponse, None)
+            }
+        };
+        let accepted = status.files().is_some();
+        let rejected = matches!(status, FileTransferStatus::Rejected);
+        let swarm = swarm.filter(|_| accepted);
+
+        // Store transfer state
+        // (before answering the peer, so its first chunks find it, and an offer rejected by a rule is recorded)
+        self.active_transfers.lock().await.insert(
+            file_offer.unique_id,
+            FileTransferState {
+                unique_id: file_offer.unique_id,
+                peer_addr,
+                peer_name: peer_info.name.clone(),
+                peer_identity: peer_info.identity.clone(),
+                started_at: SystemTime::now(),
+                direction: FileTransferDirection::Receiving,
+                filename: file_offer.filename.clone(),
+                manifest,
+                total_size: file_offer.size,
+                bytes_transferred: 0,
+                rate: RateMeter::default(),
+                chunk_len,
+                status,
+                root_hash: Some(file_offer.root_hash),
+                merkle_tree: None,
+                ranged: false,
+                swarm,
+                ack_tx: None,
+                pause_tx: None,
+                compression: file_offer.compression,
+                wire_bytes: 0,
+                received_ranges: ByteRanges::new(),
+                delta_bases: HashMap::new(),
+                resent_files: HashSet::new(),
+                block_signatures: HashMap::new(),
+            },
+        );
+
+        let Some(rule) = rule.filter(|_| accepted || rejected) else {
+            // Send a backend event to the frontend with the file offer request
+            self.backend_event_tx
+                .send(BackendEvent::FileOffer(FileOffer {
+                    peer: peer_info.into_connection_info(peer_addr),
+                    filename: file_offer.filename,
+                    unique_id: file_offer.unique_id.to_string(),
+                    size: file_offer.size,
+                    files,
+                    space_warning,
+                }))
+                .await
+                .expect("Failed to send FileOfferRequest event to the frontend");
+            return;
+        };
+
+        // Handled by the rule: answer the peer, and tell the frontend which rule applied
+        info!(
+            "File transfer {} from peer {} {} by auto-accept rule {}",
+            file_offer.unique_id,
+            peer_addr,
+            if accepted { "accepted" } else { "rejected" },
+            rule.name
+        );
+        if accepted {
+            // Answered in the background, once the files we already have are placed
+            self.confirm_file_offer(file_offer.unique_id).await;
+            self.spawn_offer_acceptance(file_offer.unique_id, peer_tx, chunk_len);
+        } else {
+            reject_file_offer(
+                &peer_tx,
+                file_offer.unique_id,
+                "Rejected automatically".to_string(),
+            )
+            .await;
+            self.release_file_offer(file_offer.unique_id).await;
+            self.record_transfer_history(file_offer.unique_id).await;
         }
+
+        self.backend_event_tx
+            .send(BackendEvent::FileOfferAutoHandled(FileOfferAutoHandled {
+                peer: peer_info.into_connection_info(peer_addr),
+                filename: file_offer.filename,
+                unique_id: file_offer.unique_id.to_string(),
+                size: file_offer.size,
+                files,
+                accepted,
+                rule: rule.name,
+                folder: saved_folder,
+                space_warning,
+            }))
+            .await
+            .expect("Failed to send FileOfferAutoHandled event to the frontend");
     }
 }
+
+/// Reject a file offer without asking the frontend, telling the peer why.
+async fn reject_file_offer(peer_tx: &mpsc::Sender<Message>, unique_id: Uuid, reason: String) {
+    peer_tx
+        .send(Message::FileOfferResponse(FileOfferResponse {
+            unique_id,
+            accept: false,
+            max_chunk_len: MIN_CHUNK_LEN,
+            reason: Some(reason),
+            have_files: Vec::new(),
+        }))
+        .await
+        .ok(); // We ignore the error here, as the peer may have already disconnected.
+}
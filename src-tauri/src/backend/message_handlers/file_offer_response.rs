@@ -1,8 +1,16 @@
 use std::{net::SocketAddr, sync::Arc};
 
-use crate::backend::{
-    peer_manager::{FileTransferDirection, FileTransferStatus, PeerManager, PeerState},
-    protocol::FileOfferResponse,
+use tokio::sync::{mpsc, watch};
+use tracing::info;
+
+use crate::{
+    backend::{
+        byte_ranges::ByteRanges,
+        manifest::FileSet,
+        peer_manager::{FileTransferDirection, FileTransferStatus, PeerManager},
+        protocol::{FileOfferResponse, MIN_CHUNK_LEN},
+    },
+    js_api::backend_event::{BackendEvent, FileTransferRejected},
 };
 
 impl PeerManager {
@@ -12,80 +20,144 @@ impl PeerManager {
         peer_addr: SocketAddr,
     ) {
         // We got a file offer response from a peer.
-        // Check if the peer is connected
-        // If the peer is connected, update the file transfer state in the PeerManager
+        // Check if the peer is authenticated
+        // If the peer is authenticated, update the file transfer state in the PeerManager
+        // If accepted, start sending the file chunks
         // If the peer is not connected, ignore the response
 
-        let mut peers = self.active_peers.lock().await;
+        let Some(peer_tx) = self
+            .authenticated_peer_tx(peer_addr, "FileOfferResponse")
+            .await
+        else {
+            return;
+        };
 
-        if let Some(peer) = peers.get_mut(&peer_addr) {
-            match &peer.state {
-                PeerState::Connected { .. } => {
-                    // Peer is not authenticated yet, but they sent a file offer response?
-                    // Disconnect the peer
-                    self.drop_peer(
-                        peer_addr,
-                        Some("Peer sent a file offer response before authentication".to_string()),
-                    )
-                    .await;
-                }
-                PeerState::Authenticated { .. } => {
-                    // Peer is authenticated.
-                    // Update the file transfer state in the PeerManager
-                    if let Some(transfer_state) = self
-                        .active_transfers
-                        .lock()
-                        .await
-                        .get_mut(&file_offer_response.unique_id)
-                    {
-                        if let FileTransferDirection::Sending { file_path } =
-                            &transfer_state.direction
-                        {
-                            // We are the one sending the file.
-                            // Was the request accepted?
-                            if file_offer_response.accept {
-                                // Open the file for reading
-                                let file_handle = Arc::new(
-                                    tokio::fs::File::open(file_path).await.unwrap_or_else(|e| {
-                                        // Failed to open the file, update the transfer state to "Error"
-                                        transfer_state.status = FileTransferStatus::Error(format!(
-                                            "Failed to open file: {}",
-                                            e
-                                        ));
-                                        // TODO: Handle the error properly
-                                        panic!("Failed to open file: {}", e);
-                                    }),
-                                );
+        // Peer is authenticated.
+        // Update the file transfer state in the PeerManager
+        let mut active_transfers = self.active_transfers.lock().await;
+        let Some(transfer_state) = active_transfers
+            .get_mut(&file_offer_response.unique_id)
+            .filter(|transfer_state| transfer_state.peer_addr == peer_addr)
+        else {
+            return;
+        };
 
-                                // Update the transfer state to "InProgress"
-                                transfer_state.status = FileTransferStatus::InProgress {
-                                    file_handle: file_handle.clone(),
-                                };
-                            } else {
-                                // Update the transfer state to "Rejected"
-                                transfer_state.status = FileTransferStatus::Rejected;
-                            }
-                        } else {
-                            // We cannot "accept" a file response if we are the one receiving the file.
-                            self.drop_peer(
-                                peer_addr,
-                                Some("Cannot accept file response while receiving".to_string()),
-                            )
-                            .await;
+        match &transfer_state.direction {
+            FileTransferDirection::Sending => {}
+            FileTransferDirection::Receiving => {
+                // We cannot "accept" a file response if we are the one receiving the file.
+                // Update the transfer state to "Error"
+                transfer_state.status = FileTransferStatus::Error(
+                    "Cannot accept file response while receiving".to_string(),
+                );
+                drop(active_transfers);
 
-                            // Update the transfer state to "Error"
-                            transfer_state.status = FileTransferStatus::Error(
-                                "Cannot accept file response while receiving".to_string(),
-                            );
-                        }
-                    }
-                }
-                PeerState::Disconnecting { .. } => {
-                    // Peer is already disconnecting, but they sent a file offer response?
-                    // Disconnect the peer
-                    self.drop_peer(peer_addr, None).await;
-                }
+                self.drop_peer(
+                    peer_addr,
+                    Some("Cannot accept file response while receiving".to_string()),
+                )
+                .await;
+                return;
             }
+        };
+
+        // We are the one sending the file.
+        // Was the request accepted?
+        if !file_offer_response.accept {
+            // Update the transfer state to "Rejected"
+            transfer_state.status = FileTransferStatus::Rejected;
+            drop(active_transfers);
+            self.release_transfer_slot(file_offer_response.unique_id)
+                .await;
+            self.record_transfer_history(file_offer_response.unique_id)
+                .await;
+
+            // Notify the frontend
+            info!(
+                "File transfer {} was rejected by the peer{}",
+                file_offer_response.unique_id,
+                file_offer_response
+                    .reason
+                    .as_ref()
+                    .map_or(String::new(), |reason| format!(": {}", reason))
+            );
+            self.backend_event_tx
+                .send(BackendEvent::FileTransferRejected(FileTransferRejected {
+                    unique_id: file_offer_response.unique_id.to_string(),
+                    reason: file_offer_response.reason,
+                }))
+                .await
+                .expect("Failed to send FileTransferRejected event to the frontend");
+            return;
         }
+
+        // The receiver may lower the maximum chunk length, but not raise it
+        if file_offer_response.max_chunk_len < MIN_CHUNK_LEN
+            || file_offer_response.max_chunk_len > transfer_state.chunk_len
+        {
+            drop(active_transfers);
+            self.fail_file_transfer(
+                file_offer_response.unique_id,
+                format!(
+                    "Peer agreed to an invalid maximum chunk length: {} bytes",
+                    file_offer_response.max_chunk_len
+                ),
+            )
+            .await;
+            return;
+        }
+        transfer_state.chunk_len = file_offer_response.max_chunk_len;
+
+        // Skip the files the peer already has
+        let mut skip = ByteRanges::new();
+        for index in &file_offer_response.have_files {
+            let Some(file) = transfer_state.manifest.files.get(*index as usize) else {
+                drop(active_transfers);
+                self.fail_file_transfer(
+                    file_offer_response.unique_id,
+                    format!("Peer claims to have file {}, which was not offered", index),
+                )
+                .await;
+                return;
+            };
+            skip.insert(file.offset, file.offset + file.size);
+        }
+        // The peer had every file: there is nothing to send
+        if !file_offer_response.have_files.is_empty() && skip.is_complete(transfer_state.total_size)
+        {
+            drop(active_transfers);
+            info!(
+                "Peer {} already has every file of file transfer {}",
+                peer_addr, file_offer_response.unique_id
+            );
+            self.complete_file_transfer(file_offer_response.unique_id)
+                .await;
+            return;
+        }
+
+        // Open the files for reading (each is opened when first read)
+        let files = Arc::new(FileSet::open(&transfer_state.manifest));
+
+        // Update the transfer state to "InProgress"
+        let (ack_tx, ack_rx) = mpsc::channel(self.flow_control.max_window);
+        let (pause_tx, pause_rx) = watch::channel(false);
+        transfer_state.status = FileTransferStatus::InProgress { files };
+        transfer_state.ack_tx = Some(ack_tx);
+        transfer_state.pause_tx = Some(pause_tx);
+        drop(active_transfers);
+
+        // Send the copy instructions of the files the peer has an older version of,
+        // then the file chunks
+        let manager = self.clone();
+        let unique_id = file_offer_response.unique_id;
+        tokio::spawn(async move {
+            let mut skip = skip;
+            manager
+                .send_file_deltas(unique_id, &peer_tx, &mut skip)
+                .await;
+            manager
+                .send_file_chunks(unique_id, peer_tx, ack_rx, pause_rx, skip)
+                .await;
+        });
     }
 }
@@ -0,0 +1,46 @@
+use std::net::SocketAddr;
+
+use tracing::warn;
+
+use crate::backend::{peer_manager::PeerManager, protocol::FilePause};
+
+impl PeerManager {
+    /// # Message Handler: `FilePause`
+    ///
+    /// The peer pauses a file transfer with us.
+    pub async fn handle_file_pause(&self, file_pause: FilePause, peer_addr: SocketAddr) {
+        if self
+            .authenticated_peer_tx(peer_addr, "FilePause")
+            .await
+            .is_none()
+        {
+            return;
+        }
+
+        // Only the peer of the transfer may pause it.
+        // The sources of a multi-source download may not: a paused source is overtaken by the others.
+        let ours = self
+            .active_transfers
+            .lock()
+            .await
+            .get(&file_pause.unique_id)
+            .is_some_and(|transfer| transfer.peer_addr == peer_addr && transfer.swarm.is_none());
+        if !ours {
+            warn!(
+                "Peer {} sent FilePause for an unknown file transfer {}. Ignoring.",
+                peer_addr, file_pause.unique_id
+            );
+            return;
+        }
+
+        if let Err(e) = self
+            .set_file_transfer_paused(file_pause.unique_id, true, true)
+            .await
+        {
+            warn!(
+                "Peer {} sent FilePause for file transfer {}, but it cannot be paused: {}. Ignoring.",
+                peer_addr, file_pause.unique_id, e
+            );
+        }
+    }
+}
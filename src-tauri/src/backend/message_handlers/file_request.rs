@@ -0,0 +1,89 @@
+use std::{net::SocketAddr, time::Instant};
+
+use tracing::{info, warn};
+
+use crate::{
+    backend::{peer_manager::PeerManager, protocol::FileRequest, shares::FileRequestState},
+    js_api::backend_event::{BackendEvent, FileRequested},
+};
+
+impl PeerManager {
+    /// # Message Handler: `FileRequest`
+    ///
+    /// The peer asks for a file (or folder) we share.
+    /// Approve it now if the share says so, or else ask the frontend.
+    pub async fn handle_file_request(&self, file_request: FileRequest, peer_addr: SocketAddr) {
+        let Some((_, peer_info)) = self.authenticated_peer(peer_addr, "FileRequest").await else {
+            return;
+        };
+        let request_id = file_request.request_id;
+
+        // The request ID becomes the unique ID of the file transfer, so it must be new
+        if self.active_transfers.lock().await.contains_key(&request_id)
+            || self
+                .received_file_requests
+                .lock()
+                .await
+                .contains_key(&request_id)
+        {
+            warn!(
+                "Peer {} sent FileRequest with a request ID already in use: {}",
+                peer_addr, request_id
+            );
+            self.deny_file_request(
+                request_id,
+                peer_addr,
+                "Request ID already in use".to_string(),
+            )
+            .await;
+            return;
+        }
+
+        // Only paths inside a share the peer may access
+        let (share, local_path) = match self
+            .resolve_shared_path(&peer_info.identity, &file_request.path)
+            .await
+        {
+            Ok(resolved) => resolved,
+            Err(reason) => {
+                self.deny_file_request(request_id, peer_addr, reason).await;
+                return;
+            }
+        };
+
+        info!(
+            "Peer {} requests {} ({})",
+            peer_addr,
+            file_request.path,
+            local_path.display()
+        );
+        let request = FileRequestState {
+            peer_addr,
+            path: file_request.path.clone(),
+            local_path: Some(local_path),
+            ranged: file_request.ranged,
+            created_at: Instant::now(),
+        };
+        if share.auto_approve {
+            if let Err(reason) = self.approve_file_request(request_id, request).await {
+                self.deny_file_request(request_id, peer_addr, reason).await;
+                return;
+            }
+        } else {
+            self.received_file_requests
+                .lock()
+                .await
+                .insert(request_id, request);
+        }
+
+        self.backend_event_tx
+            .send(BackendEvent::FileRequested(FileRequested {
+                peer: peer_info.into_connection_info(peer_addr),
+                request_id: request_id.to_string(),
+                path: file_request.path,
+                auto_approved: share.auto_approve,
+            }))
+            .await
+            .expect("Failed to send FileRequested event to the frontend");
+    }
+}
@@ -0,0 +1,95 @@
+use std::net::SocketAddr;
+
+use tracing::{info, warn};
+
+use crate::{
+    backend::{peer_manager::PeerManager, protocol::FileRequestResponse},
+    js_api::backend_event::{BackendEvent, FileRequestAnswer},
+};
+
+impl PeerManager {
+    /// # Message Handler: `FileRequestResponse`
+    ///
+    /// The peer approved or denied our file request.
+    /// If approved, the request is kept until the peer's file offer arrives.
+    /// A source of a multi-source download that denied our request is forgotten.
+    pub async fn handle_file_request_response(
+        &self,
+        file_request_response: FileRequestResponse,
+        peer_addr: SocketAddr,
+    ) {
+        let Some((_, peer_info)) = self
+            .authenticated_peer(peer_addr, "FileRequestResponse")
+            .await
+        else {
+            return;
+        };
+        let request_id = file_request_response.request_id;
+
+        // A source of one of our multi-source downloads
+        let swarm_request =
+            self.swarm_requests
+                .lock()
+                .await
+                .get(&request_id)
+                .and_then(|requests| {
+                    requests
+                        .iter()
+                        .find(|request| request.peer_addr == peer_addr)
+                        .cloned()
+                });
+        if let Some(request) = &swarm_request
+            && !file_request_response.accept
+        {
+            let reason = file_request_response
+                .reason
+                .clone()
+                .unwrap_or("Request denied".to_string());
+            self.remove_swarm_source(request_id, request.peer_addr, &reason)
+                .await;
+        }
+
+        let request = if let Some(request) = swarm_request {
+            request
+        } else {
+            let mut sent_file_requests = self.sent_file_requests.lock().await;
+            let Some(request) = sent_file_requests
+                .get(&request_id)
+                .filter(|request| request.peer_addr == peer_addr)
+                .cloned()
+            else {
+                warn!(
+                    "Peer {} sent FileRequestResponse for an unknown file request {}. Ignoring.",
+                    peer_addr, request_id
+                );
+                return;
+            };
+            if !file_request_response.accept {
+                sent_file_requests.remove(&request_id);
+            }
+            request
+        };
+
+        info!(
+            "Peer {} {} file request {} for {}",
+            peer_addr,
+            if file_request_response.accept {
+                "approved"
+            } else {
+                "denied"
+            },
+            request_id,
+            request.path
+        );
+        self.backend_event_tx
+            .send(BackendEvent::FileRequestAnswered(FileRequestAnswer {
+                peer: peer_info.into_connection_info(peer_addr),
+                request_id: request_id.to_string(),
+                path: request.path,
+                accept: file_request_response.accept,
+                reason: file_request_response.reason,
+            }))
+            .await
+            .expect("Failed to send FileRequestAnswered event to the frontend");
+    }
+}
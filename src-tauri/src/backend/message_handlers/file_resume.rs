@@ -0,0 +1,46 @@
+use std::net::SocketAddr;
+
+use tracing::warn;
+
+use crate::backend::{peer_manager::PeerManager, protocol::FileResume};
+
+impl PeerManager {
+    /// # Message Handler: `FileResume`
+    ///
+    /// The peer resumes a file transfer with us.
+    pub async fn handle_file_resume(&self, file_resume: FileResume, peer_addr: SocketAddr) {
+        if self
+            .authenticated_peer_tx(peer_addr, "FileResume")
+            .await
+            .is_none()
+        {
+            return;
+        }
+
+        // Only the peer of the transfer may resume it.
+        // The sources of a multi-source download may not: a paused source is overtaken by the others.
+        let ours = self
+            .active_transfers
+            .lock()
+            .await
+            .get(&file_resume.unique_id)
+            .is_some_and(|transfer| transfer.peer_addr == peer_addr && transfer.swarm.is_none());
+        if !ours {
+            warn!(
+                "Peer {} sent FileResume for an unknown file transfer {}. Ignoring.",
+                peer_addr, file_resume.unique_id
+            );
+            return;
+        }
+
+        if let Err(e) = self
+            .set_file_transfer_paused(file_resume.unique_id, false, true)
+            .await
+        {
+            warn!(
+                "Peer {} sent FileResume for file transfer {}, but it cannot be resumed: {}. Ignoring.",
+                peer_addr, file_resume.unique_id, e
+            );
+        }
+    }
+}
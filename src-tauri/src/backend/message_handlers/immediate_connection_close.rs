@@ -13,53 +13,29 @@ impl PeerManager {
     ) {
         // Peer wants to disconnect immediately (no ack required)
 
-        let mut peers = self.active_peers.lock().await;
-        if let Some(peer) = peers.get_mut(&peer_addr) {
-            match &peer.state {
-                PeerState::Connected { peer_info } => {
-                    // Peer wants to disconnect.
-                    // Change state to `Disconnecting`
-                    // Close the connection
-                    peer.state = PeerState::Disconnecting {
-                        reason: disconnect_request.message.clone(),
-                        peer_info: {
-                            if let Some(peer_info) = peer_info {
-                                peer_info.clone()
-                            } else {
-                                // Peer info not set?
-                                self.drop_peer(
-                                    peer_addr,
-                                    "Peer info not set when handling DisconnectRequest"
-                                        .to_string()
-                                        .into(),
-                                )
-                                .await;
-                                return;
-                            }
-                        },
-                    };
-
-                    // Drop the peer
-                    self.drop_peer(peer_addr, None).await;
-                }
-                PeerState::Disconnecting { .. } => {
-                    // Peer is already disconnecting, but they sent another disconnect request?
-                    // Disconnect the peer
-                    self.drop_peer(peer_addr, None).await;
-                }
-                PeerState::Authenticated { peer_info } => {
-                    // Peer wants to disconnect.
-                    // Change state to `Disconnecting`
-                    // Close the connection
-                    peer.state = PeerState::Disconnecting {
-                        reason: disconnect_request.message.clone(),
-                        peer_info: peer_info.clone(),
-                    };
-
-                    // Drop the peer
-                    self.drop_peer(peer_addr, None).await;
-                }
+        // Do not hold the lock while dropping the peer: `drop_peer` takes it again
+        {
+            let mut peers = self.active_peers.lock().await;
+            let Some(peer) = peers.get_mut(&peer_addr) else {
+                return;
+            };
+            let peer_info = match &peer.state {
+                PeerState::Connected { peer_info } => peer_info.clone(),
+                PeerState::Authenticated { peer_info } => Some(peer_info.clone()),
+                // Peer is already disconnecting, but they sent another disconnect request?
+                PeerState::Disconnecting { .. } => None,
+            };
+            if let Some(peer_info) = peer_info {
+                // Peer wants to disconnect.
+                // Change state to `Disconnecting`
+                peer.state = PeerState::Disconnecting {
+                    reason: disconnect_request.message.clone(),
+                    peer_info,
+                };
             }
         }
+
+        // Drop the peer
+        self.drop_peer(peer_addr, None).await;
     }
 }
@@ -0,0 +1,48 @@
+use std::net::SocketAddr;
+
+use tracing::debug;
+
+use crate::backend::{
+    peer_manager::PeerManager,
+    protocol::{DirectoryListing, ListDirectory, Message},
+};
+
+impl PeerManager {
+    /// # Message Handler: `ListDirectory`
+    ///
+    /// The peer browses a directory we share. Reply with a `DirectoryListing`.
+    pub async fn handle_list_directory(
+        &self,
+        list_directory: ListDirectory,
+        peer_addr: SocketAddr,
+    ) {
+        let Some((peer_tx, peer_info)) = self.authenticated_peer(peer_addr, "ListDirectory").await
+        else {
+            return;
+        };
+
+        debug!("Peer {} lists {:?}", peer_addr, list_directory.path);
+        let directory_listing = match self
+            .list_shared_directory(&peer_info.identity, &list_directory.path)
+            .await
+        {
+            Ok((entries, truncated)) => DirectoryListing {
+                request_id: list_directory.request_id,
+                entries,
+                truncated,
+                error: None,
+            },
+            Err(error) => DirectoryListing {
+                request_id: list_directory.request_id,
+                entries: Vec::new(),
+                truncated: false,
+                error: Some(error),
+            },
+        };
+
+        peer_tx
+            .send(Message::DirectoryListing(directory_listing))
+            .await
+            .ok(); // We ignore the error here, as the peer may have already disconnected.
+    }
+}
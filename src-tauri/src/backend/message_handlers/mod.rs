@@ -1,8 +1,34 @@
+pub mod block_signatures;
+pub mod chat_delete;
+pub mod chat_edit;
+pub mod chat_message;
+pub mod chat_message_ack;
+pub mod chat_reaction;
+pub mod connect_challenge;
+pub mod connect_proof;
 pub mod connect_request;
 pub mod connect_response;
+pub mod directory_listing;
 pub mod disconnect_ack;
 pub mod disconnect_request;
+pub mod file_cancel;
+pub mod file_chunk;
+pub mod file_chunk_ack;
+pub mod file_chunk_nack;
+pub mod file_delta;
+pub mod file_done;
+pub mod file_done_result;
 pub mod file_offer_request;
 pub mod file_offer_response;
+pub mod file_pause;
+pub mod file_request;
+pub mod file_request_response;
+pub mod file_resume;
 pub mod immediate_connection_close;
 pub mod keep_alive;
+pub mod list_directory;
+pub mod range_request;
+pub mod read_up_to;
+pub mod room_update;
+pub mod sender_key;
+pub mod typing;
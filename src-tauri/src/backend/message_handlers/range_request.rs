@@ -0,0 +1,73 @@
+use std::net::SocketAddr;
+
+use tracing::warn;
+
+use crate::backend::{
+    flow_control::ChunkReply,
+    merkle::BLOCK_LEN,
+    peer_manager::{FileTransferDirection, PeerManager},
+    protocol::RangeRequest,
+};
+
+impl PeerManager {
+    /// # Message Handler: `RangeRequest`
+    ///
+    /// The peer downloads a file we offered from several sources, and asks us for a byte range of it.
+    /// Or a file copied from the peer's older version failed to verify, and the peer asks for it again.
+    /// Forward the range to the sender task of the file transfer.
+    pub async fn handle_range_request(&self, range_request: RangeRequest, peer_addr: SocketAddr) {
+        if self
+            .authenticated_peer_tx(peer_addr, "RangeRequest")
+            .await
+            .is_none()
+        {
+            return;
+        }
+
+        // Do not hold the lock while forwarding the range
+        let ack_tx = {
+            let active_transfers = self.active_transfers.lock().await;
+            let Some(transfer) =
+                active_transfers
+                    .get(&range_request.unique_id)
+                    .filter(|transfer| {
+                        transfer.peer_addr == peer_addr
+                            && transfer.direction == FileTransferDirection::Sending
+                    })
+            else {
+                warn!(
+                    "Peer {} sent RangeRequest for an unknown file transfer {}. Ignoring.",
+                    peer_addr, range_request.unique_id
+                );
+                return;
+            };
+
+            // Chunks must stay aligned to the Merkle tree's blocks, and within the files.
+            // Only the last range of the files may end mid-block.
+            let end = range_request.offset.saturating_add(range_request.len);
+            if range_request.len == 0
+                || !range_request.offset.is_multiple_of(BLOCK_LEN)
+                || !(range_request.len.is_multiple_of(BLOCK_LEN) || end == transfer.total_size)
+                || end > transfer.total_size
+            {
+                warn!(
+                    "Peer {} requested an invalid range of file transfer {}: {} bytes at offset {}. Ignoring.",
+                    peer_addr, range_request.unique_id, range_request.len, range_request.offset
+                );
+                return;
+            }
+            transfer.ack_tx.clone()
+        };
+
+        // If there is no sender task, the transfer has ended (or is paused before it started)
+        if let Some(ack_tx) = ack_tx {
+            ack_tx
+                .send(ChunkReply::Range {
+                    offset: range_request.offset,
+                    len: range_request.len,
+                })
+                .await
+                .ok();
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use std::net::SocketAddr;
+
+use tracing::debug;
+
+use crate::backend::{chat_signals::SignalKind, peer_manager::PeerManager, protocol::ReadUpTo};
+
+impl PeerManager {
+    /// # Message Handler: `ReadUpTo`
+    ///
+    /// The peer read our conversation, or a room we are in, up to a message. Tell the frontend,
+    /// so it can show which of our messages were seen.
+    pub async fn handle_read_up_to(&self, read_up_to: ReadUpTo, peer_addr: SocketAddr) {
+        let Some((_, peer_info)) = self.authenticated_peer(peer_addr, "ReadUpTo").await else {
+            return;
+        };
+
+        let Some(key) = self
+            .accept_signal(&peer_info, SignalKind::ReadUpTo, read_up_to.conversation)
+            .await
+        else {
+            return;
+        };
+        let message_id = read_up_to.message_id.to_string();
+        if self
+            .chat_store
+            .lock()
+            .await
+            .message(&key, &message_id)
+            .is_none()
+        {
+            debug!(
+                "Peer {} read up to chat message {}, which is not in the conversation. Ignoring.",
+                peer_addr, message_id
+            );
+            return;
+        }
+        self.emit_chat_read_up_to(peer_info.into_connection_info(peer_addr), &key, message_id)
+            .await;
+    }
+}
@@ -0,0 +1,138 @@
+use std::net::SocketAddr;
+
+use tracing::{debug, info, warn};
+
+use crate::backend::{
+    peer_manager::PeerManager,
+    protocol::RoomUpdate,
+    rooms::{MAX_CLOCK_AHEAD, Room, members_not_in, normalize_members},
+};
+
+impl PeerManager {
+    /// # Message Handler: `RoomUpdate`
+    ///
+    /// The peer created a room we are a member of, changed its members, or left it.
+    /// Apply the update if it wins over the last one (see [crate::backend::rooms]), make a new
+    /// sender key for the new members, and tell the frontend.
+    pub async fn handle_room_update(&self, room_update: RoomUpdate, peer_addr: SocketAddr) {
+        let Some((_, peer_info)) = self.authenticated_peer(peer_addr, "RoomUpdate").await else {
+            return;
+        };
+
+        // The other members as we see them: the sender's, and the sender unless it left
+        let identity = self.identity.identity();
+        let mut members = room_update.members;
+        if !room_update.left {
+            members.push(peer_info.identity.clone());
+        }
+        members.retain(|member| *member != identity);
+        let members = normalize_members(members);
+
+        let (room, old_members) = {
+            let mut rooms = self.rooms.lock().await;
+            let old_members = match rooms.get_mut(&room_update.room_id) {
+                // Only we can join a room we left again
+                Some(room) if !room.joined => {
+                    debug!(
+                        "Peer {} sent an update of room {}, which we left. Ignoring.",
+                        peer_addr, room_update.room_id
+                    );
+                    return;
+                }
+                Some(room) => {
+                    if !room.members.contains(&peer_info.identity) {
+                        warn!(
+                            "Peer {} is not a member of room {}. Ignoring its update.",
+                            peer_addr, room_update.room_id
+                        );
+                        return;
+                    }
+                    if let Err(e) = room.observe_clock(room_update.clock) {
+                        warn!(
+                            "Peer {} sent an update of room {}: {}. Ignoring.",
+                            peer_addr, room_update.room_id, e
+                        );
+                        return;
+                    }
+                    // The last writer wins, whatever the order concurrent updates arrive in.
+                    // The author is the authenticated sender, not anything it claims.
+                    if !room.is_newer_membership(room_update.clock, &peer_info.identity) {
+                        debug!(
+                            "Peer {} sent an outdated update of room {}. Ignoring.",
+                            peer_addr, room_update.room_id
+                        );
+                        return;
+                    }
+                    room.name = room_update.name;
+                    room.membership_clock = room_update.clock;
+                    room.membership_author = peer_info.identity.clone();
+                    room.joined = room_update.member;
+                    std::mem::replace(&mut room.members, members)
+                }
+                // An invitation, with a clock we can move past
+                None if room_update.member
+                    && !room_update.left
+                    && room_update.clock <= MAX_CLOCK_AHEAD =>
+                {
+                    info!(
+                        "Peer {} invited us to room {} ({})",
+                        peer_addr, room_update.name, room_update.room_id
+                    );
+                    rooms.insert(
+                        room_update.room_id,
+                        Room {
+                            name: room_update.name,
+                            members,
+                            clock: room_update.clock,
+                            membership_clock: room_update.clock,
+                            membership_author: peer_info.identity.clone(),
+                            joined: true,
+                            sender_keys: Vec::new(),
+                        },
+                    );
+                    Vec::new()
+                }
+                None => {
+                    debug!(
+                        "Peer {} sent an update of unknown room {}. Ignoring.",
+                        peer_addr, room_update.room_id
+                    );
+                    return;
+                }
+            };
+            if let Err(e) = rooms.save().await {
+                warn!("{}", e);
+            }
+            let Some(room) = rooms.get(&room_update.room_id) else {
+                return;
+            };
+            (room.clone(), old_members)
+        };
+
+        let added = members_not_in(&room.members, &old_members);
+        let removed = members_not_in(&old_members, &room.members);
+        // Our sender key changes with the members, so removed members cannot read new messages
+        if room.joined {
+            {
+                let mut received_sender_keys = self.received_sender_keys.lock().await;
+                for member in &removed {
+                    received_sender_keys.forget_member(room_update.room_id, member);
+                }
+            }
+            self.rotate_sender_key(room_update.room_id).await;
+        } else {
+            self.received_sender_keys
+                .lock()
+                .await
+                .forget_room(room_update.room_id);
+        }
+        self.emit_room_updated(
+            room_update.room_id,
+            &room,
+            Some(peer_info.identity),
+            added,
+            removed,
+        )
+        .await;
+    }
+}
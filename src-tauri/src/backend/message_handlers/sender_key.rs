@@ -0,0 +1,67 @@
+use std::net::SocketAddr;
+
+use tracing::{debug, warn};
+
+use crate::backend::{
+    peer_manager::PeerManager, protocol::SealedSenderKey, sender_keys::open_sender_key,
+};
+
+impl PeerManager {
+    /// # Message Handler: `SenderKey`
+    ///
+    /// The peer sent one of its sender keys for a room, to decrypt its messages to the room with.
+    pub async fn handle_sender_key(
+        &self,
+        sealed_sender_key: SealedSenderKey,
+        peer_addr: SocketAddr,
+    ) {
+        let Some((_, peer_info)) = self.authenticated_peer(peer_addr, "SenderKey").await else {
+            return;
+        };
+        let Some(session_key) = self.session_key(peer_addr).await else {
+            warn!(
+                "Peer {} sent a sender key without a session. Ignoring.",
+                peer_addr
+            );
+            return;
+        };
+        let sender_key = match open_sender_key(&session_key, &sealed_sender_key) {
+            Ok(sender_key) => sender_key,
+            Err(e) => {
+                warn!(
+                    "Peer {} sent an invalid sender key: {}. Ignoring.",
+                    peer_addr, e
+                );
+                return;
+            }
+        };
+
+        // The key may arrive before the update adding the peer to the room (sent by another member),
+        // so it is kept unless we left the room. Messages are only decrypted for members, and only
+        // so many keys are kept per peer (see [crate::backend::sender_keys]).
+        if self
+            .rooms
+            .lock()
+            .await
+            .get(&sender_key.room_id)
+            .is_some_and(|room| !room.joined)
+        {
+            debug!(
+                "Peer {} sent a sender key for room {}, which we left. Ignoring.",
+                peer_addr, sender_key.room_id
+            );
+            return;
+        }
+        if let Err(e) = self
+            .received_sender_keys
+            .lock()
+            .await
+            .insert(&peer_info.identity, sender_key)
+        {
+            warn!(
+                "Peer {} sent an invalid sender key: {}. Ignoring.",
+                peer_addr, e
+            );
+        }
+    }
+}
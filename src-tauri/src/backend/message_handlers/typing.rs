@@ -0,0 +1,23 @@
+use std::net::SocketAddr;
+
+use crate::backend::{chat_signals::SignalKind, peer_manager::PeerManager, protocol::Typing};
+
+impl PeerManager {
+    /// # Message Handler: `Typing`
+    ///
+    /// The peer is typing in our conversation, or in a room we are in. Tell the frontend.
+    pub async fn handle_typing(&self, typing: Typing, peer_addr: SocketAddr) {
+        let Some((_, peer_info)) = self.authenticated_peer(peer_addr, "Typing").await else {
+            return;
+        };
+
+        let Some(key) = self
+            .accept_signal(&peer_info, SignalKind::Typing, typing.conversation)
+            .await
+        else {
+            return;
+        };
+        self.emit_peer_typing(peer_info.into_connection_info(peer_addr), &key)
+            .await;
+    }
+}
@@ -6,12 +6,38 @@ use crate::js_api::{
     backend_event::{BackendEvent, BackendFatal},
 };
 
+pub mod auto_accept;
+pub mod bandwidth;
+pub mod byte_ranges;
+pub mod chat;
+pub mod chat_outbox;
+pub mod chat_signals;
+pub mod chat_store;
+pub mod chat_updates;
+pub mod chunk_sizing;
+pub mod compression;
+pub mod delta;
 pub mod ecdsa_identity;
+pub mod file_metadata;
+pub mod file_sender;
+pub mod flow_control;
 pub mod frontend_handlers;
 pub mod frontend_manager;
+pub mod hash_index;
+pub mod history;
+pub mod known_peers;
+pub mod manifest;
+pub mod merkle;
 pub mod message_handlers;
+pub mod offer_policy;
 pub mod peer_manager;
 pub mod protocol;
+pub mod rooms;
+pub mod sender_keys;
+pub mod sessions;
+pub mod shares;
+pub mod swarm;
+pub mod transfer_scheduler;
 
 /// Log versions and other important information.
 /// This macro is used to log the versions of the backend and frontend.
@@ -124,8 +150,7 @@ pub async fn init(
     // Log versions and other important information
     log_backend_info!();
 
-    // Setup peer ECDSA Identity
-    // ecdsa_identity::setup_ecdsa_identity().await;
+    // Our ECDSA identity is loaded (or created) with the PeerManager, see `ecdsa_identity`
 
     // Awaiting confirmation from the frontend that it is ready
     // to receive messages from the backend.
@@ -0,0 +1,329 @@
+//! # Offer Policy
+//!
+//! Preflight checks of incoming file offers, before they are shown to the frontend.
+//!
+//! - The volume the offer is saved to (the download directory, or the folder of the
+//!   auto-accept rule accepting it) must have room for the offer, on top of every incoming transfer
+//!   already accepted. If not, the offer is rejected with a reason sent back to the sender.
+//!   If it fits but leaves less than [LOW_SPACE_MARGIN], the frontend is warned.
+//! - No single file may be larger than [OfferPolicy::max_file_size].
+//! - A peer may not send more than [OfferPolicy::max_daily_bytes_per_peer] per (UTC) day.
+//!   An offer counts towards the limit as soon as it is shown to the frontend, so several offers
+//!   waiting for an answer cannot overrun it. Its bytes are given back if it is rejected, or ends
+//!   before it is accepted. Accepted offers count, whether they complete or not.
+//! - Offers waiting for an answer reserve disk space too, like the incoming transfers accepted.
+//!
+//! Limits can be changed at runtime with `FrontendEvent::SetOfferPolicy`. The bytes accepted
+//! today are persisted to [OFFER_USAGE_FILE], so restarting does not reset the daily limit.
+
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::{
+    manifest::Manifest,
+    peer_manager::{FileTransferDirection, PeerManager},
+};
+
+/// File the bytes accepted per peer today are saved to, next to the log file.
+pub const OFFER_USAGE_FILE: &str = "kuaip2p-offer-usage.json";
+
+/// Free space to leave on the download volume before warning the frontend (1 GB).
+const LOW_SPACE_MARGIN: u64 = 1024 * 1024 * 1024;
+
+/// Size limits for incoming file offers, and how much each peer has sent today.
+#[derive(Debug, Default)]
+pub struct OfferPolicy {
+    /// Maximum size of a single file (None is unlimited)
+    pub max_file_size: Option<u64>,
+    /// Maximum bytes a single peer may send per day (None is unlimited)
+    pub max_daily_bytes_per_peer: Option<u64>,
+    /// Bytes accepted per peer (by identity) today, and which day that is
+    daily_usage: HashMap<String, (u64, u64)>,
+    /// Offers waiting for an answer: the peer (by identity) and the bytes they reserve
+    pending: HashMap<Uuid, (String, u64)>,
+}
+
+impl OfferPolicy {
+    /// Load the bytes accepted today saved to [OFFER_USAGE_FILE]. No limits are set.
+    pub fn load() -> Self {
+        let Ok(json) = std::fs::read_to_string(OFFER_USAGE_FILE) else {
+            return Self::default();
+        };
+        match serde_json::from_str::<HashMap<String, (u64, u64)>>(&json) {
+            Ok(daily_usage) => {
+                info!("Loaded the daily usage of {} peers", daily_usage.len());
+                Self {
+                    daily_usage,
+                    ..Self::default()
+                }
+            }
+            Err(e) => {
+                warn!("Ignoring invalid {}: {}", OFFER_USAGE_FILE, e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Save the bytes accepted today to [OFFER_USAGE_FILE].
+    pub async fn save(&self) -> Result<(), String> {
+        let today = today();
+        let daily_usage: HashMap<&String, &(u64, u64)> = self
+            .daily_usage
+            .iter()
+            .filter(|(_, (day, _))| *day == today)
+            .collect();
+        let json = serde_json::to_string(&daily_usage)
+            .map_err(|e| format!("Failed to serialize offer usage: {}", e))?;
+        tokio::fs::write(OFFER_USAGE_FILE, json)
+            .await
+            .map_err(|e| format!("Failed to save offer usage: {}", e))
+    }
+
+    /// Check an offer against the size limits, and reserve its bytes towards the peer's daily limit
+    /// until it is accepted ([Self::confirm]) or not ([Self::release]).
+    pub fn reserve(
+        &mut self,
+        unique_id: Uuid,
+        peer_identity: &str,
+        manifest: &Manifest,
+    ) -> Result<(), String> {
+        if let Some(max_file_size) = self.max_file_size
+            && let Some(file) = manifest.files.iter().find(|file| file.size > max_file_size)
+        {
+            return Err(format!(
+                "{} is {} bytes, larger than the {} byte limit per file",
+                file.path, file.size, max_file_size
+            ));
+        }
+
+        if let Some(max_daily_bytes) = self.max_daily_bytes_per_peer {
+            let used = self.used_today(peer_identity);
+            if used.saturating_add(manifest.total_size()) > max_daily_bytes {
+                return Err(format!(
+                    "Daily limit of {} bytes reached ({} bytes already offered today)",
+                    max_daily_bytes, used
+                ));
+            }
+        }
+
+        self.pending.insert(
+            unique_id,
+            (peer_identity.to_string(), manifest.total_size()),
+        );
+        Ok(())
+    }
+
+    /// Count an accepted offer towards the peer's daily limit for good.
+    ///
+    /// Returns false if the offer reserved nothing.
+    pub fn confirm(&mut self, unique_id: Uuid) -> bool {
+        let Some((peer_identity, bytes)) = self.pending.remove(&unique_id) else {
+            return false;
+        };
+        let today = today();
+        let usage = self.daily_usage.entry(peer_identity).or_insert((today, 0));
+        if usage.0 != today {
+            *usage = (today, 0);
+        }
+        usage.1 = usage.1.saturating_add(bytes);
+        true
+    }
+
+    /// Give back the bytes an offer reserved, if it was not accepted.
+    pub fn release(&mut self, unique_id: Uuid) {
+        self.pending.remove(&unique_id);
+    }
+
+    /// Bytes accepted from a peer today, and offered by it and waiting for an answer.
+    fn used_today(&self, peer_identity: &str) -> u64 {
+        let accepted = match self.daily_usage.get(peer_identity) {
+            Some((day, bytes)) if *day == today() => *bytes,
+            _ => 0,
+        };
+        self.pending
+            .values()
+            .filter(|(pending_peer, _)| pending_peer == peer_identity)
+            .fold(accepted, |used, (_, bytes)| used.saturating_add(*bytes))
+    }
+}
+
+/// Days since the UNIX epoch (UTC)
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / (24 * 60 * 60)
+}
+
+impl PeerManager {
+    /// Check an incoming offer to be saved under `folder` against the free space and the offer
+    /// policy, and reserve its bytes (see [OfferPolicy::reserve]).
+    ///
+    /// Returns the reason to reject the offer with, or an optional low space warning for the frontend.
+    pub(crate) async fn preflight_file_offer(
+        &self,
+        unique_id: Uuid,
+        peer_identity: &str,
+        manifest: &Manifest,
+        folder: PathBuf,
+    ) -> Result<Option<String>, String> {
+        // Space still needed by the incoming transfers already accepted, or waiting for an answer
+        let reserved: u64 = self
+            .active_transfers
+            .lock()
+            .await
+            .values()
+            .filter(|transfer| {
+                transfer.direction == FileTransferDirection::Receiving
+                    && !transfer.status.is_finished()
+            })
+            .map(|transfer| transfer.total_size - transfer.bytes_transferred)
+            .sum();
+
+        let available = available_space(folder).await?;
+        let needed = manifest.total_size().saturating_add(reserved);
+
+        if needed > available {
+            return Err(format!(
+                "Not enough disk space: {} bytes needed, {} bytes available",
+                needed, available
+            ));
+        }
+
+        self.offer_policy
+            .lock()
+            .await
+            .reserve(unique_id, peer_identity, manifest)?;
+
+        if available - needed < LOW_SPACE_MARGIN {
+            return Ok(Some(format!(
+                "Low disk space: only {} bytes would be left after this transfer",
+                available - needed
+            )));
+        }
+        Ok(None)
+    }
+
+    /// Count an accepted offer towards the peer's daily limit, and save the usage.
+    pub(crate) async fn confirm_file_offer(&self, unique_id: Uuid) {
+        let mut offer_policy = self.offer_policy.lock().await;
+        if offer_policy.confirm(unique_id)
+            && let Err(e) = offer_policy.save().await
+        {
+            warn!("{}", e);
+        }
+    }
+
+    /// Give back the bytes reserved by an offer that was not accepted.
+    pub(crate) async fn release_file_offer(&self, unique_id: Uuid) {
+        self.offer_policy.lock().await.release(unique_id);
+    }
+}
+
+/// Free space available to us on the volume of `path`, or of its closest existing ancestor
+/// (an auto-accept rule's folder is only created once an offer is accepted into it).
+async fn available_space(path: PathBuf) -> Result<u64, String> {
+    tokio::task::spawn_blocking(move || {
+        let existing = path
+            .ancestors()
+            .find(|ancestor| ancestor.exists())
+            .unwrap_or(&path);
+        fs4::available_space(existing)
+    })
+    .await
+    .map_err(|e| format!("Failed to check disk space: {}", e))?
+    .map_err(|e| format!("Failed to check disk space: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::protocol::{FileMetadata, ManifestEntry};
+
+    fn offer(sizes: &[u64]) -> Manifest {
+        let entries = sizes
+            .iter()
+            .enumerate()
+            .map(|(i, size)| ManifestEntry {
+                path: format!("file-{}", i),
+                size: *size,
+                checksum: vec![0; blake3::OUT_LEN],
+                metadata: FileMetadata::default(),
+            })
+            .collect();
+        Manifest::from_offer(entries, sizes.iter().sum()).unwrap()
+    }
+
+    #[test]
+    fn files_over_the_size_limit_are_refused() {
+        let mut policy = OfferPolicy {
+            max_file_size: Some(100),
+            ..OfferPolicy::default()
+        };
+        assert!(
+            policy
+                .reserve(Uuid::new_v4(), "alice", &offer(&[100, 100]))
+                .is_ok()
+        );
+        assert!(
+            policy
+                .reserve(Uuid::new_v4(), "alice", &offer(&[1, 101]))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn pending_and_accepted_offers_count_towards_the_daily_limit() {
+        let mut policy = OfferPolicy {
+            max_daily_bytes_per_peer: Some(100),
+            ..OfferPolicy::default()
+        };
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        assert!(policy.reserve(first, "alice", &offer(&[60])).is_ok());
+        // The first offer is still waiting for an answer, but counts already
+        assert!(policy.reserve(second, "alice", &offer(&[60])).is_err());
+        assert!(policy.reserve(Uuid::new_v4(), "bob", &offer(&[60])).is_ok());
+
+        // Rejected: its bytes are given back
+        policy.release(first);
+        assert!(policy.reserve(second, "alice", &offer(&[60])).is_ok());
+        // Accepted: its bytes count for the rest of the day
+        assert!(policy.confirm(second));
+        assert!(!policy.confirm(second));
+        assert_eq!(policy.used_today("alice"), 60);
+        assert!(
+            policy
+                .reserve(Uuid::new_v4(), "alice", &offer(&[41]))
+                .is_err()
+        );
+        assert!(
+            policy
+                .reserve(Uuid::new_v4(), "alice", &offer(&[40]))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn usage_of_an_earlier_day_does_not_count() {
+        let mut policy = OfferPolicy {
+            max_daily_bytes_per_peer: Some(100),
+            ..OfferPolicy::default()
+        };
+        policy
+            .daily_usage
+            .insert("alice".to_string(), (today() - 1, 100));
+        assert_eq!(policy.used_today("alice"), 0);
+
+        let unique_id = Uuid::new_v4();
+        assert!(policy.reserve(unique_id, "alice", &offer(&[100])).is_ok());
+        policy.confirm(unique_id);
+        assert_eq!(policy.daily_usage["alice"], (today(), 100));
+    }
+}
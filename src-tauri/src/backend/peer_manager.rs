@@ -1,17 +1,52 @@
-use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+    time::{Instant, SystemTime},
+};
 
-// use base64::{Engine, prelude::BASE64_STANDARD};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     net::{TcpListener, TcpStream, tcp::OwnedReadHalf},
-    sync::{Mutex, mpsc, oneshot},
+    sync::{Mutex, mpsc, oneshot, watch},
 };
 use tracing::{debug, error, info, trace, warn};
 use uuid::Uuid;
 
-use crate::js_api::backend_event::{BackendEvent, ConnectionCloseOrBroken, ConnectionInfo};
+use crate::js_api::backend_event::{
+    self, BackendEvent, BackendWarning, ConnectionCloseOrBroken, ConnectionInfo,
+};
 
-use super::protocol::{BINCODE_CONFIG, DisconnectRequest, MAX_MESSAGE_SIZE, Message};
+use super::{
+    auto_accept::AutoAcceptRules,
+    bandwidth::{BandwidthLimiter, RateMeter},
+    byte_ranges::ByteRanges,
+    chat_outbox::ChatOutbox,
+    chat_signals::{SIGNAL_QUEUE_LEN, SIGNAL_TURN, SignalLimiter, is_stale},
+    chat_store::ChatStore,
+    chat_updates::PendingUpdates,
+    chunk_sizing::LinkQuality,
+    ecdsa_identity::{Handshake, Identity},
+    file_metadata::MetadataPolicy,
+    flow_control::{ChunkReply, FlowControlConfig},
+    hash_index::HashIndex,
+    history::TransferHistory,
+    known_peers::KnownPeers,
+    manifest::{FileSet, Manifest},
+    merkle::{Hash, MerkleTree},
+    offer_policy::OfferPolicy,
+    protocol::{
+        BINCODE_CONFIG, BlockSignatures, Compression, DisconnectRequest, FileCancel, FilePause,
+        FileResume, MAX_MESSAGE_SIZE, Message, PROTOCOL_VERSION,
+    },
+    rooms::Rooms,
+    sender_keys::ReceivedSenderKeys,
+    sessions::PeerSession,
+    shares::{FileRequestState, SharedFolders},
+    swarm::SwarmDownload,
+    transfer_scheduler::TransferScheduler,
+};
 
 /// Peer Manager
 ///
@@ -27,18 +62,68 @@ pub struct PeerManager {
     pub(crate) backend_event_tx: mpsc::Sender<BackendEvent>,
     /// Shutdown one-shot sender. If None, the PeerManager has been shutdown.
     pub(crate) shutdown_tx: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+    /// Flow control configuration for outgoing file transfers
+    pub(crate) flow_control: FlowControlConfig,
+    /// Global and per-peer bandwidth limits for file transfers
+    pub(crate) bandwidth: Arc<BandwidthLimiter>,
+    /// Queue and concurrency limits of outgoing file transfers
+    pub(crate) scheduler: Arc<Mutex<TransferScheduler>>,
+    /// Directory incoming files are saved to
+    pub(crate) download_dir: Arc<Mutex<PathBuf>>,
+    /// Size limits for incoming file offers
+    pub(crate) offer_policy: Arc<Mutex<OfferPolicy>>,
+    /// Which metadata of received files is applied
+    pub(crate) metadata_policy: Arc<Mutex<MetadataPolicy>>,
+    /// Rules accepting or rejecting incoming file offers without asking the frontend
+    pub(crate) auto_accept_rules: Arc<Mutex<AutoAcceptRules>>,
+    /// Folders we share with our peers
+    pub(crate) shared_folders: Arc<Mutex<SharedFolders>>,
+    /// File requests we sent, waiting for the peer's offer, keyed by request ID
+    pub(crate) sent_file_requests: Arc<Mutex<HashMap<Uuid, FileRequestState>>>,
+    /// File requests received, waiting for the frontend's approval, keyed by request ID
+    pub(crate) received_file_requests: Arc<Mutex<HashMap<Uuid, FileRequestState>>>,
+    /// Directory listings we requested, keyed by request ID
+    pub(crate) directory_requests: Arc<Mutex<HashMap<Uuid, FileRequestState>>>,
+    /// Sources of our multi-source downloads whose offer has not arrived yet, keyed by download ID
+    pub(crate) swarm_requests: Arc<Mutex<HashMap<Uuid, Vec<FileRequestState>>>>,
+    /// Every chat conversation, with a peer or a room
+    pub(crate) chat_store: Arc<Mutex<ChatStore>>,
+    /// Chat messages waiting for each peer's ack, queued while it is offline
+    pub(crate) chat_outbox: Arc<Mutex<ChatOutbox>>,
+    /// Changes received before the chat message they change
+    pub(crate) pending_chat_updates: Arc<Mutex<PendingUpdates>>,
+    /// The chat rooms we know of, keyed by room ID
+    pub(crate) rooms: Arc<Mutex<Rooms>>,
+    /// Every peer that ever authenticated, with the last name it used
+    pub(crate) known_peers: Arc<Mutex<KnownPeers>>,
+    /// The sender keys other members of our rooms sent us
+    pub(crate) received_sender_keys: Arc<Mutex<ReceivedSenderKeys>>,
+    /// How many typing indicators and read receipts were sent and received lately
+    pub(crate) signal_limiter: Arc<Mutex<SignalLimiter>>,
+    /// The files we already have, so they are not transferred again
+    pub(crate) hash_index: Arc<Mutex<HashIndex>>,
+    /// Every file transfer that has ended
+    pub(crate) transfer_history: Arc<Mutex<TransferHistory>>,
+    /// Our long-term identity
+    pub(crate) identity: Arc<Identity>,
 }
 
 /// File Transfer Direction
 #[derive(Debug, PartialEq)]
 pub enum FileTransferDirection {
-    Sending {
-        /// The file path of the file being sent
-        file_path: String,
-    },
+    Sending,
     Receiving,
 }
 
+impl From<&FileTransferDirection> for backend_event::FileTransferDirection {
+    fn from(direction: &FileTransferDirection) -> Self {
+        match direction {
+            FileTransferDirection::Sending => backend_event::FileTransferDirection::Sending,
+            FileTransferDirection::Receiving => backend_event::FileTransferDirection::Receiving,
+        }
+    }
+}
+
 /// Represents the state of a file transfer.
 #[derive(Debug)]
 pub struct FileTransferState {
@@ -46,34 +131,99 @@ pub struct FileTransferState {
     pub unique_id: Uuid,
     /// IP/Socket address of the peer
     pub peer_addr: std::net::SocketAddr,
+    /// Name of the peer (kept for the transfer history, once the peer is gone)
+    pub peer_name: String,
+    /// Identity of the peer (see [super::ecdsa_identity])
+    pub peer_identity: String,
+    /// When the transfer was queued (sending) or offered (receiving)
+    pub started_at: SystemTime,
     /// Direction of the file transfer
     pub direction: FileTransferDirection,
-    /// The file handle of the file being transferred
+    /// The name of the offer (the file name, or the folder name for a folder)
     pub filename: String,
-    /// The size of the file being transferred
+    /// The files being transferred
+    pub manifest: Manifest,
+    /// Root of the Merkle tree over the transfer's byte stream. For outgoing transfers, None until the files are hashed.
+    pub root_hash: Option<Hash>,
+    /// The whole Merkle tree, to prove the chunks sent (sending only)
+    pub merkle_tree: Option<Arc<MerkleTree>>,
+    /// Whether chunks are only sent as the peer requests them with `RangeRequest`
+    /// (sending to a multi-source download)
+    pub ranged: bool,
+    /// The sources of a multi-source download, and the ranges requested from each (receiving only)
+    pub swarm: Option<SwarmDownload>,
+    /// The size of every file being transferred
     pub total_size: u64,
     /// The number of bytes transferred so far
     pub bytes_transferred: u64,
-    /// The length of the chunks being transferred
+    /// The effective transfer rate
+    pub rate: RateMeter,
+    /// The maximum length of the chunks being transferred (negotiated with the peer)
     pub chunk_len: u64,
     /// The status of the file transfer
     pub status: FileTransferStatus,
-    // Optionally: file handles, checksums, etc.
+    /// Forwards the peer's replies to chunks (acks and nacks) to the chunk sender task (sending only)
+    pub ack_tx: Option<mpsc::Sender<ChunkReply>>,
+    /// Tells the chunk sender task whether the transfer is paused (sending only)
+    pub pause_tx: Option<watch::Sender<bool>>,
+    /// The compression chunks may use, as announced in the offer
+    pub compression: Option<Compression>,
+    /// The number of chunk payload bytes sent or received over the wire, after compression (retransmissions included)
+    pub wire_bytes: u64,
+    /// Byte ranges received so far, so retransmitted chunks are not counted twice (receiving only)
+    pub received_ranges: ByteRanges,
+    /// Older versions of files we sent block signatures of, which `FileDelta` copies from, by file index (receiving only)
+    pub delta_bases: HashMap<usize, PathBuf>,
+    /// Files asked for again, after the data copied from their older version failed to verify (receiving only)
+    pub resent_files: HashSet<usize>,
+    /// Block signatures of the peer's older versions of files, received before it accepted, by file index (sending only)
+    pub block_signatures: HashMap<usize, BlockSignatures>,
+}
+
+impl FileTransferState {
+    /// Is the peer the peer of the transfer, or one of the sources of a multi-source download?
+    pub fn has_peer(&self, peer_addr: SocketAddr) -> bool {
+        self.peer_addr == peer_addr
+            || self
+                .swarm
+                .as_ref()
+                .is_some_and(|swarm| swarm.has_source(peer_addr))
+    }
+
+    /// Every peer of the transfer: the peer, or the sources of a multi-source download.
+    pub fn peers(&self) -> Vec<SocketAddr> {
+        match &self.swarm {
+            Some(swarm) => swarm
+                .sources()
+                .iter()
+                .map(|source| source.peer_addr)
+                .collect(),
+            None => vec![self.peer_addr],
+        }
+    }
 }
 
 /// File Transfer Status
 #[derive(Debug)]
 pub enum FileTransferStatus {
+    /// Waiting in the transfer queue (the offer has not been sent yet)
+    Queued,
     /// Waiting for peer's response (we do not accept file chunks yet)
     WaitingForPeerResponse,
     /// The file transfer is in progress (we can accept file chunks now)
     InProgress {
-        /// Handle to file being transferred
-        file_handle: Arc<tokio::fs::File>,
+        /// The files being transferred
+        files: Arc<FileSet>,
+    },
+    /// The file transfer is paused by either side.
+    /// No new chunks are sent, but chunks already in flight are still accepted.
+    Paused {
+        /// The files being transferred
+        files: Arc<FileSet>,
     },
     /// The file transfer is completed
     Completed,
-    /// The file transfer is cancelled (but was accepted)
+    /// The file transfer is cancelled (by either side)
     Cancelled,
     /// The file transfer was rejected (not accepted)
     Rejected,
@@ -81,6 +231,24 @@ pub enum FileTransferStatus {
     Error(String),
 }
 
+impl FileTransferStatus {
+    /// Has the file transfer ended (completed, cancelled, rejected or failed)?
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            Self::Completed | Self::Cancelled | Self::Rejected | Self::Error(_)
+        )
+    }
+
+    /// The files being transferred, if the transfer is in progress or paused.
+    pub fn files(&self) -> Option<&Arc<FileSet>> {
+        match self {
+            Self::InProgress { files } | Self::Paused { files } => Some(files),
+            _ => None,
+        }
+    }
+}
+
 /// Peer
 ///
 /// Represents a peer that the application is connected to.
@@ -92,6 +260,15 @@ pub struct Peer {
     pub state: PeerState,
     /// The sender to send messages to the peer
     pub tx: mpsc::Sender<Message>,
+    /// The sender of typing indicators and read receipts, with when they were queued. They are
+    /// sent to the peer when nothing is waiting on `tx`, or in turn (see [super::chat_signals])
+    pub signal_tx: mpsc::Sender<(Instant, Message)>,
+    /// Link quality measured by the last file transfer sent to the peer
+    pub link_quality: Option<LinkQuality>,
+    /// The end-to-end encrypted session, derived in the identity handshake
+    pub session: Option<PeerSession>,
+    /// The identity handshake, while it is in progress
+    pub handshake: Option<Handshake>,
 }
 
 impl Drop for Peer {
@@ -140,12 +317,14 @@ pub enum PeerState {
 
 #[derive(Debug, Clone)]
 pub struct PeerInfo {
-    /// The name of the peer
+    /// The name of the peer (for display only: anyone may claim any name)
     pub name: String,
-    // /// The ECDSA public key of the peer
-    // pub ecdsa_public_key: Vec<u8>,
+    /// The identity of the peer: its ECDSA public key in base64, verified in the handshake
+    pub identity: String,
     /// The Backend version of the peer
     pub backend_version: String,
+    /// The compression algorithms the peer supports for file chunks
+    pub compression: Vec<Compression>,
 }
 
 impl PeerInfo {
@@ -155,7 +334,7 @@ impl PeerInfo {
             name: self.name.clone(),
             ip: peer_addr.ip().to_string(),
             backend_version: self.backend_version.clone(),
-            // identitiy: BASE64_STANDARD.encode(&self.ecdsa_public_key),
+            identity: self.identity.clone(),
         }
     }
 }
@@ -163,11 +342,35 @@ impl PeerInfo {
 impl PeerManager {
     /// Create a new PeerManager
     pub fn new(backend_event_tx: mpsc::Sender<BackendEvent>) -> Self {
+        let chat_store = ChatStore::load();
+        let rooms = Rooms::load(&chat_store);
         Self {
             active_peers: Arc::new(Mutex::new(HashMap::new())),
             active_transfers: Arc::new(Mutex::new(HashMap::new())),
             backend_event_tx,
             shutdown_tx: Arc::new(Mutex::new(None)),
+            flow_control: FlowControlConfig::default(),
+            bandwidth: Arc::new(BandwidthLimiter::default()),
+            scheduler: Arc::new(Mutex::new(TransferScheduler::default())),
+            download_dir: Arc::new(Mutex::new(PathBuf::from("."))),
+            offer_policy: Arc::new(Mutex::new(OfferPolicy::load())),
+            metadata_policy: Arc::new(Mutex::new(MetadataPolicy::default())),
+            auto_accept_rules: Arc::new(Mutex::new(AutoAcceptRules::load())),
+            shared_folders: Arc::new(Mutex::new(SharedFolders::load())),
+            sent_file_requests: Arc::new(Mutex::new(HashMap::new())),
+            received_file_requests: Arc::new(Mutex::new(HashMap::new())),
+            directory_requests: Arc::new(Mutex::new(HashMap::new())),
+            swarm_requests: Arc::new(Mutex::new(HashMap::new())),
+            chat_store: Arc::new(Mutex::new(chat_store)),
+            chat_outbox: Arc::new(Mutex::new(ChatOutbox::load())),
+            pending_chat_updates: Arc::new(Mutex::new(PendingUpdates::default())),
+            rooms: Arc::new(Mutex::new(rooms)),
+            known_peers: Arc::new(Mutex::new(KnownPeers::load())),
+            received_sender_keys: Arc::new(Mutex::new(ReceivedSenderKeys::default())),
+            signal_limiter: Arc::new(Mutex::new(SignalLimiter::default())),
+            hash_index: Arc::new(Mutex::new(HashIndex::load())),
+            transfer_history: Arc::new(Mutex::new(TransferHistory::load())),
+            identity: Arc::new(Identity::load()),
         }
     }
 
@@ -191,10 +394,17 @@ impl PeerManager {
             }
         }
 
-        let mut active_peers = self.active_peers.lock().await;
-        for (peer_addr, peer) in active_peers.drain() {
+        // Do not hold the lock while dropping the peers: `drop_peer` takes it again
+        let active_peers: Vec<_> = self
+            .active_peers
+            .lock()
+            .await
+            .iter()
+            .map(|(peer_addr, peer)| (*peer_addr, peer.tx.clone()))
+            .collect();
+        for (peer_addr, peer_tx) in active_peers {
             // Send an ImmediateConnectionClose message to the peer
-            peer.tx
+            peer_tx
                 .send(Message::ImmediateConnectionClose(DisconnectRequest {
                     message: "Peer is shutting down".to_string().into(),
                 }))
@@ -226,6 +436,15 @@ impl PeerManager {
 
         info!("Listening for incoming connections on {}", listen_addr);
 
+        // Start the transfer scheduler
+        let manager = self.clone();
+        let transfer_scheduler = tokio::spawn(async move {
+            manager.run_transfer_scheduler().await;
+        });
+
+        // Index the files we already have
+        self.spawn_hash_index_rescan();
+
         // Accept incoming connections
         // Once accepted, spawn a new task to handle the connection
         loop {
@@ -247,6 +466,7 @@ impl PeerManager {
 
                 _ = &mut shutdown_rx => {
                         info!("PeerManager will not accept new connections. Goodbye!");
+                        transfer_scheduler.abort();
                         break Ok(());
                 }
             }
@@ -285,6 +505,7 @@ impl PeerManager {
     /// Handle connections from a peer
     async fn handle_connection(&self, stream: TcpStream, peer_addr: SocketAddr) {
         let (tx, mut rx) = mpsc::channel(32);
+        let (signal_tx, mut signal_rx) = mpsc::channel(SIGNAL_QUEUE_LEN);
         let (reader, mut writer) = stream.into_split();
 
         // Insert sender into active peers
@@ -296,6 +517,10 @@ impl PeerManager {
                     addr: peer_addr,
                     state: PeerState::Connected { peer_info: None },
                     tx,
+                    signal_tx,
+                    link_quality: None,
+                    session: None,
+                    handshake: None,
                 },
             );
         }
@@ -309,16 +534,49 @@ impl PeerManager {
 
         // Spawn a task to write to the peer
         tokio::spawn(async move {
-            while let Some(message) = rx.recv().await {
+            // Number of other messages written since the last signal
+            let mut since_signal: u32 = 0;
+            loop {
+                // Signals have the lowest priority: they wait for other messages, file chunks included,
+                // but get a turn every SIGNAL_TURN messages
+                let (queued_at, message) = if since_signal >= SIGNAL_TURN
+                    && let Ok((queued_at, signal)) = signal_rx.try_recv()
+                {
+                    (Some(queued_at), signal)
+                } else {
+                    tokio::select! {
+                        biased;
+                        message = rx.recv() => match message {
+                            Some(message) => (None, message),
+                            None => break,
+                        },
+                        Some((queued_at, signal)) = signal_rx.recv() => (Some(queued_at), signal),
+                    }
+                };
+                match queued_at {
+                    Some(queued_at) => {
+                        since_signal = 0;
+                        if is_stale(&message, queued_at) {
+                            trace!("Dropping stale signal to peer {}: {:?}", peer_addr, message);
+                            continue;
+                        }
+                    }
+                    None => since_signal = since_signal.saturating_add(1),
+                }
+
                 match &message {
                     Message::FileChunk(chunk) => {
                         info!(
-                            "Sending FileChunk: ID={} Chunk={:4}/{:4}",
+                            "Sending FileChunk: ID={} Chunk={:4} Offset={} Len={}",
                             chunk.unique_id,
                             chunk.chunk_id,
-                            chunk.chunk_len - 1
+                            chunk.offset,
+                            chunk.data.len()
                         );
                     }
+                    Message::Typing(_) | Message::ReadUpTo(_) => {
+                        trace!("Sending signal: {:?}", message);
+                    }
                     _ => info!("Sending control message: {:?}", message),
                 }
 
@@ -461,6 +719,25 @@ impl PeerManager {
                                         peer_addr, &buf
                                     );
 
+                                    // Before the handshake is over, the peer most likely speaks another version
+                                    let handshaking =
+                                        self.active_peers.lock().await.get(&peer_addr).is_some_and(
+                                            |peer| {
+                                                matches!(peer.state, PeerState::Connected { .. })
+                                            },
+                                        );
+                                    if handshaking {
+                                        self.drop_incompatible_peer(
+                                            peer_addr,
+                                            format!(
+                                                "its handshake cannot be read ({}), it may speak another protocol version than ours ({})",
+                                                e, PROTOCOL_VERSION
+                                            ),
+                                        )
+                                        .await;
+                                        break 'recv;
+                                    }
+
                                     // Remove peer from active peers to drop the sender
                                     self.drop_peer(
                                         peer_addr,
@@ -527,6 +804,13 @@ impl PeerManager {
                 self.handle_connect_request(connection_info, peer_addr)
                     .await;
             }
+            Message::ConnectChallenge(connection_info) => {
+                self.handle_connect_challenge(connection_info, peer_addr)
+                    .await;
+            }
+            Message::ConnectProof(connect_proof) => {
+                self.handle_connect_proof(connect_proof, peer_addr).await;
+            }
             Message::ConnectResponse(connection_response) => {
                 self.handle_connect_response(connection_response, peer_addr)
                     .await;
@@ -549,11 +833,386 @@ impl PeerManager {
                 self.handle_file_offer_response(file_offer_response, peer_addr)
                     .await;
             }
-            Message::FileChunk(_file_chunk) => todo!(),
-            Message::FileChunkAck(_file_chunk_ack) => todo!(),
-            Message::FileDone(_file_done) => todo!(),
-            Message::FileDoneResult(_file_done_result) => todo!(),
+            Message::FileChunk(file_chunk) => {
+                self.handle_file_chunk(file_chunk, peer_addr).await;
+            }
+            Message::FileChunkAck(file_chunk_ack) => {
+                self.handle_file_chunk_ack(file_chunk_ack, peer_addr).await;
+            }
+            Message::FileChunkNack(file_chunk_nack) => {
+                self.handle_file_chunk_nack(file_chunk_nack, peer_addr)
+                    .await;
+            }
+            Message::FileDone(file_done) => {
+                self.handle_file_done(file_done, peer_addr).await;
+            }
+            Message::FileDoneResult(file_done_result) => {
+                self.handle_file_done_result(file_done_result, peer_addr)
+                    .await;
+            }
+            Message::FilePause(file_pause) => {
+                self.handle_file_pause(file_pause, peer_addr).await;
+            }
+            Message::FileResume(file_resume) => {
+                self.handle_file_resume(file_resume, peer_addr).await;
+            }
+            Message::FileCancel(file_cancel) => {
+                self.handle_file_cancel(file_cancel, peer_addr).await;
+            }
+            Message::FileRequest(file_request) => {
+                self.handle_file_request(file_request, peer_addr).await;
+            }
+            Message::FileRequestResponse(file_request_response) => {
+                self.handle_file_request_response(file_request_response, peer_addr)
+                    .await;
+            }
+            Message::ListDirectory(list_directory) => {
+                self.handle_list_directory(list_directory, peer_addr).await;
+            }
+            Message::DirectoryListing(directory_listing) => {
+                self.handle_directory_listing(directory_listing, peer_addr)
+                    .await;
+            }
+            Message::RangeRequest(range_request) => {
+                self.handle_range_request(range_request, peer_addr).await;
+            }
+            Message::BlockSignatures(block_signatures) => {
+                self.handle_block_signatures(block_signatures, peer_addr)
+                    .await;
+            }
+            Message::FileDelta(file_delta) => {
+                self.handle_file_delta(file_delta, peer_addr).await;
+            }
+            Message::ChatMessage(chat_message) => {
+                self.handle_chat_message(chat_message, peer_addr).await;
+            }
+            Message::ChatMessageAck(chat_message_ack) => {
+                self.handle_chat_message_ack(chat_message_ack, peer_addr)
+                    .await;
+            }
+            Message::ChatEdit(chat_edit) => {
+                self.handle_chat_edit(chat_edit, peer_addr).await;
+            }
+            Message::ChatDelete(chat_delete) => {
+                self.handle_chat_delete(chat_delete, peer_addr).await;
+            }
+            Message::ChatReaction(chat_reaction) => {
+                self.handle_chat_reaction(chat_reaction, peer_addr).await;
+            }
+            Message::RoomUpdate(room_update) => {
+                self.handle_room_update(room_update, peer_addr).await;
+            }
+            Message::SenderKey(sealed_sender_key) => {
+                self.handle_sender_key(sealed_sender_key, peer_addr).await;
+            }
+            Message::Typing(typing) => {
+                self.handle_typing(typing, peer_addr).await;
+            }
+            Message::ReadUpTo(read_up_to) => {
+                self.handle_read_up_to(read_up_to, peer_addr).await;
+            }
+        }
+    }
+
+    /// Get the message sender of an authenticated peer.
+    ///
+    /// If the peer is connected but not authenticated (or already disconnecting), it sent
+    /// `message_name` when it was not allowed to, so the peer is dropped and `None` is returned.
+    /// If the peer is not found, `None` is returned.
+    pub(crate) async fn authenticated_peer_tx(
+        &self,
+        peer_addr: SocketAddr,
+        message_name: &str,
+    ) -> Option<mpsc::Sender<Message>> {
+        self.authenticated_peer(peer_addr, message_name)
+            .await
+            .map(|(peer_tx, _)| peer_tx)
+    }
+
+    /// Get the message sender and the info of an authenticated peer.
+    ///
+    /// Same as [Self::authenticated_peer_tx], for handlers that need to know who the peer is.
+    pub(crate) async fn authenticated_peer(
+        &self,
+        peer_addr: SocketAddr,
+        message_name: &str,
+    ) -> Option<(mpsc::Sender<Message>, PeerInfo)> {
+        // Do not hold the lock while dropping the peer
+        let state = {
+            let peers = self.active_peers.lock().await;
+            let peer = peers.get(&peer_addr)?;
+            match &peer.state {
+                PeerState::Authenticated { peer_info } => {
+                    return Some((peer.tx.clone(), peer_info.clone()));
+                }
+                PeerState::Connected { .. } => Some(format!(
+                    "Peer sent a {} message before authentication",
+                    message_name
+                )),
+                PeerState::Disconnecting { .. } => None,
+            }
+        };
+
+        // Peer is not authenticated, or is already disconnecting, but they sent a file message?
+        // Disconnect the peer
+        self.drop_peer(peer_addr, state).await;
+        None
+    }
+
+    /// Find an authenticated peer by the address given by the frontend.
+    ///
+    /// Returns the error to report to the frontend if the address is invalid, or the peer is not connected.
+    pub(crate) async fn frontend_peer(
+        &self,
+        ip: &str,
+    ) -> Result<(SocketAddr, mpsc::Sender<Message>), String> {
+        let peer_addr: SocketAddr = ip.parse().map_err(|_| "Invalid IP address".to_string())?;
+        self.active_peers
+            .lock()
+            .await
+            .get(&peer_addr)
+            .filter(|peer| matches!(peer.state, PeerState::Authenticated { .. }))
+            .map(|peer| (peer_addr, peer.tx.clone()))
+            .ok_or(format!("Peer {} is not connected", peer_addr))
+    }
+
+    /// Emit the transfer error to the frontend, and mark the transfer as failed.
+    ///
+    /// Does nothing if the transfer has already ended.
+    pub(crate) async fn fail_file_transfer(&self, unique_id: Uuid, message: String) {
+        if !self
+            .end_file_transfer(unique_id, FileTransferStatus::Error(message.clone()))
+            .await
+        {
+            return;
+        }
+        warn!("File transfer {} failed: {}", unique_id, message);
+        self.cancel_swarm_sources(unique_id, &message).await;
+
+        self.backend_event_tx
+            .send(BackendEvent::FileTransferError(
+                backend_event::FileTransferError {
+                    unique_id: unique_id.to_string(),
+                    message,
+                },
+            ))
+            .await
+            .expect("Failed to send FileTransferError event to the frontend");
+    }
+
+    /// Cancel a file transfer, and notify the frontend.
+    ///
+    /// If we cancelled the transfer ourselves, the peer is told with a `FileCancel` message
+    /// (unless the offer was never sent). If the peer did, `by_peer` is true.
+    pub(crate) async fn cancel_file_transfer(
+        &self,
+        unique_id: Uuid,
+        message: Option<String>,
+        by_peer: bool,
+    ) -> Result<(), String> {
+        let (peers, offered) = {
+            let active_transfers = self.active_transfers.lock().await;
+            let transfer = active_transfers
+                .get(&unique_id)
+                .ok_or("No such file transfer")?;
+            if transfer.status.is_finished() {
+                return Err("File transfer has already ended".to_string());
+            }
+            (
+                transfer.peers(),
+                !matches!(transfer.status, FileTransferStatus::Queued),
+            )
+        };
+
+        if !self
+            .end_file_transfer(unique_id, FileTransferStatus::Cancelled)
+            .await
+        {
+            return Err("File transfer has already ended".to_string());
+        }
+
+        // Tell the peer (every source of a multi-source download)
+        if !by_peer && offered {
+            for peer_addr in peers {
+                let peer_tx = self
+                    .active_peers
+                    .lock()
+                    .await
+                    .get(&peer_addr)
+                    .map(|peer| peer.tx.clone());
+                if let Some(peer_tx) = peer_tx {
+                    peer_tx
+                        .send(Message::FileCancel(FileCancel {
+                            unique_id,
+                            message: message.clone(),
+                        }))
+                        .await
+                        .ok(); // We ignore the error here, as the peer may have already disconnected.
+                }
+            }
+        }
+
+        info!(
+            "File transfer {} cancelled by {}",
+            unique_id,
+            if by_peer { "the peer" } else { "us" }
+        );
+
+        self.backend_event_tx
+            .send(BackendEvent::FileTransferCancelled(
+                backend_event::FileTransferCancelled {
+                    unique_id: unique_id.to_string(),
+                    message,
+                    by_peer,
+                },
+            ))
+            .await
+            .expect("Failed to send FileTransferCancelled event to the frontend");
+
+        Ok(())
+    }
+
+    /// Put an unfinished transfer into a final `status`: stop its sender task, free its
+    /// scheduler slot, and remove the `.kuaip2p-part` files of an incoming transfer.
+    ///
+    /// Returns false if the transfer was not found, or had already ended.
+    async fn end_file_transfer(&self, unique_id: Uuid, status: FileTransferStatus) -> bool {
+        let part_files = {
+            let mut active_transfers = self.active_transfers.lock().await;
+            let Some(transfer) = active_transfers.get_mut(&unique_id) else {
+                return false;
+            };
+            if transfer.status.is_finished() {
+                return false;
+            }
+            let part_files = match transfer.direction {
+                FileTransferDirection::Receiving => transfer.status.files().cloned(),
+                FileTransferDirection::Sending => None,
+            };
+            transfer.status = status;
+            transfer.ack_tx = None;
+            transfer.pause_tx = None;
+            part_files
+        };
+
+        self.release_transfer_slot(unique_id).await;
+        self.release_file_offer(unique_id).await;
+        if let Some(files) = part_files {
+            files.discard().await;
         }
+        self.record_transfer_history(unique_id).await;
+        true
+    }
+
+    /// Pause or resume an in-progress file transfer, and notify the frontend.
+    ///
+    /// If we paused (or resumed) the transfer ourselves, the peer is told with a
+    /// `FilePause` (or `FileResume`) message. If the peer did, `by_peer` is true.
+    pub(crate) async fn set_file_transfer_paused(
+        &self,
+        unique_id: Uuid,
+        paused: bool,
+        by_peer: bool,
+    ) -> Result<(), String> {
+        let peers = {
+            let mut active_transfers = self.active_transfers.lock().await;
+            let transfer = active_transfers
+                .get_mut(&unique_id)
+                .ok_or("No such file transfer")?;
+
+            transfer.status = match (&transfer.status, paused) {
+                (FileTransferStatus::InProgress { files }, true) => FileTransferStatus::Paused {
+                    files: files.clone(),
+                },
+                (FileTransferStatus::Paused { files }, false) => FileTransferStatus::InProgress {
+                    files: files.clone(),
+                },
+                (FileTransferStatus::Paused { .. }, true) => {
+                    return Err("File transfer is already paused".to_string());
+                }
+                (FileTransferStatus::InProgress { .. }, false) => {
+                    return Err("File transfer is not paused".to_string());
+                }
+                _ => return Err("File transfer is not in progress".to_string()),
+            };
+
+            // Wake up the chunk sender task, if we are the one sending
+            if let Some(pause_tx) = &transfer.pause_tx {
+                pause_tx.send_replace(paused);
+            }
+
+            // Do not count the time spent paused in the transfer rate
+            transfer.rate.reset();
+
+            transfer.peers()
+        };
+
+        // Tell the peer (every source of a multi-source download)
+        if !by_peer {
+            for peer_addr in peers {
+                let peer_tx = self
+                    .active_peers
+                    .lock()
+                    .await
+                    .get(&peer_addr)
+                    .map(|peer| peer.tx.clone());
+                if let Some(peer_tx) = peer_tx {
+                    let message = if paused {
+                        Message::FilePause(FilePause { unique_id })
+                    } else {
+                        Message::FileResume(FileResume { unique_id })
+                    };
+                    // We ignore the error here, as the peer may have already disconnected.
+                    // The transfer is then failed when the peer is dropped.
+                    peer_tx.send(message).await.ok();
+                }
+            }
+        }
+
+        info!(
+            "File transfer {} {} by {}",
+            unique_id,
+            if paused { "paused" } else { "resumed" },
+            if by_peer { "the peer" } else { "us" }
+        );
+
+        let event = backend_event::FileTransferPausedOrResumed {
+            unique_id: unique_id.to_string(),
+            by_peer,
+        };
+        self.backend_event_tx
+            .send(if paused {
+                BackendEvent::FileTransferPaused(event)
+            } else {
+                BackendEvent::FileTransferResumed(event)
+            })
+            .await
+            .expect("Failed to send FileTransferPaused/FileTransferResumed event to the frontend");
+
+        // A resumed multi-source download requests segments again
+        if !paused {
+            self.request_swarm_ranges(unique_id).await;
+        }
+
+        Ok(())
+    }
+
+    /// Tell the frontend a peer speaks an incompatible version of the protocol, and disconnect it.
+    ///
+    /// Only during the handshake: an authenticated peer is reported on disconnection anyway.
+    pub(crate) async fn drop_incompatible_peer(&self, peer_addr: SocketAddr, reason: String) {
+        warn!(
+            "Peer {} is incompatible: {}. Disconnecting.",
+            peer_addr, reason
+        );
+        self.backend_event_tx
+            .send(BackendEvent::BackendWarning(BackendWarning {
+                message: format!("Cannot connect to peer {}: {}", peer_addr, reason),
+            }))
+            .await
+            .expect("Failed to send BackendWarning event to the frontend");
+        self.drop_peer(peer_addr, Some(format!("Incompatible peer: {}", reason)))
+            .await;
     }
 
     /// Drop a peer.
@@ -565,16 +1224,39 @@ impl PeerManager {
     ///
     /// Message is optional, however will always override the reason for disconnection.
     pub async fn drop_peer(&self, peer_addr: SocketAddr, message: Option<String>) {
-        let mut active_peers = self.active_peers.lock().await;
-        let removed_peer = active_peers.remove(&peer_addr);
+        let removed_peer = self.active_peers.lock().await.remove(&peer_addr);
+
+        // Fail every unfinished file transfer with the peer, freeing their scheduler slots.
+        // Multi-source downloads carry on with their other sources.
+        let unfinished_transfers: Vec<Uuid> = self
+            .active_transfers
+            .lock()
+            .await
+            .values()
+            .filter(|transfer| {
+                transfer.peer_addr == peer_addr
+                    && transfer.swarm.is_none()
+                    && !transfer.status.is_finished()
+            })
+            .map(|transfer| transfer.unique_id)
+            .collect();
+        for unique_id in unfinished_transfers {
+            self.fail_file_transfer(unique_id, format!("Peer {} disconnected", peer_addr))
+                .await;
+        }
+        self.forget_swarm_source(peer_addr, &format!("Peer {} disconnected", peer_addr))
+            .await;
+        self.forget_file_requests(peer_addr).await;
+
         if let Some(removed_peer) = removed_peer {
             match &removed_peer.state {
                 PeerState::Authenticated {
                     peer_info:
                         PeerInfo {
                             name,
-                            // ecdsa_public_key,
+                            identity,
                             backend_version,
+                            ..
                         },
                 } => {
                     self.backend_event_tx
@@ -583,7 +1265,7 @@ impl PeerManager {
                                 name: name.to_string(),
                                 ip: peer_addr.ip().to_string(),
                                 backend_version: backend_version.to_string(),
-                                // identitiy: BASE64_STANDARD.encode(ecdsa_public_key),
+                                identity: identity.to_string(),
                             },
                             message,
                         }))
@@ -597,7 +1279,7 @@ impl PeerManager {
                                 name: peer_info.name.clone(),
                                 ip: peer_addr.ip().to_string(),
                                 backend_version: peer_info.backend_version.clone(),
-                                // identitiy: BASE64_STANDARD.encode(&peer_info.ecdsa_public_key),
+                                identity: peer_info.identity.clone(),
                             },
                             message: {
                                 if let Some(message) = message {
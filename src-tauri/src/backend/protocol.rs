@@ -12,12 +12,18 @@
 //! Maximum message size is 10 MB (10 * 1024 * 1024 bytes) (see [MAX_MESSAGE_SIZE])
 //!
 //! If the message size exceeds this limit, the connection will be closed immediately.
+//!
+//! Peers exchange their [PROTOCOL_VERSION] in the connect handshake, and refuse a peer of another version.
+//! A peer whose handshake messages cannot be decoded is reported to speak an incompatible version.
+//!
+//! File chunks are between [MIN_CHUNK_LEN] and [MAX_CHUNK_LEN] bytes long. The maximum is negotiated
+//! per transfer in the `FileOffer`/`FileOfferResponse` exchange (see [super::chunk_sizing]).
 
 use bincode::config::{self, Configuration};
 use once_cell::sync::Lazy;
 use uuid::Uuid;
 
-use super::peer_manager::PeerInfo;
+use super::{ecdsa_identity::encode_identity, merkle::Hash, peer_manager::PeerInfo};
 
 /// Bincode v2 Configuration static
 pub static BINCODE_CONFIG: Lazy<Configuration> = Lazy::new(|| {
@@ -29,6 +35,17 @@ pub static BINCODE_CONFIG: Lazy<Configuration> = Lazy::new(|| {
 /// Maximum message size for the protocol in bytes (10 MB)
 pub const MAX_MESSAGE_SIZE: usize = 1024 * 1024 * 10; // 10 MB
 
+/// Version of the protocol. Bumped whenever messages change in a way older versions cannot decode.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Minimum length of a file chunk in bytes (16 KB)
+pub const MIN_CHUNK_LEN: u64 = 1024 * 16; // 16 KB
+
+/// Maximum length of a file chunk in bytes (8 MB)
+///
+/// Leaves headroom below [MAX_MESSAGE_SIZE] for the rest of the `FileChunk` message.
+pub const MAX_CHUNK_LEN: u64 = 1024 * 1024 * 8; // 8 MB
+
 /// The Message Enum.
 ///
 /// This is the protocol for the backend to communicate with the frontend.
@@ -36,7 +53,7 @@ pub const MAX_MESSAGE_SIZE: usize = 1024 * 1024 * 10; // 10 MB
 pub enum Message {
     /// Keep-alive message to prevent TCP connections from timing out
     KeepAlive,
-    /// Request to connect to the peer
+    /// Request to connect to the peer, with the sender's identity (see [super::ecdsa_identity])
     ConnectRequest(ConnectionInfo),
     /// Response to a connect request
     ConnectResponse(ConnectionResponse),
@@ -54,26 +71,87 @@ pub enum Message {
     FileChunk(FileChunk),
     /// Response to a file chunk request
     FileChunkAck(FileChunkAck),
+    /// Response to a file chunk that failed verification. The chunk is to be resent at once.
+    FileChunkNack(FileChunkAck),
     /// Request to send a file done message to the peer
     FileDone(FileDone),
     /// Response to a file done request
     FileDoneResult(FileDoneResult),
+    /// Pause an in-progress file transfer (either side may send this)
+    FilePause(FilePause),
+    /// Resume a paused file transfer (either side may send this)
+    FileResume(FileResume),
+    /// Cancel a file transfer (either side may send this)
+    FileCancel(FileCancel),
+    /// Request a file (or folder) the peer shares. If approved, the peer offers it as a file transfer.
+    FileRequest(FileRequest),
+    /// Response to a file request
+    FileRequestResponse(FileRequestResponse),
+    /// Request the listing of a directory the peer shares
+    ListDirectory(ListDirectory),
+    /// Response to a directory listing request
+    DirectoryListing(DirectoryListing),
+    /// Request a byte range of a ranged file transfer (multi-source downloads),
+    /// or a file again after the data copied from its older version failed to verify
+    RangeRequest(RangeRequest),
+    /// Block signatures of an older version of an offered file the receiver has. Sent before accepting the offer.
+    BlockSignatures(BlockSignatures),
+    /// Ranges of a file the receiver copies from its older version, instead of receiving them
+    FileDelta(FileDelta),
+    /// A text chat message (see [super::chat])
+    ChatMessage(ChatMessage),
+    /// Response to a chat message (or edit, deletion or reaction): it was delivered
+    ChatMessageAck(ChatMessageAck),
+    /// The author changed the text of one of its chat messages
+    ChatEdit(ChatEdit),
+    /// The author deleted one of its chat messages
+    ChatDelete(ChatDelete),
+    /// A reaction to a chat message was added or removed
+    ChatReaction(ChatReaction),
+    /// A room was created or its members changed (or the sender left it)
+    RoomUpdate(RoomUpdate),
+    /// One of the sender's keys for a room, sealed with the session key
+    SenderKey(SealedSenderKey),
+    /// The sender is typing in a conversation with the receiver (see [super::chat_signals])
+    Typing(Typing),
+    /// The sender read a conversation with the receiver up to a message
+    ReadUpTo(ReadUpTo),
+    /// Answer to a connect request, before it is shown to the user: the responder's identity, signed
+    ConnectChallenge(ConnectionInfo),
+    /// The initiator's signature of the handshake, proving its identity
+    ConnectProof(ConnectProof),
 }
 
+/// The identity of a peer in the handshake (see [super::ecdsa_identity])
 #[derive(Debug, bincode::Encode, bincode::Decode)]
 pub struct EcdsaConnectionInfo {
+    /// The long-term public key of the peer (SEC1, compressed)
     pub public_key: Vec<u8>,
+    /// The signature of the handshake (responder only, empty otherwise)
     pub signature: Vec<u8>,
+    /// A new ephemeral secp256k1 public key for every handshake (SEC1, compressed),
+    /// from which the end-to-end encrypted session is derived (see [super::sessions])
     pub nonce: Vec<u8>,
 }
 
 #[derive(Debug, bincode::Encode, bincode::Decode)]
 pub struct ConnectionInfo {
+    /// The [PROTOCOL_VERSION] of the peer. First, so it still decodes if the rest of the message changes.
+    pub protocol_version: u32,
     pub name: String,
     // Use Cargo.toml to set the version
     pub backend_version: String,
-    // /// The ECDSA public key of the peer
-    // pub identitiy: EcdsaConnectionInfo,
+    /// The compression algorithms the peer supports for file chunks, preferred first (see [super::compression])
+    pub compression: Vec<Compression>,
+    /// The identity of the peer
+    pub identity: EcdsaConnectionInfo,
+}
+
+/// Compression algorithms for file chunks
+#[derive(Debug, Clone, Copy, PartialEq, bincode::Encode, bincode::Decode)]
+pub enum Compression {
+    Zstd,
+    Lz4,
 }
 
 impl From<ConnectionInfo> for PeerInfo {
@@ -81,11 +159,18 @@ impl From<ConnectionInfo> for PeerInfo {
         PeerInfo {
             name: info.name,
             backend_version: info.backend_version,
-            // ecdsa_public_key: info.identitiy,
+            compression: info.compression,
+            identity: encode_identity(&info.identity.public_key),
         }
     }
 }
 
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct ConnectProof {
+    /// Signature of the handshake by the initiator's identity
+    pub signature: Vec<u8>,
+}
+
 #[derive(Debug, bincode::Encode, bincode::Decode)]
 pub struct ConnectionResponse {
     pub permit: ConnectionPermit,
@@ -108,11 +193,40 @@ pub enum ConnectionPermit {
 
 #[derive(Debug, bincode::Encode, bincode::Decode)]
 pub struct FileOffer {
+    /// Name of the offer (the file name, or the folder name for a folder)
     pub filename: String,
     #[bincode(with_serde)]
     pub unique_id: Uuid,
+    /// Total size of every file in the manifest
+    pub size: u64,
+    /// Maximum chunk length the sender would like to use
+    pub max_chunk_len: u64,
+    /// The files offered, laid out back to back in this order (see [super::manifest])
+    pub manifest: Vec<ManifestEntry>,
+    /// Root of the Merkle tree over the files, back to back (see [super::merkle])
+    pub root_hash: Hash,
+    /// The compression chunks may use, if any (supported by both peers, see [super::compression])
+    pub compression: Option<Compression>,
+}
+
+#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+pub struct ManifestEntry {
+    /// Path relative to the root of the offer (`/`-separated)
+    pub path: String,
     pub size: u64,
-    pub chunk_len: u64,
+    /// BLAKE3 hash of the file
+    pub checksum: Vec<u8>,
+    /// Modification time and permissions of the file (see [super::file_metadata])
+    pub metadata: FileMetadata,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, bincode::Encode, bincode::Decode)]
+pub struct FileMetadata {
+    /// Modification time, in nanoseconds since the Unix epoch
+    pub modified: Option<u64>,
+    /// Unix permission bits (None if the sender is not on Unix)
+    pub mode: Option<u32>,
+    pub read_only: bool,
 }
 
 #[derive(Debug, bincode::Encode, bincode::Decode)]
@@ -120,15 +234,27 @@ pub struct FileOfferResponse {
     #[bincode(with_serde)]
     pub unique_id: Uuid,
     pub accept: bool,
+    /// Maximum chunk length the receiver agrees to (at most the offered `max_chunk_len`)
+    pub max_chunk_len: u64,
+    /// Why the offer was rejected, if the receiver rejected it automatically
+    pub reason: Option<String>,
+    /// Indices (in the manifest) of the files the receiver already has. They are not to be sent.
+    pub have_files: Vec<u32>,
 }
 
 #[derive(Debug, bincode::Encode, bincode::Decode)]
 pub struct FileChunk {
     #[bincode(with_serde)]
     pub unique_id: Uuid,
+    /// Sequence number of this chunk, starting at 0. Acknowledged by `FileChunkAck`.
     pub chunk_id: u64,
-    pub chunk_len: u64,
+    /// Byte offset of this chunk within the transfer (every file of the manifest, back to back)
+    pub offset: u64,
     pub data: Vec<u8>,
+    /// Merkle proof of the chunk against the offer's `root_hash` (see [super::merkle])
+    pub proof: Vec<Hash>,
+    /// Whether `data` is compressed, with the offer's compression. The proof is of the data once decompressed.
+    pub compressed: bool,
 }
 
 #[derive(Debug, bincode::Encode, bincode::Decode)]
@@ -142,7 +268,6 @@ pub struct FileChunkAck {
 pub struct FileDone {
     #[bincode(with_serde)]
     pub unique_id: Uuid,
-    pub checksum: Vec<u8>,
 }
 
 #[derive(Debug, bincode::Encode, bincode::Decode)]
@@ -152,3 +277,280 @@ pub struct FileDoneResult {
     pub success: bool,
     pub message: Option<String>,
 }
+
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct FilePause {
+    #[bincode(with_serde)]
+    pub unique_id: Uuid,
+}
+
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct FileResume {
+    #[bincode(with_serde)]
+    pub unique_id: Uuid,
+}
+
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct FileCancel {
+    #[bincode(with_serde)]
+    pub unique_id: Uuid,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct FileRequest {
+    /// Unique ID of the request. The file transfer of an approved request uses it as its unique ID.
+    #[bincode(with_serde)]
+    pub request_id: Uuid,
+    /// The requested path (`<share name>/<path inside the share>`)
+    pub path: String,
+    /// Whether the file is requested for a multi-source download.
+    /// If so, the chunks of the transfer are only sent as requested with `RangeRequest`.
+    pub ranged: bool,
+}
+
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct FileRequestResponse {
+    #[bincode(with_serde)]
+    pub request_id: Uuid,
+    /// Whether the request is approved. If so, the file offer follows.
+    pub accept: bool,
+    /// Why the request was denied
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct RangeRequest {
+    #[bincode(with_serde)]
+    pub unique_id: Uuid,
+    /// Byte offset of the range within the transfer, aligned to the Merkle tree's blocks
+    pub offset: u64,
+    pub len: u64,
+}
+
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct BlockSignatures {
+    #[bincode(with_serde)]
+    pub unique_id: Uuid,
+    /// Index of the file in the manifest
+    pub file_index: u32,
+    /// Length of the blocks (the last, shorter block of the file is left out)
+    pub block_len: u64,
+    /// Signatures of the blocks of the older version, in order (see [super::delta])
+    pub blocks: Vec<BlockSignature>,
+}
+
+#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+pub struct BlockSignature {
+    /// Rolling checksum of the block
+    pub weak: u32,
+    /// BLAKE3 hash of the block, truncated to 16 bytes
+    pub strong: Vec<u8>,
+}
+
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct FileDelta {
+    #[bincode(with_serde)]
+    pub unique_id: Uuid,
+    /// Index of the file in the manifest
+    pub file_index: u32,
+    pub copies: Vec<DeltaCopy>,
+}
+
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
+pub struct DeltaCopy {
+    /// Offset within the new version of the file
+    pub offset: u64,
+    /// Offset within the older version of the file
+    pub source_offset: u64,
+    pub len: u64,
+}
+
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct ListDirectory {
+    #[bincode(with_serde)]
+    pub request_id: Uuid,
+    /// The directory (`<share name>/<path inside the share>`). Empty to list the shares.
+    pub path: String,
+}
+
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct DirectoryListing {
+    #[bincode(with_serde)]
+    pub request_id: Uuid,
+    /// Entries of the directory, sorted by name
+    pub entries: Vec<DirectoryEntry>,
+    /// Whether there were too many entries to list them all
+    pub truncated: bool,
+    /// Why the directory could not be listed
+    pub error: Option<String>,
+}
+
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct DirectoryEntry {
+    pub name: String,
+    /// Size in bytes (0 for directories)
+    pub size: u64,
+    /// Last modification time, in seconds since the UNIX epoch
+    pub modified: Option<u64>,
+    pub is_dir: bool,
+}
+
+#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+pub struct ChatMessage {
+    #[bincode(with_serde)]
+    pub id: Uuid,
+    /// When the message was sent, in milliseconds since the Unix epoch (sender's clock)
+    pub timestamp: u64,
+    /// The text of the message (empty if it is encrypted)
+    pub body: String,
+    /// The room the message was sent to, None if it was sent to the peer only
+    #[bincode(with_serde)]
+    pub room_id: Option<Uuid>,
+    /// Lamport clock of the room when the message was sent (0 if it was sent to the peer only)
+    pub clock: u64,
+    /// The text of a room message, encrypted with the sender's key for the room
+    pub encrypted: Option<EncryptedBody>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode)]
+pub struct EncryptedBody {
+    /// ID of the sender key the body is encrypted with
+    #[bincode(with_serde)]
+    pub key_id: Uuid,
+    /// XChaCha20-Poly1305 nonce (24 bytes)
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+    /// ECDSA signature of the message, with the signing key of the sender key
+    pub signature: Vec<u8>,
+}
+
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct ChatMessageAck {
+    /// ID of the chat message (or edit, deletion or reaction) delivered
+    #[bincode(with_serde)]
+    pub id: Uuid,
+}
+
+#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+pub struct RoomUpdate {
+    #[bincode(with_serde)]
+    pub room_id: Uuid,
+    pub name: String,
+    /// The members of the room (by identity), other than the sender and the receiver
+    pub members: Vec<String>,
+    /// Whether the receiver is a member of the room
+    pub member: bool,
+    /// Whether the sender left the room
+    pub left: bool,
+    /// Lamport clock of the room when the update was made
+    pub clock: u64,
+}
+
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct ChatEdit {
+    /// ID of the edit
+    #[bincode(with_serde)]
+    pub id: Uuid,
+    /// ID of the chat message edited
+    #[bincode(with_serde)]
+    pub message_id: Uuid,
+    /// When the message was edited, in milliseconds since the Unix epoch (sender's clock)
+    pub timestamp: u64,
+    /// The room of the message, None if it was sent to the peer only
+    #[bincode(with_serde)]
+    pub room_id: Option<Uuid>,
+    /// Lamport clock of the room when the message was edited (0 if it was sent to the peer only)
+    pub clock: u64,
+    /// The new text of the message (empty if it is encrypted)
+    pub body: String,
+    /// The new text, encrypted with the sender's key for the room
+    pub encrypted: Option<EncryptedBody>,
+}
+
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct ChatDelete {
+    /// ID of the deletion
+    #[bincode(with_serde)]
+    pub id: Uuid,
+    /// ID of the chat message deleted
+    #[bincode(with_serde)]
+    pub message_id: Uuid,
+    /// When the message was deleted, in milliseconds since the Unix epoch (sender's clock)
+    pub timestamp: u64,
+    /// The room of the message, None if it was sent to the peer only
+    #[bincode(with_serde)]
+    pub room_id: Option<Uuid>,
+    /// Lamport clock of the room when the message was deleted (0 if it was sent to the peer only)
+    pub clock: u64,
+    /// Signature of the deletion (empty text), with the sender's key for the room
+    pub encrypted: Option<EncryptedBody>,
+}
+
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct ChatReaction {
+    /// ID of the reaction
+    #[bincode(with_serde)]
+    pub id: Uuid,
+    /// ID of the chat message reacted to
+    #[bincode(with_serde)]
+    pub message_id: Uuid,
+    /// When the reaction was added or removed, in milliseconds since the Unix epoch (sender's clock)
+    pub timestamp: u64,
+    /// The room of the message, None if it was sent to the peer only
+    #[bincode(with_serde)]
+    pub room_id: Option<Uuid>,
+    /// Lamport clock of the room when the reaction was made (0 if it was sent to the peer only)
+    pub clock: u64,
+    /// The emoji (empty if it is encrypted)
+    pub emoji: String,
+    /// Whether the reaction was added (or removed)
+    pub added: bool,
+    /// The emoji, encrypted with the sender's key for the room
+    pub encrypted: Option<EncryptedBody>,
+}
+
+/// A conversation between the sender and the receiver of a message
+#[derive(Debug, Clone, Copy, bincode::Encode, bincode::Decode)]
+pub enum Conversation {
+    /// The conversation between the two of them
+    Direct,
+    /// A room both are members of
+    Room {
+        #[bincode(with_serde)]
+        room_id: Uuid,
+    },
+}
+
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct Typing {
+    pub conversation: Conversation,
+}
+
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct ReadUpTo {
+    pub conversation: Conversation,
+    /// ID of the last chat message of the conversation the sender read
+    #[bincode(with_serde)]
+    pub message_id: Uuid,
+}
+
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct SealedSenderKey {
+    /// XChaCha20-Poly1305 nonce (24 bytes)
+    pub nonce: Vec<u8>,
+    /// The [SenderKey], encrypted with the session key
+    pub ciphertext: Vec<u8>,
+}
+
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct SenderKey {
+    #[bincode(with_serde)]
+    pub room_id: Uuid,
+    #[bincode(with_serde)]
+    pub key_id: Uuid,
+    /// Symmetric key the sender encrypts its messages to the room with
+    pub key: [u8; 32],
+    /// Public key verifying the sender's signatures of its messages (SEC1, compressed)
+    pub verifying_key: Vec<u8>,
+}
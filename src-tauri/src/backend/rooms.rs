@@ -0,0 +1,713 @@
+//! # Rooms
+//!
+//! Group chat: a room is a named set of peers, and a message sent to it goes to every member.
+//!
+//! - A room has a unique ID, a name and its members, by identity (see [super::ecdsa_identity]).
+//!   Each peer keeps the *other* members of the room. Rooms can only be created with, and members
+//!   only added from, peers we know (see [super::known_peers]).
+//! - Creating a room, changing its members or leaving it is announced with `RoomUpdate` to every
+//!   member, and to those removed. A peer told of a room it does not know, and that it is a member
+//!   of, joins it. Members that are not connected are told once they connect.
+//! - A message sent to a room is encrypted once (see [super::sender_keys]), then sent to every
+//!   connected member over each of its connections, and queued for the others (see [super::chat_outbox]).
+//!   It is delivered once every member acknowledged it.
+//! - Messages and updates carry the room's Lamport clock: a peer increments it for every message
+//!   or update it makes, and moves it past every one it receives. Messages are ordered by clock,
+//!   then ID, so every member sees the same history (see [super::chat_store]).
+//!   A clock more than [MAX_CLOCK_AHEAD] ahead of ours is refused, so a member cannot run it out.
+//! - Membership updates carry the clock, and are attributed to the authenticated member who sent
+//!   them. The last writer wins: an update is applied only if its `(clock, author)` is greater
+//!   than that of the last one applied, so concurrent updates resolve the same way on every
+//!   member, whatever the order they arrive in.
+//! - Only members may update a room or send messages to it. Anything else is ignored.
+//! - Once we left a room (or were removed from it), updates from other members are ignored:
+//!   they cannot make us join it again.
+//!
+//! Known limitation: a member removed while it is not connected is not told.
+//!
+//! The rooms are persisted to [ROOMS_FILE], so they survive restarts. Clocks moved by the messages
+//! received are not saved every time: they are caught up with the chat history on load.
+//! The file holds our sender keys, so like our identity it is readable by us only.
+
+use std::{collections::HashMap, net::SocketAddr, time::SystemTime};
+
+use serde::{Deserialize, Serialize};
+use tokio::{io::AsyncWriteExt, sync::mpsc};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::js_api::backend_event::{
+    self, BackendEvent, ChatHistoryMessage, ChatMessageQueued, ChatMessageState, RoomInfo,
+    RoomMember, RoomUpdated, RoomsInfo,
+};
+
+use super::{
+    chat::check_body,
+    chat_outbox::{ChatAction, OutboxMessage},
+    chat_store::{ChatStore, ConversationKey},
+    history::unix_millis,
+    known_peers::KnownPeers,
+    peer_manager::{PeerInfo, PeerManager, PeerState},
+    protocol::{Message, RoomUpdate},
+    sender_keys::OwnSenderKey,
+};
+
+/// File the rooms are saved to, next to the log file.
+pub const ROOMS_FILE: &str = "kuaip2p-rooms.json";
+
+/// How far ahead of our clock a clock received may be. Far more messages and updates than
+/// a room ever gets, and far less than it takes to run the clock out.
+pub const MAX_CLOCK_AHEAD: u64 = 1 << 32;
+
+/// A chat room.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Room {
+    pub name: String,
+    /// The other members of the room, by identity, sorted
+    pub members: Vec<String>,
+    /// Lamport clock: the latest of the messages and updates sent or received
+    pub clock: u64,
+    /// Clock of the last membership update applied
+    pub membership_clock: u64,
+    /// Identity of the member who made the last membership update applied (us included)
+    #[serde(default)]
+    pub membership_author: String,
+    /// Whether we are a member (false once we left the room, or were removed from it)
+    pub joined: bool,
+    /// Our sender keys for the room, the current one last
+    #[serde(default)]
+    pub sender_keys: Vec<OwnSenderKey>,
+}
+
+impl Room {
+    /// The room as shown to the frontend, members named after the last name they were known by.
+    pub fn info(&self, room_id: Uuid, known_peers: &KnownPeers) -> RoomInfo {
+        RoomInfo {
+            room_id: room_id.to_string(),
+            name: self.name.clone(),
+            members: self
+                .members
+                .iter()
+                .map(|identity| RoomMember {
+                    identity: identity.clone(),
+                    name: known_peers.name(identity).map(str::to_string),
+                })
+                .collect(),
+            joined: self.joined,
+        }
+    }
+
+    /// The current membership of the room, as announced to a peer (member or not).
+    pub fn update_for(&self, room_id: Uuid, peer_identity: &str, left: bool) -> RoomUpdate {
+        RoomUpdate {
+            room_id,
+            name: self.name.clone(),
+            members: self
+                .members
+                .iter()
+                .filter(|member| *member != peer_identity)
+                .cloned()
+                .collect(),
+            member: self.members.iter().any(|member| member == peer_identity),
+            left,
+            clock: self.membership_clock,
+        }
+    }
+
+    /// Move the clock past one received in a message or update.
+    /// Fails if it is more than [MAX_CLOCK_AHEAD] ahead of ours.
+    pub fn observe_clock(&mut self, clock: u64) -> Result<(), String> {
+        if clock.saturating_sub(self.clock) > MAX_CLOCK_AHEAD {
+            return Err(format!(
+                "Clock {} is too far ahead of ours ({})",
+                clock, self.clock
+            ));
+        }
+        self.clock = self.clock.max(clock);
+        Ok(())
+    }
+
+    /// The clock of the next message or update we make.
+    pub fn next_clock(&self) -> Result<u64, String> {
+        self.clock
+            .checked_add(1)
+            .ok_or_else(|| "The room's clock ran out".to_string())
+    }
+
+    /// Increment the clock for a message or update we make, and return it.
+    pub fn tick(&mut self) -> Result<u64, String> {
+        self.clock = self.next_clock()?;
+        Ok(self.clock)
+    }
+
+    /// Whether a membership update made at `clock` by `author` wins over the last one applied.
+    pub fn is_newer_membership(&self, clock: u64, author: &str) -> bool {
+        (clock, author) > (self.membership_clock, self.membership_author.as_str())
+    }
+}
+
+/// Replace a file with one readable by us only: written next to it, then renamed over it,
+/// so it is never left half written or readable by others.
+async fn write_private(path: &str, contents: &[u8]) -> std::io::Result<()> {
+    let temp_path = format!("{}.tmp", path);
+    // A leftover from a crash keeps its permissions if it is reused
+    match tokio::fs::remove_file(&temp_path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(&temp_path).await?;
+    file.write_all(contents).await?;
+    file.sync_all().await?;
+    drop(file);
+    tokio::fs::rename(&temp_path, path).await
+}
+
+/// Trim, sort and deduplicate member identities. Empty ones are dropped.
+pub fn normalize_members(members: Vec<String>) -> Vec<String> {
+    let mut members: Vec<String> = members
+        .into_iter()
+        .map(|member| member.trim().to_string())
+        .filter(|member| !member.is_empty())
+        .collect();
+    members.sort();
+    members.dedup();
+    members
+}
+
+/// The members in `members` but not in `other`.
+pub fn members_not_in(members: &[String], other: &[String]) -> Vec<String> {
+    members
+        .iter()
+        .filter(|member| !other.contains(member))
+        .cloned()
+        .collect()
+}
+
+/// Every room we know of, keyed by room ID.
+#[derive(Debug, Default)]
+pub struct Rooms {
+    rooms: HashMap<Uuid, Room>,
+}
+
+impl Rooms {
+    /// Load the rooms saved to [ROOMS_FILE]. Empty if there are none saved, or they are invalid.
+    ///
+    /// The clock of each room is moved past the messages of its history, as it is not saved
+    /// whenever a message is received.
+    pub fn load(chat_store: &ChatStore) -> Self {
+        let Ok(json) = std::fs::read_to_string(ROOMS_FILE) else {
+            return Self::default();
+        };
+        match serde_json::from_str::<HashMap<Uuid, Room>>(&json) {
+            Ok(mut rooms) => {
+                info!("Loaded {} chat rooms", rooms.len());
+                for (room_id, room) in &mut rooms {
+                    let last_clock = chat_store.last_clock(&ConversationKey::Room(*room_id));
+                    room.clock = room.clock.max(last_clock);
+                }
+                Self { rooms }
+            }
+            Err(e) => {
+                warn!("Ignoring invalid {}: {}", ROOMS_FILE, e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Save the rooms to [ROOMS_FILE], readable by us only.
+    pub async fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string(&self.rooms)
+            .map_err(|e| format!("Failed to serialize chat rooms: {}", e))?;
+        write_private(ROOMS_FILE, json.as_bytes())
+            .await
+            .map_err(|e| format!("Failed to save chat rooms: {}", e))
+    }
+
+    pub fn get(&self, room_id: &Uuid) -> Option<&Room> {
+        self.rooms.get(room_id)
+    }
+
+    pub fn get_mut(&mut self, room_id: &Uuid) -> Option<&mut Room> {
+        self.rooms.get_mut(room_id)
+    }
+
+    pub fn insert(&mut self, room_id: Uuid, room: Room) {
+        self.rooms.insert(room_id, room);
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&Uuid, &mut Room)> {
+        self.rooms.iter_mut()
+    }
+
+    /// Every room, sorted by name.
+    pub fn info(&self, known_peers: &KnownPeers) -> RoomsInfo {
+        let mut rooms: Vec<RoomInfo> = self
+            .rooms
+            .iter()
+            .map(|(room_id, room)| room.info(*room_id, known_peers))
+            .collect();
+        rooms.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.room_id.cmp(&b.room_id)));
+        RoomsInfo { rooms }
+    }
+
+    /// The joined rooms the peer (by identity) is a member of, as announced to it.
+    pub fn updates_for(&self, peer_identity: &str) -> Vec<RoomUpdate> {
+        self.rooms
+            .iter()
+            .filter(|(_, room)| room.joined && room.members.iter().any(|m| m == peer_identity))
+            .map(|(room_id, room)| room.update_for(*room_id, peer_identity, false))
+            .collect()
+    }
+}
+
+/// Parse the ID of a room, sent by the frontend.
+fn parse_room_id(room_id: &str) -> Result<Uuid, String> {
+    room_id
+        .parse()
+        .map_err(|e| format!("Invalid room ID (UUID): {}", e))
+}
+
+/// Check the members the frontend adds to a room: peers we know, other than us.
+fn check_added_members(
+    added: &[String],
+    known_peers: &KnownPeers,
+    identity: &str,
+) -> Result<(), String> {
+    if added.iter().any(|member| member == identity) {
+        return Err("We are not one of the other members of our own room".to_string());
+    }
+    match added.iter().find(|member| !known_peers.contains(member)) {
+        Some(member) => Err(format!("Unknown peer {}", member)),
+        None => Ok(()),
+    }
+}
+
+impl PeerManager {
+    /// Create a room with other peers (by identity), and invite them.
+    pub(crate) async fn create_room(
+        &self,
+        name: String,
+        members: Vec<String>,
+    ) -> Result<(), String> {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Err("Empty room name".to_string());
+        }
+        let members = normalize_members(members);
+        if members.is_empty() {
+            return Err("A room needs at least one other member".to_string());
+        }
+        let identity = self.identity.identity();
+        check_added_members(&members, &*self.known_peers.lock().await, &identity)?;
+
+        let room_id = Uuid::new_v4();
+        let room = Room {
+            name,
+            members: members.clone(),
+            clock: 1,
+            membership_clock: 1,
+            membership_author: identity,
+            joined: true,
+            sender_keys: Vec::new(),
+        };
+        {
+            let mut rooms = self.rooms.lock().await;
+            rooms.insert(room_id, room.clone());
+            if let Err(e) = rooms.save().await {
+                warn!("{}", e);
+            }
+        }
+        info!(
+            "Created room {} ({}) with {:?}",
+            room.name, room_id, members
+        );
+
+        self.announce_room_update(room_id, &room, &members, false)
+            .await;
+        self.rotate_sender_key(room_id).await;
+        self.emit_room_updated(room_id, &room, None, members, Vec::new())
+            .await;
+        Ok(())
+    }
+
+    /// Replace the other members of a room (by identity), and tell both the old and the new ones.
+    pub(crate) async fn set_room_members(
+        &self,
+        room_id: &str,
+        members: Vec<String>,
+    ) -> Result<(), String> {
+        let room_id = parse_room_id(room_id)?;
+        let members = normalize_members(members);
+        if members.is_empty() {
+            return Err("A room needs at least one other member".to_string());
+        }
+        let identity = self.identity.identity();
+
+        let (room, old_members) = {
+            let known_peers = self.known_peers.lock().await;
+            let mut rooms = self.rooms.lock().await;
+            let room = rooms
+                .get_mut(&room_id)
+                .filter(|room| room.joined)
+                .ok_or_else(|| format!("Not a member of room {}", room_id))?;
+            // Members added by others may be peers we never met
+            check_added_members(
+                &members_not_in(&members, &room.members),
+                &known_peers,
+                &identity,
+            )?;
+            room.membership_clock = room.tick()?;
+            let old_members = std::mem::replace(&mut room.members, members);
+            room.membership_author = identity;
+            let room = room.clone();
+            if let Err(e) = rooms.save().await {
+                warn!("{}", e);
+            }
+            (room, old_members)
+        };
+
+        let added = members_not_in(&room.members, &old_members);
+        let removed = members_not_in(&old_members, &room.members);
+        // The removed members are told they are no longer members
+        let mut recipients = room.members.clone();
+        recipients.extend(removed.iter().cloned());
+        self.announce_room_update(room_id, &room, &recipients, false)
+            .await;
+        // Removed members must not be able to read new messages
+        {
+            let mut received_sender_keys = self.received_sender_keys.lock().await;
+            for member in &removed {
+                received_sender_keys.forget_member(room_id, member);
+            }
+        }
+        self.rotate_sender_key(room_id).await;
+        self.emit_room_updated(room_id, &room, None, added, removed)
+            .await;
+        Ok(())
+    }
+
+    /// Leave a room, and tell its members. Its history is kept.
+    pub(crate) async fn leave_room(&self, room_id: &str) -> Result<(), String> {
+        let room_id = parse_room_id(room_id)?;
+        let room = {
+            let mut rooms = self.rooms.lock().await;
+            let room = rooms
+                .get_mut(&room_id)
+                .filter(|room| room.joined)
+                .ok_or_else(|| format!("Not a member of room {}", room_id))?;
+            room.membership_clock = room.tick()?;
+            room.membership_author = self.identity.identity();
+            room.joined = false;
+            let room = room.clone();
+            if let Err(e) = rooms.save().await {
+                warn!("{}", e);
+            }
+            room
+        };
+
+        self.announce_room_update(room_id, &room, &room.members, true)
+            .await;
+        self.received_sender_keys.lock().await.forget_room(room_id);
+        self.emit_room_updated(room_id, &room, None, Vec::new(), Vec::new())
+            .await;
+        Ok(())
+    }
+
+    /// Send a chat message to every member of a room, and notify the frontend.
+    ///
+    /// Members that are not connected get the message once they connect (see [super::chat_outbox]).
+    pub(crate) async fn send_room_message(
+        &self,
+        room_id: &str,
+        body: String,
+    ) -> Result<(), String> {
+        check_body(&body)?;
+        let room_id = parse_room_id(room_id)?;
+        let (members, clock, sender_key) = {
+            let mut rooms = self.rooms.lock().await;
+            let room = rooms
+                .get_mut(&room_id)
+                .filter(|room| room.joined)
+                .ok_or_else(|| format!("Not a member of room {}", room_id))?;
+            let clock = room.tick()?;
+            // The clock is caught up with the history on load, only a new sender key is saved
+            let created = room.sender_keys.is_empty();
+            let sent = (room.members.clone(), clock, room.sender_key().clone());
+            if created && let Err(e) = rooms.save().await {
+                warn!("{}", e);
+            }
+            sent
+        };
+
+        let mut message = OutboxMessage {
+            id: Uuid::new_v4(),
+            timestamp: unix_millis(SystemTime::now()),
+            body,
+            room_id: Some(room_id),
+            clock,
+            encrypted: None,
+            action: ChatAction::Message,
+        };
+        // Encrypted once, for every member
+        message.encrypted = Some(sender_key.encrypt(room_id, &message.header(), &message.body));
+        // Queue the message first: members may ack it at once, or be gone already
+        {
+            let mut chat_outbox = self.chat_outbox.lock().await;
+            for member in &members {
+                chat_outbox.push(member, message.clone());
+            }
+            if let Err(e) = chat_outbox.save().await {
+                warn!("{}", e);
+            }
+        }
+        let key = ConversationKey::Room(room_id);
+        self.store_chat_message(
+            &key,
+            ChatHistoryMessage {
+                id: message.id.to_string(),
+                timestamp: message.timestamp,
+                body: message.body.clone(),
+                outgoing: true,
+                author: None,
+                author_name: None,
+                clock,
+                state: ChatMessageState::Queued,
+                edited_at: None,
+                deleted: false,
+                reactions: Vec::new(),
+            },
+        )
+        .await;
+
+        let connected = self.connected_peers().await;
+        for member in members {
+            let mut sent = false;
+            // A member may be connected more than once, it ignores the copies (same ID)
+            for (peer_addr, peer_tx, peer_info) in connected
+                .iter()
+                .filter(|(_, _, peer_info)| peer_info.identity == member)
+            {
+                if peer_tx.send(message.to_message()).await.is_err() {
+                    continue;
+                }
+                sent = true;
+                self.store_chat_sent(&key, &message.id.to_string()).await;
+                self.backend_event_tx
+                    .send(BackendEvent::ChatMessageSent(backend_event::ChatMessage {
+                        peer: peer_info.into_connection_info(*peer_addr),
+                        room_id: Some(room_id.to_string()),
+                        id: message.id.to_string(),
+                        timestamp: message.timestamp,
+                        body: message.body.clone(),
+                    }))
+                    .await
+                    .expect("Failed to send ChatMessageSent event to the frontend");
+            }
+            if !sent {
+                debug!(
+                    "Member {} of room {} is not connected, chat message {} queued",
+                    member, room_id, message.id
+                );
+                self.backend_event_tx
+                    .send(BackendEvent::ChatMessageQueued(ChatMessageQueued {
+                        peer_identity: member,
+                        room_id: Some(room_id.to_string()),
+                        id: message.id.to_string(),
+                        timestamp: message.timestamp,
+                        body: message.body.clone(),
+                    }))
+                    .await
+                    .expect("Failed to send ChatMessageQueued event to the frontend");
+            }
+        }
+        Ok(())
+    }
+
+    /// Check a message received in a room, and move the room's clock past it.
+    /// The clock is not saved: it is caught up with the history on load.
+    ///
+    /// Returns false if we are not in the room, the sender (by identity) is not a member of it,
+    /// or the clock is too far ahead of ours.
+    pub(crate) async fn accept_room_message(
+        &self,
+        room_id: Uuid,
+        sender: &str,
+        clock: u64,
+    ) -> bool {
+        let mut rooms = self.rooms.lock().await;
+        let Some(room) = rooms
+            .get_mut(&room_id)
+            .filter(|room| room.joined && room.members.iter().any(|member| member == sender))
+        else {
+            return false;
+        };
+        if let Err(e) = room.observe_clock(clock) {
+            warn!(
+                "Member {} of room {} sent a message: {}. Ignoring.",
+                sender, room_id, e
+            );
+            return false;
+        }
+        true
+    }
+
+    /// Send the rooms a peer that just authenticated is a member of, so it knows about
+    /// the rooms (and membership changes) made while it was not connected.
+    pub(crate) async fn sync_rooms(&self, peer_addr: SocketAddr) {
+        let (peer_tx, peer_info) = {
+            let active_peers = self.active_peers.lock().await;
+            match active_peers.get(&peer_addr) {
+                Some(peer) => match &peer.state {
+                    PeerState::Authenticated { peer_info } => (peer.tx.clone(), peer_info.clone()),
+                    _ => return,
+                },
+                None => return,
+            }
+        };
+        let updates = self.rooms.lock().await.updates_for(&peer_info.identity);
+        for update in updates {
+            if peer_tx.send(Message::RoomUpdate(update)).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Tell peers (by identity) of the current membership of a room. Those not connected are told
+    /// once they connect, if they are still members.
+    async fn announce_room_update(
+        &self,
+        room_id: Uuid,
+        room: &Room,
+        recipients: &[String],
+        left: bool,
+    ) {
+        let connected = self.connected_peers().await;
+        for peer_identity in recipients {
+            let peer_txs: Vec<_> = connected
+                .iter()
+                .filter(|(_, _, peer_info)| peer_info.identity == *peer_identity)
+                .map(|(_, peer_tx, _)| peer_tx)
+                .collect();
+            if peer_txs.is_empty() {
+                debug!(
+                    "Peer {} is not connected, it is told of room {} once it connects",
+                    peer_identity, room_id
+                );
+            }
+            for peer_tx in peer_txs {
+                peer_tx
+                    .send(Message::RoomUpdate(room.update_for(
+                        room_id,
+                        peer_identity,
+                        left,
+                    )))
+                    .await
+                    .ok(); // We ignore the error here, as the peer may have already disconnected.
+            }
+        }
+    }
+
+    /// Tell the frontend a room was created or changed. `by`, `added` and `removed` are identities.
+    pub(crate) async fn emit_room_updated(
+        &self,
+        room_id: Uuid,
+        room: &Room,
+        by: Option<String>,
+        added: Vec<String>,
+        removed: Vec<String>,
+    ) {
+        let room = room.info(room_id, &*self.known_peers.lock().await);
+        self.backend_event_tx
+            .send(BackendEvent::RoomUpdated(RoomUpdated {
+                room,
+                by,
+                added,
+                removed,
+            }))
+            .await
+            .expect("Failed to send RoomUpdated event to the frontend");
+    }
+
+    /// The authenticated peers. A peer connected more than once is listed once per connection.
+    pub(crate) async fn connected_peers(
+        &self,
+    ) -> Vec<(SocketAddr, mpsc::Sender<Message>, PeerInfo)> {
+        self.active_peers
+            .lock()
+            .await
+            .iter()
+            .filter_map(|(peer_addr, peer)| match &peer.state {
+                PeerState::Authenticated { peer_info } => {
+                    Some((*peer_addr, peer.tx.clone(), peer_info.clone()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn room(membership_clock: u64, membership_author: &str) -> Room {
+        Room {
+            name: "room".to_string(),
+            members: Vec::new(),
+            clock: membership_clock,
+            membership_clock,
+            membership_author: membership_author.to_string(),
+            joined: true,
+            sender_keys: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn last_writer_wins() {
+        let room = room(5, "b");
+        assert!(room.is_newer_membership(6, "a"));
+        assert!(!room.is_newer_membership(4, "c"));
+        // Concurrent updates (same clock) are resolved by author, the same way on every member
+        assert!(room.is_newer_membership(5, "c"));
+        assert!(!room.is_newer_membership(5, "a"));
+        // The update already applied
+        assert!(!room.is_newer_membership(5, "b"));
+    }
+
+    #[test]
+    fn concurrent_updates_converge() {
+        let updates = [(7, "a"), (7, "c"), (6, "d")];
+        let mut winners = Vec::new();
+        for order in [[0, 1, 2], [1, 0, 2], [2, 1, 0], [2, 0, 1]] {
+            let mut room = room(5, "b");
+            for index in order {
+                let (clock, author) = updates[index];
+                if room.is_newer_membership(clock, author) {
+                    room.membership_clock = clock;
+                    room.membership_author = author.to_string();
+                }
+            }
+            winners.push((room.membership_clock, room.membership_author));
+        }
+        assert!(winners.iter().all(|winner| *winner == (7, "c".to_string())));
+    }
+
+    #[test]
+    fn clocks_far_ahead_are_refused() {
+        let mut room = room(5, "b");
+        assert!(room.observe_clock(3).is_ok());
+        assert_eq!(room.clock, 5);
+        assert!(room.observe_clock(5 + MAX_CLOCK_AHEAD).is_ok());
+        assert_eq!(room.clock, 5 + MAX_CLOCK_AHEAD);
+        assert!(room.observe_clock(u64::MAX).is_err());
+        assert_eq!(room.tick(), Ok(6 + MAX_CLOCK_AHEAD));
+
+        room.clock = u64::MAX;
+        assert!(room.tick().is_err());
+        assert_eq!(room.clock, u64::MAX);
+    }
+}
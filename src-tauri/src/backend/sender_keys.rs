@@ -0,0 +1,498 @@
+//! # Sender Keys
+//!
+//! End-to-end encryption of room messages (see [super::rooms]): a message is encrypted once,
+//! and only the members of the room can read it, whoever relays it.
+//!
+//! - Each member has its own sender key for each room: a symmetric key its messages are encrypted
+//!   with (XChaCha20-Poly1305), and an ECDSA key pair they are signed with, so that other members
+//!   cannot forge them. Everything else a message carries is authenticated with its text.
+//!   Edits, deletions and reactions in a room are encrypted and signed the same way.
+//! - A member sends its sender key to every other member with `SenderKey`, sealed with their
+//!   pairwise session (see [super::sessions]): when it makes the key, and when the member connects.
+//! - Every member makes a new sender key whenever the members of the room change (including when it
+//!   is told of a change), and forgets the keys of removed members. Removed members never get the
+//!   new keys, so they cannot read new messages.
+//! - Our previous keys are kept while messages encrypted with them wait to be delivered, and are sent
+//!   along with the current one to the members the messages are for.
+//! - A room message we have no key for is not acknowledged, so it is sent again (after the key)
+//!   on the next connection.
+//!
+//! - A key may arrive before we know of the room (the invitation may come from another member),
+//!   so keys of unknown rooms are kept too. At most [MAX_SENDER_KEYS_PER_MEMBER] keys of each
+//!   member are kept, the oldest dropped first, so a peer cannot fill our memory with keys.
+//!
+//! Our sender keys are saved with the rooms (see [super::rooms::ROOMS_FILE]). The keys of other
+//! members are only kept in memory: they are sent again on every connection.
+
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+};
+
+use k256::ecdsa::{
+    Signature, SigningKey, VerifyingKey,
+    signature::{Signer, Verifier},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use super::{
+    chat_outbox::ChatAction,
+    peer_manager::{PeerManager, PeerState},
+    protocol::{BINCODE_CONFIG, EncryptedBody, Message, SealedSenderKey, SenderKey},
+    rooms::Room,
+    sessions::{open, random_secret, seal},
+};
+
+/// Associated data of a sealed [SenderKey], so it cannot be taken for anything else
+const SENDER_KEY_AAD: &[u8] = b"kuaip2p sender key";
+
+/// Maximum number of sender keys kept for each member, over all rooms
+pub const MAX_SENDER_KEYS_PER_MEMBER: usize = 64;
+
+/// One of our sender keys for a room.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OwnSenderKey {
+    pub key_id: Uuid,
+    /// Symmetric key our messages are encrypted with
+    pub key: [u8; 32],
+    /// ECDSA secret key our messages are signed with
+    pub signing_key: [u8; 32],
+}
+
+impl std::fmt::Debug for OwnSenderKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Keep the secrets out of the logs
+        write!(f, "OwnSenderKey({})", self.key_id)
+    }
+}
+
+impl OwnSenderKey {
+    pub fn generate() -> Self {
+        Self {
+            key_id: Uuid::new_v4(),
+            key: rand::random(),
+            signing_key: random_secret().to_bytes().into(),
+        }
+    }
+
+    fn signing_key(&self) -> SigningKey {
+        SigningKey::from_slice(&self.signing_key).expect("Sender signing keys are valid scalars")
+    }
+
+    /// The key as sent to the other members of the room.
+    pub fn to_sender_key(&self, room_id: Uuid) -> SenderKey {
+        SenderKey {
+            room_id,
+            key_id: self.key_id,
+            key: self.key,
+            verifying_key: self
+                .signing_key()
+                .verifying_key()
+                .to_encoded_point(true)
+                .as_bytes()
+                .to_vec(),
+        }
+    }
+
+    /// Encrypt and sign the text of a message (or edit, deletion or reaction) to the room,
+    /// along with its [header].
+    pub fn encrypt(&self, room_id: Uuid, header: &[u8], text: &str) -> EncryptedBody {
+        let aad = associated_data(room_id, self.key_id, header);
+        let (nonce, ciphertext) = seal(&self.key, text.as_bytes(), &aad);
+        let signature: Signature = self
+            .signing_key()
+            .sign(&signed_data(&aad, &nonce, &ciphertext));
+        EncryptedBody {
+            key_id: self.key_id,
+            nonce,
+            ciphertext,
+            signature: signature.to_bytes().to_vec(),
+        }
+    }
+}
+
+/// Everything a room message (or edit, deletion or reaction) carries besides its text:
+/// its ID, time, clock, and what it is (and which message it changes).
+pub fn header(id: Uuid, timestamp: u64, clock: u64, action: &ChatAction) -> Vec<u8> {
+    let mut header = Vec::with_capacity(64);
+    header.extend_from_slice(id.as_bytes());
+    header.extend_from_slice(&timestamp.to_le_bytes());
+    header.extend_from_slice(&clock.to_le_bytes());
+    match action {
+        ChatAction::Message => header.push(0),
+        ChatAction::Edit { message_id } => {
+            header.push(1);
+            header.extend_from_slice(message_id.as_bytes());
+        }
+        ChatAction::Delete { message_id } => {
+            header.push(2);
+            header.extend_from_slice(message_id.as_bytes());
+        }
+        ChatAction::Reaction { message_id, added } => {
+            header.push(3);
+            header.extend_from_slice(message_id.as_bytes());
+            header.push(u8::from(*added));
+        }
+    }
+    header
+}
+
+/// The room, the sender key and the header, authenticated with the text.
+fn associated_data(room_id: Uuid, key_id: Uuid, header: &[u8]) -> Vec<u8> {
+    [room_id.as_bytes(), key_id.as_bytes(), header].concat()
+}
+
+/// What the sender signs: everything the receiver decrypts.
+fn signed_data(aad: &[u8], nonce: &[u8], ciphertext: &[u8]) -> Vec<u8> {
+    [aad, nonce, ciphertext].concat()
+}
+
+impl Room {
+    /// Our current sender key for the room, made if there is none yet.
+    pub fn sender_key(&mut self) -> &OwnSenderKey {
+        if self.sender_keys.is_empty() {
+            self.sender_keys.push(OwnSenderKey::generate());
+        }
+        self.sender_keys.last().expect("A sender key was just made")
+    }
+
+    /// Make a new sender key for the room. Previous keys are only kept if some message
+    /// encrypted with them still waits to be delivered (`pending`).
+    pub fn rotate_sender_key(&mut self, pending: &HashSet<Uuid>) -> &OwnSenderKey {
+        self.sender_keys
+            .retain(|sender_key| pending.contains(&sender_key.key_id));
+        self.sender_keys.push(OwnSenderKey::generate());
+        self.sender_keys.last().expect("A sender key was just made")
+    }
+}
+
+/// A sender key of another member of a room.
+struct ReceivedSenderKey {
+    key: [u8; 32],
+    verifying_key: VerifyingKey,
+    /// When the key was first received, relative to the others
+    received: u64,
+}
+
+/// The sender keys other members sent us, keyed by room, member identity and key ID.
+#[derive(Default)]
+pub struct ReceivedSenderKeys {
+    keys: HashMap<(Uuid, String, Uuid), ReceivedSenderKey>,
+    /// Number of keys received so far, to tell the oldest
+    received: u64,
+}
+
+impl std::fmt::Debug for ReceivedSenderKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Keep the secrets out of the logs
+        write!(f, "ReceivedSenderKeys({} keys)", self.keys.len())
+    }
+}
+
+impl ReceivedSenderKeys {
+    /// Keep a sender key of a member. If it already has [MAX_SENDER_KEYS_PER_MEMBER] keys,
+    /// its oldest is dropped. A key sent again keeps its place.
+    pub fn insert(&mut self, member: &str, sender_key: SenderKey) -> Result<(), String> {
+        let verifying_key = VerifyingKey::from_sec1_bytes(&sender_key.verifying_key)
+            .map_err(|e| format!("Invalid verifying key: {}", e))?;
+        let id = (sender_key.room_id, member.to_string(), sender_key.key_id);
+        let received = match self.keys.get(&id) {
+            Some(existing) => existing.received,
+            None => {
+                let member_keys = self
+                    .keys
+                    .iter()
+                    .filter(|((_, identity, _), _)| identity == member);
+                if member_keys.clone().count() >= MAX_SENDER_KEYS_PER_MEMBER
+                    && let Some(oldest) = member_keys
+                        .min_by_key(|(_, sender_key)| sender_key.received)
+                        .map(|(id, _)| id.clone())
+                {
+                    self.keys.remove(&oldest);
+                }
+                self.received += 1;
+                self.received
+            }
+        };
+        self.keys.insert(
+            id,
+            ReceivedSenderKey {
+                key: sender_key.key,
+                verifying_key,
+                received,
+            },
+        );
+        Ok(())
+    }
+
+    /// Forget the keys of a member removed from a room.
+    pub fn forget_member(&mut self, room_id: Uuid, member: &str) {
+        self.keys
+            .retain(|(room, identity, _), _| *room != room_id || identity != member);
+    }
+
+    /// Forget every key of a room we are no longer in.
+    pub fn forget_room(&mut self, room_id: Uuid) {
+        self.keys.retain(|(room, _, _), _| *room != room_id);
+    }
+
+    /// Check the signature of a message (or edit, deletion or reaction) a member sent to a room,
+    /// and decrypt its text.
+    pub fn decrypt(
+        &self,
+        room_id: Uuid,
+        member: &str,
+        header: &[u8],
+        encrypted: Option<&EncryptedBody>,
+    ) -> Result<String, String> {
+        let encrypted = encrypted.ok_or("Room message is not encrypted")?;
+        let sender_key = self
+            .keys
+            .get(&(room_id, member.to_string(), encrypted.key_id))
+            .ok_or_else(|| format!("Unknown sender key {}", encrypted.key_id))?;
+
+        let aad = associated_data(room_id, encrypted.key_id, header);
+        let signature = Signature::from_slice(&encrypted.signature)
+            .map_err(|e| format!("Invalid signature: {}", e))?;
+        sender_key
+            .verifying_key
+            .verify(
+                &signed_data(&aad, &encrypted.nonce, &encrypted.ciphertext),
+                &signature,
+            )
+            .map_err(|_| "Invalid signature".to_string())?;
+        let body = open(
+            &sender_key.key,
+            &encrypted.nonce,
+            &encrypted.ciphertext,
+            &aad,
+        )?;
+        String::from_utf8(body).map_err(|_| "Text is not valid UTF-8".to_string())
+    }
+}
+
+/// Seal a sender key with the session key of the member it is sent to.
+fn seal_sender_key(session_key: &[u8; 32], sender_key: &SenderKey) -> SealedSenderKey {
+    let plaintext = bincode::encode_to_vec(sender_key, *BINCODE_CONFIG)
+        .expect("Sender keys can always be encoded");
+    let (nonce, ciphertext) = seal(session_key, &plaintext, SENDER_KEY_AAD);
+    SealedSenderKey { nonce, ciphertext }
+}
+
+/// Open a sender key sealed with [seal_sender_key].
+pub fn open_sender_key(
+    session_key: &[u8; 32],
+    sealed: &SealedSenderKey,
+) -> Result<SenderKey, String> {
+    let plaintext = open(
+        session_key,
+        &sealed.nonce,
+        &sealed.ciphertext,
+        SENDER_KEY_AAD,
+    )?;
+    bincode::decode_from_slice(&plaintext, *BINCODE_CONFIG)
+        .map(|(sender_key, _)| sender_key)
+        .map_err(|e| format!("Invalid sender key: {}", e))
+}
+
+impl PeerManager {
+    /// Make a new sender key for a room we are in, and send it to the connected members.
+    pub(crate) async fn rotate_sender_key(&self, room_id: Uuid) {
+        let pending = self.chat_outbox.lock().await.key_ids();
+        let (sender_key, members) = {
+            let mut rooms = self.rooms.lock().await;
+            let Some(room) = rooms.get_mut(&room_id).filter(|room| room.joined) else {
+                return;
+            };
+            let sender_key = room.rotate_sender_key(&pending).to_sender_key(room_id);
+            let members = room.members.clone();
+            if let Err(e) = rooms.save().await {
+                warn!("{}", e);
+            }
+            (sender_key, members)
+        };
+        debug!("New sender key {} for room {}", sender_key.key_id, room_id);
+
+        let recipients: Vec<SocketAddr> = self
+            .active_peers
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, peer)| match &peer.state {
+                PeerState::Authenticated { peer_info } => members.contains(&peer_info.identity),
+                _ => false,
+            })
+            .map(|(peer_addr, _)| *peer_addr)
+            .collect();
+        for peer_addr in recipients {
+            self.send_sender_key(peer_addr, &sender_key).await;
+        }
+    }
+
+    /// Send a peer that just authenticated our sender keys: the current one of every
+    /// room we are in with it, and those of the messages waiting for it.
+    pub(crate) async fn send_sender_keys(&self, peer_addr: SocketAddr) {
+        let Some(peer_identity) = self
+            .active_peers
+            .lock()
+            .await
+            .get(&peer_addr)
+            .and_then(|peer| match &peer.state {
+                PeerState::Authenticated { peer_info } => Some(peer_info.identity.clone()),
+                _ => None,
+            })
+        else {
+            return;
+        };
+        let pending: HashSet<Uuid> = self
+            .chat_outbox
+            .lock()
+            .await
+            .messages(&peer_identity)
+            .iter()
+            .filter_map(|message| message.encrypted.as_ref())
+            .map(|encrypted| encrypted.key_id)
+            .collect();
+
+        let sender_keys: Vec<SenderKey> = {
+            let mut rooms = self.rooms.lock().await;
+            let mut sender_keys = Vec::new();
+            let mut created = false;
+            for (room_id, room) in rooms.iter_mut() {
+                let member = room.joined && room.members.contains(&peer_identity);
+                if member && room.sender_keys.is_empty() {
+                    created = true;
+                }
+                let current = member.then(|| room.sender_key().key_id);
+                sender_keys.extend(
+                    room.sender_keys
+                        .iter()
+                        .filter(|sender_key| {
+                            Some(sender_key.key_id) == current
+                                || pending.contains(&sender_key.key_id)
+                        })
+                        .map(|sender_key| sender_key.to_sender_key(*room_id)),
+                );
+            }
+            if created && let Err(e) = rooms.save().await {
+                warn!("{}", e);
+            }
+            sender_keys
+        };
+        for sender_key in sender_keys {
+            self.send_sender_key(peer_addr, &sender_key).await;
+        }
+    }
+
+    /// Send one of our sender keys to a peer, sealed with its session key.
+    /// Not sent if the handshake is not done yet: it is sent once the peer is authenticated.
+    async fn send_sender_key(&self, peer_addr: SocketAddr, sender_key: &SenderKey) {
+        let Some(session_key) = self.session_key(peer_addr).await else {
+            return;
+        };
+        let Some(peer_tx) = self
+            .active_peers
+            .lock()
+            .await
+            .get(&peer_addr)
+            .map(|peer| peer.tx.clone())
+        else {
+            return;
+        };
+        peer_tx
+            .send(Message::SenderKey(seal_sender_key(
+                &session_key,
+                sender_key,
+            )))
+            .await
+            .ok(); // We ignore the error here, as the peer may have already disconnected.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decrypt_checks_the_signature() {
+        let room_id = Uuid::new_v4();
+        let own = OwnSenderKey::generate();
+        let mut received = ReceivedSenderKeys::default();
+        received
+            .insert("alice", own.to_sender_key(room_id))
+            .unwrap();
+        let header = header(Uuid::new_v4(), 1, 1, &ChatAction::Message);
+        let encrypted = own.encrypt(room_id, &header, "hello");
+
+        assert_eq!(
+            received
+                .decrypt(room_id, "alice", &header, Some(&encrypted))
+                .unwrap(),
+            "hello"
+        );
+        // Sent by another member
+        assert!(
+            received
+                .decrypt(room_id, "bob", &header, Some(&encrypted))
+                .is_err()
+        );
+
+        let mut altered = encrypted.clone();
+        altered.signature[0] ^= 1;
+        assert!(
+            received
+                .decrypt(room_id, "alice", &header, Some(&altered))
+                .is_err()
+        );
+
+        // Forged by someone who has the symmetric key, but not the signing key
+        let forger = OwnSenderKey {
+            signing_key: random_secret().to_bytes().into(),
+            ..own.clone()
+        };
+        let forged = forger.encrypt(room_id, &header, "hello");
+        assert!(
+            received
+                .decrypt(room_id, "alice", &header, Some(&forged))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn keys_of_a_member_are_capped() {
+        let room_id = Uuid::new_v4();
+        let mut received = ReceivedSenderKeys::default();
+        let first = OwnSenderKey::generate();
+        received
+            .insert("alice", first.to_sender_key(room_id))
+            .unwrap();
+        for _ in 1..MAX_SENDER_KEYS_PER_MEMBER {
+            received
+                .insert(
+                    "alice",
+                    OwnSenderKey::generate().to_sender_key(Uuid::new_v4()),
+                )
+                .unwrap();
+        }
+        received
+            .insert("bob", OwnSenderKey::generate().to_sender_key(room_id))
+            .unwrap();
+        // Sent again: nothing dropped
+        received
+            .insert("alice", first.to_sender_key(room_id))
+            .unwrap();
+        assert_eq!(received.keys.len(), MAX_SENDER_KEYS_PER_MEMBER + 1);
+
+        received
+            .insert("alice", OwnSenderKey::generate().to_sender_key(room_id))
+            .unwrap();
+        assert_eq!(received.keys.len(), MAX_SENDER_KEYS_PER_MEMBER + 1);
+        assert!(
+            !received
+                .keys
+                .contains_key(&(room_id, "alice".to_string(), first.key_id))
+        );
+    }
+}
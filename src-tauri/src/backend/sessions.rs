@@ -0,0 +1,184 @@
+//! # Sessions
+//!
+//! An end-to-end encrypted session with each authenticated peer, to send it secrets
+//! (the sender keys of rooms, see [super::sender_keys]) that only it can read.
+//!
+//! - The session is agreed in the identity handshake (see [super::ecdsa_identity]): the nonce
+//!   each side sends is a new ephemeral secp256k1 public key.
+//! - Both derive the session key from the ECDH shared secret and the whole handshake: both
+//!   identities and both ephemeral keys. Each side signs them with its identity, so the session
+//!   is bound to both identities, and a man in the middle cannot swap the ephemeral keys.
+//!   Data sealed with it (XChaCha20-Poly1305, random nonce) can only be opened by the two peers.
+//! - A session lasts as long as the connection. A new one is made on every connection, and the
+//!   ephemeral secrets are forgotten once the handshake is done.
+//! - Once the peer is authenticated, we send it our sender keys for the rooms it is in,
+//!   then the chat messages waiting for it (see [super::chat_outbox]).
+
+use std::net::SocketAddr;
+
+use chacha20poly1305::{
+    KeyInit, XChaCha20Poly1305, XNonce,
+    aead::{Aead, Payload},
+};
+use k256::{PublicKey, SecretKey, ecdh::diffie_hellman, elliptic_curve::sec1::ToEncodedPoint};
+
+use super::{
+    ecdsa_identity::handshake_parts, peer_manager::PeerManager, protocol::EcdsaConnectionInfo,
+};
+
+/// Context of the session key derivation
+const SESSION_KEY_CONTEXT: &str = "kuaip2p 2025-06 pairwise session key";
+
+/// Length of an XChaCha20-Poly1305 nonce, in bytes
+const NONCE_LEN: usize = 24;
+
+/// The end-to-end encrypted session with a peer.
+pub struct PeerSession {
+    pub key: [u8; 32],
+}
+
+impl std::fmt::Debug for PeerSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Keep the secret out of the logs
+        write!(f, "PeerSession")
+    }
+}
+
+/// A new ephemeral secret key.
+pub fn random_secret() -> SecretKey {
+    loop {
+        // Fails only for the (astronomically unlikely) bytes that are not a valid scalar
+        if let Ok(secret) = SecretKey::from_slice(&rand::random::<[u8; 32]>()) {
+            return secret;
+        }
+    }
+}
+
+/// The public key of a secret key, as sent to peers (SEC1, compressed).
+pub fn public_key_bytes(secret: &SecretKey) -> Vec<u8> {
+    secret
+        .public_key()
+        .to_encoded_point(true)
+        .as_bytes()
+        .to_vec()
+}
+
+/// Derive the session key of a handshake, from our ephemeral secret and the peer's ephemeral
+/// public key (its nonce).
+///
+/// `initiator` and `responder` are the identity infos of the handshake, in the same order on both sides.
+pub fn derive_session_key(
+    secret: &SecretKey,
+    peer_nonce: &[u8],
+    initiator: &EcdsaConnectionInfo,
+    responder: &EcdsaConnectionInfo,
+) -> Result<[u8; 32], String> {
+    let peer_public = PublicKey::from_sec1_bytes(peer_nonce)
+        .map_err(|e| format!("Invalid handshake key: {}", e))?;
+    let shared = diffie_hellman(secret.to_nonzero_scalar(), peer_public.as_affine());
+
+    let mut material = shared.raw_secret_bytes().to_vec();
+    material.extend_from_slice(&handshake_parts(initiator, responder));
+    Ok(blake3::derive_key(SESSION_KEY_CONTEXT, &material))
+}
+
+/// Encrypt and authenticate data with a symmetric key. Returns the random nonce and the ciphertext.
+pub fn seal(key: &[u8; 32], plaintext: &[u8], aad: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let nonce = rand::random::<[u8; NONCE_LEN]>();
+    let ciphertext = XChaCha20Poly1305::new(key.into())
+        .encrypt(
+            &XNonce::from(nonce),
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .expect("XChaCha20-Poly1305 encryption cannot fail");
+    (nonce.to_vec(), ciphertext)
+}
+
+/// Decrypt data sealed with [seal]. Fails if it was not sealed with this key and `aad`, or was altered.
+pub fn open(
+    key: &[u8; 32],
+    nonce: &[u8],
+    ciphertext: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>, String> {
+    let nonce: [u8; NONCE_LEN] = nonce
+        .try_into()
+        .map_err(|_| format!("Invalid nonce length {}", nonce.len()))?;
+    XChaCha20Poly1305::new(key.into())
+        .decrypt(
+            &XNonce::from(nonce),
+            Payload {
+                msg: ciphertext,
+                aad,
+            },
+        )
+        .map_err(|_| "Failed to decrypt: wrong key, or altered data".to_string())
+}
+
+impl PeerManager {
+    /// The session key with a peer, once its handshake is done.
+    pub(crate) async fn session_key(&self, peer_addr: SocketAddr) -> Option<[u8; 32]> {
+        let active_peers = self.active_peers.lock().await;
+        active_peers
+            .get(&peer_addr)?
+            .session
+            .as_ref()
+            .map(|session| session.key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(nonce: Vec<u8>) -> EcdsaConnectionInfo {
+        EcdsaConnectionInfo {
+            public_key: public_key_bytes(&random_secret()),
+            signature: Vec::new(),
+            nonce,
+        }
+    }
+
+    #[test]
+    fn sealed_data_opens_only_unaltered() {
+        let key = rand::random::<[u8; 32]>();
+        let (nonce, ciphertext) = seal(&key, b"secret", b"aad");
+        assert_eq!(open(&key, &nonce, &ciphertext, b"aad").unwrap(), b"secret");
+
+        let mut altered = ciphertext.clone();
+        altered[0] ^= 1;
+        assert!(open(&key, &nonce, &altered, b"aad").is_err());
+        assert!(open(&key, &nonce, &ciphertext, b"other aad").is_err());
+        assert!(open(&rand::random(), &nonce, &ciphertext, b"aad").is_err());
+        assert!(open(&key, &nonce[1..], &ciphertext, b"aad").is_err());
+    }
+
+    #[test]
+    fn both_sides_derive_the_same_session_key() {
+        let (initiator_secret, responder_secret) = (random_secret(), random_secret());
+        let initiator = info(public_key_bytes(&initiator_secret));
+        let responder = info(public_key_bytes(&responder_secret));
+
+        let initiator_key =
+            derive_session_key(&initiator_secret, &responder.nonce, &initiator, &responder)
+                .unwrap();
+        let responder_key =
+            derive_session_key(&responder_secret, &initiator.nonce, &initiator, &responder)
+                .unwrap();
+        assert_eq!(initiator_key, responder_key);
+
+        // Bound to the identities of the handshake
+        let other = EcdsaConnectionInfo {
+            public_key: public_key_bytes(&random_secret()),
+            ..info(responder.nonce.clone())
+        };
+        let other_key =
+            derive_session_key(&initiator_secret, &responder.nonce, &initiator, &other).unwrap();
+        assert_ne!(initiator_key, other_key);
+
+        assert!(derive_session_key(&initiator_secret, &[0; 33], &initiator, &responder).is_err());
+    }
+}
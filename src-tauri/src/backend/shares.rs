@@ -0,0 +1,540 @@
+//! # Shared Folders
+//!
+//! Folders we publish to our peers, so they can pull files from them with `FileRequest`.
+//!
+//! - Shared paths are addressed as `<share name>/<path inside the share>` (`/`-separated).
+//! - A path is resolved inside its share, and must stay inside it (symlinks included).
+//! - Each share may be limited to some peers (by identity, see [super::ecdsa_identity]), and may
+//!   approve requests automatically. Otherwise the frontend is asked for every request.
+//! - An approved request is sent through the normal transfer queue, the transfer
+//!   using the request ID as its unique ID so the requester can match the offer. Requests (and
+//!   directory listings) not answered with an offer (or listing) within [FILE_REQUEST_TIMEOUT]
+//!   are forgotten: a late offer is then shown to the frontend like any other.
+//! - A file may also be requested for a multi-source download (see [super::swarm]). The transfer
+//!   then only sends the byte ranges the requester asks for with `RangeRequest`.
+//! - Peers may browse the shares they may access with `ListDirectory`. The empty path lists the shares.
+//!   Listings skip symlinks, like folder transfers do.
+//!
+//! Shares are persisted to [SHARED_FOLDERS_FILE], and replaced with `FrontendEvent::SetSharedFolders`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::js_api::frontend_event::SharedFolder;
+
+use super::{
+    manifest::{Manifest, sanitize_path},
+    peer_manager::PeerManager,
+    protocol::{DirectoryEntry, FileRequestResponse, Message},
+};
+
+/// File the shares are saved to, next to the log file.
+pub const SHARED_FOLDERS_FILE: &str = "kuaip2p-shares.json";
+
+/// Maximum number of entries in a directory listing. Keeps the listing well below [super::protocol::MAX_MESSAGE_SIZE].
+const MAX_DIRECTORY_ENTRIES: usize = 10_000;
+
+/// How long a file request we sent waits for the peer's offer (or listing)
+pub const FILE_REQUEST_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// A pending file (or directory listing) request, sent to or received from a peer.
+#[derive(Debug, Clone)]
+pub struct FileRequestState {
+    /// The peer the request was sent to, or received from
+    pub peer_addr: SocketAddr,
+    /// The requested path (`<share name>/<path inside the share>`)
+    pub path: String,
+    /// Where the requested file is on disk (received requests only)
+    pub local_path: Option<PathBuf>,
+    /// Whether the file is requested for a multi-source download (see [super::swarm])
+    pub ranged: bool,
+    /// When the request was sent or received
+    pub created_at: Instant,
+}
+
+impl FileRequestState {
+    /// Has the request waited longer than [FILE_REQUEST_TIMEOUT]?
+    pub fn expired(&self) -> bool {
+        self.created_at.elapsed() > FILE_REQUEST_TIMEOUT
+    }
+}
+
+/// Forget the requests we sent that expired.
+pub fn expire_file_requests(requests: &mut HashMap<Uuid, FileRequestState>) {
+    requests.retain(|_, request| !request.expired());
+}
+
+/// The folders we share with our peers.
+#[derive(Debug, Default)]
+pub struct SharedFolders {
+    shares: Vec<SharedFolder>,
+}
+
+impl SharedFolders {
+    /// Check the shares: unique plain names, and existing folders.
+    pub fn new(shares: Vec<SharedFolder>) -> Result<Self, String> {
+        let mut names = HashSet::new();
+        for share in &shares {
+            if sanitize_path(&share.name).is_none_or(|name| name.components().count() != 1) {
+                return Err(format!("Invalid share name: {}", share.name));
+            }
+            if !names.insert(share.name.as_str()) {
+                return Err(format!("Two shares are named {}", share.name));
+            }
+            if !Path::new(&share.path).is_dir() {
+                return Err(format!(
+                    "Share {}: folder {} does not exist",
+                    share.name, share.path
+                ));
+            }
+        }
+        Ok(Self { shares })
+    }
+
+    /// Load the shares saved to [SHARED_FOLDERS_FILE]. No shares if there are none saved, or they are invalid.
+    pub fn load() -> Self {
+        let Ok(json) = std::fs::read_to_string(SHARED_FOLDERS_FILE) else {
+            return Self::default();
+        };
+        match serde_json::from_str(&json)
+            .map_err(|e| e.to_string())
+            .and_then(Self::new)
+        {
+            Ok(shares) => {
+                info!("Loaded {} shared folders", shares.shares.len());
+                shares
+            }
+            Err(e) => {
+                warn!("Ignoring invalid {}: {}", SHARED_FOLDERS_FILE, e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Save the shares to [SHARED_FOLDERS_FILE].
+    pub async fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&self.shares)
+            .map_err(|e| format!("Failed to serialize shared folders: {}", e))?;
+        tokio::fs::write(SHARED_FOLDERS_FILE, json)
+            .await
+            .map_err(|e| format!("Failed to save shared folders: {}", e))
+    }
+
+    /// The shares.
+    pub fn shares(&self) -> &[SharedFolder] {
+        &self.shares
+    }
+
+    /// The shares the peer (by identity) may access.
+    fn visible(&self, peer_identity: &str) -> impl Iterator<Item = &SharedFolder> {
+        self.shares.iter().filter(move |share| {
+            share.peer_identities.is_empty()
+                || share
+                    .peer_identities
+                    .iter()
+                    .any(|identity| identity == peer_identity)
+        })
+    }
+
+    /// The share a path is in, and the rest of the path (None for the root of the share),
+    /// if the peer may access it.
+    fn find(
+        &self,
+        peer_identity: &str,
+        path: &str,
+    ) -> Result<(SharedFolder, Option<PathBuf>), String> {
+        let (name, rest) = match path.split_once('/') {
+            Some((name, rest)) => (name, Some(rest)),
+            None => (path, None),
+        };
+        let share = self
+            .visible(peer_identity)
+            .find(|share| share.name == name)
+            .ok_or(format!("No such shared folder: {}", name))?;
+        let rest = match rest.filter(|rest| !rest.is_empty()) {
+            Some(rest) => Some(sanitize_path(rest).ok_or(format!("Invalid path: {}", path))?),
+            None => None,
+        };
+        Ok((share.clone(), rest))
+    }
+}
+
+impl PeerManager {
+    /// Resolve a shared path requested by a peer to the file or folder on disk.
+    ///
+    /// Fails if the path is not in a share the peer may access, does not exist,
+    /// or leaves the share (e.g. through a symlink).
+    pub(crate) async fn resolve_shared_path(
+        &self,
+        peer_identity: &str,
+        path: &str,
+    ) -> Result<(SharedFolder, PathBuf), String> {
+        let (share, rest) = self.shared_folders.lock().await.find(peer_identity, path)?;
+        let local_path = resolve_in_share(&share, rest, path).await?;
+        Ok((share, local_path))
+    }
+
+    /// List a shared directory requested by a peer. The empty path lists the shares the peer may access.
+    ///
+    /// Returns the entries sorted by name, and whether the listing was truncated.
+    pub(crate) async fn list_shared_directory(
+        &self,
+        peer_identity: &str,
+        path: &str,
+    ) -> Result<(Vec<DirectoryEntry>, bool), String> {
+        let path = path.trim_matches('/');
+        let mut entries = Vec::new();
+
+        if path.is_empty() {
+            let shares: Vec<SharedFolder> = self
+                .shared_folders
+                .lock()
+                .await
+                .visible(peer_identity)
+                .cloned()
+                .collect();
+            for share in shares {
+                let modified = tokio::fs::metadata(&share.path)
+                    .await
+                    .ok()
+                    .and_then(|metadata| modified_secs(&metadata));
+                entries.push(DirectoryEntry {
+                    name: share.name,
+                    size: 0,
+                    modified,
+                    is_dir: true,
+                });
+            }
+            return Ok((entries, false));
+        }
+
+        let (_, local_path) = self.resolve_shared_path(peer_identity, path).await?;
+        read_shared_directory(&local_path, path).await
+    }
+
+    /// Forget the file requests sent to or received from a peer.
+    pub(crate) async fn forget_file_requests(&self, peer_addr: SocketAddr) {
+        for requests in [
+            &self.sent_file_requests,
+            &self.received_file_requests,
+            &self.directory_requests,
+        ] {
+            requests
+                .lock()
+                .await
+                .retain(|_, request| request.peer_addr != peer_addr);
+        }
+    }
+
+    /// Approve a file request received from a peer: queue the transfer of the file
+    /// (under the request ID), and tell the peer.
+    pub(crate) async fn approve_file_request(
+        &self,
+        request_id: Uuid,
+        request: FileRequestState,
+    ) -> Result<(), String> {
+        let local_path = request
+            .local_path
+            .ok_or("The file request has no local path")?;
+        let filename = request
+            .path
+            .rsplit('/')
+            .next()
+            .unwrap_or(&request.path)
+            .to_string();
+        let manifest = Manifest::from_local_paths(&[local_path], &filename).await?;
+
+        self.send_file_request_response(request.peer_addr, request_id, true, None)
+            .await?;
+        self.queue_file_transfer(
+            request_id,
+            request.peer_addr,
+            filename,
+            manifest,
+            0,
+            request.ranged,
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Deny a file request received from a peer, telling the peer why.
+    pub(crate) async fn deny_file_request(
+        &self,
+        request_id: Uuid,
+        peer_addr: SocketAddr,
+        reason: String,
+    ) {
+        info!(
+            "Denying file request {} from {}: {}",
+            request_id, peer_addr, reason
+        );
+        // We ignore the error here, as the peer may have already disconnected.
+        self.send_file_request_response(peer_addr, request_id, false, Some(reason))
+            .await
+            .ok();
+    }
+
+    async fn send_file_request_response(
+        &self,
+        peer_addr: SocketAddr,
+        request_id: Uuid,
+        accept: bool,
+        reason: Option<String>,
+    ) -> Result<(), String> {
+        let peer_tx = self
+            .active_peers
+            .lock()
+            .await
+            .get(&peer_addr)
+            .map(|peer| peer.tx.clone())
+            .ok_or(format!("Peer {} is no longer connected", peer_addr))?;
+        peer_tx
+            .send(Message::FileRequestResponse(FileRequestResponse {
+                request_id,
+                accept,
+                reason,
+            }))
+            .await
+            .map_err(|e| {
+                format!(
+                    "Failed to send FileRequestResponse message to the peer: {}",
+                    e
+                )
+            })
+    }
+}
+
+/// List the entries of a shared directory (`path` is only used in errors), sorted by name,
+/// and whether the listing was truncated. Symlinks are skipped.
+async fn read_shared_directory(
+    local_path: &Path,
+    path: &str,
+) -> Result<(Vec<DirectoryEntry>, bool), String> {
+    let mut entries = Vec::new();
+    let mut read_dir = tokio::fs::read_dir(&local_path)
+        .await
+        .map_err(|_| format!("Not a folder: {}", path))?;
+    let mut truncated = false;
+    while let Some(entry) = read_dir
+        .next_entry()
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?
+    {
+        if entries.len() == MAX_DIRECTORY_ENTRIES {
+            truncated = true;
+            break;
+        }
+        // Symlinks may lead out of the share, and are never sent
+        let Ok(metadata) = tokio::fs::symlink_metadata(entry.path()).await else {
+            continue;
+        };
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        if !metadata.is_dir() && !metadata.is_file() {
+            continue;
+        }
+        entries.push(DirectoryEntry {
+            name,
+            size: if metadata.is_file() {
+                metadata.len()
+            } else {
+                0
+            },
+            modified: modified_secs(&metadata),
+            is_dir: metadata.is_dir(),
+        });
+    }
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok((entries, truncated))
+}
+
+/// Resolve `rest` (a sanitized path, None for the root) inside a share, to the file or folder on disk.
+///
+/// Fails if it does not exist, or leaves the share (e.g. through a symlink).
+async fn resolve_in_share(
+    share: &SharedFolder,
+    rest: Option<PathBuf>,
+    path: &str,
+) -> Result<PathBuf, String> {
+    let not_found = |_| format!("No such file or folder: {}", path);
+    let root = tokio::fs::canonicalize(&share.path)
+        .await
+        .map_err(not_found)?;
+    let local_path = match rest {
+        Some(rest) => tokio::fs::canonicalize(root.join(rest))
+            .await
+            .map_err(not_found)?,
+        None => root.clone(),
+    };
+    if !local_path.starts_with(&root) {
+        return Err(format!("No such file or folder: {}", path));
+    }
+    Ok(local_path)
+}
+
+/// Last modification time, in seconds since the UNIX epoch.
+fn modified_secs(metadata: &std::fs::Metadata) -> Option<u64> {
+    metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|modified| modified.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A share with `docs/a.txt`, next to a `secret` file outside it.
+    async fn share_dir() -> (PathBuf, SharedFolder) {
+        let dir = std::env::temp_dir().join(format!("kuaip2p-shares-{}", Uuid::new_v4()));
+        let root = dir.join("share");
+        tokio::fs::create_dir_all(root.join("docs")).await.unwrap();
+        tokio::fs::write(root.join("docs/a.txt"), b"shared")
+            .await
+            .unwrap();
+        tokio::fs::write(dir.join("secret"), b"secret")
+            .await
+            .unwrap();
+        let share = SharedFolder {
+            name: "share".to_string(),
+            path: root.display().to_string(),
+            peer_identities: Vec::new(),
+            auto_approve: false,
+        };
+        (dir, share)
+    }
+
+    async fn resolve(
+        shares: &SharedFolders,
+        peer_identity: &str,
+        path: &str,
+    ) -> Result<PathBuf, String> {
+        let (share, rest) = shares.find(peer_identity, path)?;
+        resolve_in_share(&share, rest, path).await
+    }
+
+    #[tokio::test]
+    async fn paths_resolve_inside_their_share() {
+        let (dir, share) = share_dir().await;
+        let shares = SharedFolders::new(vec![share]).unwrap();
+        let root = tokio::fs::canonicalize(dir.join("share")).await.unwrap();
+
+        assert_eq!(resolve(&shares, "alice", "share").await.unwrap(), root);
+        assert_eq!(
+            resolve(&shares, "alice", "share/docs/a.txt").await.unwrap(),
+            root.join("docs/a.txt")
+        );
+        for path in [
+            "share/../secret",
+            "share/docs/../../secret",
+            "share//etc/passwd",
+            "/etc/passwd",
+            "share/missing",
+            "other/docs/a.txt",
+        ] {
+            assert!(resolve(&shares, "alice", path).await.is_err(), "{}", path);
+        }
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlinks_may_not_leave_the_share() {
+        let (dir, share) = share_dir().await;
+        let root = dir.join("share");
+        std::os::unix::fs::symlink(dir.join("secret"), root.join("escape")).unwrap();
+        std::os::unix::fs::symlink(&dir, root.join("parent")).unwrap();
+        std::os::unix::fs::symlink(root.join("docs/a.txt"), root.join("inner")).unwrap();
+        let shares = SharedFolders::new(vec![share]).unwrap();
+
+        assert!(resolve(&shares, "alice", "share/escape").await.is_err());
+        assert!(
+            resolve(&shares, "alice", "share/parent/secret")
+                .await
+                .is_err()
+        );
+        assert!(resolve(&shares, "alice", "share/inner").await.is_ok());
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn shares_are_limited_to_their_peers() {
+        let (dir, share) = share_dir().await;
+        let shares = SharedFolders::new(vec![SharedFolder {
+            peer_identities: vec!["alice".to_string()],
+            ..share
+        }])
+        .unwrap();
+
+        assert!(resolve(&shares, "alice", "share/docs/a.txt").await.is_ok());
+        assert!(resolve(&shares, "bob", "share/docs/a.txt").await.is_err());
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    async fn list(shares: &SharedFolders, path: &str) -> Result<Vec<(String, u64, bool)>, String> {
+        let local_path = resolve(shares, "alice", path).await?;
+        let (entries, truncated) = read_shared_directory(&local_path, path).await?;
+        assert!(!truncated);
+        Ok(entries
+            .into_iter()
+            .map(|entry| (entry.name, entry.size, entry.is_dir))
+            .collect())
+    }
+
+    #[tokio::test]
+    async fn listings_are_sorted_and_skip_symlinks() {
+        let (dir, share) = share_dir().await;
+        let root = dir.join("share");
+        tokio::fs::write(root.join("b.bin"), [0; 5]).await.unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.join("secret"), root.join("escape")).unwrap();
+        let shares = SharedFolders::new(vec![share]).unwrap();
+
+        assert_eq!(
+            list(&shares, "share").await.unwrap(),
+            vec![
+                ("b.bin".to_string(), 5, false),
+                ("docs".to_string(), 0, true)
+            ]
+        );
+        assert_eq!(
+            list(&shares, "share/docs").await.unwrap(),
+            vec![("a.txt".to_string(), 6, false)]
+        );
+        assert!(list(&shares, "share/docs/a.txt").await.is_err());
+        assert!(list(&shares, "share/..").await.is_err());
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[test]
+    fn invalid_shares_are_refused() {
+        let share = |name: &str, path: &Path| SharedFolder {
+            name: name.to_string(),
+            path: path.display().to_string(),
+            peer_identities: Vec::new(),
+            auto_approve: false,
+        };
+        let dir = std::env::temp_dir();
+        assert!(SharedFolders::new(vec![share("a/b", &dir)]).is_err());
+        assert!(SharedFolders::new(vec![share("..", &dir)]).is_err());
+        assert!(SharedFolders::new(vec![share("a", &dir), share("a", &dir)]).is_err());
+        assert!(
+            SharedFolders::new(vec![share("a", &dir.join(Uuid::new_v4().to_string()))]).is_err()
+        );
+    }
+}
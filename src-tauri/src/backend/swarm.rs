@@ -0,0 +1,669 @@
+//! # Multi-Source Downloads
+//!
+//! Downloads the same content from several peers at once, each sending different byte ranges.
+//!
+//! - The frontend names a shared path on every source. Every source is sent a ranged `FileRequest`
+//!   under the same ID, which becomes the unique ID of the download.
+//! - The first offer starts the download: a receiving transfer with a [SwarmDownload].
+//!   Later offers join it if they have the same root hash (see [super::merkle]) and size,
+//!   whatever the file names. Otherwise they are rejected.
+//! - The transfer is split into [SEGMENT_LEN] segments. Each source is asked for up to
+//!   [SEGMENTS_PER_SOURCE] segments at a time with `RangeRequest`, so faster sources get more of them.
+//! - Once every segment has been requested, an idle source is also asked for the oldest segment
+//!   another source still owes, so a slow source does not hold up the end of the download.
+//!   A chunk received twice is written again, but only counted once.
+//! - A source that disconnects or cancels hands its segments back to the others.
+//!   The download only fails once no source is left.
+//! - Once every byte is received and verified, every source is sent `FileDoneResult`.
+
+use std::{collections::BTreeSet, net::SocketAddr, time::Instant};
+
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::js_api::backend_event::{BackendEvent, SwarmSourceInfo, SwarmSourcesInfo};
+
+use super::{
+    byte_ranges::ByteRanges,
+    merkle::BLOCK_LEN,
+    peer_manager::{FileTransferStatus, PeerManager, PeerState},
+    protocol::{
+        FileCancel, FileDoneResult, FileOffer, FileOfferResponse, FileRequest, MIN_CHUNK_LEN,
+        Message, RangeRequest,
+    },
+    shares::FileRequestState,
+};
+
+/// Length of the segments requested from the sources (4 MB). A multiple of [BLOCK_LEN].
+pub const SEGMENT_LEN: u64 = 256 * BLOCK_LEN;
+
+/// Number of segments requested from a source at a time.
+const SEGMENTS_PER_SOURCE: usize = 2;
+
+/// A source of a multi-source download.
+#[derive(Debug)]
+pub struct SwarmSource {
+    /// IP/Socket address of the source
+    pub peer_addr: SocketAddr,
+    /// Segments requested from the source, and not fully received yet, with when they were requested
+    requested: Vec<(u64, Instant)>,
+    /// Bytes received from the source (chunks received twice included)
+    pub bytes_received: u64,
+}
+
+/// The sources of a multi-source download, and the segments requested from each.
+#[derive(Debug)]
+pub struct SwarmDownload {
+    /// The sources whose offer joined the download
+    sources: Vec<SwarmSource>,
+    /// Segments not requested from any source yet
+    unassigned: BTreeSet<u64>,
+    /// The size of every file being downloaded
+    total_size: u64,
+    /// Every byte was received, and the files are being verified
+    pub finishing: bool,
+}
+
+impl SwarmDownload {
+    pub fn new(total_size: u64) -> Self {
+        Self {
+            sources: Vec::new(),
+            unassigned: (0..total_size.div_ceil(SEGMENT_LEN)).collect(),
+            total_size,
+            finishing: false,
+        }
+    }
+
+    /// The sources whose offer joined the download.
+    pub fn sources(&self) -> &[SwarmSource] {
+        &self.sources
+    }
+
+    pub fn has_source(&self, peer_addr: SocketAddr) -> bool {
+        self.sources
+            .iter()
+            .any(|source| source.peer_addr == peer_addr)
+    }
+
+    pub fn add_source(&mut self, peer_addr: SocketAddr) {
+        if !self.has_source(peer_addr) {
+            self.sources.push(SwarmSource {
+                peer_addr,
+                requested: Vec::new(),
+                bytes_received: 0,
+            });
+        }
+    }
+
+    /// Remove a source, handing back the segments no other source was asked for.
+    ///
+    /// Returns false if it was not a source.
+    pub fn remove_source(&mut self, peer_addr: SocketAddr) -> bool {
+        let Some(index) = self
+            .sources
+            .iter()
+            .position(|source| source.peer_addr == peer_addr)
+        else {
+            return false;
+        };
+        let source = self.sources.remove(index);
+        for (segment, _) in source.requested {
+            if !self.is_requested(segment) {
+                self.unassigned.insert(segment);
+            }
+        }
+        true
+    }
+
+    /// Count the bytes of a chunk received from a source.
+    pub fn record(&mut self, peer_addr: SocketAddr, len: u64) {
+        if let Some(source) = self
+            .sources
+            .iter_mut()
+            .find(|source| source.peer_addr == peer_addr)
+        {
+            source.bytes_received += len;
+        }
+    }
+
+    /// Pick the segments to request next, for every source with room for more.
+    ///
+    /// Returns the source, offset and length of every range to request.
+    pub fn assign(&mut self, received: &ByteRanges) -> Vec<(SocketAddr, u64, u64)> {
+        let total_size = self.total_size;
+        let is_received = |segment: u64| {
+            let (start, end) = segment_range(segment, total_size);
+            received.contains(start, end)
+        };
+
+        // Forget the segments fully received
+        for source in &mut self.sources {
+            source
+                .requested
+                .retain(|(segment, _)| !is_received(*segment));
+        }
+
+        let mut requests = Vec::new();
+        for index in 0..self.sources.len() {
+            while self.sources[index].requested.len() < SEGMENTS_PER_SOURCE {
+                let segment = match self.next_unassigned(&is_received) {
+                    Some(segment) => segment,
+                    // Nothing left to assign, help the slowest source instead
+                    None => match self.oldest_owed(index) {
+                        Some(segment) => segment,
+                        None => break,
+                    },
+                };
+                let source = &mut self.sources[index];
+                source.requested.push((segment, Instant::now()));
+                let (start, end) = segment_range(segment, total_size);
+                requests.push((source.peer_addr, start, end - start));
+            }
+        }
+        requests
+    }
+
+    /// The first segment not requested yet, and not already received.
+    fn next_unassigned(&mut self, is_received: &impl Fn(u64) -> bool) -> Option<u64> {
+        while let Some(segment) = self.unassigned.pop_first() {
+            if !is_received(segment) {
+                return Some(segment);
+            }
+        }
+        None
+    }
+
+    /// The segment requested the longest ago from another source, that this source was not asked for.
+    fn oldest_owed(&self, index: usize) -> Option<u64> {
+        let own = &self.sources[index].requested;
+        self.sources
+            .iter()
+            .enumerate()
+            .filter(|(other, _)| *other != index)
+            .flat_map(|(_, source)| source.requested.iter())
+            .filter(|(segment, _)| own.iter().all(|(own, _)| own != segment))
+            .min_by_key(|(_, requested_at)| *requested_at)
+            .map(|(segment, _)| *segment)
+    }
+
+    fn is_requested(&self, segment: u64) -> bool {
+        self.sources
+            .iter()
+            .any(|source| source.requested.iter().any(|(other, _)| *other == segment))
+    }
+}
+
+/// The byte range `[start, end)` of a segment.
+fn segment_range(segment: u64, total_size: u64) -> (u64, u64) {
+    let start = segment * SEGMENT_LEN;
+    (start, (start + SEGMENT_LEN).min(total_size))
+}
+
+impl PeerManager {
+    /// Start a multi-source download: request the path given for every source,
+    /// all under the same ID. The first offer received starts the download.
+    ///
+    /// Returns the ID of the download, or the error if no request could be sent.
+    pub(crate) async fn start_swarm_download(
+        &self,
+        sources: Vec<(SocketAddr, mpsc::Sender<Message>, String)>,
+    ) -> Result<Uuid, String> {
+        let unique_id = Uuid::new_v4();
+        self.swarm_requests.lock().await.insert(
+            unique_id,
+            sources
+                .iter()
+                .map(|(peer_addr, _, path)| FileRequestState {
+                    peer_addr: *peer_addr,
+                    path: path.clone(),
+                    local_path: None,
+                    ranged: true,
+                    created_at: Instant::now(),
+                })
+                .collect(),
+        );
+
+        let mut error = None;
+        for (peer_addr, peer_tx, path) in sources {
+            info!(
+                "Requesting {} from {} for multi-source download {}",
+                path, peer_addr, unique_id
+            );
+            if let Err(e) = peer_tx
+                .send(Message::FileRequest(FileRequest {
+                    request_id: unique_id,
+                    path,
+                    ranged: true,
+                }))
+                .await
+            {
+                self.take_swarm_request(unique_id, peer_addr).await;
+                error = Some(format!(
+                    "Failed to send FileRequest message to peer {}: {}",
+                    peer_addr, e
+                ));
+            }
+        }
+
+        match error {
+            Some(error) if !self.swarm_requests.lock().await.contains_key(&unique_id) => Err(error),
+            _ => Ok(unique_id),
+        }
+    }
+
+    /// Forget the request sent to a source of a multi-source download, once it answered (or left).
+    pub(crate) async fn take_swarm_request(
+        &self,
+        unique_id: Uuid,
+        peer_addr: SocketAddr,
+    ) -> Option<FileRequestState> {
+        let mut swarm_requests = self.swarm_requests.lock().await;
+        let requests = swarm_requests.get_mut(&unique_id)?;
+        let index = requests
+            .iter()
+            .position(|request| request.peer_addr == peer_addr)?;
+        let request = requests.remove(index);
+        if requests.is_empty() {
+            swarm_requests.remove(&unique_id);
+        }
+        Some(request)
+    }
+
+    /// Join the offer of a source to a multi-source download that has already started.
+    ///
+    /// The offer is accepted if it has the same content as the download, and rejected otherwise.
+    /// Returns false if the download has not started yet: the offer starts it.
+    pub(crate) async fn join_swarm_download(
+        &self,
+        file_offer: &FileOffer,
+        peer_addr: SocketAddr,
+        peer_tx: &mpsc::Sender<Message>,
+    ) -> bool {
+        let response = {
+            let mut active_transfers = self.active_transfers.lock().await;
+            let Some(transfer) = active_transfers.get_mut(&file_offer.unique_id) else {
+                return false;
+            };
+            let chunk_len = transfer.chunk_len;
+            let same_content = transfer.root_hash == Some(file_offer.root_hash)
+                && transfer.total_size == file_offer.size;
+            match transfer.swarm.as_mut() {
+                Some(_) if transfer.status.is_finished() => {
+                    Err("The download has already ended".to_string())
+                }
+                Some(_) if !same_content => {
+                    Err("The content differs from the other sources".to_string())
+                }
+                Some(_) if file_offer.compression != transfer.compression => {
+                    Err("The compression differs from the other sources".to_string())
+                }
+                Some(swarm) => {
+                    swarm.add_source(peer_addr);
+                    // The chunks of every source must fit the length negotiated with the first one
+                    Ok(file_offer.max_chunk_len.clamp(MIN_CHUNK_LEN, chunk_len))
+                }
+                None => Err("Not a multi-source download".to_string()),
+            }
+        };
+
+        if let Err(reason) = &response {
+            warn!(
+                "Rejecting source {} of multi-source download {}: {}",
+                peer_addr, file_offer.unique_id, reason
+            );
+        }
+        peer_tx
+            .send(Message::FileOfferResponse(FileOfferResponse {
+                unique_id: file_offer.unique_id,
+                accept: response.is_ok(),
+                max_chunk_len: *response.as_ref().unwrap_or(&MIN_CHUNK_LEN),
+                reason: response.as_ref().err().cloned(),
+                have_files: Vec::new(),
+            }))
+            .await
+            .ok(); // We ignore the error here, as the peer may have already disconnected.
+
+        if response.is_ok() {
+            info!(
+                "Source {} joined multi-source download {}",
+                peer_addr, file_offer.unique_id
+            );
+            self.send_swarm_sources(file_offer.unique_id).await;
+            self.request_swarm_ranges(file_offer.unique_id).await;
+        }
+        true
+    }
+
+    /// Request more segments from the sources of a multi-source download that have room for them.
+    ///
+    /// Nothing is requested while the download is paused.
+    pub(crate) async fn request_swarm_ranges(&self, unique_id: Uuid) {
+        let requests = {
+            let mut active_transfers = self.active_transfers.lock().await;
+            let Some(transfer) = active_transfers.get_mut(&unique_id).filter(|transfer| {
+                matches!(transfer.status, FileTransferStatus::InProgress { .. })
+            }) else {
+                return;
+            };
+            match transfer.swarm.as_mut() {
+                Some(swarm) if !swarm.finishing => swarm.assign(&transfer.received_ranges),
+                _ => return,
+            }
+        };
+
+        for (peer_addr, offset, len) in requests {
+            let peer_tx = self
+                .active_peers
+                .lock()
+                .await
+                .get(&peer_addr)
+                .map(|peer| peer.tx.clone());
+            if let Some(peer_tx) = peer_tx {
+                peer_tx
+                    .send(Message::RangeRequest(RangeRequest {
+                        unique_id,
+                        offset,
+                        len,
+                    }))
+                    .await
+                    .ok(); // We ignore the error here, the source is removed when dropped.
+            }
+        }
+    }
+
+    /// Forget a source of a multi-source download, which disconnected, cancelled, or denied our request.
+    ///
+    /// Its segments are requested from the other sources. The download fails once no source is left.
+    pub(crate) async fn remove_swarm_source(
+        &self,
+        unique_id: Uuid,
+        peer_addr: SocketAddr,
+        reason: &str,
+    ) {
+        let requested = self
+            .take_swarm_request(unique_id, peer_addr)
+            .await
+            .is_some();
+
+        let (removed, remaining) = {
+            let mut active_transfers = self.active_transfers.lock().await;
+            let Some(transfer) = active_transfers
+                .get_mut(&unique_id)
+                .filter(|transfer| !transfer.status.is_finished())
+            else {
+                // The download has not started yet (or is over)
+                return;
+            };
+            let Some(swarm) = transfer.swarm.as_mut() else {
+                return;
+            };
+            let removed = swarm.remove_source(peer_addr);
+            let remaining = swarm.sources().len();
+            // The transfer's peer is always one of the sources
+            if let Some(source) = swarm.sources().first()
+                && transfer.peer_addr == peer_addr
+            {
+                transfer.peer_addr = source.peer_addr;
+            }
+            (removed, remaining)
+        };
+        if !removed && !requested {
+            return;
+        }
+
+        warn!(
+            "Source {} left multi-source download {}: {}",
+            peer_addr, unique_id, reason
+        );
+        let waiting = self.swarm_requests.lock().await.contains_key(&unique_id);
+        if remaining == 0 && !waiting {
+            self.fail_file_transfer(
+                unique_id,
+                format!("Every source left the download. Last one: {}", reason),
+            )
+            .await;
+            return;
+        }
+
+        if removed {
+            self.send_swarm_sources(unique_id).await;
+            self.request_swarm_ranges(unique_id).await;
+        }
+    }
+
+    /// Forget a peer that was dropped, in every multi-source download it is a source of.
+    pub(crate) async fn forget_swarm_source(&self, peer_addr: SocketAddr, reason: &str) {
+        let mut downloads: BTreeSet<Uuid> = self
+            .active_transfers
+            .lock()
+            .await
+            .values()
+            .filter(|transfer| {
+                !transfer.status.is_finished()
+                    && transfer
+                        .swarm
+                        .as_ref()
+                        .is_some_and(|swarm| swarm.has_source(peer_addr))
+            })
+            .map(|transfer| transfer.unique_id)
+            .collect();
+        downloads.extend(
+            self.swarm_requests
+                .lock()
+                .await
+                .iter()
+                .filter(|(_, requests)| {
+                    requests
+                        .iter()
+                        .any(|request| request.peer_addr == peer_addr)
+                })
+                .map(|(unique_id, _)| *unique_id),
+        );
+
+        for unique_id in downloads {
+            self.remove_swarm_source(unique_id, peer_addr, reason).await;
+        }
+    }
+
+    /// Cancel the transfer of every source of a failed multi-source download,
+    /// as they would otherwise wait for range requests until they disconnect.
+    pub(crate) async fn cancel_swarm_sources(&self, unique_id: Uuid, message: &str) {
+        let sources = self
+            .active_transfers
+            .lock()
+            .await
+            .get(&unique_id)
+            .filter(|transfer| transfer.swarm.is_some())
+            .map(|transfer| transfer.peers())
+            .unwrap_or_default();
+        for peer_addr in sources {
+            let peer_tx = self
+                .active_peers
+                .lock()
+                .await
+                .get(&peer_addr)
+                .map(|peer| peer.tx.clone());
+            if let Some(peer_tx) = peer_tx {
+                peer_tx
+                    .send(Message::FileCancel(FileCancel {
+                        unique_id,
+                        message: Some(message.to_string()),
+                    }))
+                    .await
+                    .ok(); // We ignore the error here, as the peer may have already disconnected.
+            }
+        }
+    }
+
+    /// Every byte of a multi-source download was received: verify the files, move them into place,
+    /// and tell every source.
+    pub(crate) async fn finish_swarm_download(&self, unique_id: Uuid) {
+        if let Err(message) = self.commit_received_files(unique_id).await {
+            self.fail_file_transfer(unique_id, message).await;
+            return;
+        }
+
+        // Complete the transfer of every source
+        let sources = self
+            .active_transfers
+            .lock()
+            .await
+            .get(&unique_id)
+            .map(|transfer| transfer.peers())
+            .unwrap_or_default();
+        for peer_addr in sources {
+            let peer_tx = self
+                .active_peers
+                .lock()
+                .await
+                .get(&peer_addr)
+                .map(|peer| peer.tx.clone());
+            if let Some(peer_tx) = peer_tx {
+                peer_tx
+                    .send(Message::FileDoneResult(FileDoneResult {
+                        unique_id,
+                        success: true,
+                        message: None,
+                    }))
+                    .await
+                    .ok(); // We ignore the error here, as the peer may have already disconnected.
+            }
+        }
+
+        info!("Multi-source download {} completed", unique_id);
+        self.complete_file_transfer(unique_id).await;
+    }
+
+    /// Tell the frontend the current sources of a multi-source download.
+    pub(crate) async fn send_swarm_sources(&self, unique_id: Uuid) {
+        let sources: Vec<(SocketAddr, u64)> = self
+            .active_transfers
+            .lock()
+            .await
+            .get(&unique_id)
+            .and_then(|transfer| transfer.swarm.as_ref())
+            .map(|swarm| {
+                swarm
+                    .sources()
+                    .iter()
+                    .map(|source| (source.peer_addr, source.bytes_received))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let sources = {
+            let active_peers = self.active_peers.lock().await;
+            sources
+                .into_iter()
+                .filter_map(|(peer_addr, bytes_received)| {
+                    match &active_peers.get(&peer_addr)?.state {
+                        PeerState::Authenticated { peer_info } => Some(SwarmSourceInfo {
+                            peer: peer_info.into_connection_info(peer_addr),
+                            bytes_received,
+                        }),
+                        _ => None,
+                    }
+                })
+                .collect()
+        };
+
+        self.backend_event_tx
+            .send(BackendEvent::SwarmSources(SwarmSourcesInfo {
+                unique_id: unique_id.to_string(),
+                sources,
+            }))
+            .await
+            .expect("Failed to send SwarmSources event to the frontend");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: SocketAddr = SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), 1);
+    const B: SocketAddr = SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), 2);
+
+    fn swarm(total_size: u64, sources: &[SocketAddr]) -> SwarmDownload {
+        let mut swarm = SwarmDownload::new(total_size);
+        for source in sources {
+            swarm.add_source(*source);
+        }
+        swarm
+    }
+
+    /// The segments assigned to each source, by index.
+    fn segments(requests: Vec<(SocketAddr, u64, u64)>) -> Vec<(SocketAddr, u64)> {
+        requests
+            .into_iter()
+            .map(|(peer_addr, offset, _)| (peer_addr, offset / SEGMENT_LEN))
+            .collect()
+    }
+
+    fn receive(received: &mut ByteRanges, segment: u64) {
+        received.insert(segment * SEGMENT_LEN, (segment + 1) * SEGMENT_LEN);
+    }
+
+    #[test]
+    fn segments_are_spread_over_the_sources() {
+        let mut swarm = swarm(5 * SEGMENT_LEN + 100, &[A, B]);
+        let mut received = ByteRanges::new();
+        assert_eq!(
+            segments(swarm.assign(&received)),
+            vec![(A, 0), (A, 1), (B, 2), (B, 3)]
+        );
+        // Every source has as many segments as it may
+        assert_eq!(swarm.assign(&received), vec![]);
+
+        // A source that delivered is given more, up to the end of the download
+        receive(&mut received, 0);
+        receive(&mut received, 1);
+        assert_eq!(
+            swarm.assign(&received),
+            vec![(A, 4 * SEGMENT_LEN, SEGMENT_LEN), (A, 5 * SEGMENT_LEN, 100)]
+        );
+    }
+
+    #[test]
+    fn received_segments_are_not_requested() {
+        let mut swarm = swarm(4 * SEGMENT_LEN, &[A]);
+        let mut received = ByteRanges::new();
+        receive(&mut received, 1);
+        assert_eq!(segments(swarm.assign(&received)), vec![(A, 0), (A, 2)]);
+    }
+
+    #[test]
+    fn segments_of_a_failed_source_are_reassigned() {
+        let mut swarm = swarm(6 * SEGMENT_LEN, &[A, B]);
+        let mut received = ByteRanges::new();
+        swarm.assign(&received);
+
+        assert!(swarm.remove_source(A));
+        assert!(!swarm.remove_source(A));
+        assert!(!swarm.has_source(A));
+        receive(&mut received, 2);
+        assert_eq!(segments(swarm.assign(&received)), vec![(B, 0)]);
+        receive(&mut received, 3);
+        assert_eq!(segments(swarm.assign(&received)), vec![(B, 1)]);
+    }
+
+    #[test]
+    fn idle_sources_help_with_the_segments_still_owed() {
+        let mut swarm = swarm(4 * SEGMENT_LEN, &[A, B]);
+        let mut received = ByteRanges::new();
+        swarm.assign(&received);
+        receive(&mut received, 0);
+        receive(&mut received, 1);
+        assert_eq!(segments(swarm.assign(&received)), vec![(A, 2), (A, 3)]);
+
+        // Both sources were asked for them, losing one loses nothing
+        assert!(swarm.remove_source(B));
+        assert!(swarm.unassigned.is_empty());
+
+        swarm.record(A, 10);
+        swarm.record(A, 5);
+        swarm.record(B, 7);
+        assert_eq!(swarm.sources()[0].bytes_received, 15);
+    }
+}
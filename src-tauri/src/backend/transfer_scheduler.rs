@@ -0,0 +1,468 @@
+//! # Transfer Scheduler
+//!
+//! Queues outgoing file transfers, and starts them (sends the `FileOfferRequest`) once there is room.
+//!
+//! - At most [TransferScheduler::max_concurrent] transfers run at once, and at most
+//!   [TransferScheduler::max_concurrent_per_peer] per peer.
+//! - The queue is FIFO within a priority. Higher priorities are started first.
+//! - The frontend may reorder, reprioritise, pause and resume queued transfers.
+//!   A paused transfer keeps its place in the queue, but is skipped until resumed.
+//!
+//! A transfer holds its slot from the moment it is started (its files are hashed, then its offer is sent),
+//! until it completes, fails, or is rejected.
+
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    sync::Arc,
+    time::SystemTime,
+};
+
+use tokio::sync::Notify;
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use crate::js_api::backend_event::{self, BackendEvent};
+
+use super::{
+    bandwidth::RateMeter,
+    byte_ranges::ByteRanges,
+    manifest::Manifest,
+    peer_manager::{
+        FileTransferDirection, FileTransferState, FileTransferStatus, Peer, PeerManager, PeerState,
+    },
+    protocol::{FileOffer, MAX_CHUNK_LEN, Message},
+};
+
+/// A transfer waiting in the queue.
+#[derive(Debug)]
+struct QueuedTransfer {
+    unique_id: Uuid,
+    peer_addr: SocketAddr,
+    priority: i32,
+    paused: bool,
+}
+
+/// Transfer queue and concurrency limits.
+#[derive(Debug)]
+pub struct TransferScheduler {
+    /// Maximum number of transfers running at once
+    pub max_concurrent: usize,
+    /// Maximum number of transfers running at once with a single peer
+    pub max_concurrent_per_peer: usize,
+    /// Queued transfers, in the order they will be started
+    queue: Vec<QueuedTransfer>,
+    /// Running transfers, and the peer they run with
+    running: HashMap<Uuid, SocketAddr>,
+    /// Wakes up the scheduler task when a transfer may be started
+    notify: Arc<Notify>,
+}
+
+impl Default for TransferScheduler {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 3,
+            max_concurrent_per_peer: 2,
+            queue: Vec::new(),
+            running: HashMap::new(),
+            notify: Arc::new(Notify::new()),
+        }
+    }
+}
+
+impl TransferScheduler {
+    /// Add a transfer to the queue, behind every transfer of the same or higher priority.
+    pub fn enqueue(&mut self, unique_id: Uuid, peer_addr: SocketAddr, priority: i32) {
+        self.insert_by_priority(QueuedTransfer {
+            unique_id,
+            peer_addr,
+            priority,
+            paused: false,
+        });
+    }
+
+    /// Move a queued transfer to `position` (0 is the front of the queue).
+    ///
+    /// Returns false if the transfer is not queued.
+    pub fn reorder(&mut self, unique_id: Uuid, position: usize) -> bool {
+        let Some(index) = self.index_of(unique_id) else {
+            return false;
+        };
+        let queued = self.queue.remove(index);
+        self.queue.insert(position.min(self.queue.len()), queued);
+        self.notify.notify_one();
+        true
+    }
+
+    /// Change the priority of a queued transfer, moving it accordingly.
+    ///
+    /// Returns false if the transfer is not queued.
+    pub fn set_priority(&mut self, unique_id: Uuid, priority: i32) -> bool {
+        let Some(index) = self.index_of(unique_id) else {
+            return false;
+        };
+        let mut queued = self.queue.remove(index);
+        queued.priority = priority;
+        self.insert_by_priority(queued);
+        true
+    }
+
+    /// Pause or resume a queued transfer.
+    ///
+    /// Returns false if the transfer is not queued.
+    pub fn set_paused(&mut self, unique_id: Uuid, paused: bool) -> bool {
+        let Some(index) = self.index_of(unique_id) else {
+            return false;
+        };
+        self.queue[index].paused = paused;
+        self.notify.notify_one();
+        true
+    }
+
+    /// Change the concurrency limits. A limit of 0 is treated as 1.
+    pub fn set_limits(&mut self, max_concurrent: usize, max_concurrent_per_peer: usize) {
+        self.max_concurrent = max_concurrent.max(1);
+        self.max_concurrent_per_peer = max_concurrent_per_peer.max(1);
+        self.notify.notify_one();
+    }
+
+    /// Is the transfer waiting in the queue?
+    pub fn is_queued(&self, unique_id: Uuid) -> bool {
+        self.index_of(unique_id).is_some()
+    }
+
+    /// Take the next transfer that may be started, and mark it as running.
+    pub fn next_startable(&mut self) -> Option<Uuid> {
+        if self.running.len() >= self.max_concurrent {
+            return None;
+        }
+
+        let index = self.queue.iter().position(|queued| {
+            !queued.paused
+                && self
+                    .running
+                    .values()
+                    .filter(|peer_addr| **peer_addr == queued.peer_addr)
+                    .count()
+                    < self.max_concurrent_per_peer
+        })?;
+
+        let queued = self.queue.remove(index);
+        self.running.insert(queued.unique_id, queued.peer_addr);
+        Some(queued.unique_id)
+    }
+
+    /// The transfer has finished (or was removed), free its slot or queue entry.
+    pub fn finish(&mut self, unique_id: Uuid) {
+        if self.running.remove(&unique_id).is_some() {
+            self.notify.notify_one();
+        }
+        if let Some(index) = self.index_of(unique_id) {
+            self.queue.remove(index);
+        }
+    }
+
+    /// The queued transfers, in order, as backend events.
+    fn queued_events(&self) -> Vec<backend_event::FileTransferQueued> {
+        self.queue
+            .iter()
+            .enumerate()
+            .map(|(position, queued)| backend_event::FileTransferQueued {
+                unique_id: queued.unique_id.to_string(),
+                position: position as u32,
+                priority: queued.priority,
+                paused: queued.paused,
+            })
+            .collect()
+    }
+
+    /// Insert behind every transfer of the same or higher priority.
+    fn insert_by_priority(&mut self, queued: QueuedTransfer) {
+        let position = self
+            .queue
+            .iter()
+            .position(|other| other.priority < queued.priority)
+            .unwrap_or(self.queue.len());
+        self.queue.insert(position, queued);
+        self.notify.notify_one();
+    }
+
+    fn index_of(&self, unique_id: Uuid) -> Option<usize> {
+        self.queue
+            .iter()
+            .position(|queued| queued.unique_id == unique_id)
+    }
+}
+
+impl PeerManager {
+    /// Add an outgoing transfer of the files of `manifest` to the queue.
+    ///
+    /// A `ranged` transfer only sends the byte ranges the peer requests (multi-source downloads).
+    pub(crate) async fn queue_file_transfer(
+        &self,
+        unique_id: Uuid,
+        peer_addr: SocketAddr,
+        filename: String,
+        manifest: Manifest,
+        priority: i32,
+        ranged: bool,
+    ) {
+        let (peer_name, peer_identity) = match self.active_peers.lock().await.get(&peer_addr) {
+            Some(Peer {
+                state: PeerState::Authenticated { peer_info },
+                ..
+            }) => (peer_info.name.clone(), peer_info.identity.clone()),
+            _ => (String::new(), String::new()),
+        };
+
+        // Store transfer state
+        self.active_transfers.lock().await.insert(
+            unique_id,
+            FileTransferState {
+                unique_id,
+                peer_addr,
+                peer_name,
+                peer_identity,
+                started_at: SystemTime::now(),
+                direction: FileTransferDirection::Sending,
+                filename,
+                total_size: manifest.total_size(),
+                manifest,
+                bytes_transferred: 0,
+                rate: RateMeter::default(),
+                // The actual chunk length is picked by the sender once the peer agrees to a maximum
+                chunk_len: MAX_CHUNK_LEN,
+                status: FileTransferStatus::Queued,
+                root_hash: None,
+                merkle_tree: None,
+                ranged,
+                swarm: None,
+                ack_tx: None,
+                pause_tx: None,
+                compression: None,
+                wire_bytes: 0,
+                received_ranges: ByteRanges::new(),
+                delta_bases: HashMap::new(),
+                resent_files: HashSet::new(),
+                block_signatures: HashMap::new(),
+            },
+        );
+
+        info!("Queued file transfer {} to {}", unique_id, peer_addr);
+        self.scheduler
+            .lock()
+            .await
+            .enqueue(unique_id, peer_addr, priority);
+    }
+
+    /// Start queued transfers whenever there is room, until the task is aborted.
+    pub(crate) async fn run_transfer_scheduler(&self) {
+        let notify = self.scheduler.lock().await.notify.clone();
+        loop {
+            self.schedule_transfers().await;
+            notify.notified().await;
+        }
+    }
+
+    /// Start every queued transfer there is room for, then report the queue to the frontend.
+    async fn schedule_transfers(&self) {
+        loop {
+            let Some(unique_id) = self.scheduler.lock().await.next_startable() else {
+                break;
+            };
+
+            // Hashing the files may take a while, do not hold up the other transfers
+            let manager = self.clone();
+            tokio::spawn(async move {
+                if let Err(message) = manager.start_queued_transfer(unique_id).await {
+                    manager.fail_file_transfer(unique_id, message).await;
+                }
+            });
+        }
+
+        self.emit_transfer_queue().await;
+    }
+
+    /// Hash the files of a transfer that was taken off the queue, and send its `FileOfferRequest`.
+    async fn start_queued_transfer(&self, unique_id: Uuid) -> Result<(), String> {
+        let (mut manifest, peer_addr) = self
+            .active_transfers
+            .lock()
+            .await
+            .get(&unique_id)
+            .map(|transfer| (transfer.manifest.clone(), transfer.peer_addr))
+            .ok_or("File transfer state not found")?;
+
+        // Compute the checksums and the Merkle tree for the manifest, without holding the lock
+        let merkle_tree = manifest.compute_checksums().await?;
+        let compression = self.choose_compression(peer_addr, &manifest).await;
+
+        let offer = {
+            let mut active_transfers = self.active_transfers.lock().await;
+            let transfer = active_transfers
+                .get_mut(&unique_id)
+                .ok_or("File transfer state not found")?;
+            if transfer.status.is_finished() {
+                // Failed (e.g. the peer disconnected) while hashing
+                return Ok(());
+            }
+            transfer.status = FileTransferStatus::WaitingForPeerResponse;
+            let offer = FileOffer {
+                filename: transfer.filename.clone(),
+                unique_id,
+                size: transfer.total_size,
+                max_chunk_len: transfer.chunk_len,
+                manifest: manifest.to_entries(),
+                root_hash: merkle_tree.root(),
+                compression,
+            };
+            transfer.compression = compression;
+            transfer.manifest = manifest;
+            transfer.root_hash = Some(merkle_tree.root());
+            transfer.merkle_tree = Some(Arc::new(merkle_tree));
+            (transfer.peer_addr, offer)
+        };
+        let (peer_addr, offer) = offer;
+
+        let peer_tx = self
+            .active_peers
+            .lock()
+            .await
+            .get(&peer_addr)
+            .map(|peer| peer.tx.clone())
+            .ok_or(format!("Peer {} is no longer connected", peer_addr))?;
+
+        info!(
+            "Starting queued file transfer {} to {}",
+            unique_id, peer_addr
+        );
+        peer_tx
+            .send(Message::FileOfferRequest(offer))
+            .await
+            .map_err(|e| format!("Failed to send FileOfferRequest message to the peer: {}", e))
+    }
+
+    /// Free the scheduler slot (or queue entry) of a transfer that has finished.
+    pub(crate) async fn release_transfer_slot(&self, unique_id: Uuid) {
+        self.scheduler.lock().await.finish(unique_id);
+    }
+
+    /// Report every queued transfer to the frontend, with its position in the queue.
+    pub(crate) async fn emit_transfer_queue(&self) {
+        let queued = self.scheduler.lock().await.queued_events();
+        debug!("{} file transfers queued", queued.len());
+
+        for file_transfer_queued in queued {
+            self.backend_event_tx
+                .send(BackendEvent::FileTransferQueued(file_transfer_queued))
+                .await
+                .expect("Failed to send FileTransferQueued event to the frontend");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([192, 168, 1, 2], port))
+    }
+
+    fn ids(n: usize) -> Vec<Uuid> {
+        (0..n).map(|_| Uuid::new_v4()).collect()
+    }
+
+    /// Start every transfer there is room for.
+    fn start_all(scheduler: &mut TransferScheduler) -> Vec<Uuid> {
+        std::iter::from_fn(|| scheduler.next_startable()).collect()
+    }
+
+    fn unlimited() -> TransferScheduler {
+        TransferScheduler {
+            max_concurrent: usize::MAX,
+            max_concurrent_per_peer: usize::MAX,
+            ..TransferScheduler::default()
+        }
+    }
+
+    #[test]
+    fn higher_priorities_start_first_fifo_within_a_priority() {
+        let mut scheduler = unlimited();
+        let id = ids(4);
+        scheduler.enqueue(id[0], addr(1), 0);
+        scheduler.enqueue(id[1], addr(1), 5);
+        scheduler.enqueue(id[2], addr(1), 0);
+        scheduler.enqueue(id[3], addr(1), 5);
+        assert_eq!(start_all(&mut scheduler), vec![id[1], id[3], id[0], id[2]]);
+    }
+
+    #[test]
+    fn reorder_and_reprioritise() {
+        let mut scheduler = unlimited();
+        let id = ids(4);
+        for unique_id in &id {
+            scheduler.enqueue(*unique_id, addr(1), 0);
+        }
+        assert!(scheduler.reorder(id[3], 0));
+        assert!(scheduler.reorder(id[0], 100));
+        // Behind the transfers already at that priority
+        assert!(scheduler.set_priority(id[2], 1));
+        assert!(!scheduler.reorder(Uuid::new_v4(), 0));
+        assert!(!scheduler.set_priority(Uuid::new_v4(), 1));
+        assert_eq!(start_all(&mut scheduler), vec![id[2], id[3], id[1], id[0]]);
+    }
+
+    #[test]
+    fn paused_transfers_keep_their_place() {
+        let mut scheduler = unlimited();
+        let id = ids(3);
+        for unique_id in &id {
+            scheduler.enqueue(*unique_id, addr(1), 0);
+        }
+        assert!(scheduler.set_paused(id[0], true));
+        assert_eq!(start_all(&mut scheduler), vec![id[1], id[2]]);
+        assert!(scheduler.is_queued(id[0]));
+
+        let later = Uuid::new_v4();
+        scheduler.enqueue(later, addr(1), 0);
+        assert!(scheduler.set_paused(id[0], false));
+        assert_eq!(start_all(&mut scheduler), vec![id[0], later]);
+        assert!(!scheduler.set_paused(id[0], true));
+    }
+
+    #[test]
+    fn concurrency_is_limited_globally_and_per_peer() {
+        let mut scheduler = TransferScheduler::default();
+        scheduler.set_limits(3, 2);
+        let id = ids(5);
+        scheduler.enqueue(id[0], addr(1), 0);
+        scheduler.enqueue(id[1], addr(1), 0);
+        scheduler.enqueue(id[2], addr(1), 0);
+        scheduler.enqueue(id[3], addr(2), 0);
+        scheduler.enqueue(id[4], addr(3), 0);
+
+        // The third transfer to the first peer waits, the others may pass it
+        assert_eq!(start_all(&mut scheduler), vec![id[0], id[1], id[3]]);
+        scheduler.finish(id[3]);
+        assert_eq!(start_all(&mut scheduler), vec![id[4]]);
+        scheduler.finish(id[0]);
+        assert_eq!(start_all(&mut scheduler), vec![id[2]]);
+
+        // A limit of 0 would start nothing
+        scheduler.set_limits(0, 0);
+        assert_eq!(scheduler.max_concurrent, 1);
+        assert_eq!(scheduler.max_concurrent_per_peer, 1);
+    }
+
+    #[test]
+    fn finish_removes_queued_transfers() {
+        let mut scheduler = unlimited();
+        let id = ids(2);
+        scheduler.enqueue(id[0], addr(1), 0);
+        scheduler.enqueue(id[1], addr(1), 0);
+        scheduler.finish(id[0]);
+        assert!(!scheduler.is_queued(id[0]));
+        assert_eq!(start_all(&mut scheduler), vec![id[1]]);
+    }
+}
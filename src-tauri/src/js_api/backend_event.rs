@@ -3,7 +3,7 @@ use ts_rs::TS;
 
 use crate::backend::peer_manager::PeerInfo;
 
-use super::frontend_event::FrontendEvent;
+use super::frontend_event::{AutoAcceptRule, FrontendEvent, SharedFolder};
 
 /// Enum of events that occur in the backend and should be sent to the frontend.
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -46,12 +46,68 @@ pub enum BackendEvent {
 
     /// Response Required: A file offer from the backend to the frontend.
     FileOffer(FileOffer),
+    /// Notification:      A file offer from a peer was rejected automatically (not enough space, or over a size limit).
+    FileOfferRejected(FileOfferRejected),
+    /// Notification:      A file offer from a peer was accepted or rejected by an auto-accept rule.
+    FileOfferAutoHandled(FileOfferAutoHandled),
+    /// Info:              The auto-accept rules, after they were requested or changed.
+    AutoAcceptRules(AutoAcceptRulesInfo),
+    /// Info:              The shared folders, after they were requested or changed.
+    SharedFolders(SharedFoldersInfo),
+    /// Response Required: A peer requests a file we share. Notification only if approved automatically.
+    FileRequested(FileRequested),
+    /// Notification:      A peer approved or denied our file request.
+    FileRequestAnswered(FileRequestAnswer),
+    /// Response:          The listing of a directory a peer shares, as requested with `ListDirectory`.
+    DirectoryListing(DirectoryListing),
+    /// Progress Update:   The sources of a multi-source download, whenever one joins or leaves.
+    SwarmSources(SwarmSourcesInfo),
+    /// Info:              The library folders and the size of the hash index, after they were requested or rescanned.
+    HashIndex(HashIndexInfo),
+    /// Notification:      Files of an incoming file transfer we already had were placed from local copies, and not transferred.
+    FileTransferDeduplicated(FileTransferDeduplicated),
+    /// Progress Update:   An outgoing file transfer waiting in the transfer queue.
+    FileTransferQueued(FileTransferQueued),
     /// Notification:      A file transfer completion from the backend to the frontend.
     FileTransferComplete(FileTransferComplete),
+    /// Notification:      A single file of an incoming file transfer was received and verified.
+    FileTransferFileComplete(FileTransferFileComplete),
     /// Warn:              A file transfer error from the backend to the frontend.
     FileTransferError(FileTransferError),
+    /// Notification:      A file transfer was cancelled, by us or by the peer.
+    FileTransferCancelled(FileTransferCancelled),
+    /// Notification:      An outgoing file offer was rejected by the peer.
+    FileTransferRejected(FileTransferRejected),
     /// Progress Update:   A file transfer progress update from the backend to the frontend.
     FileTransferProgress(FileTransferProgress),
+    /// Notification:      An in-progress file transfer was paused, by us or by the peer.
+    FileTransferPaused(FileTransferPausedOrResumed),
+    /// Notification:      A paused file transfer was resumed, by us or by the peer.
+    FileTransferResumed(FileTransferPausedOrResumed),
+    /// Notification:      A chat message we sent to a peer.
+    ChatMessageSent(ChatMessage),
+    /// Notification:      A chat message to a peer that is not connected was queued, to be sent once it connects.
+    ChatMessageQueued(ChatMessageQueued),
+    /// Notification:      A chat message received from a peer.
+    ChatMessageReceived(ChatMessage),
+    /// Notification:      A chat message we sent was delivered to the peer.
+    ChatMessageDelivered(ChatMessageDelivered),
+    /// Notification:      A chat message was edited, deleted or reacted to, by us or a peer.
+    ChatMessageUpdated(ChatMessageUpdated),
+    /// Notification:      A peer is typing in a conversation with us.
+    PeerTyping(PeerTyping),
+    /// Notification:      A peer read a conversation with us up to a message.
+    ChatReadUpTo(ChatReadUpTo),
+    /// Info:              A page of the chat history with a peer or a room, after it was requested.
+    ChatHistory(ChatHistoryPage),
+    /// Info:              Every chat conversation and its number of unread messages, after it was requested or changed.
+    ChatConversations(ChatConversations),
+    /// Info:              Every chat room we know of, after it was requested.
+    Rooms(RoomsInfo),
+    /// Notification:      A chat room was created, or its members changed.
+    RoomUpdated(RoomUpdated),
+    /// Info:              A page of the transfer history, after it was queried.
+    TransferHistory(TransferHistoryPage),
     /// General Message:   A general message from the backend to the frontend.
     Message(BackendMessage),
 }
@@ -108,9 +164,9 @@ pub struct ConnectionInfo {
     pub ip: String,
     /// The version of the backend.
     pub backend_version: String,
-    // /// The ECDSA public key of the connection.
-    // /// As a string encoded in base64.
-    // pub identitiy: String,
+    /// The identity (ECDSA public key) of the connection, verified in the handshake.
+    /// As a string encoded in base64.
+    pub identity: String,
 }
 
 impl From<ConnectionInfo> for PeerInfo {
@@ -118,7 +174,9 @@ impl From<ConnectionInfo> for PeerInfo {
         PeerInfo {
             name: info.name,
             backend_version: info.backend_version,
-            // ecdsa_public_key: info.identitiy,
+            // Only known from the peer's handshake
+            compression: Vec::new(),
+            identity: info.identity,
         }
     }
 }
@@ -155,10 +213,198 @@ pub struct FileOffer {
     pub filename: String,
     /// A unique identifier for the file. (UUID)
     pub unique_id: String,
+    /// The total size of every file in bytes.
+    pub size: u64,
+    /// The files being offered. A single file, or every file of a folder or selection.
+    pub files: Vec<FileOfferEntry>,
+    /// A warning if accepting the offer would leave little free disk space.
+    pub space_warning: Option<String>,
+}
+
+/// Struct representing a file of a file offer.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct FileOfferEntry {
+    /// The path of the file, relative to the download directory. (`/`-separated)
+    pub path: String,
     /// The size of the file in bytes.
     pub size: u64,
 }
 
+/// Struct representing a file offer that was rejected automatically.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct FileOfferRejected {
+    /// The peer that offered the file.
+    pub peer: ConnectionInfo,
+    /// The filename of the file that was offered.
+    pub filename: String,
+    /// A unique identifier for the file. (UUID)
+    pub unique_id: String,
+    /// The total size of every file in bytes.
+    pub size: u64,
+    /// Why the offer was rejected. Also sent to the peer.
+    pub reason: String,
+}
+
+/// Struct representing a file offer handled by an auto-accept rule.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct FileOfferAutoHandled {
+    /// The peer that offered the file.
+    pub peer: ConnectionInfo,
+    /// The filename of the file that was offered.
+    pub filename: String,
+    /// A unique identifier for the file. (UUID)
+    pub unique_id: String,
+    /// The total size of every file in bytes.
+    pub size: u64,
+    /// The files that were offered.
+    pub files: Vec<FileOfferEntry>,
+    /// Whether the offer was accepted (rather than rejected).
+    pub accepted: bool,
+    /// The name of the rule that was applied.
+    pub rule: String,
+    /// The folder the files are saved to, if accepted.
+    pub folder: Option<String>,
+    /// A warning if accepting the offer leaves little free disk space.
+    pub space_warning: Option<String>,
+}
+
+/// Struct representing the auto-accept rules.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct AutoAcceptRulesInfo {
+    /// The rules, in the order they are evaluated.
+    pub rules: Vec<AutoAcceptRule>,
+}
+
+/// Struct representing the shared folders.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SharedFoldersInfo {
+    /// The folders we share with our peers.
+    pub shares: Vec<SharedFolder>,
+}
+
+/// Struct representing a file request from a peer.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct FileRequested {
+    /// The peer requesting the file.
+    pub peer: ConnectionInfo,
+    /// A unique identifier for the request, and the file transfer if approved. (UUID)
+    pub request_id: String,
+    /// The requested path, as `<share name>/<path inside the share>`.
+    pub path: String,
+    /// Whether the request was approved automatically by the share.
+    pub auto_approved: bool,
+}
+
+/// Struct representing a peer's response to our file request.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct FileRequestAnswer {
+    /// The peer the file was requested from.
+    pub peer: ConnectionInfo,
+    /// A unique identifier for the request, and the file transfer if approved. (UUID)
+    pub request_id: String,
+    /// The requested path.
+    pub path: String,
+    /// Whether the request was approved. If so, the file offer follows and is accepted automatically.
+    pub accept: bool,
+    /// Why the request was denied.
+    pub reason: Option<String>,
+}
+
+/// Struct representing the listing of a directory a peer shares.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct DirectoryListing {
+    /// The peer sharing the directory.
+    pub peer: ConnectionInfo,
+    /// The directory that was listed. Empty for the shared folders themselves.
+    pub path: String,
+    /// The entries of the directory, sorted by name.
+    pub entries: Vec<DirectoryEntry>,
+    /// Whether the directory had too many entries to list them all.
+    pub truncated: bool,
+    /// Why the directory could not be listed.
+    pub error: Option<String>,
+}
+
+/// Struct representing an entry of a directory listing.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct DirectoryEntry {
+    /// The name of the file or directory.
+    pub name: String,
+    /// The size in bytes. (0 for directories)
+    pub size: u64,
+    /// The last modification time, in seconds since the UNIX epoch.
+    pub modified: Option<u64>,
+    /// Whether the entry is a directory.
+    pub is_dir: bool,
+}
+
+/// Struct representing the sources of a multi-source download.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SwarmSourcesInfo {
+    /// The unique identifier of the download (the file transfer). (UUID)
+    pub unique_id: String,
+    /// The sources currently sending the download.
+    pub sources: Vec<SwarmSourceInfo>,
+}
+
+/// Struct representing a source of a multi-source download.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SwarmSourceInfo {
+    /// The peer sending the download.
+    pub peer: ConnectionInfo,
+    /// The number of bytes received from the peer so far.
+    pub bytes_received: u64,
+}
+
+/// Struct representing the hash index of the files we already have.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct HashIndexInfo {
+    /// The library folders indexed besides the download directory. (absolute paths)
+    pub libraries: Vec<String>,
+    /// The number of files indexed.
+    pub files: u64,
+    /// The total size of the files indexed, in bytes.
+    pub bytes: u64,
+}
+
+/// Struct representing the files of an incoming file transfer we already had.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct FileTransferDeduplicated {
+    /// The unique identifier of the file transfer. (UUID)
+    pub unique_id: String,
+    /// The paths (relative to the root of the transfer) of the files placed from local copies.
+    pub paths: Vec<String>,
+    /// The number of bytes not transferred.
+    pub bytes: u64,
+}
+
+/// Struct representing a file transfer waiting in the transfer queue.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct FileTransferQueued {
+    /// The unique identifier of the queued file transfer. (UUID)
+    pub unique_id: String,
+    /// The position in the queue (0 is the next to start).
+    pub position: u32,
+    /// The priority of the file transfer. Higher priorities start first.
+    pub priority: i32,
+    /// Whether the queued file transfer is paused.
+    pub paused: bool,
+}
+
 /// Struct representing a file transfer completion.
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export)]
@@ -167,6 +413,16 @@ pub struct FileTransferComplete {
     pub unique_id: String,
 }
 
+/// Struct representing the completion of a single file of an incoming file transfer.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct FileTransferFileComplete {
+    /// The unique identifier of the file transfer. (UUID)
+    pub unique_id: String,
+    /// The path of the file, relative to the download directory. (`/`-separated)
+    pub path: String,
+}
+
 /// Struct representing a file transfer error.
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export)]
@@ -177,6 +433,28 @@ pub struct FileTransferError {
     pub message: String,
 }
 
+/// Struct representing a file transfer cancellation.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct FileTransferCancelled {
+    /// The unique identifier of the cancelled file transfer. (UUID)
+    pub unique_id: String,
+    /// Optional message sent with the cancellation.
+    pub message: Option<String>,
+    /// Whether the peer cancelled the file transfer (rather than us).
+    pub by_peer: bool,
+}
+
+/// Struct representing an outgoing file offer rejected by the peer.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct FileTransferRejected {
+    /// The unique identifier of the rejected file transfer. (UUID)
+    pub unique_id: String,
+    /// Why the peer rejected the offer, if it was rejected automatically.
+    pub reason: Option<String>,
+}
+
 /// Struct representing a file transfer progress update.
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export)]
@@ -187,13 +465,27 @@ pub struct FileTransferProgress {
     pub bytes_transferred: u64,
     /// The total number of bytes to transfer.
     pub total_bytes: u64,
+    /// The number of chunk bytes sent or received over the network so far, after compression (retransmissions included).
+    pub wire_bytes: u64,
+    /// The current effective transfer rate in bytes per second.
+    pub rate: u64,
     /// Sending or receiving the file?
     pub sending: FileTransferDirection,
 }
 
-/// Enum representing whether a file transfer is sending or receiving.
+/// Struct representing a file transfer pause or resumption.
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export)]
+pub struct FileTransferPausedOrResumed {
+    /// The unique identifier of the file transfer. (UUID)
+    pub unique_id: String,
+    /// Whether the peer paused/resumed the file transfer (rather than us).
+    pub by_peer: bool,
+}
+
+/// Enum representing whether a file transfer is sending or receiving.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
 pub enum FileTransferDirection {
     /// Sending the file.
     Sending,
@@ -201,6 +493,305 @@ pub enum FileTransferDirection {
     Receiving,
 }
 
+/// Struct representing a chat message, sent or received.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ChatMessage {
+    /// The peer the message was sent to, or received from.
+    pub peer: ConnectionInfo,
+    /// The room the message was sent to. (UUID) None if it was sent to the peer only.
+    pub room_id: Option<String>,
+    /// The unique identifier of the message. (UUID)
+    pub id: String,
+    /// When the message was sent, in milliseconds since the Unix epoch. (sender's clock)
+    pub timestamp: u64,
+    /// The text of the message.
+    pub body: String,
+}
+
+/// Struct representing a chat message queued for a peer that is not connected.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ChatMessageQueued {
+    /// The identity of the peer the message is for.
+    pub peer_identity: String,
+    /// The room the message was sent to. (UUID) None if it was sent to the peer only.
+    pub room_id: Option<String>,
+    /// The unique identifier of the message. (UUID)
+    pub id: String,
+    /// When the message was queued, in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The text of the message.
+    pub body: String,
+}
+
+/// Struct representing the delivery of a chat message we sent.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ChatMessageDelivered {
+    /// The peer the message was delivered to.
+    pub peer: ConnectionInfo,
+    /// The unique identifier of the message. (UUID)
+    pub id: String,
+}
+
+/// Struct representing an edit, deletion or reaction to a chat message.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ChatMessageUpdated {
+    /// The identity of the peer of the conversation. None for a room.
+    pub peer_identity: Option<String>,
+    /// The room of the conversation. (UUID) None for a peer.
+    pub room_id: Option<String>,
+    /// The message, after the change.
+    pub message: ChatHistoryMessage,
+    /// The identity of the peer who made the change. None if we did.
+    pub by: Option<String>,
+    /// The name of the peer who made the change. None if we did.
+    pub by_name: Option<String>,
+}
+
+/// Struct representing a peer typing in a conversation with us.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct PeerTyping {
+    /// The peer who is typing.
+    pub peer: ConnectionInfo,
+    /// The room the peer is typing in. (UUID) None for the conversation with the peer.
+    pub room_id: Option<String>,
+    /// How long to show that the peer is typing, in milliseconds, unless another one arrives.
+    pub timeout_ms: u64,
+}
+
+/// Struct representing a read receipt: a peer read a conversation up to a message.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ChatReadUpTo {
+    /// The peer who read the conversation.
+    pub peer: ConnectionInfo,
+    /// The room that was read. (UUID) None for the conversation with the peer.
+    pub room_id: Option<String>,
+    /// The unique identifier of the last message the peer read. (UUID) Every message before it was read too.
+    pub message_id: String,
+}
+
+/// Struct representing a page of the chat history with a peer or a room.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ChatHistoryPage {
+    /// The identity of the peer. None for a room.
+    pub peer_identity: Option<String>,
+    /// The room. (UUID) None for a peer.
+    pub room_id: Option<String>,
+    /// The messages of the page, oldest first.
+    pub messages: Vec<ChatHistoryMessage>,
+    /// Whether there are older messages before the page.
+    pub has_more: bool,
+}
+
+/// Struct representing a chat message in the chat history.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ChatHistoryMessage {
+    /// The unique identifier of the message. (UUID)
+    pub id: String,
+    /// When the message was sent, in milliseconds since the Unix epoch. (sender's clock)
+    pub timestamp: u64,
+    /// The text of the message.
+    pub body: String,
+    /// Whether we sent the message (rather than received it).
+    pub outgoing: bool,
+    /// The identity of the peer who sent the message. None if we did.
+    #[serde(default)]
+    pub author: Option<String>,
+    /// The name of the peer who sent the message, when it was sent (for display only). None if we did.
+    #[serde(default)]
+    pub author_name: Option<String>,
+    /// The Lamport clock of the room when the message was sent. (0 for messages to a peer only)
+    #[serde(default)]
+    pub clock: u64,
+    /// The delivery state of the message.
+    pub state: ChatMessageState,
+    /// When the message was last edited, in milliseconds since the Unix epoch. (editor's clock) None if it was not.
+    #[serde(default)]
+    pub edited_at: Option<u64>,
+    /// Whether the message was deleted by its author. The text of a deleted message is empty.
+    #[serde(default)]
+    pub deleted: bool,
+    /// The reactions to the message, oldest first.
+    #[serde(default)]
+    pub reactions: Vec<ChatMessageReaction>,
+}
+
+/// Struct representing an emoji reaction to a chat message.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ChatMessageReaction {
+    /// The emoji.
+    pub emoji: String,
+    /// The identity of the peer who reacted. None if we did.
+    pub by: Option<String>,
+    /// The name of the peer who reacted, when it reacted (for display only). None if we did.
+    #[serde(default)]
+    pub by_name: Option<String>,
+}
+
+/// Enum representing the delivery state of a chat message.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum ChatMessageState {
+    /// We sent the message while the peer was not connected, it is sent once the peer connects.
+    Queued,
+    /// We sent the message, the peer has not acknowledged it yet.
+    Sent,
+    /// We sent the message, and the peer acknowledged it.
+    Delivered,
+    /// We received the message, and it was not read yet.
+    Unread,
+    /// We received the message, and it was read.
+    Read,
+}
+
+/// Struct representing every chat conversation.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ChatConversations {
+    /// The conversations, most recent first.
+    pub conversations: Vec<ChatConversation>,
+}
+
+/// Struct representing a chat conversation with a peer or a room.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ChatConversation {
+    /// The identity of the peer. None for a room.
+    pub peer_identity: Option<String>,
+    /// The last name the peer was known by. None for a room.
+    pub peer_name: Option<String>,
+    /// The room. (UUID) None for a peer.
+    pub room_id: Option<String>,
+    /// The number of messages received in the conversation that were not read yet.
+    pub unread: u32,
+    /// When the last message was sent, in milliseconds since the Unix epoch. (None if there are none)
+    pub last_timestamp: Option<u64>,
+}
+
+/// Struct representing every chat room we know of.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct RoomsInfo {
+    /// The rooms, by name.
+    pub rooms: Vec<RoomInfo>,
+}
+
+/// Struct representing a chat room.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct RoomInfo {
+    /// The unique identifier of the room. (UUID)
+    pub room_id: String,
+    /// The name of the room.
+    pub name: String,
+    /// The other members of the room.
+    pub members: Vec<RoomMember>,
+    /// Whether we are a member of the room. (False once we left it, or were removed from it)
+    pub joined: bool,
+}
+
+/// Struct representing a member of a chat room.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct RoomMember {
+    /// The identity of the member.
+    pub identity: String,
+    /// The last name the member was known by. None if it never connected to us.
+    pub name: Option<String>,
+}
+
+/// Struct representing a change to a chat room.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct RoomUpdated {
+    /// The room, after the change.
+    pub room: RoomInfo,
+    /// The identity of the peer who made the change. None if we did.
+    pub by: Option<String>,
+    /// The identities of the members who were added.
+    pub added: Vec<String>,
+    /// The identities of the members who were removed, or left.
+    pub removed: Vec<String>,
+}
+
+/// Struct representing a page of the transfer history matching a query.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct TransferHistoryPage {
+    /// The records of the page, most recent first.
+    pub records: Vec<TransferRecord>,
+    /// The number of records skipped before the page.
+    pub offset: u32,
+    /// The number of records matching the query, over every page.
+    pub total: u32,
+}
+
+/// Struct representing a finished file transfer, as recorded in the transfer history.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct TransferRecord {
+    /// The unique identifier of the file transfer. (UUID)
+    pub unique_id: String,
+    /// The name of the peer.
+    pub peer_name: String,
+    /// The IP address of the peer.
+    pub peer_ip: String,
+    /// The name of the offer (the file name, or the folder name for a folder).
+    pub filename: String,
+    /// The size of every file of the transfer, in bytes.
+    pub size: u64,
+    /// The number of bytes transferred before the transfer ended.
+    pub bytes_transferred: u64,
+    /// Whether we sent or received the files.
+    pub direction: FileTransferDirection,
+    /// When the transfer was queued (sending) or offered (receiving), in milliseconds since the Unix epoch.
+    pub started_at: u64,
+    /// When the transfer ended, in milliseconds since the Unix epoch.
+    pub ended_at: u64,
+    /// How the transfer ended.
+    pub outcome: TransferOutcome,
+    /// Why the transfer failed or was rejected, if known.
+    pub reason: Option<String>,
+    /// Where the file (or folder) was saved to, or sent from. (None if it was never saved)
+    pub local_path: Option<String>,
+    /// The files of the transfer.
+    pub files: Vec<TransferRecordFile>,
+}
+
+/// Struct representing a file of a finished file transfer.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct TransferRecordFile {
+    /// The path of the file, relative to the root of the transfer. (`/`-separated)
+    pub path: String,
+    /// The size of the file, in bytes.
+    pub size: u64,
+    /// The BLAKE3 checksum of the file, in hex. (None if the file was never hashed)
+    pub checksum: Option<String>,
+}
+
+/// Enum representing how a file transfer ended.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum TransferOutcome {
+    /// Every file was transferred and verified.
+    Completed,
+    /// The transfer was cancelled, by us or by the peer.
+    Cancelled,
+    /// The offer was rejected.
+    Rejected,
+    /// The transfer failed.
+    Failed,
+}
+
 /// Struct representing a general message from the backend.
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export)]
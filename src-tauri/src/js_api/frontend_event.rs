@@ -4,6 +4,8 @@ use ts_rs::TS;
 
 use std::ops::{Deref, DerefMut};
 
+use super::backend_event::{FileTransferDirection, TransferOutcome};
+
 /// Enum of events that occur in the frontend and should be sent to the backend.
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[serde(tag = "type")]
@@ -23,6 +25,72 @@ pub enum FrontendEvent {
     FileOfferResponse(FileOfferResponse),
     /// New request: Cancel a file transfer.
     CancelFileTransfer(CancelFileTransfer),
+    /// New request: Move a queued file transfer to another position in the queue.
+    ReorderFileTransfer(ReorderFileTransfer),
+    /// New request: Change the priority of a queued file transfer.
+    SetFileTransferPriority(SetFileTransferPriority),
+    /// New request: Pause a queued or in-progress file transfer.
+    PauseFileTransfer(PauseFileTransfer),
+    /// New request: Resume a paused file transfer (queued or in progress).
+    ResumeFileTransfer(ResumeFileTransfer),
+    /// New request: Change the maximum number of concurrent file transfers.
+    SetTransferConcurrency(SetTransferConcurrency),
+    /// New request: Change the upload/download bandwidth limits, globally or for a peer.
+    SetBandwidthLimits(SetBandwidthLimits),
+    /// New request: Change the directory incoming files are saved to.
+    SetDownloadDir(SetDownloadDir),
+    /// New request: Change the size limits for incoming file offers.
+    SetOfferPolicy(SetOfferPolicy),
+    /// New request: Choose which metadata (modification time, permissions) of received files is applied.
+    SetMetadataPolicy(SetMetadataPolicy),
+    /// New request: Replace the auto-accept rules for incoming file offers.
+    SetAutoAcceptRules(SetAutoAcceptRules),
+    /// New request: Get the auto-accept rules for incoming file offers.
+    GetAutoAcceptRules,
+    /// New request: Replace the folders we share with our peers.
+    SetSharedFolders(SetSharedFolders),
+    /// New request: Get the folders we share with our peers.
+    GetSharedFolders,
+    /// New request: Ask a peer for a file (or folder) it shares.
+    RequestFile(RequestFile),
+    /// Response: Approve or deny a file request from a peer.
+    FileRequestResponse(FileRequestResponse),
+    /// New request: List a directory a peer shares.
+    ListDirectory(ListDirectory),
+    /// New request: Download the same file (or folder) from several peers at once.
+    SwarmDownload(SwarmDownloadRequest),
+    /// New request: Replace the library folders indexed for files we already have.
+    SetLibraryFolders(SetLibraryFolders),
+    /// New request: Get the library folders and the size of the hash index.
+    GetHashIndex,
+    /// New request: Send a chat message to a peer.
+    SendChatMessage(SendChatMessage),
+    /// New request: Tell the peer (or the members of a room) we are typing. Sent repeatedly while typing.
+    SendTyping(SendTyping),
+    /// New request: Edit a chat message we sent to a peer or a room.
+    EditChatMessage(EditChatMessage),
+    /// New request: Delete a chat message we sent to a peer or a room.
+    DeleteChatMessage(DeleteChatMessage),
+    /// New request: Add or remove an emoji reaction to a chat message.
+    ReactToChatMessage(ReactToChatMessage),
+    /// New request: Get a page of the chat history with a peer or a room.
+    GetChatHistory(GetChatHistory),
+    /// New request: Mark every message received from a peer or in a room as read.
+    MarkChatRead(MarkChatRead),
+    /// New request: Get every chat conversation and its number of unread messages.
+    GetChatConversations,
+    /// New request: Create a chat room with connected or known peers.
+    CreateRoom(CreateRoom),
+    /// New request: Change the members of a chat room.
+    SetRoomMembers(SetRoomMembers),
+    /// New request: Leave a chat room.
+    LeaveRoom(LeaveRoom),
+    /// New request: Send a chat message to every member of a room.
+    SendRoomMessage(SendRoomMessage),
+    /// New request: Get every chat room we know of.
+    GetRooms,
+    /// New request: Search the history of finished file transfers.
+    QueryTransferHistory(QueryTransferHistory),
 
     /// Startup: Frontend is ready to receive messages from the backend.
     FrontendReady(BackendStartupConfig),
@@ -71,10 +139,14 @@ pub struct ConnectionRequestResponse {
 pub struct TransmitFile {
     /// The IP address of the peer to send the file to.
     pub ip: String,
-    /// The absolute path to the file to transmit.
-    pub path: String,
-    /// The filename to transmit.
+    /// The absolute paths to the files and folders to transmit, as a single offer.
+    /// Folders are sent with every file inside them.
+    pub paths: Vec<String>,
+    /// The name of the offer. If a single file is transmitted, the filename to transmit it as.
     pub filename: String,
+    /// The priority of the file transfer in the transfer queue. Higher priorities start first.
+    /// If None, defaults to 0.
+    pub priority: Option<i32>,
 }
 
 /// Struct representing a file offer response.
@@ -97,6 +169,370 @@ pub struct CancelFileTransfer {
     pub message: Option<String>,
 }
 
+/// Struct representing a move of a queued file transfer.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ReorderFileTransfer {
+    /// The unique identifier of the queued file transfer. (UUID)
+    pub unique_id: String,
+    /// The new position in the queue (0 is the next to start).
+    pub position: u32,
+}
+
+/// Struct representing a priority change of a queued file transfer.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SetFileTransferPriority {
+    /// The unique identifier of the queued file transfer. (UUID)
+    pub unique_id: String,
+    /// The new priority. Higher priorities start first.
+    pub priority: i32,
+}
+
+/// Struct representing a file transfer pause.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct PauseFileTransfer {
+    /// The unique identifier of the file transfer to pause. (UUID)
+    pub unique_id: String,
+}
+
+/// Struct representing a file transfer resumption.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ResumeFileTransfer {
+    /// The unique identifier of the file transfer to resume. (UUID)
+    pub unique_id: String,
+}
+
+/// Struct representing a change of the file transfer concurrency limits.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SetTransferConcurrency {
+    /// Maximum number of file transfers running at once. (0 is treated as 1)
+    pub max_concurrent: u32,
+    /// Maximum number of file transfers running at once with a single peer. (0 is treated as 1)
+    pub max_concurrent_per_peer: u32,
+}
+
+/// Struct representing a bandwidth limit change.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SetBandwidthLimits {
+    /// The IP address of the peer to limit.
+    /// If None, the limits apply to all peers combined.
+    pub ip: Option<String>,
+    /// Upload limit in bytes per second. None (or 0) is unlimited.
+    pub upload: Option<u64>,
+    /// Download limit in bytes per second. None (or 0) is unlimited.
+    pub download: Option<u64>,
+}
+
+/// Struct representing a download directory change.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SetDownloadDir {
+    /// The absolute path to the directory incoming files are saved to.
+    pub path: String,
+}
+
+/// Struct representing a change of the size limits for incoming file offers.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SetOfferPolicy {
+    /// Maximum size of a single incoming file in bytes. (None or 0 is unlimited)
+    pub max_file_size: Option<u64>,
+    /// Maximum bytes a single peer may send per day. (None or 0 is unlimited)
+    pub max_daily_bytes_per_peer: Option<u64>,
+}
+
+/// Struct representing a choice of which metadata of received files is applied.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SetMetadataPolicy {
+    /// Apply the modification time of the sender's files.
+    pub modified: bool,
+    /// Apply the Unix permissions (including the executable bit) of the sender's files.
+    /// Group and world write permissions are never applied.
+    pub mode: bool,
+    /// Make files read-only if they were read-only on the sender.
+    pub read_only: bool,
+}
+
+/// Struct representing a chat message to send.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SendChatMessage {
+    /// The IP address of the peer to send the message to,
+    /// or the identity of the peer if it is not connected (the message is sent once it connects).
+    /// Messages are only queued for peers that connected before.
+    pub peer: String,
+    /// The text of the message.
+    pub body: String,
+}
+
+/// Struct representing the conversation we are typing in.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SendTyping {
+    /// The identity of the peer of the conversation. (Either this or `room_id`)
+    pub peer_identity: Option<String>,
+    /// The room of the conversation. (UUID) (Either this or `peer_identity`)
+    pub room_id: Option<String>,
+}
+
+/// Struct representing an edit of a chat message we sent.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct EditChatMessage {
+    /// The identity of the peer of the conversation. (Either this or `room_id`)
+    pub peer_identity: Option<String>,
+    /// The room of the conversation. (UUID) (Either this or `peer_identity`)
+    pub room_id: Option<String>,
+    /// The unique identifier of the message. (UUID)
+    pub message_id: String,
+    /// The new text of the message.
+    pub body: String,
+}
+
+/// Struct representing a deletion of a chat message we sent.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct DeleteChatMessage {
+    /// The identity of the peer of the conversation. (Either this or `room_id`)
+    pub peer_identity: Option<String>,
+    /// The room of the conversation. (UUID) (Either this or `peer_identity`)
+    pub room_id: Option<String>,
+    /// The unique identifier of the message. (UUID)
+    pub message_id: String,
+}
+
+/// Struct representing an emoji reaction to a chat message, added or removed.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ReactToChatMessage {
+    /// The identity of the peer of the conversation. (Either this or `room_id`)
+    pub peer_identity: Option<String>,
+    /// The room of the conversation. (UUID) (Either this or `peer_identity`)
+    pub room_id: Option<String>,
+    /// The unique identifier of the message. (UUID)
+    pub message_id: String,
+    /// The emoji.
+    pub emoji: String,
+    /// Whether to add the reaction (or remove it).
+    pub added: bool,
+}
+
+/// Struct representing a request for a page of the chat history with a peer or a room.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct GetChatHistory {
+    /// The identity of the peer. (Either this or `room_id`)
+    pub peer_identity: Option<String>,
+    /// The room. (UUID) (Either this or `peer_identity`)
+    pub room_id: Option<String>,
+    /// Only messages before this one. (UUID) If None, the most recent messages.
+    pub before: Option<String>,
+    /// The maximum number of messages to return. (None is 50, at most 500)
+    pub limit: Option<u32>,
+}
+
+/// Struct representing a conversation to mark as read.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct MarkChatRead {
+    /// The identity of the peer. (Either this or `room_id`)
+    pub peer_identity: Option<String>,
+    /// The room. (UUID) (Either this or `peer_identity`)
+    pub room_id: Option<String>,
+}
+
+/// Struct representing a chat room to create.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct CreateRoom {
+    /// The name of the room.
+    pub name: String,
+    /// The identities of the peers to invite, peers that connected before.
+    /// Those not connected are invited once they connect.
+    pub members: Vec<String>,
+}
+
+/// Struct representing a change to the members of a chat room.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SetRoomMembers {
+    /// The room. (UUID)
+    pub room_id: String,
+    /// The identities of the other members of the room, after the change.
+    /// Those added must be peers that connected before.
+    pub members: Vec<String>,
+}
+
+/// Struct representing a chat room to leave.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct LeaveRoom {
+    /// The room. (UUID)
+    pub room_id: String,
+}
+
+/// Struct representing a chat message to send to a room.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SendRoomMessage {
+    /// The room. (UUID)
+    pub room_id: String,
+    /// The text of the message.
+    pub body: String,
+}
+
+/// Struct representing a search of the transfer history.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct QueryTransferHistory {
+    /// Only transfers with this peer. (by name)
+    pub peer_name: Option<String>,
+    /// Only transfers whose name, or the path of one of whose files, contains this text. (case-insensitive)
+    pub filename: Option<String>,
+    /// Only transfers in this direction.
+    pub direction: Option<FileTransferDirection>,
+    /// Only transfers that ended this way.
+    pub outcome: Option<TransferOutcome>,
+    /// Only transfers that ended at or after this time, in milliseconds since the Unix epoch.
+    pub since: Option<u64>,
+    /// Only transfers that ended before this time, in milliseconds since the Unix epoch.
+    pub until: Option<u64>,
+    /// The number of matching records to skip, most recent first. (None is 0)
+    pub offset: Option<u32>,
+    /// The maximum number of records to return. (None is 50, at most 500)
+    pub limit: Option<u32>,
+}
+
+/// Struct representing a replacement of the auto-accept rules.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SetAutoAcceptRules {
+    /// The rules, in the order they are evaluated. The first rule matching an offer applies.
+    pub rules: Vec<AutoAcceptRule>,
+}
+
+/// Struct representing an auto-accept rule for incoming file offers.
+///
+/// Criteria left empty (None) match any offer.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+// Rules saved with a `peer_name` are refused, rather than loaded as matching any peer
+#[serde(deny_unknown_fields)]
+#[ts(export)]
+pub struct AutoAcceptRule {
+    /// The name of the rule, reported when the rule is applied.
+    pub name: String,
+    /// The identity (base64 ECDSA public key) of the peer the rule applies to.
+    pub peer_identity: Option<String>,
+    /// Extensions (e.g. "jpg") every file must have one of. Empty matches any file.
+    pub extensions: Vec<String>,
+    /// Maximum total size of the offer in bytes.
+    pub max_size: Option<u64>,
+    /// Glob pattern (e.g. "photos/**/*.jpg") every file path must match.
+    pub pattern: Option<String>,
+    /// What to do with a matching offer.
+    pub action: AutoAcceptAction,
+}
+
+/// Enum of actions an auto-accept rule may take.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(tag = "type")]
+#[ts(export)]
+pub enum AutoAcceptAction {
+    /// Accept the offer, saving the files to `folder` (absolute path), or the download directory if None.
+    Accept { folder: Option<String> },
+    /// Reject the offer.
+    Reject,
+    /// Ask the frontend, as if no rule matched.
+    Prompt,
+}
+
+/// Struct representing a replacement of the shared folders.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SetSharedFolders {
+    /// The folders to share. Shares not listed are no longer shared.
+    pub shares: Vec<SharedFolder>,
+}
+
+/// Struct representing a replacement of the library folders.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SetLibraryFolders {
+    /// The folders to index besides the download directory. (absolute paths)
+    pub folders: Vec<String>,
+}
+
+/// Struct representing a folder shared with our peers.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SharedFolder {
+    /// The name peers see the folder as. Requested paths start with it. (e.g. "logs")
+    pub name: String,
+    /// The absolute path to the folder.
+    pub path: String,
+    /// The identities (base64 ECDSA public keys) of the peers that may access the folder.
+    /// Empty for every peer.
+    pub peer_identities: Vec<String>,
+    /// Whether to approve file requests automatically, rather than asking the frontend.
+    pub auto_approve: bool,
+}
+
+/// Struct representing a request for a file a peer shares.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct RequestFile {
+    /// The IP address of the peer to request the file from.
+    pub ip: String,
+    /// The path of the file or folder, as `<share name>/<path inside the share>`. (`/`-separated)
+    pub path: String,
+}
+
+/// Struct representing a request to download the same content from several peers at once.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SwarmDownloadRequest {
+    /// The peers to download from, and where each shares the content.
+    /// Peers whose content differs from the first offer received are left out.
+    pub sources: Vec<SwarmDownloadSource>,
+}
+
+/// Struct representing a peer to download from in a multi-source download.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SwarmDownloadSource {
+    /// The IP address of the peer.
+    pub ip: String,
+    /// The path of the file or folder on that peer, as `<share name>/<path inside the share>`. (`/`-separated)
+    pub path: String,
+}
+
+/// Struct representing a request for the listing of a directory a peer shares.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ListDirectory {
+    /// The IP address of the peer to list the directory of.
+    pub ip: String,
+    /// The directory, as `<share name>/<path inside the share>`. (`/`-separated)
+    /// Empty to list the shared folders themselves.
+    pub path: String,
+}
+
+/// Struct representing a file request response.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct FileRequestResponse {
+    /// The unique identifier of the file request. (UUID)
+    pub request_id: String,
+    /// Whether the file request is approved.
+    pub accept: bool,
+}
+
 /// Struct representing the configuration for the backend startup.
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export)]
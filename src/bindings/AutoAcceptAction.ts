@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Enum of actions an auto-accept rule may take.
+ */
+export type AutoAcceptAction = { "type": "Accept", folder: string | null, } | { "type": "Reject" } | { "type": "Prompt" };
@@ -0,0 +1,33 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AutoAcceptAction } from "./AutoAcceptAction";
+
+/**
+ * Struct representing an auto-accept rule for incoming file offers.
+ *
+ * Criteria left empty (None) match any offer.
+ */
+export type AutoAcceptRule = { 
+/**
+ * The name of the rule, reported when the rule is applied.
+ */
+name: string, 
+/**
+ * The identity (base64 ECDSA public key) of the peer the rule applies to.
+ */
+peer_identity: string | null, 
+/**
+ * Extensions (e.g. "jpg") every file must have one of. Empty matches any file.
+ */
+extensions: Array<string>, 
+/**
+ * Maximum total size of the offer in bytes.
+ */
+max_size: bigint | null, 
+/**
+ * Glob pattern (e.g. "photos/**/*.jpg") every file path must match.
+ */
+pattern: string | null, 
+/**
+ * What to do with a matching offer.
+ */
+action: AutoAcceptAction, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AutoAcceptRule } from "./AutoAcceptRule";
+
+/**
+ * Struct representing the auto-accept rules.
+ */
+export type AutoAcceptRulesInfo = { 
+/**
+ * The rules, in the order they are evaluated.
+ */
+rules: Array<AutoAcceptRule>, };
@@ -1,19 +1,45 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AutoAcceptRulesInfo } from "./AutoAcceptRulesInfo";
 import type { BackendError } from "./BackendError";
 import type { BackendFatal } from "./BackendFatal";
 import type { BackendInfo } from "./BackendInfo";
 import type { BackendMessage } from "./BackendMessage";
 import type { BackendWarning } from "./BackendWarning";
 import type { BadFrontendEvent } from "./BadFrontendEvent";
+import type { ChatConversations } from "./ChatConversations";
+import type { ChatHistoryPage } from "./ChatHistoryPage";
+import type { ChatMessage } from "./ChatMessage";
+import type { ChatMessageDelivered } from "./ChatMessageDelivered";
+import type { ChatMessageQueued } from "./ChatMessageQueued";
+import type { ChatMessageUpdated } from "./ChatMessageUpdated";
+import type { ChatReadUpTo } from "./ChatReadUpTo";
 import type { ConnectionCloseOrBroken } from "./ConnectionCloseOrBroken";
 import type { ConnectionInfo } from "./ConnectionInfo";
 import type { ConnectionRequestResponse } from "./ConnectionRequestResponse";
+import type { DirectoryListing } from "./DirectoryListing";
 import type { FileOffer } from "./FileOffer";
+import type { FileOfferAutoHandled } from "./FileOfferAutoHandled";
+import type { FileOfferRejected } from "./FileOfferRejected";
+import type { FileRequestAnswer } from "./FileRequestAnswer";
+import type { FileRequested } from "./FileRequested";
+import type { FileTransferCancelled } from "./FileTransferCancelled";
 import type { FileTransferComplete } from "./FileTransferComplete";
+import type { FileTransferDeduplicated } from "./FileTransferDeduplicated";
 import type { FileTransferError } from "./FileTransferError";
+import type { FileTransferFileComplete } from "./FileTransferFileComplete";
+import type { FileTransferPausedOrResumed } from "./FileTransferPausedOrResumed";
 import type { FileTransferProgress } from "./FileTransferProgress";
+import type { FileTransferQueued } from "./FileTransferQueued";
+import type { FileTransferRejected } from "./FileTransferRejected";
+import type { HashIndexInfo } from "./HashIndexInfo";
+import type { PeerTyping } from "./PeerTyping";
+import type { RoomUpdated } from "./RoomUpdated";
+import type { RoomsInfo } from "./RoomsInfo";
+import type { SharedFoldersInfo } from "./SharedFoldersInfo";
+import type { SwarmSourcesInfo } from "./SwarmSourcesInfo";
+import type { TransferHistoryPage } from "./TransferHistoryPage";
 
 /**
  * Enum of events that occur in the backend and should be sent to the frontend.
  */
-export type BackendEvent = { "type": "BackendError" } & BackendError | { "type": "BackendFatal" } & BackendFatal | { "type": "BackendReady" } & BackendInfo | { "type": "FatalLostComms" } & BackendFatal | { "type": "BackendShutdown" } | { "type": "BackendWarning" } & BackendWarning | { "type": "BadFrontendEvent" } & BadFrontendEvent | { "type": "ConnectRequest" } & ConnectionInfo | { "type": "ConnectionRequestResponse" } & ConnectionRequestResponse | { "type": "AutoConnectionClose" } & ConnectionInfo | { "type": "ConnectionClose" } & ConnectionCloseOrBroken | { "type": "ConnectionBroken" } & ConnectionCloseOrBroken | { "type": "FileOffer" } & FileOffer | { "type": "FileTransferComplete" } & FileTransferComplete | { "type": "FileTransferError" } & FileTransferError | { "type": "FileTransferProgress" } & FileTransferProgress | { "type": "Message" } & BackendMessage;
+export type BackendEvent = { "type": "BackendError" } & BackendError | { "type": "BackendFatal" } & BackendFatal | { "type": "BackendReady" } & BackendInfo | { "type": "FatalLostComms" } & BackendFatal | { "type": "BackendShutdown" } | { "type": "BackendWarning" } & BackendWarning | { "type": "BadFrontendEvent" } & BadFrontendEvent | { "type": "ConnectRequest" } & ConnectionInfo | { "type": "ConnectionRequestResponse" } & ConnectionRequestResponse | { "type": "AutoConnectionClose" } & ConnectionInfo | { "type": "ConnectionClose" } & ConnectionCloseOrBroken | { "type": "ConnectionBroken" } & ConnectionCloseOrBroken | { "type": "FileOffer" } & FileOffer | { "type": "FileOfferRejected" } & FileOfferRejected | { "type": "FileOfferAutoHandled" } & FileOfferAutoHandled | { "type": "AutoAcceptRules" } & AutoAcceptRulesInfo | { "type": "SharedFolders" } & SharedFoldersInfo | { "type": "FileRequested" } & FileRequested | { "type": "FileRequestAnswered" } & FileRequestAnswer | { "type": "DirectoryListing" } & DirectoryListing | { "type": "SwarmSources" } & SwarmSourcesInfo | { "type": "HashIndex" } & HashIndexInfo | { "type": "FileTransferDeduplicated" } & FileTransferDeduplicated | { "type": "FileTransferQueued" } & FileTransferQueued | { "type": "FileTransferComplete" } & FileTransferComplete | { "type": "FileTransferFileComplete" } & FileTransferFileComplete | { "type": "FileTransferError" } & FileTransferError | { "type": "FileTransferCancelled" } & FileTransferCancelled | { "type": "FileTransferRejected" } & FileTransferRejected | { "type": "FileTransferProgress" } & FileTransferProgress | { "type": "FileTransferPaused" } & FileTransferPausedOrResumed | { "type": "FileTransferResumed" } & FileTransferPausedOrResumed | { "type": "ChatMessageSent" } & ChatMessage | { "type": "ChatMessageQueued" } & ChatMessageQueued | { "type": "ChatMessageReceived" } & ChatMessage | { "type": "ChatMessageDelivered" } & ChatMessageDelivered | { "type": "ChatMessageUpdated" } & ChatMessageUpdated | { "type": "PeerTyping" } & PeerTyping | { "type": "ChatReadUpTo" } & ChatReadUpTo | { "type": "ChatHistory" } & ChatHistoryPage | { "type": "ChatConversations" } & ChatConversations | { "type": "Rooms" } & RoomsInfo | { "type": "RoomUpdated" } & RoomUpdated | { "type": "TransferHistory" } & TransferHistoryPage | { "type": "Message" } & BackendMessage;
@@ -0,0 +1,26 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a chat conversation with a peer or a room.
+ */
+export type ChatConversation = { 
+/**
+ * The identity of the peer. None for a room.
+ */
+peer_identity: string | null, 
+/**
+ * The last name the peer was known by. None for a room.
+ */
+peer_name: string | null, 
+/**
+ * The room. (UUID) None for a peer.
+ */
+room_id: string | null, 
+/**
+ * The number of messages received in the conversation that were not read yet.
+ */
+unread: number, 
+/**
+ * When the last message was sent, in milliseconds since the Unix epoch. (None if there are none)
+ */
+last_timestamp: bigint | null, };
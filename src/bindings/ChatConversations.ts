@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ChatConversation } from "./ChatConversation";
+
+/**
+ * Struct representing every chat conversation.
+ */
+export type ChatConversations = { 
+/**
+ * The conversations, most recent first.
+ */
+conversations: Array<ChatConversation>, };
@@ -0,0 +1,52 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ChatMessageReaction } from "./ChatMessageReaction";
+import type { ChatMessageState } from "./ChatMessageState";
+
+/**
+ * Struct representing a chat message in the chat history.
+ */
+export type ChatHistoryMessage = { 
+/**
+ * The unique identifier of the message. (UUID)
+ */
+id: string, 
+/**
+ * When the message was sent, in milliseconds since the Unix epoch. (sender's clock)
+ */
+timestamp: bigint, 
+/**
+ * The text of the message.
+ */
+body: string, 
+/**
+ * Whether we sent the message (rather than received it).
+ */
+outgoing: boolean, 
+/**
+ * The identity of the peer who sent the message. None if we did.
+ */
+author: string | null, 
+/**
+ * The name of the peer who sent the message, when it was sent (for display only). None if we did.
+ */
+author_name: string | null, 
+/**
+ * The Lamport clock of the room when the message was sent. (0 for messages to a peer only)
+ */
+clock: bigint, 
+/**
+ * The delivery state of the message.
+ */
+state: ChatMessageState, 
+/**
+ * When the message was last edited, in milliseconds since the Unix epoch. (editor's clock) None if it was not.
+ */
+edited_at: bigint | null, 
+/**
+ * Whether the message was deleted by its author. The text of a deleted message is empty.
+ */
+deleted: boolean, 
+/**
+ * The reactions to the message, oldest first.
+ */
+reactions: Array<ChatMessageReaction>, };
@@ -0,0 +1,23 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ChatHistoryMessage } from "./ChatHistoryMessage";
+
+/**
+ * Struct representing a page of the chat history with a peer or a room.
+ */
+export type ChatHistoryPage = { 
+/**
+ * The identity of the peer. None for a room.
+ */
+peer_identity: string | null, 
+/**
+ * The room. (UUID) None for a peer.
+ */
+room_id: string | null, 
+/**
+ * The messages of the page, oldest first.
+ */
+messages: Array<ChatHistoryMessage>, 
+/**
+ * Whether there are older messages before the page.
+ */
+has_more: boolean, };
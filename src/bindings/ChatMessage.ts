@@ -0,0 +1,27 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConnectionInfo } from "./ConnectionInfo";
+
+/**
+ * Struct representing a chat message, sent or received.
+ */
+export type ChatMessage = { 
+/**
+ * The peer the message was sent to, or received from.
+ */
+peer: ConnectionInfo, 
+/**
+ * The room the message was sent to. (UUID) None if it was sent to the peer only.
+ */
+room_id: string | null, 
+/**
+ * The unique identifier of the message. (UUID)
+ */
+id: string, 
+/**
+ * When the message was sent, in milliseconds since the Unix epoch. (sender's clock)
+ */
+timestamp: bigint, 
+/**
+ * The text of the message.
+ */
+body: string, };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConnectionInfo } from "./ConnectionInfo";
+
+/**
+ * Struct representing the delivery of a chat message we sent.
+ */
+export type ChatMessageDelivered = { 
+/**
+ * The peer the message was delivered to.
+ */
+peer: ConnectionInfo, 
+/**
+ * The unique identifier of the message. (UUID)
+ */
+id: string, };
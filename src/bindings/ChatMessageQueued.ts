@@ -0,0 +1,26 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a chat message queued for a peer that is not connected.
+ */
+export type ChatMessageQueued = { 
+/**
+ * The identity of the peer the message is for.
+ */
+peer_identity: string, 
+/**
+ * The room the message was sent to. (UUID) None if it was sent to the peer only.
+ */
+room_id: string | null, 
+/**
+ * The unique identifier of the message. (UUID)
+ */
+id: string, 
+/**
+ * When the message was queued, in milliseconds since the Unix epoch.
+ */
+timestamp: bigint, 
+/**
+ * The text of the message.
+ */
+body: string, };
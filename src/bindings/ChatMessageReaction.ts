@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing an emoji reaction to a chat message.
+ */
+export type ChatMessageReaction = { 
+/**
+ * The emoji.
+ */
+emoji: string, 
+/**
+ * The identity of the peer who reacted. None if we did.
+ */
+by: string | null, 
+/**
+ * The name of the peer who reacted, when it reacted (for display only). None if we did.
+ */
+by_name: string | null, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Enum representing the delivery state of a chat message.
+ */
+export type ChatMessageState = "Queued" | "Sent" | "Delivered" | "Unread" | "Read";
@@ -0,0 +1,27 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ChatHistoryMessage } from "./ChatHistoryMessage";
+
+/**
+ * Struct representing an edit, deletion or reaction to a chat message.
+ */
+export type ChatMessageUpdated = { 
+/**
+ * The identity of the peer of the conversation. None for a room.
+ */
+peer_identity: string | null, 
+/**
+ * The room of the conversation. (UUID) None for a peer.
+ */
+room_id: string | null, 
+/**
+ * The message, after the change.
+ */
+message: ChatHistoryMessage, 
+/**
+ * The identity of the peer who made the change. None if we did.
+ */
+by: string | null, 
+/**
+ * The name of the peer who made the change. None if we did.
+ */
+by_name: string | null, };
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConnectionInfo } from "./ConnectionInfo";
+
+/**
+ * Struct representing a read receipt: a peer read a conversation up to a message.
+ */
+export type ChatReadUpTo = { 
+/**
+ * The peer who read the conversation.
+ */
+peer: ConnectionInfo, 
+/**
+ * The room that was read. (UUID) None for the conversation with the peer.
+ */
+room_id: string | null, 
+/**
+ * The unique identifier of the last message the peer read. (UUID) Every message before it was read too.
+ */
+message_id: string, };
@@ -17,7 +17,7 @@ ip: string,
  */
 backend_version: string, 
 /**
- * The ECDSA public key of the connection.
+ * The identity (ECDSA public key) of the connection, verified in the handshake.
  * As a string encoded in base64.
  */
-identitiy: string, };
+identity: string, };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a chat room to create.
+ */
+export type CreateRoom = { 
+/**
+ * The name of the room.
+ */
+name: string, 
+/**
+ * The identities of the peers to invite, peers that connected before.
+ * Those not connected are invited once they connect.
+ */
+members: Array<string>, };
@@ -0,0 +1,22 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing an entry of a directory listing.
+ */
+export type DirectoryEntry = { 
+/**
+ * The name of the file or directory.
+ */
+name: string, 
+/**
+ * The size in bytes. (0 for directories)
+ */
+size: bigint, 
+/**
+ * The last modification time, in seconds since the UNIX epoch.
+ */
+modified: bigint | null, 
+/**
+ * Whether the entry is a directory.
+ */
+is_dir: boolean, };
@@ -0,0 +1,28 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConnectionInfo } from "./ConnectionInfo";
+import type { DirectoryEntry } from "./DirectoryEntry";
+
+/**
+ * Struct representing the listing of a directory a peer shares.
+ */
+export type DirectoryListing = { 
+/**
+ * The peer sharing the directory.
+ */
+peer: ConnectionInfo, 
+/**
+ * The directory that was listed. Empty for the shared folders themselves.
+ */
+path: string, 
+/**
+ * The entries of the directory, sorted by name.
+ */
+entries: Array<DirectoryEntry>, 
+/**
+ * Whether the directory had too many entries to list them all.
+ */
+truncated: boolean, 
+/**
+ * Why the directory could not be listed.
+ */
+error: string | null, };
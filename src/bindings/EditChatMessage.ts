@@ -0,0 +1,22 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing an edit of a chat message we sent.
+ */
+export type EditChatMessage = { 
+/**
+ * The identity of the peer of the conversation. (Either this or `room_id`)
+ */
+peer_identity: string | null, 
+/**
+ * The room of the conversation. (UUID) (Either this or `peer_identity`)
+ */
+room_id: string | null, 
+/**
+ * The unique identifier of the message. (UUID)
+ */
+message_id: string, 
+/**
+ * The new text of the message.
+ */
+body: string, };
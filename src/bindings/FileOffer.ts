@@ -1,5 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { ConnectionInfo } from "./ConnectionInfo";
+import type { FileOfferEntry } from "./FileOfferEntry";
 
 /**
  * Struct representing a file offer.
@@ -14,10 +15,18 @@ peer: ConnectionInfo,
  */
 filename: string, 
 /**
- * A unique identifier for the file.
+ * A unique identifier for the file. (UUID)
  */
-unique_id: bigint, 
+unique_id: string, 
 /**
- * The size of the file in bytes.
+ * The total size of every file in bytes.
  */
-size: bigint, };
+size: bigint, 
+/**
+ * The files being offered. A single file, or every file of a folder or selection.
+ */
+files: Array<FileOfferEntry>, 
+/**
+ * A warning if accepting the offer would leave little free disk space.
+ */
+space_warning: string | null, };
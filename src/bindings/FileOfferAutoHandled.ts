@@ -0,0 +1,44 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConnectionInfo } from "./ConnectionInfo";
+import type { FileOfferEntry } from "./FileOfferEntry";
+
+/**
+ * Struct representing a file offer handled by an auto-accept rule.
+ */
+export type FileOfferAutoHandled = { 
+/**
+ * The peer that offered the file.
+ */
+peer: ConnectionInfo, 
+/**
+ * The filename of the file that was offered.
+ */
+filename: string, 
+/**
+ * A unique identifier for the file. (UUID)
+ */
+unique_id: string, 
+/**
+ * The total size of every file in bytes.
+ */
+size: bigint, 
+/**
+ * The files that were offered.
+ */
+files: Array<FileOfferEntry>, 
+/**
+ * Whether the offer was accepted (rather than rejected).
+ */
+accepted: boolean, 
+/**
+ * The name of the rule that was applied.
+ */
+rule: string, 
+/**
+ * The folder the files are saved to, if accepted.
+ */
+folder: string | null, 
+/**
+ * A warning if accepting the offer leaves little free disk space.
+ */
+space_warning: string | null, };
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a file of a file offer.
+ */
+export type FileOfferEntry = { 
+/**
+ * The path of the file, relative to the download directory. (`/`-separated)
+ */
+path: string, 
+/**
+ * The size of the file in bytes.
+ */
+size: bigint, };
@@ -0,0 +1,27 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConnectionInfo } from "./ConnectionInfo";
+
+/**
+ * Struct representing a file offer that was rejected automatically.
+ */
+export type FileOfferRejected = { 
+/**
+ * The peer that offered the file.
+ */
+peer: ConnectionInfo, 
+/**
+ * The filename of the file that was offered.
+ */
+filename: string, 
+/**
+ * A unique identifier for the file. (UUID)
+ */
+unique_id: string, 
+/**
+ * The total size of every file in bytes.
+ */
+size: bigint, 
+/**
+ * Why the offer was rejected. Also sent to the peer.
+ */
+reason: string, };
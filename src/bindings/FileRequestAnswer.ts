@@ -0,0 +1,27 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConnectionInfo } from "./ConnectionInfo";
+
+/**
+ * Struct representing a peer's response to our file request.
+ */
+export type FileRequestAnswer = { 
+/**
+ * The peer the file was requested from.
+ */
+peer: ConnectionInfo, 
+/**
+ * A unique identifier for the request, and the file transfer if approved. (UUID)
+ */
+request_id: string, 
+/**
+ * The requested path.
+ */
+path: string, 
+/**
+ * Whether the request was approved. If so, the file offer follows and is accepted automatically.
+ */
+accept: boolean, 
+/**
+ * Why the request was denied.
+ */
+reason: string | null, };
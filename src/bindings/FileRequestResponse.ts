@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a file request response.
+ */
+export type FileRequestResponse = { 
+/**
+ * The unique identifier of the file request. (UUID)
+ */
+request_id: string, 
+/**
+ * Whether the file request is approved.
+ */
+accept: boolean, };
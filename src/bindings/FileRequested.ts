@@ -0,0 +1,23 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConnectionInfo } from "./ConnectionInfo";
+
+/**
+ * Struct representing a file request from a peer.
+ */
+export type FileRequested = { 
+/**
+ * The peer requesting the file.
+ */
+peer: ConnectionInfo, 
+/**
+ * A unique identifier for the request, and the file transfer if approved. (UUID)
+ */
+request_id: string, 
+/**
+ * The requested path, as `<share name>/<path inside the share>`.
+ */
+path: string, 
+/**
+ * Whether the request was approved automatically by the share.
+ */
+auto_approved: boolean, };
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a file transfer cancellation.
+ */
+export type FileTransferCancelled = { 
+/**
+ * The unique identifier of the cancelled file transfer. (UUID)
+ */
+unique_id: string, 
+/**
+ * Optional message sent with the cancellation.
+ */
+message: string | null, 
+/**
+ * Whether the peer cancelled the file transfer (rather than us).
+ */
+by_peer: boolean, };
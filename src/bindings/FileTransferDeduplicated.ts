@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing the files of an incoming file transfer we already had.
+ */
+export type FileTransferDeduplicated = { 
+/**
+ * The unique identifier of the file transfer. (UUID)
+ */
+unique_id: string, 
+/**
+ * The paths (relative to the root of the transfer) of the files placed from local copies.
+ */
+paths: Array<string>, 
+/**
+ * The number of bytes not transferred.
+ */
+bytes: bigint, };
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing the completion of a single file of an incoming file transfer.
+ */
+export type FileTransferFileComplete = { 
+/**
+ * The unique identifier of the file transfer. (UUID)
+ */
+unique_id: string, 
+/**
+ * The path of the file, relative to the download directory. (`/`-separated)
+ */
+path: string, };
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a file transfer pause or resumption.
+ */
+export type FileTransferPausedOrResumed = { 
+/**
+ * The unique identifier of the file transfer. (UUID)
+ */
+unique_id: string, 
+/**
+ * Whether the peer paused/resumed the file transfer (rather than us).
+ */
+by_peer: boolean, };
@@ -6,9 +6,9 @@ import type { FileTransferDirection } from "./FileTransferDirection";
  */
 export type FileTransferProgress = { 
 /**
- * The unique identifier of the file being transferred.
+ * The unique identifier of the file being transferred. (UUID)
  */
-unique_id: bigint, 
+unique_id: string, 
 /**
  * The number of bytes transferred so far.
  */
@@ -17,6 +17,14 @@ bytes_transferred: bigint,
  * The total number of bytes to transfer.
  */
 total_bytes: bigint, 
+/**
+ * The number of chunk bytes sent or received over the network so far, after compression (retransmissions included).
+ */
+wire_bytes: bigint, 
+/**
+ * The current effective transfer rate in bytes per second.
+ */
+rate: bigint, 
 /**
  * Sending or receiving the file?
  */
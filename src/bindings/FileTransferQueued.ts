@@ -0,0 +1,22 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a file transfer waiting in the transfer queue.
+ */
+export type FileTransferQueued = { 
+/**
+ * The unique identifier of the queued file transfer. (UUID)
+ */
+unique_id: string, 
+/**
+ * The position in the queue (0 is the next to start).
+ */
+position: number, 
+/**
+ * The priority of the file transfer. Higher priorities start first.
+ */
+priority: number, 
+/**
+ * Whether the queued file transfer is paused.
+ */
+paused: boolean, };
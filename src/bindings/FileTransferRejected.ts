@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing an outgoing file offer rejected by the peer.
+ */
+export type FileTransferRejected = { 
+/**
+ * The unique identifier of the rejected file transfer. (UUID)
+ */
+unique_id: string, 
+/**
+ * Why the peer rejected the offer, if it was rejected automatically.
+ */
+reason: string | null, };
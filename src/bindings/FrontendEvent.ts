@@ -3,11 +3,39 @@ import type { BackendStartupConfig } from "./BackendStartupConfig";
 import type { CancelFileTransfer } from "./CancelFileTransfer";
 import type { ConnectRequest } from "./ConnectRequest";
 import type { ConnectionRequestResponse } from "./ConnectionRequestResponse";
+import type { CreateRoom } from "./CreateRoom";
+import type { DeleteChatMessage } from "./DeleteChatMessage";
 import type { DisconnectRequest } from "./DisconnectRequest";
+import type { EditChatMessage } from "./EditChatMessage";
 import type { FileOfferResponse } from "./FileOfferResponse";
+import type { FileRequestResponse } from "./FileRequestResponse";
+import type { GetChatHistory } from "./GetChatHistory";
+import type { LeaveRoom } from "./LeaveRoom";
+import type { ListDirectory } from "./ListDirectory";
+import type { MarkChatRead } from "./MarkChatRead";
+import type { PauseFileTransfer } from "./PauseFileTransfer";
+import type { QueryTransferHistory } from "./QueryTransferHistory";
+import type { ReactToChatMessage } from "./ReactToChatMessage";
+import type { ReorderFileTransfer } from "./ReorderFileTransfer";
+import type { RequestFile } from "./RequestFile";
+import type { ResumeFileTransfer } from "./ResumeFileTransfer";
+import type { SendChatMessage } from "./SendChatMessage";
+import type { SendRoomMessage } from "./SendRoomMessage";
+import type { SendTyping } from "./SendTyping";
+import type { SetAutoAcceptRules } from "./SetAutoAcceptRules";
+import type { SetBandwidthLimits } from "./SetBandwidthLimits";
+import type { SetDownloadDir } from "./SetDownloadDir";
+import type { SetFileTransferPriority } from "./SetFileTransferPriority";
+import type { SetLibraryFolders } from "./SetLibraryFolders";
+import type { SetMetadataPolicy } from "./SetMetadataPolicy";
+import type { SetOfferPolicy } from "./SetOfferPolicy";
+import type { SetRoomMembers } from "./SetRoomMembers";
+import type { SetSharedFolders } from "./SetSharedFolders";
+import type { SetTransferConcurrency } from "./SetTransferConcurrency";
+import type { SwarmDownloadRequest } from "./SwarmDownloadRequest";
 import type { TransmitFile } from "./TransmitFile";
 
 /**
  * Enum of events that occur in the frontend and should be sent to the backend.
  */
-export type FrontendEvent = { "type": "ConnectRequest" } & ConnectRequest | { "type": "DisconnectRequest" } & DisconnectRequest | { "type": "ConnectionRequestResponse" } & ConnectionRequestResponse | { "type": "TransmitFile" } & TransmitFile | { "type": "FileOfferResponse" } & FileOfferResponse | { "type": "CancelFileTransfer" } & CancelFileTransfer | { "type": "FrontendReady" } & BackendStartupConfig | { "type": "Shutdown" } | { "type": "Start" } & BackendStartupConfig | { "type": "Restart" } & BackendStartupConfig;
+export type FrontendEvent = { "type": "ConnectRequest" } & ConnectRequest | { "type": "DisconnectRequest" } & DisconnectRequest | { "type": "ConnectionRequestResponse" } & ConnectionRequestResponse | { "type": "TransmitFile" } & TransmitFile | { "type": "FileOfferResponse" } & FileOfferResponse | { "type": "CancelFileTransfer" } & CancelFileTransfer | { "type": "ReorderFileTransfer" } & ReorderFileTransfer | { "type": "SetFileTransferPriority" } & SetFileTransferPriority | { "type": "PauseFileTransfer" } & PauseFileTransfer | { "type": "ResumeFileTransfer" } & ResumeFileTransfer | { "type": "SetTransferConcurrency" } & SetTransferConcurrency | { "type": "SetBandwidthLimits" } & SetBandwidthLimits | { "type": "SetDownloadDir" } & SetDownloadDir | { "type": "SetOfferPolicy" } & SetOfferPolicy | { "type": "SetMetadataPolicy" } & SetMetadataPolicy | { "type": "SetAutoAcceptRules" } & SetAutoAcceptRules | { "type": "GetAutoAcceptRules" } | { "type": "SetSharedFolders" } & SetSharedFolders | { "type": "GetSharedFolders" } | { "type": "RequestFile" } & RequestFile | { "type": "FileRequestResponse" } & FileRequestResponse | { "type": "ListDirectory" } & ListDirectory | { "type": "SwarmDownload" } & SwarmDownloadRequest | { "type": "SetLibraryFolders" } & SetLibraryFolders | { "type": "GetHashIndex" } | { "type": "SendChatMessage" } & SendChatMessage | { "type": "SendTyping" } & SendTyping | { "type": "EditChatMessage" } & EditChatMessage | { "type": "DeleteChatMessage" } & DeleteChatMessage | { "type": "ReactToChatMessage" } & ReactToChatMessage | { "type": "GetChatHistory" } & GetChatHistory | { "type": "MarkChatRead" } & MarkChatRead | { "type": "GetChatConversations" } | { "type": "CreateRoom" } & CreateRoom | { "type": "SetRoomMembers" } & SetRoomMembers | { "type": "LeaveRoom" } & LeaveRoom | { "type": "SendRoomMessage" } & SendRoomMessage | { "type": "GetRooms" } | { "type": "QueryTransferHistory" } & QueryTransferHistory | { "type": "FrontendReady" } & BackendStartupConfig | { "type": "Shutdown" } | { "type": "Start" } & BackendStartupConfig | { "type": "Restart" } & BackendStartupConfig;
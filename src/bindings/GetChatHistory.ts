@@ -0,0 +1,22 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a request for a page of the chat history with a peer or a room.
+ */
+export type GetChatHistory = { 
+/**
+ * The identity of the peer. (Either this or `room_id`)
+ */
+peer_identity: string | null, 
+/**
+ * The room. (UUID) (Either this or `peer_identity`)
+ */
+room_id: string | null, 
+/**
+ * Only messages before this one. (UUID) If None, the most recent messages.
+ */
+before: string | null, 
+/**
+ * The maximum number of messages to return. (None is 50, at most 500)
+ */
+limit: number | null, };
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing the hash index of the files we already have.
+ */
+export type HashIndexInfo = { 
+/**
+ * The library folders indexed besides the download directory. (absolute paths)
+ */
+libraries: Array<string>, 
+/**
+ * The number of files indexed.
+ */
+files: bigint, 
+/**
+ * The total size of the files indexed, in bytes.
+ */
+bytes: bigint, };
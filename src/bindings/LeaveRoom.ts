@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a chat room to leave.
+ */
+export type LeaveRoom = { 
+/**
+ * The room. (UUID)
+ */
+room_id: string, };
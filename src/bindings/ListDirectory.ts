@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a request for the listing of a directory a peer shares.
+ */
+export type ListDirectory = { 
+/**
+ * The IP address of the peer to list the directory of.
+ */
+ip: string, 
+/**
+ * The directory, as `<share name>/<path inside the share>`. (`/`-separated)
+ * Empty to list the shared folders themselves.
+ */
+path: string, };
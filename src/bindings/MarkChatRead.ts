@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a conversation to mark as read.
+ */
+export type MarkChatRead = { 
+/**
+ * The identity of the peer. (Either this or `room_id`)
+ */
+peer_identity: string | null, 
+/**
+ * The room. (UUID) (Either this or `peer_identity`)
+ */
+room_id: string | null, };
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a file transfer pause.
+ */
+export type PauseFileTransfer = { 
+/**
+ * The unique identifier of the file transfer to pause. (UUID)
+ */
+unique_id: string, };
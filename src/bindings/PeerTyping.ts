@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConnectionInfo } from "./ConnectionInfo";
+
+/**
+ * Struct representing a peer typing in a conversation with us.
+ */
+export type PeerTyping = { 
+/**
+ * The peer who is typing.
+ */
+peer: ConnectionInfo, 
+/**
+ * The room the peer is typing in. (UUID) None for the conversation with the peer.
+ */
+room_id: string | null, 
+/**
+ * How long to show that the peer is typing, in milliseconds, unless another one arrives.
+ */
+timeout_ms: bigint, };
@@ -0,0 +1,40 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FileTransferDirection } from "./FileTransferDirection";
+import type { TransferOutcome } from "./TransferOutcome";
+
+/**
+ * Struct representing a search of the transfer history.
+ */
+export type QueryTransferHistory = { 
+/**
+ * Only transfers with this peer. (by name)
+ */
+peer_name: string | null, 
+/**
+ * Only transfers whose name, or the path of one of whose files, contains this text. (case-insensitive)
+ */
+filename: string | null, 
+/**
+ * Only transfers in this direction.
+ */
+direction: FileTransferDirection | null, 
+/**
+ * Only transfers that ended this way.
+ */
+outcome: TransferOutcome | null, 
+/**
+ * Only transfers that ended at or after this time, in milliseconds since the Unix epoch.
+ */
+since: bigint | null, 
+/**
+ * Only transfers that ended before this time, in milliseconds since the Unix epoch.
+ */
+until: bigint | null, 
+/**
+ * The number of matching records to skip, most recent first. (None is 0)
+ */
+offset: number | null, 
+/**
+ * The maximum number of records to return. (None is 50, at most 500)
+ */
+limit: number | null, };
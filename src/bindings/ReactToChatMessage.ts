@@ -0,0 +1,26 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing an emoji reaction to a chat message, added or removed.
+ */
+export type ReactToChatMessage = { 
+/**
+ * The identity of the peer of the conversation. (Either this or `room_id`)
+ */
+peer_identity: string | null, 
+/**
+ * The room of the conversation. (UUID) (Either this or `peer_identity`)
+ */
+room_id: string | null, 
+/**
+ * The unique identifier of the message. (UUID)
+ */
+message_id: string, 
+/**
+ * The emoji.
+ */
+emoji: string, 
+/**
+ * Whether to add the reaction (or remove it).
+ */
+added: boolean, };
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a move of a queued file transfer.
+ */
+export type ReorderFileTransfer = { 
+/**
+ * The unique identifier of the queued file transfer. (UUID)
+ */
+unique_id: string, 
+/**
+ * The new position in the queue (0 is the next to start).
+ */
+position: number, };
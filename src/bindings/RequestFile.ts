@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a request for a file a peer shares.
+ */
+export type RequestFile = { 
+/**
+ * The IP address of the peer to request the file from.
+ */
+ip: string, 
+/**
+ * The path of the file or folder, as `<share name>/<path inside the share>`. (`/`-separated)
+ */
+path: string, };
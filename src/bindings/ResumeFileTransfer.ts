@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a file transfer resumption.
+ */
+export type ResumeFileTransfer = { 
+/**
+ * The unique identifier of the file transfer to resume. (UUID)
+ */
+unique_id: string, };
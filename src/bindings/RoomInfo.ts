@@ -0,0 +1,23 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RoomMember } from "./RoomMember";
+
+/**
+ * Struct representing a chat room.
+ */
+export type RoomInfo = { 
+/**
+ * The unique identifier of the room. (UUID)
+ */
+room_id: string, 
+/**
+ * The name of the room.
+ */
+name: string, 
+/**
+ * The other members of the room.
+ */
+members: Array<RoomMember>, 
+/**
+ * Whether we are a member of the room. (False once we left it, or were removed from it)
+ */
+joined: boolean, };
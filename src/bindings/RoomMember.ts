@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a member of a chat room.
+ */
+export type RoomMember = { 
+/**
+ * The identity of the member.
+ */
+identity: string, 
+/**
+ * The last name the member was known by. None if it never connected to us.
+ */
+name: string | null, };
@@ -0,0 +1,23 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RoomInfo } from "./RoomInfo";
+
+/**
+ * Struct representing a change to a chat room.
+ */
+export type RoomUpdated = { 
+/**
+ * The room, after the change.
+ */
+room: RoomInfo, 
+/**
+ * The identity of the peer who made the change. None if we did.
+ */
+by: string | null, 
+/**
+ * The identities of the members who were added.
+ */
+added: Array<string>, 
+/**
+ * The identities of the members who were removed, or left.
+ */
+removed: Array<string>, };
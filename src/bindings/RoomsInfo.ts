@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RoomInfo } from "./RoomInfo";
+
+/**
+ * Struct representing every chat room we know of.
+ */
+export type RoomsInfo = { 
+/**
+ * The rooms, by name.
+ */
+rooms: Array<RoomInfo>, };
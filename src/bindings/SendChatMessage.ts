@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a chat message to send.
+ */
+export type SendChatMessage = { 
+/**
+ * The IP address of the peer to send the message to,
+ * or the identity of the peer if it is not connected (the message is sent once it connects).
+ * Messages are only queued for peers that connected before.
+ */
+peer: string, 
+/**
+ * The text of the message.
+ */
+body: string, };
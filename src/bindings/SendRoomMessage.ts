@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a chat message to send to a room.
+ */
+export type SendRoomMessage = { 
+/**
+ * The room. (UUID)
+ */
+room_id: string, 
+/**
+ * The text of the message.
+ */
+body: string, };
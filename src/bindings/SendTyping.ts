@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing the conversation we are typing in.
+ */
+export type SendTyping = { 
+/**
+ * The identity of the peer of the conversation. (Either this or `room_id`)
+ */
+peer_identity: string | null, 
+/**
+ * The room of the conversation. (UUID) (Either this or `peer_identity`)
+ */
+room_id: string | null, };
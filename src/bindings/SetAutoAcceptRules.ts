@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AutoAcceptRule } from "./AutoAcceptRule";
+
+/**
+ * Struct representing a replacement of the auto-accept rules.
+ */
+export type SetAutoAcceptRules = { 
+/**
+ * The rules, in the order they are evaluated. The first rule matching an offer applies.
+ */
+rules: Array<AutoAcceptRule>, };
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a bandwidth limit change.
+ */
+export type SetBandwidthLimits = { 
+/**
+ * The IP address of the peer to limit.
+ * If None, the limits apply to all peers combined.
+ */
+ip: string | null, 
+/**
+ * Upload limit in bytes per second. None (or 0) is unlimited.
+ */
+upload: bigint | null, 
+/**
+ * Download limit in bytes per second. None (or 0) is unlimited.
+ */
+download: bigint | null, };
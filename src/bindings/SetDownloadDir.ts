@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a download directory change.
+ */
+export type SetDownloadDir = { 
+/**
+ * The absolute path to the directory incoming files are saved to.
+ */
+path: string, };
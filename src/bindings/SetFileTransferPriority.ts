@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a priority change of a queued file transfer.
+ */
+export type SetFileTransferPriority = { 
+/**
+ * The unique identifier of the queued file transfer. (UUID)
+ */
+unique_id: string, 
+/**
+ * The new priority. Higher priorities start first.
+ */
+priority: number, };
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a replacement of the library folders.
+ */
+export type SetLibraryFolders = { 
+/**
+ * The folders to index besides the download directory. (absolute paths)
+ */
+folders: Array<string>, };
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a choice of which metadata of received files is applied.
+ */
+export type SetMetadataPolicy = { 
+/**
+ * Apply the modification time of the sender's files.
+ */
+modified: boolean, 
+/**
+ * Apply the Unix permissions (including the executable bit) of the sender's files.
+ * Group and world write permissions are never applied.
+ */
+mode: boolean, 
+/**
+ * Make files read-only if they were read-only on the sender.
+ */
+read_only: boolean, };
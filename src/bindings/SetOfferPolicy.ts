@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a change of the size limits for incoming file offers.
+ */
+export type SetOfferPolicy = { 
+/**
+ * Maximum size of a single incoming file in bytes. (None or 0 is unlimited)
+ */
+max_file_size: bigint | null, 
+/**
+ * Maximum bytes a single peer may send per day. (None or 0 is unlimited)
+ */
+max_daily_bytes_per_peer: bigint | null, };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a change to the members of a chat room.
+ */
+export type SetRoomMembers = { 
+/**
+ * The room. (UUID)
+ */
+room_id: string, 
+/**
+ * The identities of the other members of the room, after the change.
+ * Those added must be peers that connected before.
+ */
+members: Array<string>, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SharedFolder } from "./SharedFolder";
+
+/**
+ * Struct representing a replacement of the shared folders.
+ */
+export type SetSharedFolders = { 
+/**
+ * The folders to share. Shares not listed are no longer shared.
+ */
+shares: Array<SharedFolder>, };
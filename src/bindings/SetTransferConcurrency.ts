@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a change of the file transfer concurrency limits.
+ */
+export type SetTransferConcurrency = { 
+/**
+ * Maximum number of file transfers running at once. (0 is treated as 1)
+ */
+max_concurrent: number, 
+/**
+ * Maximum number of file transfers running at once with a single peer. (0 is treated as 1)
+ */
+max_concurrent_per_peer: number, };
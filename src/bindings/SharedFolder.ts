@@ -0,0 +1,23 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a folder shared with our peers.
+ */
+export type SharedFolder = { 
+/**
+ * The name peers see the folder as. Requested paths start with it. (e.g. "logs")
+ */
+name: string, 
+/**
+ * The absolute path to the folder.
+ */
+path: string, 
+/**
+ * The identities (base64 ECDSA public keys) of the peers that may access the folder.
+ * Empty for every peer.
+ */
+peer_identities: Array<string>, 
+/**
+ * Whether to approve file requests automatically, rather than asking the frontend.
+ */
+auto_approve: boolean, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SharedFolder } from "./SharedFolder";
+
+/**
+ * Struct representing the shared folders.
+ */
+export type SharedFoldersInfo = { 
+/**
+ * The folders we share with our peers.
+ */
+shares: Array<SharedFolder>, };
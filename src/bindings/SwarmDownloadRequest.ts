@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SwarmDownloadSource } from "./SwarmDownloadSource";
+
+/**
+ * Struct representing a request to download the same content from several peers at once.
+ */
+export type SwarmDownloadRequest = { 
+/**
+ * The peers to download from, and where each shares the content.
+ * Peers whose content differs from the first offer received are left out.
+ */
+sources: Array<SwarmDownloadSource>, };
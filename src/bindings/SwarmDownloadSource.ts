@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a peer to download from in a multi-source download.
+ */
+export type SwarmDownloadSource = { 
+/**
+ * The IP address of the peer.
+ */
+ip: string, 
+/**
+ * The path of the file or folder on that peer, as `<share name>/<path inside the share>`. (`/`-separated)
+ */
+path: string, };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConnectionInfo } from "./ConnectionInfo";
+
+/**
+ * Struct representing a source of a multi-source download.
+ */
+export type SwarmSourceInfo = { 
+/**
+ * The peer sending the download.
+ */
+peer: ConnectionInfo, 
+/**
+ * The number of bytes received from the peer so far.
+ */
+bytes_received: bigint, };
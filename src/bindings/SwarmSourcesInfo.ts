@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SwarmSourceInfo } from "./SwarmSourceInfo";
+
+/**
+ * Struct representing the sources of a multi-source download.
+ */
+export type SwarmSourcesInfo = { 
+/**
+ * The unique identifier of the download (the file transfer). (UUID)
+ */
+unique_id: string, 
+/**
+ * The sources currently sending the download.
+ */
+sources: Array<SwarmSourceInfo>, };
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { TransferRecord } from "./TransferRecord";
+
+/**
+ * Struct representing a page of the transfer history matching a query.
+ */
+export type TransferHistoryPage = { 
+/**
+ * The records of the page, most recent first.
+ */
+records: Array<TransferRecord>, 
+/**
+ * The number of records skipped before the page.
+ */
+offset: number, 
+/**
+ * The number of records matching the query, over every page.
+ */
+total: number, };
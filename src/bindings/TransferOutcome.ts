@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Enum representing how a file transfer ended.
+ */
+export type TransferOutcome = "Completed" | "Cancelled" | "Rejected" | "Failed";
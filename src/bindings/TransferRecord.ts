@@ -0,0 +1,61 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FileTransferDirection } from "./FileTransferDirection";
+import type { TransferOutcome } from "./TransferOutcome";
+import type { TransferRecordFile } from "./TransferRecordFile";
+
+/**
+ * Struct representing a finished file transfer, as recorded in the transfer history.
+ */
+export type TransferRecord = { 
+/**
+ * The unique identifier of the file transfer. (UUID)
+ */
+unique_id: string, 
+/**
+ * The name of the peer.
+ */
+peer_name: string, 
+/**
+ * The IP address of the peer.
+ */
+peer_ip: string, 
+/**
+ * The name of the offer (the file name, or the folder name for a folder).
+ */
+filename: string, 
+/**
+ * The size of every file of the transfer, in bytes.
+ */
+size: bigint, 
+/**
+ * The number of bytes transferred before the transfer ended.
+ */
+bytes_transferred: bigint, 
+/**
+ * Whether we sent or received the files.
+ */
+direction: FileTransferDirection, 
+/**
+ * When the transfer was queued (sending) or offered (receiving), in milliseconds since the Unix epoch.
+ */
+started_at: bigint, 
+/**
+ * When the transfer ended, in milliseconds since the Unix epoch.
+ */
+ended_at: bigint, 
+/**
+ * How the transfer ended.
+ */
+outcome: TransferOutcome, 
+/**
+ * Why the transfer failed or was rejected, if known.
+ */
+reason: string | null, 
+/**
+ * Where the file (or folder) was saved to, or sent from. (None if it was never saved)
+ */
+local_path: string | null, 
+/**
+ * The files of the transfer.
+ */
+files: Array<TransferRecordFile>, };
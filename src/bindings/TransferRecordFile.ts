@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Struct representing a file of a finished file transfer.
+ */
+export type TransferRecordFile = { 
+/**
+ * The path of the file, relative to the root of the transfer. (`/`-separated)
+ */
+path: string, 
+/**
+ * The size of the file, in bytes.
+ */
+size: bigint, 
+/**
+ * The BLAKE3 checksum of the file, in hex. (None if the file was never hashed)
+ */
+checksum: string | null, };
@@ -5,10 +5,20 @@
  */
 export type TransmitFile = { 
 /**
- * The absolute path to the file to transmit.
+ * The IP address of the peer to send the file to.
  */
-path: string, 
+ip: string, 
 /**
- * The filename to transmit.
+ * The absolute paths to the files and folders to transmit, as a single offer.
+ * Folders are sent with every file inside them.
  */
-filename: string, };
+paths: Array<string>, 
+/**
+ * The name of the offer. If a single file is transmitted, the filename to transmit it as.
+ */
+filename: string, 
+/**
+ * The priority of the file transfer in the transfer queue. Higher priorities start first.
+ * If None, defaults to 0.
+ */
+priority: number | null, };